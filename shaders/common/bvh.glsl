@@ -0,0 +1,197 @@
+// Ray traversal of the BVHs built by vulkano_rs_common::bvh, the stand-in for acceleration
+// structures where vulkano can't build them. Declares its four storage buffers at bindings 0 to
+// 3 of set BVH_SET, 0 unless defined before `#include <bvh.glsl>`
+#ifndef BVH_GLSL
+#define BVH_GLSL
+
+#ifndef BVH_SET
+#define BVH_SET 0
+#endif
+
+// Deep enough for any tree BottomLevel builds, which panics past that
+#define BVH_STACK_SIZE 32
+
+// For inner nodes `offset` is the second child, the first comes right after the node. Leaves
+// hold `count` primitives from `offset` on
+struct BvhNode {
+    vec3 min;
+    uint offset;
+    vec3 max;
+    uint count;
+};
+
+struct BvhTriangle {
+    vec3 a;
+    vec3 b;
+    vec3 c;
+};
+
+struct BvhInstance {
+    mat4 world_to_object;
+    uint root;
+    uint custom_index;
+};
+
+layout(std430, set = BVH_SET, binding = 0) readonly buffer TopLevelNodes {
+    BvhNode tlas_nodes[];
+};
+layout(std430, set = BVH_SET, binding = 1) readonly buffer Instances {
+    BvhInstance instances[];
+};
+layout(std430, set = BVH_SET, binding = 2) readonly buffer BottomLevelNodes {
+    BvhNode blas_nodes[];
+};
+layout(std430, set = BVH_SET, binding = 3) readonly buffer Triangles {
+    BvhTriangle triangles[];
+};
+
+struct Hit {
+    float t;
+    // Index into `instances`, whose custom_index tells the shaders what was hit
+    uint instance;
+    // Index into `triangles`
+    uint triangle;
+    // Weights of b and c, a gets the rest
+    vec2 barycentrics;
+};
+
+// Slab test: whether the ray enters the box before t_max
+bool bvh_enters(BvhNode node, vec3 origin, vec3 inverse_direction, float t_max) {
+    vec3 t0 = (node.min - origin) * inverse_direction;
+    vec3 t1 = (node.max - origin) * inverse_direction;
+    vec3 near = min(t0, t1);
+    vec3 far = max(t0, t1);
+    float enter = max(max(near.x, near.y), near.z);
+    float exit = min(min(far.x, far.y), far.z);
+    return enter <= exit && exit >= 0.0 && enter < t_max;
+}
+
+// Möller–Trumbore, with both faces hit like a ray tracing pipeline does without culling flags
+bool bvh_intersect(
+    BvhTriangle triangle,
+    vec3 origin,
+    vec3 direction,
+    float t_min,
+    float t_max,
+    out float t,
+    out vec2 barycentrics
+) {
+    vec3 edge1 = triangle.b - triangle.a;
+    vec3 edge2 = triangle.c - triangle.a;
+    vec3 p = cross(direction, edge2);
+    float determinant = dot(edge1, p);
+    // Parallel to the triangle
+    if (abs(determinant) < 1e-9) {
+        return false;
+    }
+    float inverse = 1.0 / determinant;
+    vec3 s = origin - triangle.a;
+    float u = dot(s, p) * inverse;
+    if (u < 0.0 || u > 1.0) {
+        return false;
+    }
+    vec3 q = cross(s, edge1);
+    float v = dot(direction, q) * inverse;
+    if (v < 0.0 || u + v > 1.0) {
+        return false;
+    }
+    t = dot(edge2, q) * inverse;
+    barycentrics = vec2(u, v);
+    return t > t_min && t < t_max;
+}
+
+// Finds the closest hit between t_min and t_max, like traceRayEXT. With any_hit it stops at
+// the first one found instead, which is all a shadow ray needs to know
+bool bvh_trace(
+    vec3 origin,
+    vec3 direction,
+    float t_min,
+    float t_max,
+    bool any_hit,
+    out Hit hit
+) {
+    hit.t = t_max;
+    bool found = false;
+    if (tlas_nodes.length() == 0) {
+        return false;
+    }
+
+    uint top_stack[BVH_STACK_SIZE];
+    int top_size = 0;
+    top_stack[top_size++] = 0u;
+    vec3 inverse_direction = 1.0 / direction;
+
+    while (top_size > 0) {
+        uint index = top_stack[--top_size];
+        BvhNode node = tlas_nodes[index];
+        if (!bvh_enters(node, origin, inverse_direction, hit.t)) {
+            continue;
+        }
+        if (node.count == 0) {
+            top_stack[top_size++] = node.offset;
+            top_stack[top_size++] = index + 1;
+            continue;
+        }
+
+        for (uint i = node.offset; i < node.offset + node.count; i++) {
+            // The bottom level is traced in the space its mesh was built in. The direction isn't
+            // normalized again, so t means the same distance as in world space
+            BvhInstance instance = instances[i];
+            vec3 object_origin = (instance.world_to_object * vec4(origin, 1.0)).xyz;
+            vec3 object_direction = (instance.world_to_object * vec4(direction, 0.0)).xyz;
+            vec3 inverse_object_direction = 1.0 / object_direction;
+
+            uint stack[BVH_STACK_SIZE];
+            int size = 0;
+            stack[size++] = instance.root;
+            while (size > 0) {
+                uint node_index = stack[--size];
+                BvhNode bottom = blas_nodes[node_index];
+                if (!bvh_enters(bottom, object_origin, inverse_object_direction, hit.t)) {
+                    continue;
+                }
+                if (bottom.count == 0) {
+                    stack[size++] = bottom.offset;
+                    stack[size++] = node_index + 1;
+                    continue;
+                }
+
+                for (uint j = bottom.offset; j < bottom.offset + bottom.count; j++) {
+                    float t;
+                    vec2 barycentrics;
+                    if (bvh_intersect(
+                        triangles[j],
+                        object_origin,
+                        object_direction,
+                        t_min,
+                        hit.t,
+                        t,
+                        barycentrics
+                    )) {
+                        hit.t = t;
+                        hit.instance = i;
+                        hit.triangle = j;
+                        hit.barycentrics = barycentrics;
+                        found = true;
+                        if (any_hit) {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    return found;
+}
+
+// The world space normal of the triangle that was hit, facing whichever way it was wound
+vec3 bvh_normal(Hit hit) {
+    BvhTriangle triangle = triangles[hit.triangle];
+    vec3 normal = cross(triangle.b - triangle.a, triangle.c - triangle.a);
+    // Normals go by the inverse transpose of the object to world matrix, which is the transpose
+    // of world_to_object
+    return normalize((transpose(instances[hit.instance].world_to_object) * vec4(normal, 0.0)).xyz);
+}
+
+#endif
@@ -0,0 +1,41 @@
+// Smooth noise for procedural textures and density fields
+#ifndef NOISE_GLSL
+#define NOISE_GLSL
+
+#include <random.glsl>
+
+// A random value at every integer lattice point
+float lattice(ivec3 p) {
+    return hash(uint(p.x) * 73856093u ^ uint(p.y) * 19349663u ^ uint(p.z) * 83492791u);
+}
+
+// Value noise in 0..1: the lattice values blended with a smoothstep curve, so the noise has no
+// creases at the cell borders
+float value_noise(vec3 p) {
+    ivec3 cell = ivec3(floor(p));
+    vec3 f = fract(p);
+    vec3 u = f * f * (3.0 - 2.0 * f);
+
+    float x00 = mix(lattice(cell), lattice(cell + ivec3(1, 0, 0)), u.x);
+    float x10 = mix(lattice(cell + ivec3(0, 1, 0)), lattice(cell + ivec3(1, 1, 0)), u.x);
+    float x01 = mix(lattice(cell + ivec3(0, 0, 1)), lattice(cell + ivec3(1, 0, 1)), u.x);
+    float x11 = mix(lattice(cell + ivec3(0, 1, 1)), lattice(cell + ivec3(1, 1, 1)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+// Fractal noise: octaves of value noise, each twice the frequency and half the amplitude of the
+// one before, for detail at every scale. Stays in 0..1
+float fbm(vec3 p, int octaves) {
+    float sum = 0.0;
+    float amplitude = 0.5;
+    float total = 0.0;
+    for (int i = 0; i < octaves; i++) {
+        sum += value_noise(p) * amplitude;
+        total += amplitude;
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return sum / total;
+}
+
+#endif
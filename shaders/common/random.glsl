@@ -0,0 +1,22 @@
+// Random numbers for shaders, which have no state to keep a generator in between invocations.
+// Included with `#include <random.glsl>` by the chapters listing shaders/common in `include`
+#ifndef RANDOM_GLSL
+#define RANDOM_GLSL
+
+// Integer hash, turned into a float in 0..1. The same n always gives the same value, so hash
+// an index with a seed to get a fresh value per element
+float hash(uint n) {
+    n = (n << 13u) ^ n;
+    n = n * (n * n * 15731u + 789221u) + 1376312589u;
+    return float(n & 0x7fffffffu) / float(0x7fffffff);
+}
+
+// PCG hash: fast, and good enough that neighbouring pixels don't show patterns. Advances state,
+// so successive calls give different values in 0..1
+float pcg_random(inout uint state) {
+    state = state * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return float((word >> 22u) ^ word) / 4294967295.0;
+}
+
+#endif
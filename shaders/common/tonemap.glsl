@@ -0,0 +1,15 @@
+// Operators mapping HDR colors into the 0..1 a display can show
+#ifndef TONEMAP_GLSL
+#define TONEMAP_GLSL
+
+// Compresses everything smoothly, but washes out the brightest colors
+vec3 reinhard(vec3 x) {
+    return x / (x + 1.0);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+#endif
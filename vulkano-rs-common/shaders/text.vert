@@ -0,0 +1,20 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec2 v_uv;
+layout(location = 1) out vec4 v_color;
+
+layout(push_constant) uniform PushConstants {
+    vec2 screen_size;
+} pc;
+
+// Vulkan's Y already points down, so pixels map to clip space with a scale and an
+// offset
+void main() {
+    v_uv = uv;
+    v_color = color;
+    gl_Position = vec4(position / pc.screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
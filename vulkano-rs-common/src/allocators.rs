@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use vulkano::command_buffer::allocator::{
+    StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::device::Device;
+
+use crate::memory::TrackingAllocator;
+
+/// The three allocators nearly every chapter needs, created once per device and shared by
+/// cloning the `Arc`s. Memory allocations are printed with `--track-memory`, see
+/// [`TrackingAllocator`].
+///
+/// Record with `&*allocators.command`: borrowing the allocator itself rather than its `Arc`
+/// keeps the builder's type `AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>`.
+#[derive(Clone)]
+pub struct Allocators {
+    pub memory: Arc<TrackingAllocator>,
+    pub command: Arc<StandardCommandBufferAllocator>,
+    pub descriptor: Arc<StandardDescriptorSetAllocator>,
+}
+
+impl Allocators {
+    /// Allocators for `device`, with command buffer pools sized for the chapters.
+    pub fn new(device: &Arc<Device>) -> Self {
+        Allocators {
+            memory: Arc::new(TrackingAllocator::new(device.clone())),
+            command: Arc::new(StandardCommandBufferAllocator::new(
+                device.clone(),
+                // Command buffers are allocated from a pool this many at a time. The defaults
+                // of 256 each suit engines recording many per frame, the chapters record a
+                // few primaries per frame and hardly ever a secondary
+                StandardCommandBufferAllocatorCreateInfo {
+                    primary_buffer_count: 32,
+                    secondary_buffer_count: 4,
+                    ..Default::default()
+                },
+            )),
+            descriptor: Arc::new(StandardDescriptorSetAllocator::new(device.clone())),
+        }
+    }
+}
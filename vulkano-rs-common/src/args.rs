@@ -0,0 +1,125 @@
+// Tiny command line helpers, the chapters only need a handful of flags. Any of them can also be
+// given a default in the config file
+
+use std::env;
+use std::str::FromStr;
+
+use crate::config;
+
+/// Whether `name` (e.g. `--stats`) was passed on the command line, or set to true in
+/// [`config::FILE_NAME`]. `--name=false` turns off one the file turns on.
+pub fn flag(name: &str) -> bool {
+    let args: Vec<String> = env::args().skip(1).collect();
+    flag_in(&args, name, || config::value(name))
+}
+
+/// The value given to `name`, either as `--name value` or `--name=value`, and otherwise the one
+/// the config file sets.
+pub fn value<T: FromStr>(name: &str) -> Option<T> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    value_in(&args, name, || config::value(name))
+}
+
+// The command line wins over the file, which is only read when the flag isn't given
+fn flag_in(args: &[String], name: &str, file: impl FnOnce() -> Option<String>) -> bool {
+    let prefix = format!("{name}=");
+    let raw = args.iter().find_map(|arg| {
+        if arg == name {
+            Some("true".to_owned())
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_owned)
+        }
+    });
+
+    match raw.or_else(file) {
+        Some(raw) => match raw.parse() {
+            Ok(value) => value,
+            Err(_) => panic!("invalid value for {name}: {raw}, expected true or false"),
+        },
+        None => false,
+    }
+}
+
+fn value_in<T: FromStr>(
+    args: &[String],
+    name: &str,
+    file: impl FnOnce() -> Option<String>,
+) -> Option<T> {
+    let prefix = format!("{name}=");
+    let raw = args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == name {
+            // The next token is another flag, not this one's value
+            match args.get(i + 1) {
+                Some(next) if !next.starts_with("--") => Some(next.clone()),
+                _ => panic!("missing value for {name}"),
+            }
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_owned)
+        }
+    });
+    let raw = raw.or_else(file)?;
+
+    match raw.parse() {
+        Ok(value) => Some(value),
+        Err(_) => panic!("invalid value for {name}: {raw}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn flag_given_alone_is_true() {
+        assert!(flag_in(&args(&["--stats"]), "--stats", || None));
+        assert!(!flag_in(&args(&["--profile"]), "--stats", || None));
+    }
+
+    #[test]
+    fn flag_false_overrides_the_file() {
+        let file = || Some("true".to_owned());
+        assert!(flag_in(&args(&[]), "--stats", file));
+        assert!(!flag_in(&args(&["--stats=false"]), "--stats", file));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected true or false")]
+    fn flag_rejects_other_values() {
+        flag_in(&args(&["--stats=yes"]), "--stats", || None);
+    }
+
+    #[test]
+    fn value_in_both_spellings() {
+        let separate = args(&["--count", "12"]);
+        let joined = args(&["--count=12"]);
+        assert_eq!(value_in::<u32>(&separate, "--count", || None), Some(12));
+        assert_eq!(value_in::<u32>(&joined, "--count", || None), Some(12));
+    }
+
+    #[test]
+    fn command_line_overrides_the_file() {
+        let file = || Some("4".to_owned());
+        assert_eq!(
+            value_in::<u32>(&args(&["--count=12"]), "--count", file),
+            Some(12)
+        );
+        assert_eq!(value_in::<u32>(&args(&[]), "--count", file), Some(4));
+        assert_eq!(value_in::<u32>(&args(&[]), "--count", || None), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "missing value for --count")]
+    fn value_rejects_a_following_flag() {
+        value_in::<u32>(&args(&["--count", "--stats"]), "--count", || None);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value for --count: many")]
+    fn value_rejects_unparsable_input() {
+        value_in::<u32>(&args(&["--count=many"]), "--count", || None);
+    }
+}
@@ -0,0 +1,366 @@
+use glam::{BVec3, Mat4, Vec3};
+use vulkano::buffer::BufferContents;
+
+// Has to match the stack size in bvh.glsl. Median splits keep the depth at about log2 of the
+// primitive count, far below this
+const MAX_DEPTH: usize = 32;
+const MAX_LEAF_SIZE: usize = 4;
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Contains nothing, the starting point for [`Aabb::union`] and [`Aabb::grow`].
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::splat(f32::INFINITY),
+        max: Vec3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn union(self, other: Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grow(self, point: Vec3) -> Aabb {
+        Aabb {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    pub fn center(self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn contains(self, other: Aabb) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
+    }
+
+    /// The box around this one once moved by `transform`, larger than it when rotated.
+    pub fn transform(self, transform: Mat4) -> Aabb {
+        let mut bounds = Aabb::EMPTY;
+        for corner in 0..8 {
+            let mask = BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0);
+            let point = Vec3::select(mask, self.max, self.min);
+            bounds = bounds.grow(transform.transform_point3(point));
+        }
+        bounds
+    }
+}
+
+/// A node of a BVH, as `BvhNode` in `shaders/common/bvh.glsl` reads it.
+#[derive(BufferContents, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct BvhNode {
+    pub min: [f32; 3],
+    /// Inner nodes: the index of the second child, the first one comes right after the node.
+    /// Leaves: the index of their first primitive.
+    pub offset: u32,
+    pub max: [f32; 3],
+    /// The primitives in a leaf, 0 for inner nodes.
+    pub count: u32,
+}
+
+const _: () = assert!(std::mem::size_of::<BvhNode>() == 32);
+
+impl BvhNode {
+    pub fn bounds(&self) -> Aabb {
+        Aabb {
+            min: Vec3::from(self.min),
+            max: Vec3::from(self.max),
+        }
+    }
+}
+
+/// A bounding volume hierarchy over primitives known by their bounds.
+pub struct Bvh {
+    /// Depth first, the root at index 0.
+    pub nodes: Vec<BvhNode>,
+    /// The indices of the primitives in the order the leaves refer to them.
+    pub order: Vec<u32>,
+}
+
+impl Bvh {
+    /// Splits the primitives in half along the longest axis of their centers, until at most
+    /// four are left in a leaf. Quick to build and balanced, but the tree is slower to trace
+    /// than one built with the surface area heuristic that drivers use.
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            order: (0..bounds.len() as u32).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.split(bounds, 0, bounds.len(), 0);
+        }
+        bvh
+    }
+
+    fn split(&mut self, bounds: &[Aabb], start: usize, end: usize, depth: usize) {
+        assert!(
+            depth < MAX_DEPTH,
+            "the BVH is deeper than bvh.glsl can trace"
+        );
+        let primitives = &mut self.order[start..end];
+        let node_bounds = primitives
+            .iter()
+            .fold(Aabb::EMPTY, |node, &i| node.union(bounds[i as usize]));
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            min: node_bounds.min.to_array(),
+            offset: start as u32,
+            max: node_bounds.max.to_array(),
+            count: primitives.len() as u32,
+        });
+        if primitives.len() <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let centers = primitives.iter().fold(Aabb::EMPTY, |centers, &i| {
+            centers.grow(bounds[i as usize].center())
+        });
+        let extent = centers.max - centers.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let middle = primitives.len() / 2;
+        primitives.select_nth_unstable_by(middle, |&a, &b| {
+            let a = bounds[a as usize].center()[axis];
+            let b = bounds[b as usize].center()[axis];
+            a.total_cmp(&b)
+        });
+
+        self.split(bounds, start, start + middle, depth + 1);
+        let second = self.nodes.len() as u32;
+        self.split(bounds, start + middle, end, depth + 1);
+        self.nodes[index].offset = second;
+        self.nodes[index].count = 0;
+    }
+}
+
+/// A triangle as `BvhTriangle` in `bvh.glsl` reads it. The padding keeps each corner in a slot
+/// of 16 bytes, as std430 lays out a `vec3`.
+#[derive(BufferContents, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct BvhTriangle {
+    pub a: [f32; 3],
+    pub _padding_a: f32,
+    pub b: [f32; 3],
+    pub _padding_b: f32,
+    pub c: [f32; 3],
+    pub _padding_c: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<BvhTriangle>() == 48);
+
+/// A placed mesh as `BvhInstance` in `bvh.glsl` reads it.
+#[derive(BufferContents, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct BvhInstance {
+    /// Takes rays into the space the mesh's bottom level was built in.
+    pub world_to_object: [[f32; 4]; 4],
+    /// The node the bottom level of the mesh starts at.
+    pub root: u32,
+    /// Whatever the shaders need to tell instances apart, e.g. a material index.
+    pub custom_index: u32,
+    pub _padding: [u32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<BvhInstance>() == 80);
+
+/// A mesh placed in the scene, for [`BottomLevel::top_level`].
+#[derive(Clone, Copy, Debug)]
+pub struct Instance {
+    /// The index of the mesh in the list [`BottomLevel::new`] was given.
+    pub mesh: usize,
+    pub transform: Mat4,
+    pub custom_index: u32,
+}
+
+/// One BVH over the triangles of each mesh, the stand-in for bottom-level acceleration
+/// structures. Built once, the meshes never change, however they are placed.
+pub struct BottomLevel {
+    /// The nodes of every mesh one after the other. Leaves index into `triangles`.
+    pub nodes: Vec<BvhNode>,
+    pub triangles: Vec<BvhTriangle>,
+    // The root node and bounds of each mesh
+    roots: Vec<(u32, Aabb)>,
+}
+
+impl BottomLevel {
+    /// `meshes` are triangle lists, as positions and three indices per triangle.
+    pub fn new(meshes: &[(Vec<Vec3>, Vec<u32>)]) -> Self {
+        let mut bottom_level = BottomLevel {
+            nodes: Vec::new(),
+            triangles: Vec::new(),
+            roots: Vec::new(),
+        };
+
+        for (positions, indices) in meshes {
+            let corners: Vec<[Vec3; 3]> = indices
+                .chunks_exact(3)
+                .map(|triangle| [0, 1, 2].map(|corner| positions[triangle[corner] as usize]))
+                .collect();
+            let bounds: Vec<Aabb> = corners
+                .iter()
+                .map(|corners| {
+                    corners
+                        .iter()
+                        .fold(Aabb::EMPTY, |b, &corner| b.grow(corner))
+                })
+                .collect();
+            let bvh = Bvh::build(&bounds);
+
+            // Moved behind the meshes before it, so the node offsets shift by as much
+            let node_base = bottom_level.nodes.len() as u32;
+            let triangle_base = bottom_level.triangles.len() as u32;
+            bottom_level.roots.push((
+                node_base,
+                bvh.nodes.first().map_or(Aabb::EMPTY, BvhNode::bounds),
+            ));
+            bottom_level.nodes.extend(bvh.nodes.iter().map(|node| {
+                let base = if node.count == 0 {
+                    node_base
+                } else {
+                    triangle_base
+                };
+                BvhNode {
+                    offset: node.offset + base,
+                    ..*node
+                }
+            }));
+            bottom_level.triangles.extend(bvh.order.iter().map(|&i| {
+                let [a, b, c] = corners[i as usize];
+                BvhTriangle {
+                    a: a.to_array(),
+                    b: b.to_array(),
+                    c: c.to_array(),
+                    ..Default::default()
+                }
+            }));
+        }
+
+        bottom_level
+    }
+
+    /// A BVH over the bounds of `instances`, the stand-in for a top-level acceleration
+    /// structure. Cheap enough with a few hundred instances to build again every frame they
+    /// move, while the bottom level stays as it is.
+    pub fn top_level(&self, instances: &[Instance]) -> TopLevel {
+        let bounds: Vec<Aabb> = instances
+            .iter()
+            .map(|instance| self.roots[instance.mesh].1.transform(instance.transform))
+            .collect();
+        let bvh = Bvh::build(&bounds);
+
+        TopLevel {
+            nodes: bvh.nodes,
+            instances: bvh
+                .order
+                .iter()
+                .map(|&i| {
+                    let instance = &instances[i as usize];
+                    BvhInstance {
+                        world_to_object: instance.transform.inverse().to_cols_array_2d(),
+                        root: self.roots[instance.mesh].0,
+                        custom_index: instance.custom_index,
+                        ..Default::default()
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The top level built by [`BottomLevel::top_level`]. Leaves index into `instances`.
+pub struct TopLevel {
+    pub nodes: Vec<BvhNode>,
+    pub instances: Vec<BvhInstance>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxes(count: u32) -> Vec<Aabb> {
+        // Scattered without a random generator: the golden ratio spreads them evenly
+        (0..count)
+            .map(|i| {
+                let t = i as f32 * 0.618_034;
+                let center = Vec3::new(t.fract() * 10.0, (t * 7.0).fract() * 10.0, i as f32);
+                Aabb {
+                    min: center - 0.5,
+                    max: center + 0.5,
+                }
+            })
+            .collect()
+    }
+
+    // Walks the tree from `index`, checking the bounds on the way, and returns the primitives
+    // found in its leaves
+    fn leaves(bvh: &Bvh, bounds: &[Aabb], index: usize) -> Vec<u32> {
+        let node = bvh.nodes[index];
+        if node.count > 0 {
+            let primitives = &bvh.order[node.offset as usize..(node.offset + node.count) as usize];
+            for &primitive in primitives {
+                assert!(node.bounds().contains(bounds[primitive as usize]));
+            }
+            return primitives.to_vec();
+        }
+
+        let second = node.offset as usize;
+        for child in [index + 1, second] {
+            assert!(node.bounds().contains(bvh.nodes[child].bounds()));
+        }
+        let mut found = leaves(bvh, bounds, index + 1);
+        found.extend(leaves(bvh, bounds, second));
+        found
+    }
+
+    #[test]
+    fn every_primitive_is_in_one_leaf() {
+        let bounds = boxes(1000);
+        let bvh = Bvh::build(&bounds);
+        let mut found = leaves(&bvh, &bounds, 0);
+        found.sort_unstable();
+        assert_eq!(found, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn small_inputs_are_a_single_leaf() {
+        assert!(Bvh::build(&[]).nodes.is_empty());
+        let bvh = Bvh::build(&boxes(3));
+        assert_eq!(bvh.nodes.len(), 1);
+        assert_eq!(bvh.nodes[0].count, 3);
+    }
+
+    #[test]
+    fn bottom_level_offsets_point_past_earlier_meshes() {
+        let square = (
+            vec![Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y],
+            vec![0, 1, 2, 0, 2, 3],
+        );
+        let bottom_level = BottomLevel::new(&[square.clone(), square]);
+        assert_eq!(bottom_level.nodes.len(), 2);
+        assert_eq!(bottom_level.triangles.len(), 4);
+        assert_eq!(bottom_level.nodes[1].offset, 2);
+
+        let top_level = bottom_level.top_level(&[Instance {
+            mesh: 1,
+            transform: Mat4::from_translation(Vec3::Z),
+            custom_index: 7,
+        }]);
+        assert_eq!(top_level.instances[0].root, 1);
+        assert_eq!(top_level.instances[0].custom_index, 7);
+        assert_eq!(top_level.nodes[0].min, [0.0, 0.0, 1.0]);
+    }
+}
@@ -0,0 +1,214 @@
+use std::f32::consts::FRAC_PI_2;
+use std::time::Instant;
+
+use glam::{Mat4, Vec3, Vec4};
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+/// A first-person camera: WASD to move, Space/Ctrl to go up/down, hold the right mouse button to
+/// look around.
+pub struct Camera {
+    pub position: Vec3,
+    // Rotation around the Y axis and up/down angle, in radians
+    pub yaw: f32,
+    pub pitch: f32,
+    // Vertical field of view in radians
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    // Units per second and radians per pixel of mouse movement
+    pub speed: f32,
+    pub sensitivity: f32,
+    input: Input,
+    last_update: Instant,
+}
+
+#[derive(Default)]
+struct Input {
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    looking: bool,
+    last_cursor: Option<PhysicalPosition<f64>>,
+}
+
+impl Camera {
+    /// A camera at `position` looking at `target`.
+    pub fn new(position: Vec3, target: Vec3) -> Self {
+        let direction = (target - position).normalize();
+
+        Camera {
+            position,
+            yaw: direction.z.atan2(direction.x),
+            pitch: direction.y.asin(),
+            fov_y: 60f32.to_radians(),
+            near: 0.1,
+            far: 100.0,
+            speed: 3.0,
+            sensitivity: 0.003,
+            input: Input::default(),
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(
+            self.yaw.cos() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.sin() * self.pitch.cos(),
+        )
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.forward().cross(Vec3::Y).normalize()
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), Vec3::Y)
+    }
+
+    /// Perspective projection for Vulkan clip space: depth goes from 0 to 1 and Y points down.
+    pub fn projection(&self, aspect_ratio: f32) -> Mat4 {
+        let mut projection = Mat4::perspective_rh(self.fov_y, aspect_ratio, self.near, self.far);
+        projection.y_axis.y *= -1.0;
+        projection
+    }
+
+    pub fn view_projection(&self, aspect_ratio: f32) -> Mat4 {
+        self.projection(aspect_ratio) * self.view()
+    }
+
+    /// Updates the pressed keys and mouse look from a window event.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                match key {
+                    VirtualKeyCode::W => self.input.forward = pressed,
+                    VirtualKeyCode::S => self.input.backward = pressed,
+                    VirtualKeyCode::A => self.input.left = pressed,
+                    VirtualKeyCode::D => self.input.right = pressed,
+                    VirtualKeyCode::Space => self.input.up = pressed,
+                    VirtualKeyCode::LControl => self.input.down = pressed,
+                    _ => {}
+                }
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state,
+                ..
+            } => {
+                self.input.looking = *state == ElementState::Pressed;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some(last)) = (self.input.looking, self.input.last_cursor) {
+                    self.yaw += (position.x - last.x) as f32 * self.sensitivity;
+                    self.pitch -= (position.y - last.y) as f32 * self.sensitivity;
+                    // Looking straight up or down would flip the view
+                    self.pitch = self.pitch.clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+                }
+                self.input.last_cursor = Some(*position);
+            }
+            // Keys released while the window wasn't focused would otherwise stay pressed
+            WindowEvent::Focused(false) => self.input = Input::default(),
+            _ => {}
+        }
+    }
+
+    /// Moves the camera according to the pressed keys and the time since the last update.
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let dt = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        let forward = self.forward();
+        let right = self.right();
+        let mut direction = Vec3::ZERO;
+
+        if self.input.forward {
+            direction += forward;
+        }
+        if self.input.backward {
+            direction -= forward;
+        }
+        if self.input.right {
+            direction += right;
+        }
+        if self.input.left {
+            direction -= right;
+        }
+        if self.input.up {
+            direction += Vec3::Y;
+        }
+        if self.input.down {
+            direction -= Vec3::Y;
+        }
+
+        self.position += direction.normalize_or_zero() * self.speed * dt;
+    }
+}
+
+/// The six planes bounding what `view_projection` can see, as (normal, distance) with unit
+/// normals pointing inside, in the order left, right, top, bottom, near, far. A point `p` is
+/// inside a plane when `dot(normal, p) + distance >= 0`, and a sphere is entirely outside when
+/// that is below minus its radius.
+pub fn frustum_planes(view_projection: Mat4) -> [[f32; 4]; 6] {
+    // Each plane is a sum or difference of two rows of the matrix: a point is inside the left
+    // plane when -w <= x in clip space, so when (row 3 + row 0) . p >= 0
+    let row = |i| view_projection.row(i);
+    let planes: [Vec4; 6] = [
+        row(3) + row(0),
+        row(3) - row(0),
+        row(3) + row(1),
+        row(3) - row(1),
+        // Vulkan's depth goes from 0 to w, so the near plane is the third row alone
+        row(2),
+        row(3) - row(2),
+    ];
+    // With unit normals, the dot product is a distance the radius can be compared to
+    planes.map(|plane| (plane / plane.truncate().length()).to_array())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distances(planes: &[[f32; 4]; 6], point: Vec3) -> [f32; 6] {
+        planes.map(|plane| Vec4::from(plane).truncate().dot(point) + plane[3])
+    }
+
+    #[test]
+    fn frustum_planes_bound_the_view() {
+        let camera = Camera::new(Vec3::ZERO, Vec3::NEG_Z);
+        let planes = frustum_planes(camera.view_projection(16.0 / 9.0));
+
+        // In front of the camera, between the near and far planes
+        assert!(distances(&planes, Vec3::new(0.0, 0.0, -10.0))
+            .iter()
+            .all(|&distance| distance > 0.0));
+        // Behind it, too close, too far and off to the side
+        for outside in [
+            Vec3::new(0.0, 0.0, 10.0),
+            Vec3::new(0.0, 0.0, -0.05),
+            Vec3::new(0.0, 0.0, -200.0),
+            Vec3::new(100.0, 0.0, -10.0),
+        ] {
+            assert!(distances(&planes, outside).iter().any(|&distance| distance < 0.0));
+        }
+
+        // The distance to the near plane is measured along the view direction
+        let near = distances(&planes, Vec3::new(0.0, 0.0, -1.0))[4];
+        assert!((near - (1.0 - camera.near)).abs() < 1e-4);
+    }
+}
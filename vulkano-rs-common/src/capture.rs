@@ -0,0 +1,55 @@
+use std::ptr;
+
+use renderdoc::{RenderDoc, V110};
+
+use crate::args;
+
+/// Captures one frame of the runner in RenderDoc, picked with `--capture-frame N` where the
+/// first frame is 0. Only works when the chapter was launched from RenderDoc, which loads its
+/// library into the process before the Vulkan instance is created.
+pub struct FrameCapture {
+    renderdoc: RenderDoc<V110>,
+    target: u64,
+    frame: u64,
+}
+
+impl FrameCapture {
+    /// `None` without `--capture-frame`, or when RenderDoc isn't there to capture.
+    pub fn from_args() -> Option<Self> {
+        let target = args::value::<u64>("--capture-frame")?;
+
+        match RenderDoc::new() {
+            Ok(renderdoc) => Some(FrameCapture {
+                renderdoc,
+                target,
+                frame: 0,
+            }),
+            Err(e) => {
+                tracing::warn!("--capture-frame needs the chapter to run under RenderDoc: {e}");
+                None
+            }
+        }
+    }
+
+    /// Starts capturing if this is the frame to capture. Everything up to
+    /// [`FrameCapture::end_frame`] goes into the capture, command buffers included as long as
+    /// they are recorded after this.
+    pub fn begin_frame(&mut self) {
+        if self.frame == self.target {
+            // Null handles let RenderDoc pick the only device and window there are
+            self.renderdoc.start_frame_capture(ptr::null(), ptr::null());
+        }
+    }
+
+    /// Ends the capture started by [`FrameCapture::begin_frame`], if any, and counts the frame.
+    pub fn end_frame(&mut self) {
+        if self.frame == self.target {
+            self.renderdoc.end_frame_capture(ptr::null(), ptr::null());
+            tracing::info!(
+                "captured frame {}, open it from the RenderDoc window",
+                self.frame
+            );
+        }
+        self.frame += 1;
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::{Arc, Mutex};
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage,
+    PrimaryAutoCommandBuffer,
+};
+use vulkano::device::Queue;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::future::{FenceSignalFuture, NowFuture};
+use vulkano::sync::{self, GpuFuture};
+
+use crate::allocators::Allocators;
+use crate::context::VulkanContext;
+use crate::descriptors::{bind_resources, Resource};
+use crate::reflect;
+
+/// The future [`run_compute`] returns, to pass to [`wait::fence`](crate::wait::fence).
+pub type ComputeFuture = FenceSignalFuture<CommandBufferExecFuture<NowFuture>>;
+
+/// Everything [`run_compute`] needs besides the shader and its data. Holds on to the pipelines
+/// it builds, so running the same shader again costs only the command buffer.
+pub struct ComputeContext {
+    pub queue: Arc<Queue>,
+    pub allocators: Allocators,
+    pipelines: Mutex<Vec<(Arc<ShaderModule>, String, Arc<ComputePipeline>)>>,
+}
+
+impl ComputeContext {
+    pub fn new(context: &VulkanContext) -> Self {
+        // The graphics queue rather than the async compute one. The buffers chapters pass in are
+        // mostly used on it too, and sharing them with another family takes ownership transfers
+        // the experiments shouldn't have to think about
+        Self::with_allocators(&context.queue, &Allocators::new(&context.device))
+    }
+
+    /// Dispatches to `queue`, with allocators the caller already has.
+    pub fn with_allocators(queue: &Arc<Queue>, allocators: &Allocators) -> Self {
+        ComputeContext {
+            queue: queue.clone(),
+            allocators: allocators.clone(),
+            pipelines: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The pipeline for `entry_point` of `shader`, built on first use.
+    pub fn pipeline(&self, shader: &Arc<ShaderModule>, entry_point: &str) -> Arc<ComputePipeline> {
+        let mut pipelines = self.pipelines.lock().unwrap();
+        // The cache keeps the module alive, so no other module can show up at the same address
+        if let Some((_, _, pipeline)) = pipelines
+            .iter()
+            .find(|(module, name, _)| Arc::ptr_eq(module, shader) && name == entry_point)
+        {
+            return pipeline.clone();
+        }
+
+        let device = self.queue.device();
+        let entry = shader
+            .entry_point(entry_point)
+            .unwrap_or_else(|| panic!("the shader has no entry point {entry_point}"));
+        // The layout comes from the shader's own SPIR-V, so a module loaded from a file at run
+        // time works as well as one compiled in
+        let layout = reflect::pipeline_layout(device, &entry);
+        let pipeline =
+            ComputePipeline::with_pipeline_layout(device.clone(), entry, &(), layout, None)
+                .expect("failed to create compute pipeline");
+        tracing::debug!(entry_point, "created compute pipeline");
+        pipelines.push((shader.clone(), entry_point.to_owned(), pipeline.clone()));
+        pipeline
+    }
+
+    /// Binds the pipeline of `entry_point` in `shader` and a set 0 holding `bindings`, as
+    /// [`run_compute`] does, but into a command buffer the caller records and submits. Returns
+    /// the pipeline, whose layout the push constants need.
+    ///
+    /// For dispatches with push constants, several passes in one command buffer or timestamps
+    /// around them.
+    ///
+    /// ```ignore
+    /// let pipeline = compute.bind(&mut builder, &shader, "main", &bindings);
+    /// builder
+    ///     .push_constants(pipeline.layout().clone(), 0, push_constants)
+    ///     .dispatch([groups, 1, 1])
+    ///     .unwrap();
+    /// ```
+    pub fn bind(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        shader: &Arc<ShaderModule>,
+        entry_point: &str,
+        bindings: &[(u32, Resource)],
+    ) -> Arc<ComputePipeline> {
+        let pipeline = self.pipeline(shader, entry_point);
+        builder.bind_pipeline_compute(pipeline.clone());
+        // A shader without resources has no set to bind
+        if !bindings.is_empty() {
+            let set = bind_resources(
+                &self.allocators.descriptor,
+                &pipeline,
+                0,
+                bindings.iter().cloned(),
+            );
+            builder.bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            );
+        }
+        pipeline
+    }
+}
+
+/// Dispatches `group_counts` work groups of `entry_point` in `shader`, with `bindings` bound to
+/// set 0 at the binding numbers they are paired with, and submits right away. Wait on the
+/// returned future before reading what the shader wrote.
+///
+/// Meant for quick experiments with a single dispatch. Chapters with push constants, timestamps
+/// or chained passes record their own command buffer around [`ComputeContext::bind`].
+///
+/// ```ignore
+/// let bindings = [(0, Resource::buffer(data.clone()))];
+/// let future = run_compute(&compute, &shader, "main", &bindings, [1024, 1, 1]);
+/// wait::fence(&future);
+/// ```
+pub fn run_compute(
+    ctx: &ComputeContext,
+    shader: &Arc<ShaderModule>,
+    entry_point: &str,
+    bindings: &[(u32, Resource)],
+    group_counts: [u32; 3],
+) -> ComputeFuture {
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*ctx.allocators.command,
+        ctx.queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    ctx.bind(&mut builder, shader, entry_point, bindings);
+    builder.dispatch(group_counts).unwrap();
+
+    tracing::debug!(entry_point, ?group_counts, "submitting dispatch");
+    sync::now(ctx.queue.device().clone())
+        .then_execute(ctx.queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+}
@@ -0,0 +1,139 @@
+// Defaults for the command line flags, kept in a file so they carry over from chapter to chapter
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use toml::{Table, Value};
+
+/// The file looked for in the working directory and the ones above it, so one at the root of
+/// the repository covers every chapter. `learn-vulkan.example.toml` there lists what it can
+/// hold.
+pub const FILE_NAME: &str = "learn-vulkan.toml";
+
+struct Config {
+    path: PathBuf,
+    // The top-level keys, with the chapter's own section laid over them
+    values: Table,
+}
+
+static CONFIG: OnceLock<Option<Config>> = OnceLock::new();
+
+/// Where the settings were read from, `None` without a config file.
+pub fn path() -> Option<&'static Path> {
+    config().map(|config| config.path.as_path())
+}
+
+// The value set for the flag `name`, e.g. `gpu-name = "nvidia"` for `--gpu-name`, as it would
+// be written on the command line
+pub(crate) fn value(name: &str) -> Option<String> {
+    let key = name.trim_start_matches('-');
+    let value = config()?.values.get(key)?;
+    match value {
+        Value::String(string) => Some(string.clone()),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => Some(value.to_string()),
+        _ => panic!("{key} in {FILE_NAME} should be a single value, not {value}"),
+    }
+}
+
+fn config() -> Option<&'static Config> {
+    CONFIG.get_or_init(load).as_ref()
+}
+
+fn load() -> Option<Config> {
+    let directory = env::current_dir().ok()?;
+    let path = directory
+        .ancestors()
+        .map(|directory| directory.join(FILE_NAME))
+        .find(|path| path.is_file())?;
+    let text = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+    let file: Table = text
+        .parse()
+        .unwrap_or_else(|e| panic!("{} is not valid TOML: {e}", path.display()));
+
+    // A section named after the chapter's binary, e.g. [vulkano-rs-guide-62], only applies to
+    // that chapter
+    let chapter = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.file_stem()?.to_string_lossy().into_owned()));
+    let values = layer(file, chapter.as_deref());
+
+    Some(Config { path, values })
+}
+
+// The top-level keys of `file`, with the section named `chapter` laid over them. The other
+// chapters' sections are left out
+fn layer(file: Table, chapter: Option<&str>) -> Table {
+    let mut values = Table::new();
+    let mut chapter_values = Table::new();
+    for (key, value) in file {
+        match value {
+            Value::Table(section) => {
+                if chapter == Some(key.as_str()) {
+                    chapter_values = section;
+                }
+            }
+            value => {
+                values.insert(key, value);
+            }
+        }
+    }
+    for (key, value) in chapter_values {
+        values.insert(key, value);
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+        stats = true
+        gpu-name = "nvidia"
+
+        [vulkano-rs-guide-9]
+        gpu-name = "amd"
+        resolution = "1280x720"
+
+        [vulkano-rs-guide-10]
+        stats = false
+    "#;
+
+    fn file() -> Table {
+        FILE.parse().unwrap()
+    }
+
+    #[test]
+    fn top_level_values_apply_to_every_chapter() {
+        let values = layer(file(), Some("vulkano-rs-guide-5"));
+        assert_eq!(values.get("stats"), Some(&Value::Boolean(true)));
+        assert_eq!(
+            values.get("gpu-name"),
+            Some(&Value::String("nvidia".into()))
+        );
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn chapter_section_overrides_top_level_values() {
+        let values = layer(file(), Some("vulkano-rs-guide-9"));
+        assert_eq!(values.get("gpu-name"), Some(&Value::String("amd".into())));
+        assert_eq!(
+            values.get("resolution"),
+            Some(&Value::String("1280x720".into()))
+        );
+        assert_eq!(values.get("stats"), Some(&Value::Boolean(true)));
+
+        let values = layer(file(), Some("vulkano-rs-guide-10"));
+        assert_eq!(values.get("stats"), Some(&Value::Boolean(false)));
+    }
+
+    #[test]
+    fn other_chapters_sections_are_left_out() {
+        let values = layer(file(), None);
+        assert!(values.get("resolution").is_none());
+        assert!(values.get("vulkano-rs-guide-9").is_none());
+    }
+}
@@ -0,0 +1,275 @@
+use std::sync::Arc;
+
+use vulkano::device::physical::PhysicalDeviceType;
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Features, Queue};
+use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions};
+use vulkano::swapchain::Surface;
+use vulkano::VulkanLibrary;
+
+use crate::queues::{QueuePlan, Queues};
+use crate::requirements::DeviceRequirements;
+use crate::{args, config, logging, report};
+
+/// Creates an instance with `enabled_extensions` that also lists portability implementations,
+/// MoltenVK on macOS being the main one. Their devices don't implement all of Vulkan, so the
+/// loader hides them unless asked.
+///
+/// Also sets up logging (see [`logging::init`]), and with `--validation` enables the validation
+/// layer and logs its messages.
+///
+/// With `--report`, prints what the devices of the instance support as JSON and exits.
+pub fn create_instance(
+    library: Arc<VulkanLibrary>,
+    enabled_extensions: InstanceExtensions,
+) -> Arc<Instance> {
+    logging::init();
+    if let Some(path) = config::path() {
+        tracing::debug!("defaults from {}", path.display());
+    }
+
+    let enumerate_portability = library.supported_extensions().khr_portability_enumeration;
+    let validation = args::flag("--validation") && logging::validation_available(&library);
+
+    let instance = Instance::new(
+        library,
+        InstanceCreateInfo {
+            enabled_extensions: InstanceExtensions {
+                khr_portability_enumeration: enumerate_portability,
+                ext_debug_utils: validation || enabled_extensions.ext_debug_utils,
+                ..enabled_extensions
+            },
+            enabled_layers: if validation {
+                vec![logging::VALIDATION_LAYER.to_owned()]
+            } else {
+                Vec::new()
+            },
+            enumerate_portability,
+            ..Default::default()
+        },
+    )
+        .expect("failed to create instance");
+    tracing::debug!(
+        api_version = ?instance.api_version(),
+        validation,
+        "created instance"
+    );
+    if validation {
+        logging::route_validation(&instance);
+    }
+
+    // Any chapter can describe the machine it runs on, e.g. to attach to a bug report
+    if args::flag("--report") {
+        let report = report::devices(&instance);
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        std::process::exit(0);
+    }
+
+    instance
+}
+
+/// The instance, logical device and queues that every chapter starts from.
+pub struct VulkanContext {
+    pub instance: Arc<Instance>,
+    pub device: Arc<Device>,
+    /// The graphics queue, which can also compute, transfer and present. Most chapters only
+    /// need this one.
+    pub queue: Arc<Queue>,
+    pub queues: Queues,
+}
+
+impl VulkanContext {
+    /// Picks the most capable GPU that supports `device_extensions` and has a graphics + compute
+    /// queue family (able to present to `surface`, if one is given), then creates the device
+    /// with a queue of that family, plus compute-only and transfer-only ones if there are any.
+    pub fn new(
+        instance: Arc<Instance>,
+        device_extensions: DeviceExtensions,
+        surface: Option<&Surface>,
+    ) -> Self {
+        Self::with_features(instance, device_extensions, Features::empty(), surface)
+    }
+
+    /// Like [`new`](Self::new), but also enables the optional `device_features` (geometry or
+    /// tessellation shaders, wireframe...). Devices that lack any of them are skipped.
+    pub fn with_features(
+        instance: Arc<Instance>,
+        device_extensions: DeviceExtensions,
+        device_features: Features,
+        surface: Option<&Surface>,
+    ) -> Self {
+        let requirements = DeviceRequirements::new()
+            .extensions(device_extensions)
+            .features(device_features);
+        Self::with_requirements(instance, &requirements, surface)
+    }
+
+    /// Picks a device meeting `requirements` as [`new`](Self::new) does, and enables exactly
+    /// the extensions and features they list. If none does, or none of those has the queue
+    /// families needed, exits after printing what each device lacks.
+    ///
+    /// With `--software`, only CPU implementations such as lavapipe or SwiftShader are
+    /// considered, to run without a GPU or to compare against one. `--gpu-name` keeps only the
+    /// devices whose name contains the given text, ignoring case, e.g. `--gpu-name nvidia`.
+    pub fn with_requirements(
+        instance: Arc<Instance>,
+        requirements: &DeviceRequirements,
+        surface: Option<&Surface>,
+    ) -> Self {
+        let software = args::flag("--software");
+        let gpu_name = args::value::<String>("--gpu-name").map(|name| name.to_lowercase());
+
+        let physical_devices: Vec<_> = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .collect();
+
+        // A name matching nothing is more likely a typo than a reason to pick another device
+        if let Some(gpu_name) = &gpu_name {
+            let names: Vec<String> = physical_devices
+                .iter()
+                .map(|p| p.properties().device_name.clone())
+                .collect();
+            if !names
+                .iter()
+                .any(|name| name.to_lowercase().contains(gpu_name.as_str()))
+            {
+                tracing::error!("no device name contains \"{gpu_name}\", the devices are:");
+                for name in names {
+                    tracing::error!("  {name}");
+                }
+                std::process::exit(1);
+            }
+        }
+
+        // Narrowed down first, so the report below only mentions devices that could be picked
+        let physical_devices: Vec<_> = physical_devices
+            .into_iter()
+            .filter(|p| !software || p.properties().device_type == PhysicalDeviceType::Cpu)
+            .filter(|p| {
+                gpu_name.as_ref().map_or(true, |gpu_name| {
+                    let name = p.properties().device_name.to_lowercase();
+                    name.contains(gpu_name.as_str())
+                })
+            })
+            .collect();
+        if software && physical_devices.is_empty() {
+            tracing::error!("no software implementation (lavapipe/SwiftShader) found");
+            std::process::exit(1);
+        }
+
+        // A device with every capability can still lack a queue family the chapter needs
+        let portability = physical_devices
+            .iter()
+            .any(|p| p.supported_extensions().khr_portability_subset);
+        let mut candidates = Vec::new();
+        let mut missing = Vec::new();
+        for physical_device in physical_devices {
+            if let Err(lacks) = requirements.check(&physical_device) {
+                missing.push(lacks.to_string());
+                continue;
+            }
+            match QueuePlan::new(&physical_device, surface) {
+                Some(plan) => candidates.push((physical_device, plan)),
+                None => missing.push(format!(
+                    "{} lacks a graphics and compute queue family{}",
+                    physical_device.properties().device_name,
+                    if surface.is_some() {
+                        ", or one that can present to the window"
+                    } else {
+                        ""
+                    }
+                )),
+            }
+        }
+        for missing in &missing {
+            tracing::debug!("skipping a device: {missing}");
+        }
+        if candidates.is_empty() {
+            tracing::error!("no device has what this chapter needs:");
+            for missing in missing {
+                tracing::error!("  {missing}");
+            }
+            if portability {
+                tracing::warn!(
+                    "portability implementations like MoltenVK only cover part of Vulkan"
+                );
+            }
+            std::process::exit(1);
+        }
+
+        // Instead of hard-coding the second device like the guide chapters, rank every device
+        // that can run the chapter and prefer dedicated hardware
+        let (physical_device, queue_plan) = candidates
+            .into_iter()
+            .min_by_key(|(p, _)| match p.properties().device_type {
+                PhysicalDeviceType::DiscreteGpu => 0,
+                PhysicalDeviceType::IntegratedGpu => 1,
+                PhysicalDeviceType::VirtualGpu => 2,
+                PhysicalDeviceType::Cpu => 3,
+                PhysicalDeviceType::Other => 4,
+                _ => 5,
+            })
+            .unwrap();
+
+        // Hardware always ranks first, so a CPU device here means no GPU could run the chapter
+        let properties = physical_device.properties();
+        if properties.device_type == PhysicalDeviceType::Cpu && !software && gpu_name.is_none() {
+            tracing::warn!(
+                "only the software implementation {} can run this chapter, expect it to be slow",
+                properties.device_name
+            );
+        }
+        tracing::debug!(
+            name = %properties.device_name,
+            device_type = ?properties.device_type,
+            api_version = ?properties.api_version,
+            driver = properties.driver_name.as_deref().unwrap_or("unknown"),
+            "selected device"
+        );
+
+        // A portability device has to be told the application knows what it leaves out
+        let device_extensions = DeviceExtensions {
+            khr_portability_subset: physical_device
+                .supported_extensions()
+                .khr_portability_subset,
+            ..requirements.extensions
+        };
+
+        let enabled_features = requirements.enabled_features(&physical_device);
+
+        let (device, queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: queue_plan.queue_create_infos(),
+                enabled_extensions: device_extensions,
+                enabled_features,
+                ..Default::default()
+            },
+        )
+            .expect("failed to create device");
+
+        tracing::debug!(
+            graphics = queue_plan.graphics,
+            compute = ?queue_plan.compute,
+            transfer = ?queue_plan.transfer,
+            "created device"
+        );
+        let queues = queue_plan.assign(queues);
+
+        VulkanContext {
+            instance,
+            device,
+            queue: queues.graphics.clone(),
+            queues,
+        }
+    }
+
+    /// Creates a new device with the same extensions and features, after this one was lost.
+    /// Nothing created from the old device can be used with the new one.
+    pub fn recreate(&self, surface: Option<&Surface>) -> Self {
+        let requirements = DeviceRequirements::new()
+            .extensions(*self.device.enabled_extensions())
+            .features(*self.device.enabled_features());
+        Self::with_requirements(self.instance.clone(), &requirements, surface)
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::layout::DescriptorType;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::image::view::ImageViewAbstract;
+use vulkano::pipeline::Pipeline;
+use vulkano::sampler::Sampler;
+
+/// Something a shader reads or writes through a descriptor.
+#[derive(Clone)]
+pub enum Resource {
+    /// A uniform or storage buffer.
+    Buffer(Subbuffer<[u8]>),
+    /// A storage image, a sampled image or an input attachment.
+    Image(Arc<dyn ImageViewAbstract>),
+    /// A combined image sampler.
+    ImageSampler(Arc<dyn ImageViewAbstract>, Arc<Sampler>),
+    /// A sampler on its own, for images sampled separately.
+    Sampler(Arc<Sampler>),
+}
+
+impl Resource {
+    /// Any subbuffer, whatever its element type.
+    pub fn buffer<T: BufferContents + ?Sized>(buffer: Subbuffer<T>) -> Self {
+        Resource::Buffer(buffer.into_bytes())
+    }
+
+    pub fn image(view: Arc<dyn ImageViewAbstract>) -> Self {
+        Resource::Image(view)
+    }
+
+    pub fn image_sampler(view: Arc<dyn ImageViewAbstract>, sampler: Arc<Sampler>) -> Self {
+        Resource::ImageSampler(view, sampler)
+    }
+
+    fn accepts(&self, descriptor_type: DescriptorType) -> bool {
+        match self {
+            Resource::Buffer(_) => matches!(
+                descriptor_type,
+                DescriptorType::UniformBuffer | DescriptorType::StorageBuffer
+            ),
+            Resource::Image(_) => matches!(
+                descriptor_type,
+                DescriptorType::StorageImage
+                    | DescriptorType::SampledImage
+                    | DescriptorType::InputAttachment
+            ),
+            Resource::ImageSampler(..) => descriptor_type == DescriptorType::CombinedImageSampler,
+            Resource::Sampler(_) => descriptor_type == DescriptorType::Sampler,
+        }
+    }
+
+    fn write(self, binding: u32) -> WriteDescriptorSet {
+        match self {
+            Resource::Buffer(buffer) => WriteDescriptorSet::buffer(binding, buffer),
+            Resource::Image(view) => WriteDescriptorSet::image_view(binding, view),
+            Resource::ImageSampler(view, sampler) => {
+                WriteDescriptorSet::image_view_sampler(binding, view, sampler)
+            }
+            Resource::Sampler(sampler) => WriteDescriptorSet::sampler(binding, sampler),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Resource::Buffer(_) => "buffer",
+            Resource::Image(_) => "image",
+            Resource::ImageSampler(..) => "image and sampler",
+            Resource::Sampler(_) => "sampler",
+        }
+    }
+}
+
+/// Creates the descriptor set `set_index` of `pipeline`, with each resource of `resources` bound
+/// to the binding number paired with it. The layout comes from the pipeline, which got it from
+/// the shaders, so only the resources have to be given. Bindings the shader declares but leaves
+/// unused may be skipped, gaps in the numbering included.
+///
+/// Panics naming the binding if a resource doesn't fit the descriptor the shader declares
+/// there, e.g. a sampler where it reads a buffer.
+pub fn bind_resources<P: Pipeline + ?Sized>(
+    allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<P>,
+    set_index: u32,
+    resources: impl IntoIterator<Item = (u32, Resource)>,
+) -> Arc<PersistentDescriptorSet> {
+    let layout = pipeline
+        .layout()
+        .set_layouts()
+        .get(set_index as usize)
+        .unwrap_or_else(|| panic!("the pipeline has no descriptor set {set_index}"))
+        .clone();
+
+    let writes: Vec<_> = resources
+        .into_iter()
+        .map(|(binding, resource)| {
+            let descriptor_type = match layout.bindings().get(&binding) {
+                Some(layout_binding) => layout_binding.descriptor_type,
+                None => panic!(
+                    "set {set_index} has no binding {binding} for the {}",
+                    resource.kind()
+                ),
+            };
+            if !resource.accepts(descriptor_type) {
+                panic!(
+                    "binding {binding} of set {set_index} is a {descriptor_type:?}, not a {}",
+                    resource.kind()
+                );
+            }
+            resource.write(binding)
+        })
+        .collect();
+
+    PersistentDescriptorSet::new(allocator, layout, writes)
+        .expect("failed to create descriptor set")
+}
@@ -0,0 +1,42 @@
+//Code shared by the chapters that go beyond the official vulkano guide
+
+pub mod allocators;
+pub mod args;
+pub mod bvh;
+pub mod camera;
+pub mod capture;
+pub mod compute;
+pub mod config;
+pub mod context;
+pub mod descriptors;
+pub mod logging;
+pub mod memory;
+pub mod mesh;
+pub mod pipeline_stats;
+pub mod profiler;
+pub mod queues;
+pub mod raw;
+pub mod record;
+pub mod reduce;
+pub mod reflect;
+pub mod report;
+pub mod requirements;
+pub mod sprite;
+pub mod staging;
+pub mod stats;
+pub mod streaming;
+pub mod text;
+pub mod types;
+pub mod wait;
+pub mod window;
+
+// Chapters record what vulkano doesn't wrap with the vk types of the ash version it uses
+pub use ash;
+// Chapters build their overlays with the same egui version the runner renders with
+pub use egui_winit_vulkano::egui;
+// Same for the math types the camera hands out
+pub use glam;
+// And for the events passed to App::window_event
+pub use winit;
+// Chapters log through the subscriber create_instance sets up, with the macros from here
+pub use tracing;
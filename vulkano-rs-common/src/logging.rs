@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use tracing_subscriber::EnvFilter;
+use vulkano::instance::debug::{
+    DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+    DebugUtilsMessengerCreateInfo, Message,
+};
+use vulkano::instance::Instance;
+use vulkano::VulkanLibrary;
+
+use crate::args;
+
+/// The layer `--validation` enables, from the Vulkan SDK.
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+/// Prints the `tracing` events of this crate and the chapters to stderr: info and up by
+/// default, debug too with `--verbose`. `RUST_LOG` replaces both, e.g.
+/// `RUST_LOG=vulkano_rs_common=trace` to see every frame submitted. Does nothing when a
+/// subscriber is already set, so chapters that want their own can install it first.
+pub fn init() {
+    // Every crate of the repo starts with vulkano_rs, the libraries under them stay at warnings
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        if args::flag("--verbose") {
+            EnvFilter::new("warn,vulkano_rs=debug")
+        } else {
+            EnvFilter::new("warn,vulkano_rs=info")
+        }
+    });
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .without_time()
+        .try_init();
+}
+
+/// Whether the validation layer and the debug utils extension its messages arrive through are
+/// both installed. Says what is missing when not.
+pub fn validation_available(library: &VulkanLibrary) -> bool {
+    let has_layer = library.layer_properties().map_or(false, |mut layers| {
+        layers.any(|layer| layer.name() == VALIDATION_LAYER)
+    });
+    if !has_layer {
+        tracing::warn!("{VALIDATION_LAYER} isn't installed, running without validation");
+        return false;
+    }
+    if !library.supported_extensions().ext_debug_utils {
+        tracing::warn!("VK_EXT_debug_utils is missing, running without validation");
+        return false;
+    }
+    true
+}
+
+/// Sends the validation layer's messages to the `tracing` subscriber, errors and warnings at
+/// their own level, info at debug and verbose at trace. `instance` needs `ext_debug_utils`.
+pub fn route_validation(instance: &Arc<Instance>) {
+    let callback = Arc::new(|message: &Message<'_>| {
+        let layer = message.layer_prefix.unwrap_or("validation");
+        let kind = message.ty;
+        let description = message.description;
+        let severity = message.severity;
+        if severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+            tracing::error!(layer, ?kind, "{description}");
+        } else if severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+            tracing::warn!(layer, ?kind, "{description}");
+        } else if severity.intersects(DebugUtilsMessageSeverity::INFO) {
+            tracing::debug!(layer, ?kind, "{description}");
+        } else {
+            tracing::trace!(layer, ?kind, "{description}");
+        }
+    });
+    let create_info = DebugUtilsMessengerCreateInfo {
+        message_severity: DebugUtilsMessageSeverity::ERROR
+            | DebugUtilsMessageSeverity::WARNING
+            | DebugUtilsMessageSeverity::INFO
+            | DebugUtilsMessageSeverity::VERBOSE,
+        message_type: DebugUtilsMessageType::GENERAL
+            | DebugUtilsMessageType::VALIDATION
+            | DebugUtilsMessageType::PERFORMANCE,
+        ..DebugUtilsMessengerCreateInfo::user_callback(callback)
+    };
+
+    // The callback runs inside Vulkan calls, so it must not make any itself. Logging doesn't
+    let messenger = unsafe { DebugUtilsMessenger::new(instance.clone(), create_info) }
+        .expect("failed to create debug messenger");
+    // Messages are wanted until the process ends. The messenger holds on to the instance, which
+    // then outlives main, the driver frees both at exit
+    std::mem::forget(messenger);
+}
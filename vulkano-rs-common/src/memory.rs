@@ -0,0 +1,516 @@
+use std::mem::size_of;
+use std::sync::{Arc, Mutex};
+
+use ash::vk;
+use vulkano::buffer::sys::RawBuffer;
+use vulkano::buffer::{BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, AllocationCreationError, AllocationType, MemoryAlloc, MemoryAllocator,
+    MemoryTypeFilter, MemoryUsage, StandardMemoryAllocator, SuballocationCreateInfo,
+};
+use vulkano::memory::{
+    DedicatedAllocation, ExternalMemoryHandleTypes, MemoryHeapFlags, MemoryPropertyFlags,
+    MemoryRequirements,
+};
+use vulkano::{DeviceSize, Version, VulkanObject};
+
+use crate::args;
+
+// What the driver said about one heap
+#[derive(Clone, Copy)]
+struct HeapUsage {
+    budget: u64,
+    usage: u64,
+}
+
+/// Prints how much of every memory heap the process uses and how much it may use, to see where
+/// a chapter's buffers and images land. The numbers come from `VK_EXT_memory_budget`, without it
+/// only the heap sizes are known.
+///
+/// vulkano's allocator takes memory from the driver in large blocks and places resources inside
+/// them, so the first buffer of a kind may grow a heap by a whole block and the next ones not at
+/// all.
+pub struct MemoryReport {
+    physical_device: Arc<PhysicalDevice>,
+    // From the previous print, to show what changed since
+    last: Option<Vec<HeapUsage>>,
+}
+
+impl MemoryReport {
+    pub fn new(physical_device: &Arc<PhysicalDevice>) -> Self {
+        MemoryReport {
+            physical_device: physical_device.clone(),
+            last: None,
+        }
+    }
+
+    /// Logs every heap under `label`, with the change in usage since the previous call.
+    pub fn print(&mut self, label: &str) {
+        let heaps = &self.physical_device.memory_properties().memory_heaps;
+        let current = heap_usage(&self.physical_device);
+
+        for (index, heap) in heaps.iter().enumerate() {
+            let kind = if heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL) {
+                "device local"
+            } else {
+                "host"
+            };
+            let total = format_bytes(heap.size);
+
+            let Some(current) = &current else {
+                tracing::info!("memory {label}, heap {index} ({kind}): {total}");
+                continue;
+            };
+            let HeapUsage { budget, usage } = current[index];
+            let change = match &self.last {
+                Some(last) => {
+                    let change = usage as i64 - last[index].usage as i64;
+                    let sign = if change < 0 { "-" } else { "+" };
+                    format!(" ({sign}{})", format_bytes(change.unsigned_abs()))
+                }
+                None => String::new(),
+            };
+            tracing::info!(
+                "memory {label}, heap {index} ({kind}): {} used{change} of a {} budget, {total} \
+                 in total",
+                format_bytes(usage),
+                format_bytes(budget),
+            );
+        }
+        if current.is_none() {
+            tracing::info!("memory usage and budget need VK_EXT_memory_budget");
+        }
+
+        self.last = current;
+    }
+}
+
+// vulkano doesn't wrap the budget query, so this goes through the raw function pointers
+fn heap_usage(physical_device: &PhysicalDevice) -> Option<Vec<HeapUsage>> {
+    if !physical_device.supported_extensions().ext_memory_budget {
+        return None;
+    }
+
+    // The query is core in Vulkan 1.1, older instances need the extension it came from
+    let instance = physical_device.instance();
+    let get_memory_properties2 = if physical_device.api_version() >= Version::V1_1 {
+        instance.fns().v1_1.get_physical_device_memory_properties2
+    } else if instance
+        .enabled_extensions()
+        .khr_get_physical_device_properties2
+    {
+        instance
+            .fns()
+            .khr_get_physical_device_properties2
+            .get_physical_device_memory_properties2_khr
+    } else {
+        return None;
+    };
+
+    let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    {
+        let mut properties = vk::PhysicalDeviceMemoryProperties2::builder().push_next(&mut budget);
+        unsafe { get_memory_properties2(physical_device.handle(), &mut *properties) };
+    }
+
+    let heap_count = physical_device.memory_properties().memory_heaps.len();
+    Some(
+        (0..heap_count)
+            .map(|index| HeapUsage {
+                budget: budget.heap_budget[index],
+                usage: budget.heap_usage[index],
+            })
+            .collect(),
+    )
+}
+
+/// The kinds of host-visible memory [`host_buffer`] can put a buffer in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostMemory {
+    /// Reads go through the CPU caches, what buffers the host reads back should use.
+    Cached,
+    /// Every read goes over the bus, fine for the host writing data once for the GPU.
+    Uncached,
+}
+
+/// Creates a buffer of `length` elements that the GPU copies into and the host reads, in
+/// `memory`. Returns `None` when the device has no memory of that kind, or none left for it.
+pub fn host_buffer<T: BufferContents>(
+    allocator: &(impl MemoryAllocator + ?Sized),
+    length: u64,
+    memory: HostMemory,
+) -> Option<Subbuffer<[T]>> {
+    buffer_in_memory(allocator, length, BufferUsage::TRANSFER_DST, |flags| {
+        flags.intersects(MemoryPropertyFlags::HOST_VISIBLE)
+            && flags.intersects(MemoryPropertyFlags::HOST_CACHED) == (memory == HostMemory::Cached)
+    })
+}
+
+/// Creates a buffer of `length` elements with `usage`, in a memory type whose property flags
+/// `accept` returns true for, e.g. device-local and host-visible at once. Returns `None` when
+/// no memory type is accepted, or the accepted ones have no room left for the buffer.
+pub fn buffer_in_memory<T: BufferContents>(
+    allocator: &(impl MemoryAllocator + ?Sized),
+    length: u64,
+    usage: BufferUsage,
+    accept: impl Fn(MemoryPropertyFlags) -> bool,
+) -> Option<Subbuffer<[T]>> {
+    let device = allocator.device();
+    let raw_buffer = RawBuffer::new(
+        device.clone(),
+        BufferCreateInfo {
+            size: length * size_of::<T>() as u64,
+            usage,
+            ..Default::default()
+        },
+    )
+        .expect("failed to create buffer");
+
+    // MemoryUsage only states preferences, so the unwanted types are ruled out from the ones
+    // the buffer accepts, leaving the allocator to pick among the rest
+    let mut requirements = raw_buffer.memory_requirements().clone();
+    let memory_types = &device.physical_device().memory_properties().memory_types;
+    for (index, memory_type) in memory_types.iter().enumerate() {
+        if !accept(memory_type.property_flags) {
+            requirements.memory_type_bits &= !(1 << index);
+        }
+    }
+    if requirements.memory_type_bits == 0 {
+        return None;
+    }
+
+    // The BAR window of a device without resizable BAR is only 256 MiB, running out is expected
+    let allocation = allocator
+        .allocate(
+            requirements,
+            AllocationType::Linear,
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            Some(DedicatedAllocation::Buffer(&raw_buffer)),
+        )
+        .ok()?;
+    let buffer = raw_buffer
+        .bind_memory(allocation)
+        .map_err(|(error, _, _)| error)
+        .expect("failed to bind memory");
+
+    Some(Subbuffer::new(Arc::new(buffer)).reinterpret())
+}
+
+/// A buffer for results the host reads back, in cached memory when the device has any and in
+/// uncached memory otherwise.
+pub fn download_buffer<T: BufferContents>(
+    allocator: &(impl MemoryAllocator + ?Sized),
+    length: u64,
+) -> Subbuffer<[T]> {
+    host_buffer(allocator, length, HostMemory::Cached)
+        .or_else(|| host_buffer(allocator, length, HostMemory::Uncached))
+        .expect("no host-visible memory for the buffer")
+}
+
+/// A memory allocator that logs every allocation it makes when `--track-memory` is passed, and
+/// logs a summary of them when it is dropped or [`print_summary`](Self::print_summary) is
+/// called. Without the flag it only hands the calls on to the
+/// `StandardMemoryAllocator` inside.
+///
+/// In vulkano 0.33 a freed buffer or image returns its memory straight to the block it came
+/// from, the allocator never hears of it, so the frees can't be counted. What is still in use
+/// at the end comes from the heap usage the driver reports through `VK_EXT_memory_budget`,
+/// which includes the blocks vulkano holds on to for later allocations.
+pub struct TrackingAllocator {
+    inner: StandardMemoryAllocator,
+    // None without --track-memory
+    log: Option<Mutex<AllocationLog>>,
+}
+
+#[derive(Clone, Copy)]
+enum AllocationKind {
+    Buffer,
+    Image,
+    // Allocations made without saying what they are for
+    Other,
+}
+
+impl AllocationKind {
+    // The dedicated resource says it outright, otherwise linear memory is taken to be a buffer
+    // even though linear-tiled images use it too
+    fn of(
+        allocation_type: AllocationType,
+        dedicated_allocation: Option<&DedicatedAllocation<'_>>,
+    ) -> Self {
+        match (dedicated_allocation, allocation_type) {
+            (Some(DedicatedAllocation::Buffer(_)), _) | (None, AllocationType::Linear) => {
+                AllocationKind::Buffer
+            }
+            (Some(DedicatedAllocation::Image(_)), _) | (None, AllocationType::NonLinear) => {
+                AllocationKind::Image
+            }
+            _ => AllocationKind::Other,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AllocationKind::Buffer => "buffer",
+            AllocationKind::Image => "image",
+            AllocationKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Default)]
+struct AllocationTotals {
+    count: u64,
+    bytes: u64,
+    largest: u64,
+}
+
+struct AllocationLog {
+    physical_device: Arc<PhysicalDevice>,
+    // Indexed by AllocationKind
+    totals: [AllocationTotals; 3],
+    // Heap usage before the first allocation, the highest seen after any and the latest
+    start: Option<Vec<HeapUsage>>,
+    peak: Vec<u64>,
+    latest: Option<Vec<HeapUsage>>,
+    summarized: bool,
+}
+
+impl TrackingAllocator {
+    /// A `StandardMemoryAllocator` with the default block sizes, tracked when `--track-memory`
+    /// is passed.
+    pub fn new(device: Arc<Device>) -> Self {
+        let log = args::flag("--track-memory").then(|| {
+            let physical_device = device.physical_device().clone();
+            let start = heap_usage(&physical_device);
+            let peak = match &start {
+                Some(start) => start.iter().map(|heap| heap.usage).collect(),
+                None => Vec::new(),
+            };
+            Mutex::new(AllocationLog {
+                physical_device,
+                totals: Default::default(),
+                latest: start.clone(),
+                start,
+                peak,
+                summarized: false,
+            })
+        });
+
+        TrackingAllocator {
+            inner: StandardMemoryAllocator::new_default(device),
+            log,
+        }
+    }
+
+    fn record(
+        &self,
+        kind: AllocationKind,
+        usage: Option<MemoryUsage>,
+        result: &Result<MemoryAlloc, AllocationCreationError>,
+    ) {
+        let Some(log) = &self.log else {
+            return;
+        };
+        let allocation = match result {
+            Ok(allocation) => allocation,
+            Err(error) => {
+                tracing::warn!(kind = kind.name(), "allocation failed: {error}");
+                return;
+            }
+        };
+
+        let size = allocation.size();
+        tracing::info!(
+            kind = kind.name(),
+            size = %format_bytes(size),
+            usage = ?usage,
+            memory_type = allocation.device_memory().memory_type_index(),
+            "allocated"
+        );
+
+        let mut log = log.lock().unwrap();
+        let totals = &mut log.totals[kind as usize];
+        totals.count += 1;
+        totals.bytes += size;
+        totals.largest = totals.largest.max(size);
+
+        // Sampled after every allocation, frees in between only show up as lower usage
+        if let Some(current) = heap_usage(&log.physical_device) {
+            for (peak, heap) in log.peak.iter_mut().zip(&current) {
+                *peak = (*peak).max(heap.usage);
+            }
+            log.latest = Some(current);
+        }
+    }
+
+    /// Logs the allocations made so far with `--track-memory`, only the first time it is
+    /// called. Dropping the allocator calls it, so this is only needed where it lives until the
+    /// process exits, as in the windowed runner.
+    pub fn print_summary(&self) {
+        let Some(log) = &self.log else {
+            return;
+        };
+        let mut log = log.lock().unwrap();
+        if log.summarized {
+            return;
+        }
+        log.summarized = true;
+
+        for kind in [
+            AllocationKind::Buffer,
+            AllocationKind::Image,
+            AllocationKind::Other,
+        ] {
+            let totals = &log.totals[kind as usize];
+            if totals.count == 0 {
+                continue;
+            }
+            tracing::info!(
+                "allocated {} {}s, {} in total, the largest {}",
+                totals.count,
+                kind.name(),
+                format_bytes(totals.bytes),
+                format_bytes(totals.largest)
+            );
+        }
+
+        let (Some(start), Some(latest)) = (&log.start, &log.latest) else {
+            tracing::info!("peak and live memory usage need VK_EXT_memory_budget");
+            return;
+        };
+        for (index, ((start, latest), peak)) in start.iter().zip(latest).zip(&log.peak).enumerate()
+        {
+            // The usage of the whole process, other allocators' memory included
+            let live = latest.usage as i64 - start.usage as i64;
+            let sign = if live < 0 { "-" } else { "+" };
+            tracing::info!(
+                "heap {index}: {} at the peak, {} still in use ({sign}{} since the start)",
+                format_bytes(*peak),
+                format_bytes(latest.usage),
+                format_bytes(live.unsigned_abs())
+            );
+        }
+    }
+}
+
+impl Drop for TrackingAllocator {
+    fn drop(&mut self) {
+        self.print_summary();
+    }
+}
+
+unsafe impl DeviceOwned for TrackingAllocator {
+    fn device(&self) -> &Arc<Device> {
+        self.inner.device()
+    }
+}
+
+// Every call goes to the inner allocator, the ones that allocate are recorded on the way back
+unsafe impl MemoryAllocator for TrackingAllocator {
+    fn find_memory_type_index(
+        &self,
+        memory_type_bits: u32,
+        filter: MemoryTypeFilter,
+    ) -> Option<u32> {
+        self.inner.find_memory_type_index(memory_type_bits, filter)
+    }
+
+    fn allocate_from_type(
+        &self,
+        memory_type_index: u32,
+        create_info: SuballocationCreateInfo,
+    ) -> Result<MemoryAlloc, AllocationCreationError> {
+        let kind = AllocationKind::of(create_info.allocation_type, None);
+        let result = self
+            .inner
+            .allocate_from_type(memory_type_index, create_info);
+        self.record(kind, None, &result);
+        result
+    }
+
+    unsafe fn allocate_from_type_unchecked(
+        &self,
+        memory_type_index: u32,
+        create_info: SuballocationCreateInfo,
+        never_allocate: bool,
+    ) -> Result<MemoryAlloc, AllocationCreationError> {
+        let kind = AllocationKind::of(create_info.allocation_type, None);
+        let result =
+            self.inner
+                .allocate_from_type_unchecked(memory_type_index, create_info, never_allocate);
+        self.record(kind, None, &result);
+        result
+    }
+
+    fn allocate(
+        &self,
+        requirements: MemoryRequirements,
+        allocation_type: AllocationType,
+        create_info: AllocationCreateInfo,
+        dedicated_allocation: Option<DedicatedAllocation<'_>>,
+    ) -> Result<MemoryAlloc, AllocationCreationError> {
+        let kind = AllocationKind::of(allocation_type, dedicated_allocation.as_ref());
+        let usage = create_info.usage;
+        let result = self.inner.allocate(
+            requirements,
+            allocation_type,
+            create_info,
+            dedicated_allocation,
+        );
+        self.record(kind, Some(usage), &result);
+        result
+    }
+
+    unsafe fn allocate_unchecked(
+        &self,
+        requirements: MemoryRequirements,
+        allocation_type: AllocationType,
+        create_info: AllocationCreateInfo,
+        dedicated_allocation: Option<DedicatedAllocation<'_>>,
+    ) -> Result<MemoryAlloc, AllocationCreationError> {
+        let kind = AllocationKind::of(allocation_type, dedicated_allocation.as_ref());
+        let usage = create_info.usage;
+        let result = self.inner.allocate_unchecked(
+            requirements,
+            allocation_type,
+            create_info,
+            dedicated_allocation,
+        );
+        self.record(kind, Some(usage), &result);
+        result
+    }
+
+    unsafe fn allocate_dedicated_unchecked(
+        &self,
+        memory_type_index: u32,
+        allocation_size: DeviceSize,
+        dedicated_allocation: Option<DedicatedAllocation<'_>>,
+        export_handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<MemoryAlloc, AllocationCreationError> {
+        let kind = AllocationKind::of(AllocationType::Unknown, dedicated_allocation.as_ref());
+        let result = self.inner.allocate_dedicated_unchecked(
+            memory_type_index,
+            allocation_size,
+            dedicated_allocation,
+            export_handle_types,
+        );
+        self.record(kind, None, &result);
+        result
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= 1024.0 * MIB {
+        format!("{:.2} GiB", bytes as f64 / (1024.0 * MIB))
+    } else if bytes as f64 >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB)
+    } else {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    }
+}
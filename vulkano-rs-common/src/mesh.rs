@@ -0,0 +1,102 @@
+use std::f32::consts::PI;
+
+use crate::types::PosNormalUv;
+
+/// A cube from -1 to 1 as vertices and triangle indices, with a separate set of vertices per face
+/// for flat normals. The triangles wind counter-clockwise seen from outside.
+pub fn cube() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for axis in 0..3 {
+        for sign in [-1.0f32, 1.0] {
+            let mut normal = [0.0; 3];
+            normal[axis] = sign;
+            // Two directions along the face, ordered so the corners wind counter-clockwise
+            // when seen from outside
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let base = vertices.len() as u32;
+            for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let mut position = normal;
+                position[u] = a * sign;
+                position[v] = b;
+                vertices.push(PosNormalUv {
+                    position,
+                    normal,
+                    ..Default::default()
+                });
+            }
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    (vertices, indices)
+}
+
+/// A UV sphere of radius 1 with `stacks` rings from pole to pole and `sectors` slices around,
+/// like in chapter 8. The triangles wind counter-clockwise seen from outside.
+pub fn sphere(stacks: u32, sectors: u32) -> (Vec<PosNormalUv>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    for i in 0..=stacks {
+        let phi = PI * i as f32 / stacks as f32;
+        for j in 0..=sectors {
+            let theta = 2.0 * PI * j as f32 / sectors as f32;
+            let position = [phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin()];
+            vertices.push(PosNormalUv {
+                position,
+                normal: position,
+                ..Default::default()
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    for i in 0..stacks {
+        for j in 0..sectors {
+            let a = i * (sectors + 1) + j;
+            let b = a + sectors + 1;
+            indices.extend([a, b + 1, b, a, a + 1, b + 1]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    // Every triangle's face normal has to point the same way as its vertex normals, which is
+    // what counter-clockwise from outside means for a mesh around the origin
+    fn check_winding(vertices: &[PosNormalUv], indices: &[u32]) {
+        assert_eq!(indices.len() % 3, 0);
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(vertices[triangle[i] as usize].position));
+            let face_normal = (b - a).cross(c - a);
+            // The triangles touching a pole of the sphere have two vertices on it
+            if face_normal.length() < 1e-6 {
+                continue;
+            }
+            let normal = Vec3::from(vertices[triangle[0] as usize].normal);
+            assert!(face_normal.dot(normal) > 0.0, "triangle {triangle:?} faces inward");
+        }
+    }
+
+    #[test]
+    fn cube_faces_outward() {
+        let (vertices, indices) = cube();
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), 36);
+        check_winding(&vertices, &indices);
+    }
+
+    #[test]
+    fn sphere_faces_outward() {
+        let (vertices, indices) = sphere(12, 24);
+        assert_eq!(vertices.len(), 13 * 25);
+        for vertex in &vertices {
+            assert!((Vec3::from(vertex.position).length() - 1.0).abs() < 1e-5);
+        }
+        check_winding(&vertices, &indices);
+    }
+}
@@ -0,0 +1,154 @@
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::device::Queue;
+use vulkano::query::{
+    QueryControlFlags, QueryPipelineStatisticFlags, QueryPool, QueryPoolCreateInfo,
+    QueryResultFlags, QueryType,
+};
+use vulkano::sync::GpuFuture;
+
+use crate::allocators::Allocators;
+
+// Passes measured per frame, any past this are recorded without a query
+const MAX_PASSES: u32 = 16;
+// The counters each query returns, in the order of their flag bits, plus the availability
+const VALUES_PER_QUERY: usize = 4;
+
+/// Counts how many times the vertex, fragment and compute shaders ran in named passes of a
+/// frame, and logs them once per second. Needs the `pipeline_statistics_query` feature.
+pub struct PipelineStats {
+    queue: Arc<Queue>,
+    query_pool: Arc<QueryPool>,
+    allocators: Allocators,
+    // The frame in flight being recorded, each one has MAX_PASSES queries
+    slot: Cell<u32>,
+    // Names of the passes measured in each slot, in query order
+    passes: RefCell<Vec<Vec<String>>>,
+    last_log: Instant,
+}
+
+impl PipelineStats {
+    /// `frames_in_flight` is the number of slots to keep queries for, usually the number of
+    /// swapchain images.
+    pub fn new(allocators: &Allocators, queue: &Arc<Queue>, frames_in_flight: u32) -> Self {
+        let device = queue.device();
+
+        PipelineStats {
+            queue: queue.clone(),
+            query_pool: QueryPool::new(
+                device.clone(),
+                QueryPoolCreateInfo {
+                    query_count: frames_in_flight * MAX_PASSES,
+                    ..QueryPoolCreateInfo::query_type(QueryType::PipelineStatistics(
+                        QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS
+                            | QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS
+                            | QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS,
+                    ))
+                },
+            )
+                .expect("failed to create query pool"),
+            allocators: allocators.clone(),
+            slot: Cell::new(0),
+            passes: RefCell::new(vec![Vec::new(); frames_in_flight as usize]),
+            last_log: Instant::now(),
+        }
+    }
+
+    /// Collects what `slot` measured the last time it was used, then resets its queries for
+    /// the frame about to be recorded.
+    pub fn begin_frame(&mut self, slot: u32, before: Box<dyn GpuFuture>) -> Box<dyn GpuFuture> {
+        let first_query = slot * MAX_PASSES;
+
+        let measured = std::mem::take(&mut self.passes.get_mut()[slot as usize]);
+        if !measured.is_empty() && self.last_log.elapsed() >= Duration::from_secs(1) {
+            let mut results = vec![0u64; measured.len() * VALUES_PER_QUERY];
+            self.query_pool
+                .queries_range(first_query..first_query + measured.len() as u32)
+                .unwrap()
+                .get_results(&mut results, QueryResultFlags::WITH_AVAILABILITY)
+                .unwrap();
+            log_results(&measured, &results);
+            self.last_log = Instant::now();
+        }
+        self.slot.set(slot);
+
+        // Queries can only be reset outside of a render pass, so it's done ahead of the
+        // chapter's own command buffer
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        unsafe {
+            builder
+                .reset_query_pool(
+                    self.query_pool.clone(),
+                    first_query..first_query + MAX_PASSES,
+                )
+                .unwrap();
+        }
+
+        before
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    /// Records the commands of `record` inside a query, counted as `pass`.
+    pub fn measure<F>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pass: &str,
+        record: F,
+    ) where
+        F: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    {
+        let slot = self.slot.get();
+        let index = {
+            let mut passes = self.passes.borrow_mut();
+            let measured = &mut passes[slot as usize];
+            if measured.len() as u32 == MAX_PASSES {
+                None
+            } else {
+                measured.push(pass.to_owned());
+                Some(measured.len() as u32 - 1)
+            }
+        };
+
+        let query = match index {
+            Some(index) => slot * MAX_PASSES + index,
+            None => return record(builder),
+        };
+
+        unsafe {
+            builder
+                .begin_query(self.query_pool.clone(), query, QueryControlFlags::empty())
+                .unwrap();
+        }
+        record(builder);
+        builder.end_query(self.query_pool.clone(), query).unwrap();
+    }
+}
+
+fn log_results(passes: &[String], results: &[u64]) {
+    for (pass, values) in passes.iter().zip(results.chunks(VALUES_PER_QUERY)) {
+        // The GPU may still be on that frame, better to skip a pass than to wait for it
+        if values[3] == 0 {
+            tracing::debug!(pass, "pipeline statistics not ready");
+            continue;
+        }
+        tracing::info!(
+            pass,
+            vertex = values[0],
+            fragment = values[1],
+            compute = values[2],
+            "pipeline statistics"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::SamplerCreateInfo;
+use vulkano::sync::{GpuFuture, PipelineStage};
+
+use crate::allocators::Allocators;
+use crate::sprite::SpriteBatch;
+use crate::text::TextRenderer;
+use crate::window::Renderer;
+
+// Passes timed per frame, any past this are recorded without timestamps
+const MAX_PASSES: u32 = 16;
+// Frames kept in the history, one bar each in the graph
+const HISTORY: usize = 120;
+
+// Layout of the graph in the bottom left corner of the window, in pixels
+const MARGIN: f32 = 12.0;
+const PADDING: f32 = 8.0;
+const BAR_WIDTH: f32 = 3.0;
+const GRAPH_HEIGHT: f32 = 120.0;
+// The graph is never scaled below one frame at 60 Hz, so a light frame doesn't fill it
+const MIN_SCALE: f32 = 1000.0 / 60.0;
+
+// Picked in turn by the passes, in the order they were first measured
+const COLORS: [[f32; 4]; 8] = [
+    [0.30, 0.65, 1.00, 1.0],
+    [1.00, 0.55, 0.20, 1.0],
+    [0.40, 0.85, 0.40, 1.0],
+    [0.95, 0.35, 0.45, 1.0],
+    [0.70, 0.50, 0.95, 1.0],
+    [0.95, 0.85, 0.30, 1.0],
+    [0.35, 0.85, 0.85, 1.0],
+    [0.80, 0.80, 0.80, 1.0],
+];
+
+/// Times named passes of a frame on the GPU with timestamp queries, and keeps how long each
+/// one took over the last frames. [`ProfilerGraph`] draws that history.
+pub struct GpuProfiler {
+    queue: Arc<Queue>,
+    query_pool: Arc<QueryPool>,
+    allocators: Allocators,
+    // Nanoseconds per timestamp tick
+    timestamp_period: f32,
+    // The frame in flight being recorded, each one has two queries per pass
+    slot: Cell<u32>,
+    // Names of the passes measured in each slot, in query order
+    passes: RefCell<Vec<Vec<String>>>,
+    // Every pass seen so far, a frame's timings are indexed the same way
+    names: Vec<String>,
+    // Milliseconds per pass of the last frames, oldest first
+    history: VecDeque<Vec<f32>>,
+}
+
+impl GpuProfiler {
+    /// `frames_in_flight` is the number of slots to keep timestamps for, usually the number of
+    /// swapchain images. Returns `None` if the queue can't write timestamps.
+    pub fn new(allocators: &Allocators, queue: &Arc<Queue>, frames_in_flight: u32) -> Option<Self> {
+        let device = queue.device();
+        let physical_device = device.physical_device();
+
+        let supports_timestamps = physical_device.queue_family_properties()
+            [queue.queue_family_index() as usize]
+            .timestamp_valid_bits
+            .is_some();
+        if !supports_timestamps {
+            return None;
+        }
+
+        Some(GpuProfiler {
+            queue: queue.clone(),
+            query_pool: QueryPool::new(
+                device.clone(),
+                QueryPoolCreateInfo {
+                    query_count: frames_in_flight * MAX_PASSES * 2,
+                    ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+                },
+            )
+                .expect("failed to create query pool"),
+            allocators: allocators.clone(),
+            timestamp_period: physical_device.properties().timestamp_period,
+            slot: Cell::new(0),
+            passes: RefCell::new(vec![Vec::new(); frames_in_flight as usize]),
+            names: Vec::new(),
+            history: VecDeque::with_capacity(HISTORY),
+        })
+    }
+
+    /// Collects the timestamps `slot` wrote the last time it was used into the history, then
+    /// resets its queries for the frame about to be recorded.
+    pub fn begin_frame(&mut self, slot: u32, before: Box<dyn GpuFuture>) -> Box<dyn GpuFuture> {
+        let first_query = slot * MAX_PASSES * 2;
+
+        let measured = std::mem::take(&mut self.passes.get_mut()[slot as usize]);
+        if !measured.is_empty() {
+            // A timestamp and its availability for the start and end of every pass
+            let mut results = vec![0u64; measured.len() * 4];
+            self.query_pool
+                .queries_range(first_query..first_query + measured.len() as u32 * 2)
+                .unwrap()
+                .get_results(&mut results, QueryResultFlags::WITH_AVAILABILITY)
+                .unwrap();
+
+            // The GPU may still be on that frame, it's left out rather than waited for
+            let available = results.chunks(2).all(|query| query[1] != 0);
+            if available {
+                let mut frame = vec![0.0; self.names.len()];
+                for (pass, timestamps) in measured.iter().zip(results.chunks(4)) {
+                    let ticks = timestamps[2].saturating_sub(timestamps[0]);
+                    let milliseconds = ticks as f32 * self.timestamp_period / 1_000_000.0;
+
+                    let index = match self.names.iter().position(|name| name == pass) {
+                        Some(index) => index,
+                        None => {
+                            self.names.push(pass.clone());
+                            frame.push(0.0);
+                            self.names.len() - 1
+                        }
+                    };
+                    // A pass measured several times in a frame adds up
+                    frame[index] += milliseconds;
+                }
+
+                if self.history.len() == HISTORY {
+                    self.history.pop_front();
+                }
+                self.history.push_back(frame);
+            }
+        }
+        self.slot.set(slot);
+
+        // Same as for the pipeline statistics, queries are reset outside of any render pass
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        unsafe {
+            builder
+                .reset_query_pool(
+                    self.query_pool.clone(),
+                    first_query..first_query + MAX_PASSES * 2,
+                )
+                .unwrap();
+        }
+
+        before
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    /// Records the commands of `record` between two timestamps, timed as `pass`.
+    pub fn measure<F>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pass: &str,
+        record: F,
+    ) where
+        F: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    {
+        let slot = self.slot.get();
+        let index = {
+            let mut passes = self.passes.borrow_mut();
+            let measured = &mut passes[slot as usize];
+            if measured.len() as u32 == MAX_PASSES {
+                None
+            } else {
+                measured.push(pass.to_owned());
+                Some(measured.len() as u32 - 1)
+            }
+        };
+
+        let query = match index {
+            Some(index) => (slot * MAX_PASSES + index) * 2,
+            None => return record(builder),
+        };
+
+        // The start is taken as soon as the GPU gets to the pass, the end once everything
+        // before it has finished. Passes can overlap on the GPU, so their times may add up to
+        // more than the frame
+        unsafe {
+            builder
+                .write_timestamp(self.query_pool.clone(), query, PipelineStage::TopOfPipe)
+                .unwrap();
+        }
+        record(builder);
+        unsafe {
+            builder
+                .write_timestamp(
+                    self.query_pool.clone(),
+                    query + 1,
+                    PipelineStage::BottomOfPipe,
+                )
+                .unwrap();
+        }
+    }
+
+    /// The passes measured so far, in the order they were first seen.
+    pub fn passes(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The average time of the pass at `index` in [`GpuProfiler::passes`] over the history,
+    /// in milliseconds.
+    pub fn average(&self, index: usize) -> f32 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        let total: f32 = self
+            .history
+            .iter()
+            .map(|frame| frame.get(index).copied().unwrap_or(0.0))
+            .sum();
+        total / self.history.len() as f32
+    }
+}
+
+/// Draws the history of a [`GpuProfiler`] over the swapchain image: a stacked bar of pass
+/// times for every frame, and the average of each pass next to its color.
+pub struct ProfilerGraph {
+    queue: Arc<Queue>,
+    render_pass: Arc<RenderPass>,
+    allocators: Allocators,
+    sprites: SpriteBatch,
+    text: TextRenderer,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+}
+
+impl ProfilerGraph {
+    /// A graph drawing into swapchain images of `format`.
+    pub fn new(allocators: &Allocators, queue: &Arc<Queue>, format: Format) -> Self {
+        let device = queue.device();
+
+        // Loads what the chapter drew and draws on top of it
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Load,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+        let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+
+        ProfilerGraph {
+            queue: queue.clone(),
+            sprites: SpriteBatch::new(
+                allocators,
+                queue,
+                subpass.clone(),
+                SamplerCreateInfo::default(),
+            ),
+            text: TextRenderer::new(allocators, queue, subpass, 14.0),
+            render_pass,
+            allocators: allocators.clone(),
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+        }
+    }
+
+    /// Creates the framebuffers again for the renderer's current swapchain images.
+    pub fn resize(&mut self, renderer: &Renderer) {
+        self.extent = renderer.swapchain.image_extent();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    /// Draws the graph over the swapchain image at `image_index`, after `before`.
+    pub fn draw(
+        &mut self,
+        profiler: &GpuProfiler,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let passes = profiler.passes();
+        let line_height = self.text.line_height();
+        let width = HISTORY as f32 * BAR_WIDTH;
+        let height = GRAPH_HEIGHT + PADDING + passes.len() as f32 * line_height;
+        let left = MARGIN;
+        let top = self.extent[1] as f32 - MARGIN - height - 2.0 * PADDING;
+        let bottom = top + PADDING + GRAPH_HEIGHT;
+
+        self.sprites.rect(
+            [left, top],
+            [width + 2.0 * PADDING, height + 2.0 * PADDING],
+            [0.0, 0.0, 0.0, 0.6],
+        );
+
+        // The tallest frame fills the graph, so spikes stay in view
+        let scale = profiler
+            .history
+            .iter()
+            .map(|frame| frame.iter().sum::<f32>())
+            .fold(MIN_SCALE, f32::max);
+        let pixels_per_millisecond = GRAPH_HEIGHT / scale;
+
+        // The newest frame on the right, each pass stacked on top of the previous one
+        let first_bar = HISTORY - profiler.history.len();
+        for (i, frame) in profiler.history.iter().enumerate() {
+            let x = left + PADDING + (first_bar + i) as f32 * BAR_WIDTH;
+            let mut y = bottom;
+            for (pass, milliseconds) in frame.iter().enumerate() {
+                let bar_height = milliseconds * pixels_per_millisecond;
+                y -= bar_height;
+                self.sprites.rect(
+                    [x, y],
+                    [BAR_WIDTH - 1.0, bar_height],
+                    COLORS[pass % COLORS.len()],
+                );
+            }
+        }
+
+        // A line at 60 Hz, the budget most displays give a frame
+        let budget = bottom - MIN_SCALE * pixels_per_millisecond;
+        self.sprites
+            .rect([left + PADDING, budget], [width, 1.0], [1.0, 1.0, 1.0, 0.4]);
+        self.text.add(
+            &format!("{scale:.1} ms"),
+            [left + PADDING, top + PADDING],
+            [1.0, 1.0, 1.0, 0.8],
+        );
+
+        for (index, pass) in passes.iter().enumerate() {
+            let y = bottom + PADDING + index as f32 * line_height;
+            let swatch = line_height * 0.6;
+            self.sprites.rect(
+                [left + PADDING, y + (line_height - swatch) / 2.0],
+                [swatch, swatch],
+                COLORS[index % COLORS.len()],
+            );
+            self.text.add(
+                &format!("{pass}: {:.2} ms", profiler.average(index)),
+                [left + PADDING + line_height, y],
+                [1.0, 1.0, 1.0, 0.9],
+            );
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![None],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap();
+        self.sprites.draw(&mut builder, self.extent);
+        self.text.draw(&mut builder, self.extent);
+        builder.end_render_pass().unwrap();
+
+        before
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+}
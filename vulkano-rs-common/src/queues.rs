@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Queue, QueueCreateInfo, QueueFlags};
+use vulkano::swapchain::Surface;
+
+/// The queue families a device is created with, by what they are used for. Families that
+/// only do one thing usually map to separate hardware, the DMA engines for transfers for
+/// instance, and work submitted to them can run alongside the graphics queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueuePlan {
+    /// Graphics and compute.
+    pub graphics: u32,
+    /// Presents to the surface, if there is one. The graphics family whenever it can, so
+    /// swapchain images stay on a single family.
+    pub present: Option<u32>,
+    /// Compute without graphics, for async compute.
+    pub compute: Option<u32>,
+    /// Transfers only, for uploads that don't hold up rendering.
+    pub transfer: Option<u32>,
+}
+
+impl QueuePlan {
+    /// Picks the families of `physical_device`, `None` if it has no graphics and compute family,
+    /// or none presenting to `surface` when one is given.
+    pub fn new(physical_device: &PhysicalDevice, surface: Option<&Surface>) -> Option<Self> {
+        let families = physical_device.queue_family_properties();
+        let find = |accept: &dyn Fn(usize, QueueFlags) -> bool| {
+            families
+                .iter()
+                .enumerate()
+                .position(|(i, family)| accept(i, family.queue_flags))
+                .map(|i| i as u32)
+        };
+
+        let is_graphics =
+            |flags: QueueFlags| flags.contains(QueueFlags::GRAPHICS | QueueFlags::COMPUTE);
+        let presents = |i: usize| {
+            surface.map_or(false, |surface| {
+                physical_device
+                    .surface_support(i as u32, surface)
+                    .unwrap_or(false)
+            })
+        };
+
+        // Some devices present from a family other than the graphics one. Those are rare, a
+        // family doing both is preferred so the swapchain images never change hands
+        let (graphics, present) = match surface {
+            None => (find(&|_, flags| is_graphics(flags))?, None),
+            Some(_) => match find(&|i, flags| is_graphics(flags) && presents(i)) {
+                Some(family) => (family, Some(family)),
+                None => (
+                    find(&|_, flags| is_graphics(flags))?,
+                    Some(find(&|i, _| presents(i))?),
+                ),
+            },
+        };
+        let compute = find(&|_, flags| {
+            flags.intersects(QueueFlags::COMPUTE) && !flags.intersects(QueueFlags::GRAPHICS)
+        });
+        // Graphics and compute queues can do transfers too, a family that can't is the DMA one
+        let transfer = find(&|_, flags| {
+            flags.intersects(QueueFlags::TRANSFER)
+                && !flags.intersects(QueueFlags::GRAPHICS | QueueFlags::COMPUTE)
+        });
+
+        Some(QueuePlan {
+            graphics,
+            present,
+            compute,
+            transfer,
+        })
+    }
+
+    /// One queue of each family in the plan, to pass to `DeviceCreateInfo`. A family with
+    /// several roles only gets the one queue.
+    pub fn queue_create_infos(&self) -> Vec<QueueCreateInfo> {
+        self.families()
+            .map(|queue_family_index| QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Sorts the queues a device was created with from [`QueuePlan::queue_create_infos`] by
+    /// role.
+    pub fn assign(&self, queues: impl IntoIterator<Item = Arc<Queue>>) -> Queues {
+        let queues: Vec<_> = queues.into_iter().collect();
+        let find = |family: u32| {
+            queues
+                .iter()
+                .find(|queue| queue.queue_family_index() == family)
+                .cloned()
+        };
+
+        let graphics = find(self.graphics).unwrap();
+        let present = self
+            .present
+            .and_then(find)
+            .unwrap_or_else(|| graphics.clone());
+        let compute = self
+            .compute
+            .and_then(find)
+            .unwrap_or_else(|| graphics.clone());
+        let transfer = self
+            .transfer
+            .and_then(find)
+            .unwrap_or_else(|| compute.clone());
+        Queues {
+            graphics,
+            present,
+            compute,
+            transfer,
+        }
+    }
+
+    fn families(&self) -> impl Iterator<Item = u32> {
+        let mut families = vec![self.graphics];
+        for family in [self.present, self.compute, self.transfer]
+            .into_iter()
+            .flatten()
+        {
+            if !families.contains(&family) {
+                families.push(family);
+            }
+        }
+        families.into_iter()
+    }
+}
+
+/// The queues of a device by role. Without a dedicated family for a role, its queue is the one
+/// of the closest role that can do the work, so chapters can always submit to the queue named
+/// after what they do.
+#[derive(Clone)]
+pub struct Queues {
+    pub graphics: Arc<Queue>,
+    /// The graphics queue, unless another family has to present.
+    pub present: Arc<Queue>,
+    /// The graphics queue if there is no compute-only family.
+    pub compute: Arc<Queue>,
+    /// The compute queue if there is no transfer-only family.
+    pub transfer: Arc<Queue>,
+}
+
+impl Queues {
+    /// Whether async compute work runs on its own queue, rather than the graphics one.
+    pub fn has_async_compute(&self) -> bool {
+        !Arc::ptr_eq(&self.compute, &self.graphics)
+    }
+
+    /// Whether transfers have a queue of their own.
+    pub fn has_dedicated_transfer(&self) -> bool {
+        !Arc::ptr_eq(&self.transfer, &self.compute)
+    }
+}
@@ -0,0 +1,158 @@
+use std::ptr;
+use std::sync::Arc;
+
+use ash::vk;
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::VulkanObject;
+
+use crate::wait;
+
+/// A primary command buffer recorded through the raw function pointers, for the commands
+/// `AutoCommandBufferBuilder` has no method for. It comes from a pool of its own, destroyed
+/// along with it.
+///
+/// vulkano knows nothing of what gets recorded here. The caller keeps every object the commands
+/// use alive until the GPU is done with them, records the barriers vulkano would have worked
+/// out, and keeps the command buffer itself around until then too.
+///
+/// ```ignore
+/// let commands = RawCommandBuffer::begin(&device, queue.queue_family_index());
+/// unsafe {
+///     (device.fns().v1_0.cmd_dispatch)(commands.handle(), 64, 1, 1);
+/// }
+/// commands.submit_and_wait(&queue);
+/// ```
+pub struct RawCommandBuffer {
+    device: Arc<Device>,
+    pool: vk::CommandPool,
+    handle: vk::CommandBuffer,
+}
+
+impl RawCommandBuffer {
+    /// Allocates a command buffer for queues of `queue_family_index` and begins recording, for
+    /// a single submission.
+    pub fn begin(device: &Arc<Device>, queue_family_index: u32) -> Self {
+        let fns = device.fns();
+
+        // Transient: the pool's one buffer is recorded once and freed with it
+        let pool_info = vk::CommandPoolCreateInfo::builder()
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+            .queue_family_index(queue_family_index);
+        let mut pool = vk::CommandPool::null();
+        unsafe {
+            (fns.v1_0.create_command_pool)(device.handle(), &*pool_info, ptr::null(), &mut pool)
+        }
+            .result()
+            .expect("failed to create command pool");
+
+        let allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let mut handle = vk::CommandBuffer::null();
+        unsafe {
+            (fns.v1_0.allocate_command_buffers)(device.handle(), &*allocate_info, &mut handle)
+        }
+            .result()
+            .expect("failed to allocate command buffer");
+
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe { (fns.v1_0.begin_command_buffer)(handle, &*begin_info) }
+            .result()
+            .expect("failed to begin command buffer");
+
+        RawCommandBuffer {
+            device: device.clone(),
+            pool,
+            handle,
+        }
+    }
+
+    /// What the `cmd_*` functions record into, and [`queue_submit`] takes once recording ended.
+    pub fn handle(&self) -> vk::CommandBuffer {
+        self.handle
+    }
+
+    /// Ends recording, before submitting with [`queue_submit`].
+    pub fn end(&self) {
+        unsafe { (self.device.fns().v1_0.end_command_buffer)(self.handle) }
+            .result()
+            .expect("failed to end command buffer");
+    }
+
+    /// Ends recording, submits to `queue` and blocks until the GPU is done with it, giving up
+    /// after `--gpu-timeout` like [`wait::fence`](crate::wait::fence).
+    pub fn submit_and_wait(&self, queue: &Arc<Queue>) {
+        self.end();
+
+        let fns = self.device.fns();
+        let fence_info = vk::FenceCreateInfo::builder();
+        let mut fence = vk::Fence::null();
+        unsafe {
+            (fns.v1_0.create_fence)(
+                self.device.handle(),
+                &*fence_info,
+                ptr::null(),
+                &mut fence,
+            )
+        }
+            .result()
+            .expect("failed to create fence");
+
+        let command_buffers = [self.handle];
+        let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+        unsafe {
+            queue_submit(queue, &[*submit_info], fence).expect("failed to submit commands");
+        }
+        wait::with_timeout(&self.device, Some(queue.as_ref()), |step| {
+            let result = unsafe {
+                (fns.v1_0.wait_for_fences)(
+                    self.device.handle(),
+                    1,
+                    &fence,
+                    vk::TRUE,
+                    step.as_nanos() as u64,
+                )
+            };
+            match result {
+                vk::Result::SUCCESS => true,
+                vk::Result::TIMEOUT => false,
+                error => panic!("failed to wait for fence: {error}"),
+            }
+        });
+        unsafe { (fns.v1_0.destroy_fence)(self.device.handle(), fence, ptr::null()) };
+    }
+}
+
+impl Drop for RawCommandBuffer {
+    fn drop(&mut self) {
+        // Frees the command buffer too
+        unsafe {
+            (self.device.fns().v1_0.destroy_command_pool)(
+                self.device.handle(),
+                self.pool,
+                ptr::null(),
+            )
+        };
+    }
+}
+
+/// Submits to `queue` through `vkQueueSubmit`, while holding vulkano's lock on the queue so
+/// none of its own submissions can happen at the same time.
+///
+/// # Safety
+///
+/// Everything `vkQueueSubmit` requires: the command buffers have ended recording and
+/// everything they and the semaphores refer to stays alive until the GPU is done.
+pub unsafe fn queue_submit(
+    queue: &Arc<Queue>,
+    submits: &[vk::SubmitInfo],
+    fence: vk::Fence,
+) -> Result<(), vk::Result> {
+    let fns = queue.device().fns();
+    queue.with(|_guard| {
+        (fns.v1_0.queue_submit)(queue.handle(), submits.len() as u32, submits.as_ptr(), fence)
+            .result()
+    })
+}
@@ -0,0 +1,174 @@
+//Writing the frames of a headless animation to disk, as numbered PNGs or as a video encoded by
+//ffmpeg
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CopyImageToBufferInfo, PrimaryAutoCommandBuffer,
+};
+use vulkano::format::Format;
+use vulkano::image::ImageAccess;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+
+use crate::args;
+
+/// The format recorded images have to be in: four bytes per pixel in the order PNG files and
+/// ffmpeg's `rgba` input expect, already sRGB encoded like both of them assume.
+pub const FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+// Where the frames go
+enum Output {
+    Png(PathBuf),
+    Ffmpeg { child: Child, path: String },
+}
+
+/// Copies rendered images back to the host and writes them out. Chosen on the command line:
+/// `--frames` (120 by default) at `--fps` (30 by default), saved as PNGs in the `--output`
+/// directory (`frames` by default), or encoded into the file given to `--video` by an `ffmpeg`
+/// found on the `PATH`.
+pub struct Recorder {
+    pub frames: u32,
+    pub fps: u32,
+    extent: [u32; 2],
+    buffer: Subbuffer<[u8]>,
+    output: Output,
+}
+
+impl Recorder {
+    pub fn from_args(memory_allocator: &(impl MemoryAllocator + ?Sized), extent: [u32; 2]) -> Self {
+        let frames = args::value::<u32>("--frames").unwrap_or(120);
+        let fps = args::value::<u32>("--fps").unwrap_or(30).max(1);
+        let [width, height] = extent;
+
+        let output = match args::value::<String>("--video") {
+            Some(path) => {
+                // yuv420p stores color at half resolution, and encoders reject odd sizes for it.
+                // ffmpeg crops the last column or row instead
+                let even = [width, height].map(|size| size & !1);
+                if even != extent {
+                    tracing::warn!(
+                        "yuv420p needs an even width and height, {width}x{height} is encoded as \
+                         {}x{}",
+                        even[0],
+                        even[1]
+                    );
+                }
+                // Raw frames go in through stdin, ffmpeg works out the container and codec from
+                // the file name
+                let child = Command::new("ffmpeg")
+                    .args(["-y", "-loglevel", "error", "-f", "rawvideo"])
+                    .args(["-pixel_format", "rgba"])
+                    .args(["-video_size", &format!("{width}x{height}")])
+                    .args(["-framerate", &fps.to_string()])
+                    .args(["-i", "-", "-vf", &format!("crop={}:{}:0:0", even[0], even[1])])
+                    .args(["-pix_fmt", "yuv420p", &path])
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .unwrap_or_else(|err| panic!("failed to start ffmpeg: {err}"));
+                Output::Ffmpeg { child, path }
+            }
+            None => {
+                let directory =
+                    PathBuf::from(args::value::<String>("--output").unwrap_or("frames".into()));
+                fs::create_dir_all(&directory).unwrap_or_else(|err| {
+                    panic!("failed to create {}: {err}", directory.display())
+                });
+                Output::Png(directory)
+            }
+        };
+
+        // One frame's worth of host-visible memory, read back after every frame
+        let buffer = Buffer::new_slice::<u8>(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            width as u64 * height as u64 * 4,
+        )
+            .expect("failed to create buffer");
+
+        Recorder {
+            frames,
+            fps,
+            extent,
+            buffer,
+            output,
+        }
+    }
+
+    /// The animation time of `frame`, in seconds. Steps are always a whole frame apart, however
+    /// long rendering one takes.
+    pub fn time(&self, frame: u32) -> f32 {
+        frame as f32 / self.fps as f32
+    }
+
+    /// Records copying `image`, which must be in [`FORMAT`] and as large as the recording, into
+    /// the readback buffer.
+    pub fn copy(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<dyn ImageAccess>,
+    ) {
+        assert_eq!(image.format(), FORMAT, "recorded images must be {FORMAT:?}");
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                image,
+                self.buffer.clone(),
+            ))
+            .unwrap();
+    }
+
+    /// Writes out the image copied for `frame`. The copy has to have finished, so call it once
+    /// the frame's fence has been waited for.
+    pub fn write(&mut self, frame: u32) {
+        let pixels = self.buffer.read().unwrap();
+        match &mut self.output {
+            Output::Png(directory) => {
+                let [width, height] = self.extent;
+                let path = directory.join(format!("frame_{frame:05}.png"));
+                image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8)
+                    .unwrap_or_else(|err| panic!("failed to save {}: {err}", path.display()));
+            }
+            Output::Ffmpeg { child, .. } => {
+                child
+                    .stdin
+                    .as_mut()
+                    .unwrap()
+                    .write_all(&pixels)
+                    .expect("ffmpeg stopped reading frames");
+            }
+        }
+    }
+
+    /// Lets ffmpeg finish the file, and says where the frames ended up.
+    pub fn finish(self) {
+        match self.output {
+            Output::Png(directory) => {
+                tracing::info!("wrote {} frames to {}", self.frames, directory.display());
+            }
+            Output::Ffmpeg { mut child, path } => {
+                // Closing stdin is the end of the input for ffmpeg
+                drop(child.stdin.take());
+                let status = child.wait().expect("failed to wait for ffmpeg");
+                if !status.success() {
+                    tracing::error!("ffmpeg failed with {status}, {path} is missing or incomplete");
+                    std::process::exit(1);
+                }
+                tracing::info!(
+                    "encoded {} frames at {} fps into {path}",
+                    self.frames, self.fps
+                );
+            }
+        }
+    }
+}
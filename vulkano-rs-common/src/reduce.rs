@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::device::Queue;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::Pipeline;
+use vulkano::shader::ShaderModule;
+use vulkano::sync::{self, GpuFuture};
+
+use crate::allocators::Allocators;
+use crate::compute::ComputeContext;
+use crate::descriptors::Resource;
+use crate::wait;
+
+// Each work group folds two elements per invocation, has to match the shader
+const WORK_GROUP_SIZE: u64 = 256;
+const BLOCK_SIZE: u64 = WORK_GROUP_SIZE * 2;
+// Indices are 32-bit in the shader, this keeps them clear of wrapping around
+const MAX_LENGTH: u64 = 1 << 31;
+
+/// How [`gpu_reduce`] combines the elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReduceOp {
+    /// Wraps around on overflow, like `u32::wrapping_add`.
+    Sum,
+    Min,
+    Max,
+}
+
+impl ReduceOp {
+    /// The value that leaves any element unchanged, also the result for an empty buffer.
+    pub fn identity(self) -> u32 {
+        match self {
+            ReduceOp::Sum | ReduceOp::Max => 0,
+            ReduceOp::Min => u32::MAX,
+        }
+    }
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/reduce.comp",
+    }
+}
+
+/// Reduces `u32` buffers on the GPU, keeping the pipeline and allocators around between calls.
+pub struct GpuReducer {
+    compute: ComputeContext,
+    shader: Arc<ShaderModule>,
+}
+
+impl GpuReducer {
+    pub fn new(allocators: &Allocators, queue: &Arc<Queue>) -> Self {
+        let shader = cs::load(queue.device().clone()).expect("failed to create shader module");
+        let compute = ComputeContext::with_allocators(queue, allocators);
+        // Built now rather than on the first reduction, which would otherwise pay for it
+        compute.pipeline(&shader, "main");
+
+        GpuReducer { compute, shader }
+    }
+
+    /// Combines every element of `data` with `op` and waits for the result. `data` needs the
+    /// `STORAGE_BUFFER` usage.
+    ///
+    /// Panics if `data` has more than 2^31 elements.
+    pub fn reduce(&self, data: Subbuffer<[u32]>, op: ReduceOp) -> u32 {
+        if data.len() == 0 {
+            return op.identity();
+        }
+        assert!(
+            data.len() <= MAX_LENGTH,
+            "can't reduce {} elements, at most {MAX_LENGTH} fit in 32-bit indices",
+            data.len(),
+        );
+
+        let queue = &self.compute.queue;
+        let allocators = &self.compute.allocators;
+        let device = queue.device();
+        let max_groups = device
+            .physical_device()
+            .properties()
+            .max_compute_work_group_count[0];
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        // Every pass shrinks the data by at least a block size, until a single value is left.
+        // Always at least one, the result is only copied out of the buffers made here
+        let mut input = data;
+        loop {
+            let blocks = (input.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+            // One partial result per work group, capped at what a dispatch may launch
+            let length = blocks.min(max_groups as u64);
+            let output = Buffer::new_slice::<u32>(
+                &allocators.memory,
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::DeviceOnly,
+                    ..Default::default()
+                },
+                length,
+            )
+                .expect("failed to create buffer");
+
+            let bindings = [
+                (0, Resource::buffer(input.clone())),
+                (1, Resource::buffer(output.clone())),
+            ];
+            let pipeline = self
+                .compute
+                .bind(&mut builder, &self.shader, "main", &bindings);
+            builder
+                .push_constants(
+                    pipeline.layout().clone(),
+                    0,
+                    cs::PushConstants {
+                        op: op as u32,
+                        count: input.len() as u32,
+                    },
+                )
+                .dispatch([length as u32, 1, 1])
+                .unwrap();
+
+            input = output;
+            if input.len() == 1 {
+                break;
+            }
+        }
+
+        let result_buffer = Buffer::from_data(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            0u32,
+        )
+            .expect("failed to create buffer");
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(input, result_buffer.clone()))
+            .unwrap();
+
+        tracing::debug!("submitting reduction");
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let result = *result_buffer.read().unwrap();
+        result
+    }
+}
+
+/// One-off reduction of `data`, see [`GpuReducer::reduce`]. Builds the pipeline on every call,
+/// so keep a [`GpuReducer`] instead when reducing repeatedly.
+pub fn gpu_reduce(
+    allocators: &Allocators,
+    queue: &Arc<Queue>,
+    data: Subbuffer<[u32]>,
+    op: ReduceOp,
+) -> u32 {
+    GpuReducer::new(allocators, queue).reduce(data, op)
+}
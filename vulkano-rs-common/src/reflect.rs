@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use vulkano::descriptor_set::layout::{
+    DescriptorSetLayout, DescriptorSetLayoutCreateInfo, DescriptorType,
+};
+use vulkano::device::Device;
+use vulkano::pipeline::layout::{PipelineLayout, PipelineLayoutCreateInfo, PushConstantRange};
+use vulkano::shader::{EntryPoint, ShaderModule};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Loads a compiled SPIR-V file, e.g. the output of `glslc shader.comp -o shader.spv`. Panics
+/// if the file can't be read or isn't SPIR-V.
+pub fn load_spirv(device: &Arc<Device>, path: impl AsRef<Path>) -> Arc<ShaderModule> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+    let words = spirv_words(&bytes)
+        .unwrap_or_else(|| panic!("{} is not a SPIR-V module", path.display()));
+
+    // vulkano parses the module for its reflection and checks the capabilities it declares
+    // against the device, but the driver trusts the code to be valid beyond that. The file is
+    // taken to come from a compiler
+    unsafe { ShaderModule::from_words(device.clone(), &words) }.unwrap_or_else(|e| {
+        panic!(
+            "failed to create shader module from {}: {e}",
+            path.display()
+        )
+    })
+}
+
+// A stream of 32-bit words starting with the magic number. Compilers write them in either byte
+// order, the words are swapped to this machine's when the magic number reads backwards
+fn spirv_words(bytes: &[u8]) -> Option<Vec<u32>> {
+    if bytes.len() < 4 || bytes.len() % 4 != 0 {
+        return None;
+    }
+    let words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+        .collect();
+
+    if words[0] == SPIRV_MAGIC {
+        Some(words)
+    } else if words[0].swap_bytes() == SPIRV_MAGIC {
+        Some(words.into_iter().map(u32::swap_bytes).collect())
+    } else {
+        None
+    }
+}
+
+/// A descriptor an entry point uses, as read from its SPIR-V.
+#[derive(Clone, Debug)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    /// Every type the shader can take there, e.g. a storage image declared without a format
+    /// can be either a storage image or a storage texel buffer.
+    pub descriptor_types: Vec<DescriptorType>,
+    /// `None` for a runtime-sized array.
+    pub descriptor_count: Option<u32>,
+}
+
+/// The descriptors `entry_point` uses, sorted by set then binding.
+pub fn bindings(entry_point: &EntryPoint<'_>) -> Vec<ReflectedBinding> {
+    let mut bindings: Vec<_> = entry_point
+        .descriptor_binding_requirements()
+        .map(|((set, binding), requirements)| ReflectedBinding {
+            set,
+            binding,
+            descriptor_types: requirements.descriptor_types.clone(),
+            descriptor_count: requirements.descriptor_count,
+        })
+        .collect();
+    bindings.sort_by_key(|binding| (binding.set, binding.binding));
+    bindings
+}
+
+/// The push constant range `entry_point` declares, if any.
+pub fn push_constant_range(entry_point: &EntryPoint<'_>) -> Option<PushConstantRange> {
+    entry_point.push_constant_requirements().copied()
+}
+
+/// A pipeline layout with exactly the descriptor sets and push constants `entry_point` uses,
+/// for a shader whose interface isn't known until it is loaded.
+pub fn pipeline_layout(device: &Arc<Device>, entry_point: &EntryPoint<'_>) -> Arc<PipelineLayout> {
+    let create_infos = DescriptorSetLayoutCreateInfo::from_requirements(
+        entry_point.descriptor_binding_requirements(),
+    );
+    let set_layouts = create_infos
+        .into_iter()
+        .map(|create_info| {
+            DescriptorSetLayout::new(device.clone(), create_info)
+                .expect("failed to create descriptor set layout")
+        })
+        .collect();
+
+    PipelineLayout::new(
+        device.clone(),
+        PipelineLayoutCreateInfo {
+            set_layouts,
+            push_constant_ranges: push_constant_range(entry_point).into_iter().collect(),
+            ..Default::default()
+        },
+    )
+        .expect("failed to create pipeline layout")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_in_either_byte_order() {
+        let words = [SPIRV_MAGIC, 0x0001_0000];
+        let native: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+        let swapped: Vec<u8> = words
+            .iter()
+            .flat_map(|word| word.swap_bytes().to_ne_bytes())
+            .collect();
+        assert_eq!(spirv_words(&native).as_deref(), Some(&words[..]));
+        assert_eq!(spirv_words(&swapped).as_deref(), Some(&words[..]));
+    }
+
+    #[test]
+    fn rejects_what_isnt_spirv() {
+        assert_eq!(spirv_words(b"not spirv"), None);
+        assert_eq!(spirv_words(&[0; 8]), None);
+        assert_eq!(spirv_words(&[]), None);
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::instance::Instance;
+
+/// Describes every physical device of `instance` as JSON: the properties and limits the
+/// chapters depend on, the supported features and extensions, memory heaps and types, and
+/// queue families. Meant to be attached to bug reports, so it sticks to what differs between
+/// machines.
+pub fn devices(instance: &Arc<Instance>) -> Value {
+    let devices: Vec<Value> = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .map(|p| device(&p))
+        .collect();
+
+    json!({
+        "api_version": instance.api_version().to_string(),
+        "devices": devices,
+    })
+}
+
+fn device(physical_device: &PhysicalDevice) -> Value {
+    let properties = physical_device.properties();
+
+    // Only what's on, the full lists are mostly false and hundreds of entries long
+    let features: Vec<&str> = physical_device
+        .supported_features()
+        .into_iter()
+        .filter_map(|(name, supported)| supported.then_some(name))
+        .collect();
+    let extensions: Vec<&str> = physical_device
+        .supported_extensions()
+        .into_iter()
+        .filter_map(|(name, supported)| supported.then_some(name))
+        .collect();
+
+    let memory_properties = physical_device.memory_properties();
+    let memory_heaps: Vec<Value> = memory_properties
+        .memory_heaps
+        .iter()
+        .map(|heap| {
+            json!({
+                "size": heap.size,
+                "flags": format!("{:?}", heap.flags),
+            })
+        })
+        .collect();
+    let memory_types: Vec<Value> = memory_properties
+        .memory_types
+        .iter()
+        .map(|memory_type| {
+            json!({
+                "heap_index": memory_type.heap_index,
+                "property_flags": format!("{:?}", memory_type.property_flags),
+            })
+        })
+        .collect();
+
+    let queue_families: Vec<Value> = physical_device
+        .queue_family_properties()
+        .iter()
+        .map(|family| {
+            json!({
+                "queue_flags": format!("{:?}", family.queue_flags),
+                "queue_count": family.queue_count,
+                "timestamp_valid_bits": family.timestamp_valid_bits,
+                "min_image_transfer_granularity": family.min_image_transfer_granularity,
+            })
+        })
+        .collect();
+
+    let uniform_alignment = properties
+        .min_uniform_buffer_offset_alignment
+        .as_devicesize();
+    let storage_alignment = properties
+        .min_storage_buffer_offset_alignment
+        .as_devicesize();
+
+    json!({
+        "device_name": properties.device_name,
+        "device_type": format!("{:?}", properties.device_type),
+        "api_version": physical_device.api_version().to_string(),
+        "driver_version": properties.driver_version,
+        "driver_name": properties.driver_name,
+        "driver_info": properties.driver_info,
+        "vendor_id": properties.vendor_id,
+        "device_id": properties.device_id,
+        "limits": {
+            "max_image_dimension2_d": properties.max_image_dimension2_d,
+            "max_image_dimension3_d": properties.max_image_dimension3_d,
+            "max_image_array_layers": properties.max_image_array_layers,
+            "max_texel_buffer_elements": properties.max_texel_buffer_elements,
+            "max_uniform_buffer_range": properties.max_uniform_buffer_range,
+            "max_storage_buffer_range": properties.max_storage_buffer_range,
+            "max_push_constants_size": properties.max_push_constants_size,
+            "max_memory_allocation_count": properties.max_memory_allocation_count,
+            "max_bound_descriptor_sets": properties.max_bound_descriptor_sets,
+            "max_compute_shared_memory_size": properties.max_compute_shared_memory_size,
+            "max_compute_work_group_count": properties.max_compute_work_group_count,
+            "max_compute_work_group_invocations": properties.max_compute_work_group_invocations,
+            "max_compute_work_group_size": properties.max_compute_work_group_size,
+            "max_sampler_anisotropy": properties.max_sampler_anisotropy,
+            "max_framebuffer_width": properties.max_framebuffer_width,
+            "max_framebuffer_height": properties.max_framebuffer_height,
+            "max_color_attachments": properties.max_color_attachments,
+            "min_uniform_buffer_offset_alignment": uniform_alignment,
+            "min_storage_buffer_offset_alignment": storage_alignment,
+            "timestamp_period": properties.timestamp_period,
+            "subgroup_size": properties.subgroup_size,
+        },
+        "features": features,
+        "extensions": extensions,
+        "memory_heaps": memory_heaps,
+        "memory_types": memory_types,
+        "queue_families": queue_families,
+    })
+}
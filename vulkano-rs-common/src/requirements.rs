@@ -0,0 +1,123 @@
+use std::fmt;
+
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{DeviceExtensions, Features};
+
+/// The extensions and features a chapter can't run without. Built up with
+/// [`DeviceRequirements::extensions`] and [`DeviceRequirements::features`], then checked
+/// against each device, and exactly these are enabled on the one picked. Features added with
+/// [`DeviceRequirements::optional_features`] are enabled too when the picked device has them.
+///
+/// ```ignore
+/// let requirements = DeviceRequirements::new()
+///     .extensions(DeviceExtensions {
+///         khr_swapchain: true,
+///         ..DeviceExtensions::empty()
+///     })
+///     .features(Features {
+///         shader_int64: true,
+///         ..Features::empty()
+///     });
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeviceRequirements {
+    pub extensions: DeviceExtensions,
+    pub features: Features,
+    pub optional_features: Features,
+}
+
+impl DeviceRequirements {
+    /// Nothing required yet, any device will do.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `extensions` to the ones already required.
+    pub fn extensions(self, extensions: DeviceExtensions) -> Self {
+        DeviceRequirements {
+            extensions: self.extensions.union(&extensions),
+            ..self
+        }
+    }
+
+    /// Adds `features` to the ones already required.
+    pub fn features(self, features: Features) -> Self {
+        DeviceRequirements {
+            features: self.features.union(&features),
+            ..self
+        }
+    }
+
+    /// Adds `features` to the ones enabled if the picked device supports them. They never rule
+    /// a device out.
+    pub fn optional_features(self, features: Features) -> Self {
+        DeviceRequirements {
+            optional_features: self.optional_features.union(&features),
+            ..self
+        }
+    }
+
+    /// The required features, plus the optional ones `physical_device` supports.
+    pub fn enabled_features(&self, physical_device: &PhysicalDevice) -> Features {
+        self.features.union(
+            &self
+                .optional_features
+                .intersection(physical_device.supported_features()),
+        )
+    }
+
+    /// Whether `physical_device` has everything, or else what it lacks.
+    pub fn check(&self, physical_device: &PhysicalDevice) -> Result<(), MissingCapabilities> {
+        let extensions = names(
+            self.extensions
+                .difference(physical_device.supported_extensions()),
+        );
+        let features = names(
+            self.features
+                .difference(physical_device.supported_features()),
+        );
+
+        if extensions.is_empty() && features.is_empty() {
+            Ok(())
+        } else {
+            Err(MissingCapabilities {
+                device_name: physical_device.properties().device_name.clone(),
+                extensions,
+                features,
+            })
+        }
+    }
+}
+
+// The names of the members set to true
+fn names(set: impl IntoIterator<Item = (&'static str, bool)>) -> Vec<&'static str> {
+    set.into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect()
+}
+
+/// What a device lacks to meet some [`DeviceRequirements`], by Vulkan name.
+#[derive(Clone, Debug)]
+pub struct MissingCapabilities {
+    pub device_name: String,
+    pub extensions: Vec<&'static str>,
+    pub features: Vec<&'static str>,
+}
+
+impl fmt::Display for MissingCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} lacks", self.device_name)?;
+        if !self.extensions.is_empty() {
+            write!(f, " the extensions {}", self.extensions.join(", "))?;
+        }
+        if !self.extensions.is_empty() && !self.features.is_empty() {
+            write!(f, " and")?;
+        }
+        if !self.features.is_empty() {
+            write!(f, " the features {}", self.features.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingCapabilities {}
@@ -0,0 +1,301 @@
+use std::sync::Arc;
+
+use vulkano::buffer::BufferContents;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::view::{ImageView, ImageViewAbstract};
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::Subpass;
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+
+use crate::allocators::Allocators;
+use crate::streaming::StreamingBuffer;
+use crate::wait;
+
+#[derive(BufferContents, Vertex, Clone, Copy)]
+#[repr(C)]
+struct SpriteVertex {
+    // In pixels, from the top left corner of the viewport
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+    #[format(R32G32_SFLOAT)]
+    uv: [f32; 2],
+    #[format(R32G32B32A32_SFLOAT)]
+    color: [f32; 4],
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/sprite.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/sprite.frag",
+    }
+}
+
+/// A texture registered with a [`SpriteBatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureId(usize);
+
+/// A textured quad, in pixels from the top left corner of the viewport.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    pub center: [f32; 2],
+    pub size: [f32; 2],
+    /// Radians around the center, clockwise on screen.
+    pub rotation: f32,
+    /// The part of the texture shown, as its top left and bottom right texture coordinates.
+    pub uv: [[f32; 2]; 2],
+    /// Multiplies the texture, white leaves it as it is.
+    pub color: [f32; 4],
+}
+
+impl Sprite {
+    /// The whole texture, upright and untinted.
+    pub fn new(center: [f32; 2], size: [f32; 2]) -> Self {
+        Sprite {
+            center,
+            size,
+            rotation: 0.0,
+            uv: [[0.0, 0.0], [1.0, 1.0]],
+            color: [1.0; 4],
+        }
+    }
+}
+
+/// Collects sprites during a frame and draws them with one draw call per texture. All the
+/// vertices go into a single slice of streaming memory, each texture's draw covers its own
+/// range of it.
+///
+/// Sprites of one texture are drawn in the order they were added, textures in the order they
+/// were first used in the frame, so sprites that have to overlap in a given order share a
+/// texture.
+pub struct SpriteBatch {
+    queue: Arc<Queue>,
+    allocators: Allocators,
+    pipeline: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    vertices: StreamingBuffer,
+    textures: Vec<Arc<PersistentDescriptorSet>>,
+    // The vertices added this frame for each texture, and the textures in order of first use
+    batches: Vec<Vec<SpriteVertex>>,
+    order: Vec<usize>,
+    white: TextureId,
+    draw_count: usize,
+}
+
+impl SpriteBatch {
+    /// A batch drawing into `subpass`, with filtering and addressing from `sampler_info`.
+    pub fn new(
+        allocators: &Allocators,
+        queue: &Arc<Queue>,
+        subpass: Subpass,
+        sampler_info: SamplerCreateInfo,
+    ) -> Self {
+        let device = queue.device();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(SpriteVertex::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .color_blend_state(ColorBlendState::new(subpass.num_color_attachments()).blend_alpha())
+            .render_pass(subpass)
+            .build(device.clone())
+            .expect("failed to create sprite pipeline");
+        tracing::debug!("created sprite pipeline");
+
+        let mut batch = SpriteBatch {
+            queue: queue.clone(),
+            vertices: StreamingBuffer::new(allocators.memory.clone()),
+            allocators: allocators.clone(),
+            pipeline,
+            sampler: Sampler::new(device.clone(), sampler_info).expect("failed to create sampler"),
+            textures: Vec::new(),
+            batches: Vec::new(),
+            order: Vec::new(),
+            white: TextureId(0),
+            draw_count: 0,
+        };
+        // A single white texel, so plain rectangles go through the same pipeline
+        batch.white = batch.add_image(&image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+        batch
+    }
+
+    /// Registers a texture that's already on the GPU, for sprites to use.
+    pub fn add_texture(&mut self, view: Arc<dyn ImageViewAbstract>) -> TextureId {
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                view,
+                self.sampler.clone(),
+            )],
+        )
+            .unwrap();
+        self.textures.push(set);
+        self.batches.push(Vec::new());
+        TextureId(self.textures.len() - 1)
+    }
+
+    /// Uploads `image` as an sRGB texture, waits for the copy and registers it.
+    pub fn add_image(&mut self, image: &image::RgbaImage) -> TextureId {
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let texture = ImmutableImage::from_iter(
+            &self.allocators.memory,
+            image.as_raw().iter().copied(),
+            ImageDimensions::Dim2d {
+                width: image.width(),
+                height: image.height(),
+                array_layers: 1,
+            },
+            MipmapsCount::One,
+            Format::R8G8B8A8_SRGB,
+            &mut uploads,
+        )
+            .expect("failed to create sprite texture");
+        tracing::debug!(
+            width = image.width(),
+            height = image.height(),
+            "submitting sprite texture upload"
+        );
+        let future = sync::now(self.queue.device().clone())
+            .then_execute(self.queue.clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        self.add_texture(ImageView::new_default(texture).unwrap())
+    }
+
+    /// The texture to draw plain colored rectangles with.
+    pub fn white(&self) -> TextureId {
+        self.white
+    }
+
+    /// Adds `sprite` to this frame's batch for `texture`.
+    pub fn add(&mut self, texture: TextureId, sprite: &Sprite) {
+        let batch = &mut self.batches[texture.0];
+        if batch.is_empty() {
+            self.order.push(texture.0);
+        }
+
+        let (sin, cos) = sprite.rotation.sin_cos();
+        let [half_width, half_height] = sprite.size.map(|x| x / 2.0);
+        let [[u0, v0], [u1, v1]] = sprite.uv;
+        let corner = |x: f32, y: f32, uv| SpriteVertex {
+            position: [
+                sprite.center[0] + x * cos - y * sin,
+                sprite.center[1] + x * sin + y * cos,
+            ],
+            uv,
+            color: sprite.color,
+        };
+        let top_left = corner(-half_width, -half_height, [u0, v0]);
+        let top_right = corner(half_width, -half_height, [u1, v0]);
+        let bottom_right = corner(half_width, half_height, [u1, v1]);
+        let bottom_left = corner(-half_width, half_height, [u0, v1]);
+        batch.extend([
+            top_left,
+            top_right,
+            bottom_right,
+            top_left,
+            bottom_right,
+            bottom_left,
+        ]);
+    }
+
+    /// Adds a plain rectangle with its top left corner at `origin`.
+    pub fn rect(&mut self, origin: [f32; 2], size: [f32; 2], color: [f32; 4]) {
+        let sprite = Sprite {
+            color,
+            ..Sprite::new([origin[0] + size[0] / 2.0, origin[1] + size[1] / 2.0], size)
+        };
+        self.add(self.white, &sprite);
+    }
+
+    /// How many draw calls the last [`SpriteBatch::draw`] recorded.
+    pub fn draw_count(&self) -> usize {
+        self.draw_count
+    }
+
+    /// Draws everything added since the last call, over a viewport of `extent` pixels, and
+    /// starts a new batch. Has to be recorded inside the subpass given to [`SpriteBatch::new`],
+    /// and leaves the viewport set to the whole of `extent`.
+    pub fn draw(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        extent: [u32; 2],
+    ) {
+        self.draw_count = self.order.len();
+        if self.order.is_empty() {
+            return;
+        }
+
+        // Every texture's vertices one after the other, the ranges are kept to draw them apart
+        let mut ranges = Vec::with_capacity(self.order.len());
+        let mut vertices = Vec::new();
+        for &texture in &self.order {
+            let batch = &mut self.batches[texture];
+            ranges.push((texture, vertices.len() as u32, batch.len() as u32));
+            vertices.append(batch);
+        }
+        self.order.clear();
+
+        builder
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [extent[0] as f32, extent[1] as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            )
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    screen_size: [extent[0] as f32, extent[1] as f32],
+                },
+            )
+            .bind_vertex_buffers(0, self.vertices.write_iter(vertices));
+
+        for (texture, first_vertex, vertex_count) in ranges {
+            builder
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.pipeline.layout().clone(),
+                    0,
+                    self.textures[texture].clone(),
+                )
+                .draw(vertex_count, 1, first_vertex, 0)
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::device::Queue;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::GpuFuture;
+
+use crate::allocators::Allocators;
+use crate::wait;
+
+/// A device-local buffer fed from host-visible staging memory that stays mapped for the whole
+/// run. Every upload writes one of several staging regions, one per frame in flight, and copies
+/// the changed range into the device buffer.
+pub struct PersistentStaging<T: BufferContents> {
+    queue: Arc<Queue>,
+    allocators: Allocators,
+    device_buffer: Subbuffer<[T]>,
+    regions: Vec<Subbuffer<[T]>>,
+    // Signaled when the GPU is done copying out of the region, the CPU waits on it before
+    // writing there again
+    fences: Vec<Option<Arc<FenceSignalFuture<Box<dyn GpuFuture>>>>>,
+    next_region: usize,
+}
+
+impl<T: BufferContents> PersistentStaging<T> {
+    /// `length` elements in a device buffer created with `usage`, and `frames_in_flight`
+    /// staging regions of the same size.
+    pub fn new(
+        allocators: &Allocators,
+        queue: &Arc<Queue>,
+        length: u64,
+        usage: BufferUsage,
+        frames_in_flight: u32,
+    ) -> Self {
+        let device_buffer = Buffer::new_slice::<T>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: usage | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            length,
+        )
+            .expect("failed to create buffer");
+
+        // vulkano maps host-visible memory when it is allocated and keeps it mapped, writing a
+        // region is a plain memory write with no map call
+        let regions = (0..frames_in_flight)
+            .map(|_| {
+                Buffer::new_slice::<T>(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::TRANSFER_SRC,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    length,
+                )
+                    .expect("failed to create buffer")
+            })
+            .collect();
+
+        PersistentStaging {
+            queue: queue.clone(),
+            allocators: allocators.clone(),
+            device_buffer,
+            regions,
+            fences: (0..frames_in_flight).map(|_| None).collect(),
+            next_region: 0,
+        }
+    }
+
+    /// The buffer the GPU reads, bind it like any other.
+    pub fn device_buffer(&self) -> &Subbuffer<[T]> {
+        &self.device_buffer
+    }
+
+    /// Lets `write` fill `range` of the next staging region, then copies that range into the
+    /// device buffer after `before`. Commands executed after the returned future see the new
+    /// data. Waits if the GPU is still copying from the region the last time it was used.
+    pub fn upload<F>(
+        &mut self,
+        before: Box<dyn GpuFuture>,
+        range: Range<u64>,
+        write: F,
+    ) -> Box<dyn GpuFuture>
+    where
+        F: FnOnce(&mut [T]),
+    {
+        let index = self.next_region;
+        self.next_region = (index + 1) % self.regions.len();
+
+        // Once the fence is signaled the region is free, and vulkano lets the CPU write to it
+        if let Some(fence) = self.fences[index].take() {
+            wait::fence(&fence);
+        }
+        let region = self.regions[index].clone().slice(range.clone());
+        write(&mut region.write().unwrap());
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                region,
+                self.device_buffer.clone().slice(range),
+            ))
+            .unwrap();
+
+        tracing::trace!(index, "submitting staging copy");
+        let fence = Arc::new(
+            before
+                .then_execute(self.queue.clone(), builder.build().unwrap())
+                .unwrap()
+                .boxed()
+                .then_signal_fence_and_flush()
+                .unwrap(),
+        );
+        self.fences[index] = Some(fence.clone());
+        fence.boxed()
+    }
+}
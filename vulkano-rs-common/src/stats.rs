@@ -0,0 +1,238 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::device::Queue;
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::{GpuFuture, PipelineStage};
+
+use crate::allocators::Allocators;
+use crate::egui;
+
+// Number of frames the averages are computed over
+const HISTORY: usize = 120;
+
+/// Rolling frame time statistics, plus the GPU time of each frame when the queue supports
+/// timestamp queries.
+pub struct FrameStats {
+    frame_count: u64,
+    last_frame: Option<Instant>,
+    frame_times: VecDeque<Duration>,
+    gpu_times: VecDeque<Duration>,
+    gpu_timer: Option<GpuTimer>,
+}
+
+// Timestamps are written by the GPU at the start and end of each frame. Every frame in flight
+// gets its own pair of queries, which is read back the next time that slot comes around
+struct GpuTimer {
+    queue: Arc<Queue>,
+    query_pool: Arc<QueryPool>,
+    allocators: Allocators,
+    // Nanoseconds per timestamp tick
+    timestamp_period: f32,
+    written: Vec<bool>,
+}
+
+impl FrameStats {
+    /// `frames_in_flight` is the number of slots to keep timestamps for, usually the number of
+    /// swapchain images.
+    pub fn new(allocators: &Allocators, queue: &Arc<Queue>, frames_in_flight: u32) -> Self {
+        let device = queue.device();
+        let physical_device = device.physical_device();
+
+        // Not every queue family can write timestamps, fall back to CPU timings only
+        let supports_timestamps = physical_device.queue_family_properties()
+            [queue.queue_family_index() as usize]
+            .timestamp_valid_bits
+            .is_some();
+
+        let gpu_timer = supports_timestamps.then(|| GpuTimer {
+            queue: queue.clone(),
+            query_pool: QueryPool::new(
+                device.clone(),
+                QueryPoolCreateInfo {
+                    query_count: frames_in_flight * 2,
+                    ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+                },
+            )
+                .expect("failed to create query pool"),
+            allocators: allocators.clone(),
+            timestamp_period: physical_device.properties().timestamp_period,
+            written: vec![false; frames_in_flight as usize],
+        });
+
+        FrameStats {
+            frame_count: 0,
+            last_frame: None,
+            frame_times: VecDeque::with_capacity(HISTORY),
+            gpu_times: VecDeque::with_capacity(HISTORY),
+            gpu_timer,
+        }
+    }
+
+    /// Marks the start of a new frame on the CPU side.
+    pub fn frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            push(&mut self.frame_times, now - last_frame);
+        }
+        self.last_frame = Some(now);
+        self.frame_count += 1;
+    }
+
+    /// Submits a timestamp before the frame's work. `slot` identifies the frame in flight.
+    pub fn begin_gpu(&mut self, slot: u32, before: Box<dyn GpuFuture>) -> Box<dyn GpuFuture> {
+        let timer = match &mut self.gpu_timer {
+            Some(timer) => timer,
+            None => return before,
+        };
+
+        // The last frame that used this slot is done by now, collect its timestamps first
+        if timer.written[slot as usize] {
+            let mut timestamps = [0u64; 2];
+            let available = timer
+                .query_pool
+                .queries_range(slot * 2..slot * 2 + 2)
+                .unwrap()
+                .get_results(&mut timestamps, QueryResultFlags::empty())
+                .unwrap();
+
+            if available {
+                let ticks = timestamps[1].saturating_sub(timestamps[0]);
+                let nanos = ticks as f64 * timer.timestamp_period as f64;
+                push(&mut self.gpu_times, Duration::from_nanos(nanos as u64));
+            }
+        }
+
+        let mut builder = timer.builder();
+        unsafe {
+            builder
+                .reset_query_pool(timer.query_pool.clone(), slot * 2..slot * 2 + 2)
+                .unwrap()
+                .write_timestamp(timer.query_pool.clone(), slot * 2, PipelineStage::TopOfPipe)
+                .unwrap();
+        }
+        timer.written[slot as usize] = true;
+
+        before
+            .then_execute(timer.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    /// Submits a timestamp after the frame's work.
+    pub fn end_gpu(&mut self, slot: u32, after: Box<dyn GpuFuture>) -> Box<dyn GpuFuture> {
+        let timer = match &mut self.gpu_timer {
+            Some(timer) => timer,
+            None => return after,
+        };
+
+        let mut builder = timer.builder();
+        unsafe {
+            builder
+                .write_timestamp(
+                    timer.query_pool.clone(),
+                    slot * 2 + 1,
+                    PipelineStage::BottomOfPipe,
+                )
+                .unwrap();
+        }
+
+        after
+            .then_execute(timer.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn average(&self) -> Duration {
+        average(&self.frame_times)
+    }
+
+    pub fn min(&self) -> Duration {
+        self.frame_times.iter().copied().min().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.frame_times.iter().copied().max().unwrap_or_default()
+    }
+
+    pub fn fps(&self) -> f64 {
+        let average = self.average().as_secs_f64();
+        if average > 0.0 {
+            1.0 / average
+        } else {
+            0.0
+        }
+    }
+
+    /// Average GPU time per frame, `None` if the queue can't write timestamps.
+    pub fn gpu_average(&self) -> Option<Duration> {
+        self.gpu_timer.as_ref().map(|_| average(&self.gpu_times))
+    }
+
+    /// The statistics as lines of text, as shown in the overlay.
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("{:.1} fps", self.fps()),
+            format!(
+                "frame: {:.2} ms (min {:.2}, max {:.2})",
+                millis(self.average()),
+                millis(self.min()),
+                millis(self.max()),
+            ),
+            match self.gpu_average() {
+                Some(gpu) => format!("gpu: {:.2} ms", millis(gpu)),
+                None => "gpu: timestamps unsupported".into(),
+            },
+            format!("frames: {}", self.frame_count),
+        ]
+    }
+
+    /// Draws the statistics in a small overlay window.
+    pub fn ui(&self, ctx: &egui::Context) {
+        egui::Window::new("Stats")
+            .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+            .resizable(false)
+            .show(ctx, |ui| {
+                for line in self.lines() {
+                    ui.label(line);
+                }
+            });
+    }
+}
+
+impl GpuTimer {
+    fn builder(&self) -> AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap()
+    }
+}
+
+fn push(history: &mut VecDeque<Duration>, time: Duration) {
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(time);
+}
+
+fn average(history: &VecDeque<Duration>) -> Duration {
+    if history.is_empty() {
+        return Duration::ZERO;
+    }
+    history.iter().sum::<Duration>() / history.len() as u32
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
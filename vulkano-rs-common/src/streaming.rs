@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
+use vulkano::buffer::{BufferContents, BufferUsage, Subbuffer};
+
+use crate::memory::TrackingAllocator;
+
+/// Hands out slices of host-visible memory for data written anew every frame: uniforms, or
+/// vertices generated on the CPU. The slices come out of a few large buffers that are reused as
+/// soon as the GPU is done with every slice in them, so once the first frames have run nothing
+/// is allocated anymore.
+pub struct StreamingBuffer {
+    allocator: SubbufferAllocator<Arc<TrackingAllocator>>,
+}
+
+impl StreamingBuffer {
+    pub fn new(memory_allocator: Arc<TrackingAllocator>) -> Self {
+        StreamingBuffer {
+            // Uniform usage also makes every slice start at an offset uniform buffers may use.
+            // Indirect usage lets draw commands written on the CPU be read from here too
+            allocator: SubbufferAllocator::new(
+                memory_allocator,
+                SubbufferAllocatorCreateInfo {
+                    buffer_usage: BufferUsage::UNIFORM_BUFFER
+                        | BufferUsage::VERTEX_BUFFER
+                        | BufferUsage::STORAGE_BUFFER
+                        | BufferUsage::INDIRECT_BUFFER,
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+
+    /// A slice holding `data`, for this frame's commands to read.
+    pub fn write<T: BufferContents>(&self, data: T) -> Subbuffer<T> {
+        let subbuffer = self
+            .allocator
+            .allocate_sized()
+            .expect("failed to allocate streaming data");
+        *subbuffer.write().unwrap() = data;
+        subbuffer
+    }
+
+    /// A slice holding every element of `data`, for this frame's commands to read.
+    pub fn write_iter<T, I>(&self, data: I) -> Subbuffer<[T]>
+    where
+        T: BufferContents,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let data = data.into_iter();
+        let subbuffer = self
+            .allocator
+            .allocate_slice(data.len() as u64)
+            .expect("failed to allocate streaming data");
+        for (slot, value) in subbuffer.write().unwrap().iter_mut().zip(data) {
+            *slot = value;
+        }
+        subbuffer
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use vulkano::buffer::BufferContents;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::Subpass;
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+
+use crate::allocators::Allocators;
+use crate::streaming::StreamingBuffer;
+use crate::{args, egui, wait};
+
+// The glyphs are packed in rows into an atlas this wide, as tall as they need
+const ATLAS_WIDTH: u32 = 512;
+// Empty texels around each glyph, so sampling at its edge never picks up a neighbour
+const PADDING: u32 = 1;
+// Printable ASCII, anything else is drawn as REPLACEMENT
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+const REPLACEMENT: char = '?';
+
+#[derive(BufferContents, Vertex, Clone, Copy)]
+#[repr(C)]
+struct TextVertex {
+    // In pixels, from the top left corner of the viewport
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+    #[format(R32G32_SFLOAT)]
+    uv: [f32; 2],
+    #[format(R32G32B32A32_SFLOAT)]
+    color: [f32; 4],
+}
+
+// Where a glyph is in the atlas and how it sits on the line
+struct Glyph {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    size: [f32; 2],
+    // From the pen position on the baseline to the top left corner of the bitmap, Y down
+    offset: [f32; 2],
+    advance: f32,
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/text.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/text.frag",
+    }
+}
+
+/// Draws text from a font rasterized once into an atlas texture. Strings added during a frame
+/// are turned into one quad per character and drawn together with a single call.
+///
+/// The font is the file given with `--font`, or the monospace font egui comes with.
+pub struct TextRenderer {
+    pipeline: Arc<GraphicsPipeline>,
+    set: Arc<PersistentDescriptorSet>,
+    vertices: StreamingBuffer,
+    glyphs: HashMap<char, Glyph>,
+    ascent: f32,
+    line_height: f32,
+    batch: Vec<TextVertex>,
+}
+
+impl TextRenderer {
+    /// Rasterizes the font at `size` pixels and creates a pipeline drawing into `subpass`.
+    pub fn new(allocators: &Allocators, queue: &Arc<Queue>, subpass: Subpass, size: f32) -> Self {
+        let device = queue.device();
+        let font = fontdue::Font::from_bytes(font_data(), fontdue::FontSettings::default())
+            .unwrap_or_else(|err| panic!("failed to parse font: {err}"));
+        let line_metrics = font
+            .horizontal_line_metrics(size)
+            .expect("font has no horizontal metrics");
+
+        // Shelf packing: glyphs go left to right, and a new row starts below the tallest glyph
+        // of the current one when the next doesn't fit
+        let bitmaps: Vec<_> = (FIRST_CHAR..=LAST_CHAR)
+            .map(|c| (c, font.rasterize(c, size)))
+            .collect();
+        let mut cursor = [PADDING, PADDING];
+        let mut row_height = 0;
+        let mut placements = Vec::with_capacity(bitmaps.len());
+        for (_, (metrics, _)) in &bitmaps {
+            let (width, height) = (metrics.width as u32, metrics.height as u32);
+            if cursor[0] + width + PADDING > ATLAS_WIDTH {
+                cursor = [PADDING, cursor[1] + row_height + PADDING];
+                row_height = 0;
+            }
+            placements.push(cursor);
+            cursor[0] += width + PADDING;
+            row_height = row_height.max(height);
+        }
+        let atlas_height = cursor[1] + row_height + PADDING;
+
+        let mut pixels = vec![0u8; (ATLAS_WIDTH * atlas_height) as usize];
+        let mut glyphs = HashMap::with_capacity(bitmaps.len());
+        for ((c, (metrics, bitmap)), [x, y]) in bitmaps.into_iter().zip(placements) {
+            for row in 0..metrics.height {
+                let start = ((y as usize + row) * ATLAS_WIDTH as usize) + x as usize;
+                pixels[start..start + metrics.width]
+                    .copy_from_slice(&bitmap[row * metrics.width..(row + 1) * metrics.width]);
+            }
+
+            let (width, height) = (metrics.width as f32, metrics.height as f32);
+            let atlas_size = [ATLAS_WIDTH as f32, atlas_height as f32];
+            glyphs.insert(
+                c,
+                Glyph {
+                    uv_min: [x as f32 / atlas_size[0], y as f32 / atlas_size[1]],
+                    uv_max: [
+                        (x as f32 + width) / atlas_size[0],
+                        (y as f32 + height) / atlas_size[1],
+                    ],
+                    size: [width, height],
+                    // fontdue measures ymin upwards, from the baseline to the bitmap's bottom
+                    offset: [metrics.xmin as f32, -(metrics.ymin as f32 + height)],
+                    advance: metrics.advance_width,
+                },
+            );
+        }
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let atlas = ImmutableImage::from_iter(
+            &allocators.memory,
+            pixels,
+            ImageDimensions::Dim2d {
+                width: ATLAS_WIDTH,
+                height: atlas_height,
+                array_layers: 1,
+            },
+            MipmapsCount::One,
+            Format::R8_UNORM,
+            &mut uploads,
+        )
+            .expect("failed to create glyph atlas");
+        tracing::debug!("submitting glyph atlas upload");
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(TextVertex::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .color_blend_state(ColorBlendState::new(subpass.num_color_attachments()).blend_alpha())
+            .render_pass(subpass)
+            .build(device.clone())
+            .expect("failed to create text pipeline");
+        tracing::debug!("created text pipeline");
+
+        // The default sampler filters with the nearest texel, which is exact as long as the
+        // quads land on whole pixels
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::default())
+            .expect("failed to create sampler");
+        let set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                ImageView::new_default(atlas).unwrap(),
+                sampler,
+            )],
+        )
+            .unwrap();
+
+        TextRenderer {
+            pipeline,
+            set,
+            vertices: StreamingBuffer::new(allocators.memory.clone()),
+            glyphs,
+            ascent: line_metrics.ascent.ceil(),
+            line_height: line_metrics.new_line_size.ceil(),
+            batch: Vec::new(),
+        }
+    }
+
+    /// Distance between the tops of two lines, in pixels.
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Width of the longest line of `text` and height of all of them, in pixels.
+    pub fn measure(&self, text: &str) -> [f32; 2] {
+        let lines = text.lines();
+        let width = lines
+            .clone()
+            .map(|line| line.chars().map(|c| self.glyph(c).advance).sum::<f32>())
+            .fold(0.0, f32::max);
+        [width, lines.count() as f32 * self.line_height]
+    }
+
+    /// Adds `text` with the top left corner of its first line at `position`, in pixels. Every
+    /// line break starts a new line under the first.
+    pub fn add(&mut self, text: &str, position: [f32; 2], color: [f32; 4]) {
+        for (i, line) in text.lines().enumerate() {
+            let baseline = (position[1] + self.ascent + i as f32 * self.line_height).round();
+            let mut pen = position[0].round();
+            for c in line.chars() {
+                let glyph = self.glyph(c);
+                let left = pen + glyph.offset[0];
+                let top = baseline + glyph.offset[1];
+                let [right, bottom] = [left + glyph.size[0], top + glyph.size[1]];
+                let ([u0, v0], [u1, v1]) = (glyph.uv_min, glyph.uv_max);
+                pen += glyph.advance;
+
+                let vertex = |position, uv| TextVertex {
+                    position,
+                    uv,
+                    color,
+                };
+                let [top_left, top_right, bottom_right, bottom_left] = [
+                    vertex([left, top], [u0, v0]),
+                    vertex([right, top], [u1, v0]),
+                    vertex([right, bottom], [u1, v1]),
+                    vertex([left, bottom], [u0, v1]),
+                ];
+                // Two triangles, without an index buffer the shared corners are repeated
+                self.batch.extend([
+                    top_left,
+                    top_right,
+                    bottom_right,
+                    top_left,
+                    bottom_right,
+                    bottom_left,
+                ]);
+            }
+        }
+    }
+
+    /// Draws everything added since the last call, over a viewport of `extent` pixels, and
+    /// starts a new batch. Has to be recorded inside the subpass given to [`TextRenderer::new`],
+    /// and leaves the viewport set to the whole of `extent`.
+    pub fn draw(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        extent: [u32; 2],
+    ) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let vertex_count = self.batch.len() as u32;
+        let vertices = self.vertices.write_iter(self.batch.drain(..));
+
+        builder
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [extent[0] as f32, extent[1] as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            )
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    screen_size: [extent[0] as f32, extent[1] as f32],
+                },
+            )
+            .bind_vertex_buffers(0, vertices)
+            .draw(vertex_count, 1, 0, 0)
+            .unwrap();
+    }
+
+    fn glyph(&self, c: char) -> &Glyph {
+        self.glyphs
+            .get(&c)
+            .unwrap_or_else(|| &self.glyphs[&REPLACEMENT])
+    }
+}
+
+// The bytes of the font to rasterize. egui embeds its fonts to have something to show without
+// any files around, and the overlay already depends on it
+fn font_data() -> Vec<u8> {
+    match args::value::<String>("--font") {
+        Some(path) => {
+            std::fs::read(&path).unwrap_or_else(|err| panic!("failed to read {path}: {err}"))
+        }
+        None => egui::FontDefinitions::default()
+            .font_data
+            .remove("Hack")
+            .expect("egui's default fonts have no Hack")
+            .font
+            .into_owned(),
+    }
+}
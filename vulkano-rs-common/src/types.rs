@@ -0,0 +1,118 @@
+use glam::{Mat4, Vec2, Vec3};
+use vulkano::buffer::BufferContents;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+
+// vulkano matches vertex attributes to shader inputs by name, so the fields are named after the
+// `in` variables of the chapter shaders
+
+/// A 2D vertex with a color, for shaders taking `in vec2 position` and `in vec3 color`.
+#[derive(BufferContents, Vertex, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct PosColor {
+    #[format(R32G32_SFLOAT)]
+    pub position: [f32; 2],
+    #[format(R32G32B32_SFLOAT)]
+    pub color: [f32; 3],
+}
+
+impl PosColor {
+    pub fn new(position: Vec2, color: Vec3) -> Self {
+        PosColor {
+            position: position.to_array(),
+            color: color.to_array(),
+        }
+    }
+}
+
+/// A textured mesh vertex, for shaders taking `in vec3 position`, `in vec3 normal` and
+/// `in vec2 tex_coord`.
+#[derive(BufferContents, Vertex, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct PosNormalUv {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub normal: [f32; 3],
+    #[format(R32G32_SFLOAT)]
+    pub tex_coord: [f32; 2],
+}
+
+impl PosNormalUv {
+    pub fn new(position: Vec3, normal: Vec3, tex_coord: Vec2) -> Self {
+        PosNormalUv {
+            position: position.to_array(),
+            normal: normal.to_array(),
+            tex_coord: tex_coord.to_array(),
+        }
+    }
+}
+
+// Vertex buffers are tightly packed, a stray field or reordering would shift every attribute
+const _: () = assert!(std::mem::size_of::<PosColor>() == 20);
+const _: () = assert!(std::mem::size_of::<PosNormalUv>() == 32);
+
+// In std140 a vec3 is aligned to 16 bytes like a vec4, but only takes 12. A scalar declared
+// right after it fills the gap, anything else starts at the next 16 bytes, and the Rust structs
+// spell that gap out as padding
+
+/// The camera as a uniform block, matching
+///
+/// ```glsl
+/// layout(std140) uniform Camera {
+///     mat4 view_projection;
+///     vec3 position;
+/// };
+/// ```
+#[derive(BufferContents, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct CameraUniform {
+    pub view_projection: [[f32; 4]; 4],
+    pub position: [f32; 3],
+    pub _padding: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<CameraUniform>() == 80);
+
+impl CameraUniform {
+    pub fn new(view_projection: Mat4, position: Vec3) -> Self {
+        CameraUniform {
+            view_projection: view_projection.to_cols_array_2d(),
+            position: position.to_array(),
+            _padding: 0.0,
+        }
+    }
+}
+
+/// A directional light as a uniform block, matching
+///
+/// ```glsl
+/// layout(std140) uniform Light {
+///     vec3 direction;
+///     float intensity;
+///     vec3 color;
+/// };
+/// ```
+///
+/// `intensity` sits in the slot `direction` leaves free, so only the end needs padding.
+#[derive(BufferContents, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct DirectionalLight {
+    /// The direction the light travels in, normalized.
+    pub direction: [f32; 3],
+    pub intensity: f32,
+    pub color: [f32; 3],
+    pub _padding: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<DirectionalLight>() == 32);
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        DirectionalLight {
+            direction: direction.normalize().to_array(),
+            intensity,
+            color: color.to_array(),
+            _padding: 0.0,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{FlushError, GpuFuture};
+
+use crate::args;
+
+// How long a single wait lasts before the elapsed time is checked again
+const STEP: Duration = Duration::from_secs(1);
+// Waits shorter than this finish silently
+const REPORT_AFTER: Duration = Duration::from_secs(2);
+// Seconds to wait before giving up, unless --gpu-timeout says otherwise
+const DEFAULT_TIMEOUT: f64 = 30.0;
+
+/// Waits until the GPU has signaled the fence of `future`. A wait that lasts more than a couple
+/// of seconds prints how long it has been going on, and once `--gpu-timeout` seconds (30 by
+/// default) have passed the device and queue are described and the process exits, rather than
+/// waiting forever on work that may never finish.
+pub fn fence<F: GpuFuture>(future: &FenceSignalFuture<F>) {
+    let queue = future.queue();
+    with_timeout(future.device(), queue.as_deref(), |step| {
+        match future.wait(Some(step)) {
+            Ok(()) => true,
+            Err(FlushError::Timeout) => false,
+            Err(e) => {
+                diagnose(future.device(), queue.as_deref());
+                panic!("failed to wait for the GPU: {e}");
+            }
+        }
+    });
+}
+
+/// Waits on something [`fence`] can't, like a timeline semaphore, with the same reporting and
+/// the same `--gpu-timeout`. `wait` is called with how long it may block at most and returns
+/// whether the GPU is done. `queue` is the one the work was submitted to, if there is only one.
+pub fn with_timeout(
+    device: &Device,
+    queue: Option<&Queue>,
+    mut wait: impl FnMut(Duration) -> bool,
+) {
+    let timeout = Duration::from_secs_f64(args::value("--gpu-timeout").unwrap_or(DEFAULT_TIMEOUT));
+    let start = Instant::now();
+
+    while !wait(STEP.min(timeout)) {
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            tracing::error!(
+                "the GPU didn't finish within {:.0} s, giving up",
+                timeout.as_secs_f64()
+            );
+            diagnose(device, queue);
+            // Dropping an unsignaled future would wait on its fence again, so nothing is
+            // dropped
+            std::process::exit(1);
+        }
+        if elapsed >= REPORT_AFTER {
+            tracing::warn!(
+                "still waiting for the GPU after {:.0} s",
+                elapsed.as_secs_f64()
+            );
+        }
+    }
+
+    let elapsed = start.elapsed();
+    if elapsed >= REPORT_AFTER {
+        tracing::info!("the GPU finished after {:.1} s", elapsed.as_secs_f64());
+    }
+}
+
+// Everything that might explain why the work never finished
+fn diagnose(device: &Device, queue: Option<&Queue>) {
+    let physical_device = device.physical_device();
+    let properties = physical_device.properties();
+
+    tracing::error!(
+        "device: {} ({:?}), Vulkan {}",
+        properties.device_name,
+        properties.device_type,
+        properties.api_version
+    );
+    tracing::error!(
+        "driver: {} {}",
+        properties.driver_name.as_deref().unwrap_or("unknown"),
+        properties.driver_info.as_deref().unwrap_or("")
+    );
+    if let Some(queue) = queue {
+        let family =
+            &physical_device.queue_family_properties()[queue.queue_family_index() as usize];
+        tracing::error!(
+            "queue: family {}, index {}, {:?}, {} queue(s) in the family",
+            queue.queue_family_index(),
+            queue.id_within_family(),
+            family.queue_flags,
+            family.queue_count
+        );
+    }
+    tracing::error!(
+        "a shader stuck in a loop or a dispatch far larger than intended are the usual causes. \
+         Many drivers also reset a GPU that is busy for a few seconds, which loses the device"
+    );
+}
@@ -0,0 +1,885 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ash::vk;
+use egui_winit_vulkano::{Gui, GuiConfig};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceExtensions, Features, Queue};
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageUsage, SwapchainImage};
+use vulkano::swapchain::{
+    acquire_next_image, AcquireError, PresentMode, Surface, Swapchain, SwapchainCreateInfo,
+    SwapchainCreationError, SwapchainPresentInfo,
+};
+use vulkano::sync::{self, FlushError, GpuFuture, Sharing};
+use vulkano::{VulkanLibrary, VulkanObject};
+use vulkano_win::VkSurfaceBuild;
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopBuilder, EventLoopWindowTarget};
+use winit::window::{Fullscreen, Window, WindowBuilder};
+
+use crate::allocators::Allocators;
+use crate::args;
+use crate::camera::Camera;
+use crate::capture::FrameCapture;
+use crate::context::{self, VulkanContext};
+use crate::egui;
+use crate::pipeline_stats::PipelineStats;
+use crate::profiler::{GpuProfiler, ProfilerGraph};
+use crate::queues::Queues;
+use crate::requirements::DeviceRequirements;
+use crate::stats::FrameStats;
+
+/// A chapter that draws into the window managed by [`run`].
+pub trait App {
+    /// Called once after creation and again every time the swapchain is recreated, so
+    /// size-dependent resources (framebuffers, storage images...) can follow the window.
+    fn resize(&mut self, _renderer: &Renderer) {}
+
+    /// Records the frame's work into the swapchain image at `image_index`, executing it after
+    /// `before`. The returned future is presented once the overlay has been drawn on top.
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture>;
+
+    /// Builds the egui overlay, e.g. sliders for the chapter's parameters.
+    fn gui(&mut self, _ctx: &egui::Context) {}
+
+    /// Window events that the overlay didn't consume.
+    fn window_event(&mut self, _event: &WindowEvent) {}
+
+    /// 3D chapters return their camera here, the runner then feeds it input and moves it
+    /// before every frame.
+    fn camera(&mut self) -> Option<&mut Camera> {
+        None
+    }
+}
+
+/// Everything the runner owns that a chapter may need to create its resources.
+pub struct Renderer {
+    pub context: VulkanContext,
+    pub surface: Arc<Surface>,
+    pub swapchain: Arc<Swapchain>,
+    pub images: Vec<Arc<SwapchainImage>>,
+    pub image_views: Vec<Arc<ImageView<SwapchainImage>>>,
+    /// Shared by the runner's overlays and the chapter, so `--track-memory` sees everything.
+    pub allocators: Allocators,
+    pipeline_stats: Option<PipelineStats>,
+    profiler: Option<GpuProfiler>,
+}
+
+impl Renderer {
+    pub fn device(&self) -> &Arc<Device> {
+        &self.context.device
+    }
+
+    pub fn queue(&self) -> &Arc<Queue> {
+        &self.context.queue
+    }
+
+    /// Every queue of the device by role, for chapters that submit work outside of the
+    /// graphics queue.
+    pub fn queues(&self) -> &Queues {
+        &self.context.queues
+    }
+
+    pub fn window(&self) -> &Window {
+        self.surface
+            .object()
+            .unwrap()
+            .downcast_ref::<Window>()
+            .unwrap()
+    }
+
+    /// Records `record` into `builder`. With `--pipeline-stats`, its shader invocations are
+    /// also counted and printed under the name `pass`, and with `--profile` its GPU time is
+    /// graphed under that name.
+    pub fn measure<F>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pass: &str,
+        record: F,
+    ) where
+        F: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    {
+        match (&self.profiler, &self.pipeline_stats) {
+            (Some(profiler), Some(pipeline_stats)) => profiler.measure(builder, pass, |builder| {
+                pipeline_stats.measure(builder, pass, record)
+            }),
+            (Some(profiler), None) => profiler.measure(builder, pass, record),
+            (None, Some(pipeline_stats)) => pipeline_stats.measure(builder, pass, record),
+            (None, None) => record(builder),
+        }
+    }
+
+    fn recreate_swapchain(&mut self) -> Result<(), SwapchainCreationError> {
+        let (swapchain, images) = self.swapchain.recreate(SwapchainCreateInfo {
+            image_extent: self.window().inner_size().into(),
+            ..self.swapchain.create_info()
+        })?;
+
+        tracing::debug!(extent = ?swapchain.image_extent(), "recreated swapchain");
+        self.swapchain = swapchain;
+        self.image_views = create_image_views(&images);
+        self.images = images;
+        Ok(())
+    }
+}
+
+fn create_image_views(images: &[Arc<SwapchainImage>]) -> Vec<Arc<ImageView<SwapchainImage>>> {
+    images
+        .iter()
+        .map(|image| ImageView::new_default(image.clone()).unwrap())
+        .collect()
+}
+
+/// Opens a window, creates the app with `create_app` and drives it until the window is closed.
+/// `device_extensions` are enabled on top of the swapchain extension the runner needs.
+///
+/// If the device is lost, it is created again along with the app, which starts over. Pressing
+/// F12 pretends the device was lost, to try that path. F11 switches between the window and
+/// borderless fullscreen. Under RenderDoc, `--capture-frame N` captures the frame numbered `N`.
+/// `--resolution 1280x720` opens the window at that size instead of winit's default.
+pub fn run<A, F>(title: &str, device_extensions: DeviceExtensions, create_app: F) -> !
+where
+    A: App + 'static,
+    F: Fn(&Renderer) -> A + 'static,
+{
+    run_with_features(title, device_extensions, Features::empty(), create_app)
+}
+
+/// Like [`run`], for chapters that need optional device features. Exits with a message instead
+/// of opening the window when no device supports them.
+pub fn run_with_features<A, F>(
+    title: &str,
+    device_extensions: DeviceExtensions,
+    device_features: Features,
+    create_app: F,
+) -> !
+where
+    A: App + 'static,
+    F: Fn(&Renderer) -> A + 'static,
+{
+    let requirements = DeviceRequirements::new()
+        .extensions(device_extensions)
+        .features(device_features);
+    run_with_requirements(title, requirements, create_app)
+}
+
+/// Like [`run`], on a device meeting `requirements`. The swapchain extension is added to them.
+pub fn run_with_requirements<A, F>(
+    title: &str,
+    requirements: DeviceRequirements,
+    create_app: F,
+) -> !
+where
+    A: App + 'static,
+    F: Fn(&Renderer) -> A + 'static,
+{
+    // The instance needs the extensions to create a surface for the current platform
+    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let event_loop = create_event_loop(&library);
+    let required_extensions = vulkano_win::required_extensions(&library);
+    let instance = context::create_instance(library, required_extensions);
+
+    // The surface is the Vulkan side of the window, the swapchain will present to it
+    let mut window_builder = WindowBuilder::new().with_title(title);
+    if let Some(Resolution { width, height }) = args::value("--resolution") {
+        window_builder = window_builder.with_inner_size(PhysicalSize::new(width, height));
+    }
+    let surface = window_builder
+        .build_vk_surface(&event_loop, instance.clone())
+        .expect("failed to create window");
+
+    let requirements = requirements.extensions(DeviceExtensions {
+        khr_swapchain: true,
+        ..DeviceExtensions::empty()
+    });
+    // The statistics are a debugging aid, not worth refusing to run over or passing a device
+    // over for. MoltenVK has no pipeline statistics queries for instance
+    let pipeline_stats = args::flag("--pipeline-stats");
+    let requirements = requirements.optional_features(Features {
+        pipeline_statistics_query: pipeline_stats,
+        ..Features::empty()
+    });
+
+    let context = VulkanContext::with_requirements(instance, &requirements, Some(&surface));
+    if pipeline_stats && !context.device.enabled_features().pipeline_statistics_query {
+        tracing::warn!("--pipeline-stats needs the pipeline_statistics_query feature, ignoring it");
+    }
+    let mut session = Some(Session::new(context, surface, &event_loop, &create_app));
+
+    // Never dropped, the event loop below only ends with the process
+    #[cfg(feature = "puffin-server")]
+    let _puffin_server = start_puffin_server();
+
+    let mut recreate_swapchain = false;
+    let mut device_lost = false;
+    // Holding a key sends repeated presses, only the first one loses the device or toggles
+    // fullscreen
+    let mut f12_held = false;
+    let mut f11_held = false;
+    let mut capture = FrameCapture::from_args();
+
+    event_loop.run(move |event, target, control_flow| {
+        if device_lost {
+            device_lost = false;
+            tracing::warn!("the device was lost, creating everything again");
+
+            // The old swapchain has to be gone before the new one can use the surface
+            let mut old = session.take().unwrap();
+            let context = old.renderer.context.recreate(Some(&old.renderer.surface));
+            let surface = old.renderer.surface.clone();
+
+            // Nothing may still run on the old device while its objects are dropped. vulkano's
+            // wait_idle panics on a lost device, so the result is checked through the raw call
+            let device = old.renderer.device().clone();
+            let result = unsafe { (device.fns().v1_0.device_wait_idle)(device.handle()) };
+            if result == vk::Result::SUCCESS {
+                drop(old.previous_frame_end.take());
+            } else {
+                // Dropping the last frame's future waits on its fence, which a device that is
+                // really lost never signals. Only then is it leaked
+                tracing::debug!("waiting for the lost device failed: {result}");
+                std::mem::forget(old.previous_frame_end.take());
+            }
+            drop(old);
+
+            session = Some(Session::new(context, surface, target, &create_app));
+            recreate_swapchain = false;
+        }
+        let Session {
+            app,
+            gui,
+            stats,
+            profiler_graph,
+            renderer,
+            previous_frame_end,
+        } = session.as_mut().unwrap();
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = ControlFlow::Exit;
+            }
+            // The event loop exits the process without dropping the session
+            Event::LoopDestroyed => renderer.allocators.memory.print_summary(),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state,
+                                virtual_keycode: Some(VirtualKeyCode::F12),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = state == ElementState::Pressed;
+                if pressed && !f12_held {
+                    // A real device loss can't be caused on purpose, this takes the same path
+                    device_lost = true;
+                }
+                f12_held = pressed;
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state,
+                                virtual_keycode: Some(VirtualKeyCode::F11),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = state == ElementState::Pressed;
+                if pressed && !f11_held {
+                    // Borderless keeps the desktop's video mode, so switching is instant. The
+                    // Resized event that follows gets the swapchain recreated at the new size,
+                    // the app keeps its state and only sees a resize
+                    let window = renderer.window();
+                    let fullscreen = match window.fullscreen() {
+                        Some(_) => None,
+                        None => Some(Fullscreen::Borderless(None)),
+                    };
+                    window.set_fullscreen(fullscreen);
+                }
+                f11_held = pressed;
+            }
+            Event::WindowEvent { event, .. } => {
+                if let WindowEvent::Resized(_) = event {
+                    recreate_swapchain = true;
+                }
+
+                // Let the overlay look at the event first so dragging a slider doesn't also
+                // move the scene behind it
+                if !gui.update(&event) {
+                    if let Some(camera) = app.camera() {
+                        camera.handle_event(&event);
+                    }
+                    app.window_event(&event);
+                }
+            }
+            Event::RedrawEventsCleared => {
+                // Nothing to draw while the window is minimized
+                let dimensions = renderer.window().inner_size();
+                if dimensions.width == 0 || dimensions.height == 0 {
+                    return;
+                }
+
+                // The scopes below are grouped by frame in the puffin viewer
+                puffin::GlobalProfiler::lock().new_frame();
+
+                // Free the resources of the frames the GPU is done with
+                previous_frame_end.as_mut().unwrap().cleanup_finished();
+
+                if recreate_swapchain {
+                    match renderer.recreate_swapchain() {
+                        Ok(()) => {}
+                        // Happens while the user is resizing the window, just try again next
+                        // frame
+                        Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                        Err(e) => panic!("failed to recreate swapchain: {e}"),
+                    }
+                    app.resize(renderer);
+                    if let Some(profiler_graph) = profiler_graph {
+                        profiler_graph.resize(renderer);
+                    }
+                    recreate_swapchain = false;
+                }
+
+                // With FIFO this is usually where the CPU waits for the display
+                let acquired = {
+                    puffin::profile_scope!("acquire");
+                    acquire_next_image(renderer.swapchain.clone(), None)
+                };
+                let (image_index, suboptimal, acquire_future) = match acquired {
+                    Ok(r) => r,
+                    Err(AcquireError::OutOfDate) => {
+                        recreate_swapchain = true;
+                        return;
+                    }
+                    Err(AcquireError::DeviceLost) => {
+                        device_lost = true;
+                        return;
+                    }
+                    Err(e) => panic!("failed to acquire next image: {e}"),
+                };
+
+                // The image is still usable, but the swapchain no longer matches the surface
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                // Frames that couldn't acquire an image don't count, they draw nothing
+                if let Some(capture) = &mut capture {
+                    capture.begin_frame();
+                }
+
+                if let Some(camera) = app.camera() {
+                    camera.update();
+                }
+
+                {
+                    puffin::profile_scope!("gui");
+                    gui.immediate_ui(|gui| {
+                        let ctx = gui.context();
+                        app.gui(&ctx);
+                        if let Some(stats) = stats {
+                            stats.ui(&ctx);
+                        }
+                    });
+                }
+
+                // Command buffers are only built here, nothing reaches the GPU until the flush
+                let future = {
+                    puffin::profile_scope!("record");
+                    let mut future = previous_frame_end
+                        .take()
+                        .unwrap()
+                        .join(acquire_future)
+                        .boxed();
+                    if let Some(stats) = stats.as_mut() {
+                        stats.frame();
+                        future = stats.begin_gpu(image_index, future);
+                    }
+                    if let Some(pipeline_stats) = &mut renderer.pipeline_stats {
+                        future = pipeline_stats.begin_frame(image_index, future);
+                    }
+                    if let Some(profiler) = &mut renderer.profiler {
+                        future = profiler.begin_frame(image_index, future);
+                    }
+
+                    let mut future = app.render(renderer, image_index, future);
+                    // Under the overlay, so its windows can still be read over the graph
+                    if let (Some(profiler_graph), Some(profiler)) =
+                        (profiler_graph.as_mut(), &renderer.profiler)
+                    {
+                        future = profiler_graph.draw(profiler, image_index, future);
+                    }
+                    let mut future = gui
+                        .draw_on_image(future, renderer.image_views[image_index as usize].clone());
+
+                    if let Some(stats) = stats.as_mut() {
+                        future = stats.end_gpu(image_index, future);
+                    }
+                    future
+                };
+
+                // The flush submits every command buffer of the frame, then queues the present
+                let future = {
+                    puffin::profile_scope!("submit");
+                    // Every frame, so trace rather than debug to keep --verbose readable
+                    tracing::trace!(image_index, "submitting frame");
+                    future.flush().map(|()| future)
+                };
+                let future = future.and_then(|future| {
+                    puffin::profile_scope!("present");
+                    // Presenting from another queue has to wait on a semaphore for the frame
+                    let future = if has_separate_present(renderer.queues()) {
+                        future.then_signal_semaphore().boxed()
+                    } else {
+                        future
+                    };
+                    future
+                        .then_swapchain_present(
+                            renderer.queues().present.clone(),
+                            SwapchainPresentInfo::swapchain_image_index(
+                                renderer.swapchain.clone(),
+                                image_index,
+                            ),
+                        )
+                        .then_signal_fence_and_flush()
+                });
+
+                match future {
+                    Ok(future) => {
+                        *previous_frame_end = Some(future.boxed());
+                    }
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                        *previous_frame_end = Some(sync::now(renderer.device().clone()).boxed());
+                    }
+                    Err(FlushError::DeviceLost) => {
+                        device_lost = true;
+                        *previous_frame_end = Some(sync::now(renderer.device().clone()).boxed());
+                    }
+                    Err(e) => {
+                        tracing::error!("failed to flush future: {e}");
+                        *previous_frame_end = Some(sync::now(renderer.device().clone()).boxed());
+                    }
+                }
+
+                if let Some(capture) = &mut capture {
+                    capture.end_frame();
+                }
+            }
+            _ => (),
+        }
+    })
+}
+
+// Built with the puffin-server feature, the runner's CPU scopes can be watched live in
+// puffin_viewer, next to the GPU times of --profile
+#[cfg(feature = "puffin-server")]
+fn start_puffin_server() -> Option<puffin_http::Server> {
+    let address = format!("127.0.0.1:{}", puffin_http::DEFAULT_PORT);
+    match puffin_http::Server::new(&address) {
+        Ok(server) => {
+            tracing::info!("serving the CPU profile on {address}, connect puffin_viewer to it");
+            puffin::set_scopes_on(true);
+            Some(server)
+        }
+        Err(e) => {
+            tracing::warn!("failed to start the puffin server: {e}");
+            None
+        }
+    }
+}
+
+fn has_separate_present(queues: &Queues) -> bool {
+    !Arc::ptr_eq(&queues.present, &queues.graphics)
+}
+
+/// The size of the window's drawable area in pixels, given to `--resolution` as e.g. `1280x720`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size: Option<(u32, u32)> = s.split_once('x').and_then(|(width, height)| {
+            Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+        });
+        match size {
+            Some((width, height)) if width > 0 && height > 0 => Ok(Resolution { width, height }),
+            _ => Err(format!("invalid resolution {s}, expected e.g. 1280x720")),
+        }
+    }
+}
+
+/// The window systems `--display-backend` can pick between on Linux and the BSDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayBackend {
+    X11,
+    Wayland,
+}
+
+impl FromStr for DisplayBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x11" => Ok(DisplayBackend::X11),
+            "wayland" => Ok(DisplayBackend::Wayland),
+            _ => Err(format!(
+                "unknown display backend {s}, expected x11 or wayland"
+            )),
+        }
+    }
+}
+
+/// The present modes `--present-mode` can ask for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentModeArg {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl FromStr for PresentModeArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(PresentModeArg::Fifo),
+            "mailbox" => Ok(PresentModeArg::Mailbox),
+            "immediate" => Ok(PresentModeArg::Immediate),
+            _ => Err(format!(
+                "unknown present mode {s}, expected fifo, mailbox or immediate"
+            )),
+        }
+    }
+}
+
+impl From<PresentModeArg> for PresentMode {
+    fn from(arg: PresentModeArg) -> Self {
+        match arg {
+            PresentModeArg::Fifo => PresentMode::Fifo,
+            PresentModeArg::Mailbox => PresentMode::Mailbox,
+            PresentModeArg::Immediate => PresentMode::Immediate,
+        }
+    }
+}
+
+// winit picks Wayland when WAYLAND_DISPLAY is set and X11 otherwise, --display-backend overrides
+// that. The Vulkan library also has to be able to create a surface for the chosen one
+fn create_event_loop(library: &VulkanLibrary) -> EventLoop<()> {
+    // Only changed on the platforms with a choice to make
+    #[allow(unused_mut)]
+    let mut builder = EventLoopBuilder::new();
+    let Some(backend) = args::value::<DisplayBackend>("--display-backend") else {
+        return builder.build();
+    };
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        use winit::platform::wayland::EventLoopBuilderExtWayland;
+        use winit::platform::x11::EventLoopBuilderExtX11;
+
+        let extensions = library.supported_extensions();
+        let supported = match backend {
+            DisplayBackend::X11 => extensions.khr_xlib_surface || extensions.khr_xcb_surface,
+            DisplayBackend::Wayland => extensions.khr_wayland_surface,
+        };
+        if !supported {
+            tracing::error!("the Vulkan library has no surface extension for {backend:?}");
+            std::process::exit(1);
+        }
+
+        match backend {
+            DisplayBackend::X11 => builder.with_x11(),
+            DisplayBackend::Wayland => builder.with_wayland(),
+        };
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    {
+        let _ = (library, backend);
+        tracing::warn!("--display-backend only applies to Linux and the BSDs, ignoring it");
+    }
+
+    builder.build()
+}
+
+// FIFO waits for the vertical blank, as vsync does. Mailbox doesn't wait but replaces the image
+// queued for display, so nothing tears, and immediate presents right away and may tear. Only FIFO
+// is always supported, it is the fallback
+fn choose_present_mode(physical_device: &PhysicalDevice, surface: &Surface) -> PresentMode {
+    let Some(arg) = args::value::<PresentModeArg>("--present-mode") else {
+        return PresentMode::Fifo;
+    };
+    let requested = PresentMode::from(arg);
+
+    let supported = physical_device
+        .surface_present_modes(surface)
+        .unwrap()
+        .any(|mode| mode == requested);
+    if supported {
+        requested
+    } else {
+        tracing::warn!("the surface doesn't support the {requested:?} present mode, using fifo");
+        PresentMode::Fifo
+    }
+}
+
+// Everything created from the device, thrown away and created again when it is lost. The app
+// comes first so it is dropped before the swapchain images it may hold framebuffers for
+struct Session<A> {
+    app: A,
+    gui: Gui,
+    stats: Option<FrameStats>,
+    profiler_graph: Option<ProfilerGraph>,
+    renderer: Renderer,
+    previous_frame_end: Option<Box<dyn GpuFuture>>,
+}
+
+impl<A: App> Session<A> {
+    fn new<F>(
+        context: VulkanContext,
+        surface: Arc<Surface>,
+        event_loop: &EventLoopWindowTarget<()>,
+        create_app: &F,
+    ) -> Self
+    where
+        F: Fn(&Renderer) -> A,
+    {
+        // The swapchain is the list of images that are presented to the window in turn
+        let (swapchain, images) = {
+            let physical_device = context.device.physical_device();
+            let surface_capabilities = physical_device
+                .surface_capabilities(&surface, Default::default())
+                .unwrap();
+            let image_format = Some(
+                physical_device
+                    .surface_formats(&surface, Default::default())
+                    .unwrap()[0]
+                    .0,
+            );
+            let window = surface.object().unwrap().downcast_ref::<Window>().unwrap();
+
+            let present_mode = choose_present_mode(physical_device, &surface);
+
+            // When another family presents, both use the images without ownership transfers.
+            // Concurrent sharing may be a little slower, which only these rare devices pay for
+            let queues = &context.queues;
+            let image_sharing = if has_separate_present(queues) {
+                Sharing::Concurrent(
+                    vec![
+                        queues.graphics.queue_family_index(),
+                        queues.present.queue_family_index(),
+                    ]
+                    .into(),
+                )
+            } else {
+                Sharing::Exclusive
+            };
+
+            // Mailbox only skips waiting if a spare image is there to render into while one is
+            // shown and another is queued
+            let mut min_image_count = surface_capabilities.min_image_count;
+            if present_mode == PresentMode::Mailbox {
+                min_image_count += 1;
+                if let Some(max_image_count) = surface_capabilities.max_image_count {
+                    min_image_count = min_image_count.min(max_image_count);
+                }
+            }
+
+            Swapchain::new(
+                context.device.clone(),
+                surface.clone(),
+                SwapchainCreateInfo {
+                    min_image_count,
+                    image_format,
+                    image_extent: window.inner_size().into(),
+                    // Transfer destination lets compute chapters blit their result to the screen
+                    image_usage: (ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST)
+                        .intersection(surface_capabilities.supported_usage_flags),
+                    composite_alpha: surface_capabilities
+                        .supported_composite_alpha
+                        .into_iter()
+                        .next()
+                        .unwrap(),
+                    present_mode,
+                    image_sharing,
+                    ..Default::default()
+                },
+            )
+                .expect("failed to create swapchain")
+        };
+
+        let allocators = Allocators::new(&context.device);
+        let mut renderer = Renderer {
+            context,
+            surface,
+            swapchain,
+            image_views: create_image_views(&images),
+            images,
+            allocators,
+            pipeline_stats: None,
+            profiler: None,
+        };
+
+        // The overlay renders after the chapter into the same swapchain image, keeping its
+        // content
+        let gui = Gui::new(
+            event_loop,
+            renderer.surface.clone(),
+            renderer.queue().clone(),
+            GuiConfig {
+                preferred_format: Some(renderer.swapchain.image_format()),
+                is_overlay: true,
+                ..Default::default()
+            },
+        );
+
+        let mut app = create_app(&renderer);
+        app.resize(&renderer);
+
+        // One timestamp slot per swapchain image, as that bounds the number of frames in flight
+        let stats = args::flag("--stats").then(|| {
+            FrameStats::new(
+                &renderer.allocators,
+                renderer.queue(),
+                renderer.images.len() as u32,
+            )
+        });
+        if renderer
+            .device()
+            .enabled_features()
+            .pipeline_statistics_query
+        {
+            renderer.pipeline_stats = Some(PipelineStats::new(
+                &renderer.allocators,
+                renderer.queue(),
+                renderer.images.len() as u32,
+            ));
+        }
+
+        // Only the passes a chapter wraps in Renderer::measure show up in the graph
+        let mut profiler_graph = None;
+        if args::flag("--profile") {
+            renderer.profiler = GpuProfiler::new(
+                &renderer.allocators,
+                renderer.queue(),
+                renderer.images.len() as u32,
+            );
+            if renderer.profiler.is_some() {
+                let mut graph = ProfilerGraph::new(
+                    &renderer.allocators,
+                    renderer.queue(),
+                    renderer.swapchain.image_format(),
+                );
+                graph.resize(&renderer);
+                profiler_graph = Some(graph);
+            } else {
+                tracing::warn!("--profile needs a queue that can write timestamps, ignoring it");
+            }
+        }
+
+        let previous_frame_end = Some(sync::now(renderer.device().clone()).boxed());
+
+        Session {
+            app,
+            gui,
+            stats,
+            profiler_graph,
+            renderer,
+            previous_frame_end,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolution_parses_width_and_height() {
+        assert_eq!(
+            "1280x720".parse(),
+            Ok(Resolution {
+                width: 1280,
+                height: 720
+            })
+        );
+        assert_eq!(
+            "800 x 600".parse(),
+            Ok(Resolution {
+                width: 800,
+                height: 600
+            })
+        );
+    }
+
+    #[test]
+    fn resolution_rejects_malformed_sizes() {
+        for input in [
+            "1280",
+            "1280x",
+            "x720",
+            "0x720",
+            "1280x0",
+            "widexhigh",
+            "-1x720",
+        ] {
+            let error = input.parse::<Resolution>().unwrap_err();
+            assert!(error.contains("expected e.g. 1280x720"), "{input}: {error}");
+        }
+    }
+
+    #[test]
+    fn display_backend_parses_known_names() {
+        assert_eq!("x11".parse(), Ok(DisplayBackend::X11));
+        assert_eq!("wayland".parse(), Ok(DisplayBackend::Wayland));
+        let error = "X11".parse::<DisplayBackend>().unwrap_err();
+        assert!(error.contains("expected x11 or wayland"), "{error}");
+    }
+
+    #[test]
+    fn present_mode_parses_known_names() {
+        assert_eq!("mailbox".parse(), Ok(PresentModeArg::Mailbox));
+        assert_eq!(
+            PresentMode::from(PresentModeArg::Immediate),
+            PresentMode::Immediate
+        );
+        assert!("vsync".parse::<PresentModeArg>().is_err());
+    }
+}
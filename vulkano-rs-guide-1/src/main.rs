@@ -8,8 +8,12 @@ use vulkano::instance::{Instance, InstanceCreateInfo};
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
 use vulkano::sync::{self, GpuFuture};
 use vulkano::VulkanLibrary;
+use vulkano_rs_common::{logging, tracing};
 
 fn main() {
+    // Messages go through tracing, as in the rest of the chapters
+    logging::init();
+
     // Initialization
     // The instance maps vulkano to the local vulkan instalation
     let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
@@ -124,5 +128,5 @@ fn main() {
     let destination_content = destination.read().unwrap();
     assert_eq!(&*src_content, &*destination_content);
 
-    println!("Everything succeeded!");
+    tracing::info!("everything succeeded");
 }
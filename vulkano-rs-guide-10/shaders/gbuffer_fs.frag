@@ -0,0 +1,17 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+
+// One output per color attachment of the subpass
+layout(location = 0) out vec4 f_albedo;
+layout(location = 1) out vec4 f_normal;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 albedo;
+} pc;
+
+void main() {
+    f_albedo = pc.albedo;
+    f_normal = vec4(normalize(v_normal), 0.0);
+}
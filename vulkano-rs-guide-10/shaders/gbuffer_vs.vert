@@ -0,0 +1,20 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 albedo;
+} pc;
+
+void main() {
+    v_normal = mat3(pc.model) * normal;
+    gl_Position = frame.view_projection * pc.model * vec4(position, 1.0);
+}
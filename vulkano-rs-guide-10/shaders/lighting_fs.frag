@@ -0,0 +1,70 @@
+#version 460
+
+// Input attachments can only be read at the pixel being shaded, which is exactly
+// what the lighting pass needs and lets tiled GPUs keep the G-buffer on chip
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput u_albedo;
+layout(input_attachment_index = 1, set = 0, binding = 1) uniform subpassInput u_normals;
+layout(input_attachment_index = 2, set = 0, binding = 2) uniform subpassInput u_depth;
+
+layout(set = 0, binding = 3) uniform Lights {
+    // xyz: position, w: radius
+    vec4 position_radius[32];
+    vec4 color[32];
+} lights;
+
+layout(push_constant) uniform PushConstants {
+    mat4 inverse_view_projection;
+    vec2 screen_size;
+    uint light_count;
+    float ambient;
+    // 0: lit, 1: albedo, 2: normals, 3: depth
+    uint debug_view;
+} pc;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    vec3 albedo = subpassLoad(u_albedo).rgb;
+    vec3 normal = subpassLoad(u_normals).xyz;
+    float depth = subpassLoad(u_depth).x;
+
+    if (pc.debug_view == 1) {
+        f_color = vec4(albedo, 1.0);
+        return;
+    } else if (pc.debug_view == 2) {
+        f_color = vec4(normal * 0.5 + 0.5, 1.0);
+        return;
+    } else if (pc.debug_view == 3) {
+        // Most of the depth range is squeezed close to 1, stretch it to be visible
+        f_color = vec4(vec3(pow(depth, 50.0)), 1.0);
+        return;
+    }
+
+    // Nothing was drawn here
+    if (depth >= 1.0) {
+        f_color = vec4(0.02, 0.02, 0.03, 1.0);
+        return;
+    }
+
+    // The world position isn't stored, it is rebuilt from the depth and the pixel
+    // position by undoing the camera projection
+    vec2 ndc = gl_FragCoord.xy / pc.screen_size * 2.0 - 1.0;
+    vec4 world = pc.inverse_view_projection * vec4(ndc, depth, 1.0);
+    vec3 position = world.xyz / world.w;
+
+    vec3 color = albedo * pc.ambient;
+    for (uint i = 0; i < pc.light_count; i++) {
+        vec3 to_light = lights.position_radius[i].xyz - position;
+        float light_distance = length(to_light);
+        float radius = lights.position_radius[i].w;
+
+        // Smoothly reaches zero at the light's radius
+        float attenuation = clamp(1.0 - light_distance / radius, 0.0, 1.0);
+        attenuation *= attenuation;
+
+        float diffuse = max(dot(normal, to_light / light_distance), 0.0);
+        color += albedo * lights.color[i].rgb * diffuse * attenuation;
+    }
+
+    f_color = vec4(color, 1.0);
+}
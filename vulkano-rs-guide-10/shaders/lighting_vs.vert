@@ -0,0 +1,8 @@
+#version 460
+
+void main() {
+    // No vertex buffer needed: vertex 0, 1 and 2 become (-1, -1), (3, -1) and
+    // (-1, 3), a triangle that contains the whole screen
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,544 @@
+//Deferred rendering: a G-buffer subpass followed by a lighting subpass reading input attachments
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexInputState};
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Quat, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Has to match the array size in the lighting shader
+const MAX_LIGHTS: usize = 32;
+
+// Appends a square with the given center, normal and half-axes, counter-clockwise from outside
+fn push_quad(
+    vertices: &mut Vec<PosNormalUv>,
+    indices: &mut Vec<u32>,
+    center: Vec3,
+    normal: Vec3,
+    u: Vec3,
+    v: Vec3,
+) {
+    let base = vertices.len() as u32;
+    for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        vertices.push(PosNormalUv {
+            position: (center + u * su + v * sv).to_array(),
+            normal: normal.to_array(),
+            ..Default::default()
+        });
+    }
+    indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+// A unit cube with one quad per face, so every face has its own normal
+fn cube() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+    for (normal, u, v) in faces {
+        push_quad(
+            &mut vertices,
+            &mut indices,
+            normal * 0.5,
+            normal,
+            u * 0.5,
+            v * 0.5,
+        );
+    }
+    (vertices, indices)
+}
+
+fn ground(size: f32) -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    push_quad(
+        &mut vertices,
+        &mut indices,
+        Vec3::ZERO,
+        Vec3::Y,
+        Vec3::X * size,
+        Vec3::NEG_Z * size,
+    );
+    (vertices, indices)
+}
+
+struct Mesh {
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+}
+
+struct Object {
+    mesh: usize,
+    model: Mat4,
+    albedo: [f32; 4],
+}
+
+// First subpass: no lighting at all, just store what the lighting pass will need per pixel
+mod gbuffer_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/gbuffer_vs.vert",
+    }
+}
+
+mod gbuffer_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/gbuffer_fs.frag",
+    }
+}
+
+// Second subpass: a single triangle covering the screen, lighting every pixel once
+mod lighting_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/lighting_vs.vert",
+    }
+}
+
+mod lighting_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/lighting_fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DebugView {
+    Lit,
+    Albedo,
+    Normals,
+    Depth,
+}
+
+struct GBuffer {
+    albedo: Arc<ImageView<AttachmentImage>>,
+    normals: Arc<ImageView<AttachmentImage>>,
+    depth: Arc<ImageView<AttachmentImage>>,
+}
+
+struct Deferred {
+    camera: Camera,
+    allocators: Allocators,
+    uniform_buffer: StreamingBuffer,
+    meshes: Vec<Mesh>,
+    objects: Vec<Object>,
+    render_pass: Arc<RenderPass>,
+    gbuffer_pipeline: Arc<GraphicsPipeline>,
+    lighting_pipeline: Arc<GraphicsPipeline>,
+    gbuffer: Option<GBuffer>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    start: Instant,
+    // Parameters exposed in the overlay
+    light_count: u32,
+    ambient: f32,
+    debug_view: DebugView,
+}
+
+impl Deferred {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let meshes = [cube(), ground(10.0)]
+            .into_iter()
+            .map(|(vertices, indices)| Mesh {
+                vertex_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::VERTEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    vertices,
+                )
+                    .expect("failed to create vertex buffer"),
+                index_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::INDEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    indices,
+                )
+                    .expect("failed to create index buffer"),
+            })
+            .collect();
+
+        // A grid of pillars, lots of surfaces for lots of lights
+        let mut objects = vec![Object {
+            mesh: 1,
+            model: Mat4::IDENTITY,
+            albedo: [0.8, 0.8, 0.8, 1.0],
+        }];
+        for x in -3..=3 {
+            for z in -3..=3 {
+                let height = 0.5 + ((x * 7 + z * 3) as f32).sin().abs() * 1.5;
+                objects.push(Object {
+                    mesh: 0,
+                    model: Mat4::from_scale_rotation_translation(
+                        Vec3::new(0.5, height, 0.5),
+                        Quat::from_rotation_y((x + z) as f32 * 0.3),
+                        Vec3::new(x as f32 * 2.5, height / 2.0, z as f32 * 2.5),
+                    ),
+                    albedo: [0.9, 0.9, 0.9, 1.0],
+                });
+            }
+        }
+
+        // The final color is the only attachment that outlives the render pass, the G-buffer
+        // is consumed by the second subpass and never stored
+        let render_pass = vulkano::ordered_passes_renderpass!(
+            device.clone(),
+            attachments: {
+                final_color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                albedo: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::R8G8B8A8_UNORM,
+                    samples: 1,
+                },
+                normals: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::R16G16B16A16_SFLOAT,
+                    samples: 1,
+                },
+                // The positions are rebuilt from the depth, so it needs more precision than
+                // the 16-bit depth used in the other chapters
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D32_SFLOAT,
+                    samples: 1,
+                },
+            },
+            passes: [
+                {
+                    color: [albedo, normals],
+                    depth_stencil: {depth},
+                    input: [],
+                },
+                {
+                    color: [final_color],
+                    depth_stencil: {},
+                    input: [albedo, normals, depth],
+                },
+            ],
+        )
+            .unwrap();
+
+        let gbuffer_subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+        let lighting_subpass = Subpass::from(render_pass.clone(), 1).unwrap();
+
+        let gbuffer_vs = gbuffer_vs::load(device.clone()).expect("failed to create shader module");
+        let gbuffer_fs = gbuffer_fs::load(device.clone()).expect("failed to create shader module");
+        let gbuffer_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(gbuffer_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(gbuffer_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .color_blend_state(ColorBlendState::new(
+                gbuffer_subpass.num_color_attachments(),
+            ))
+            .render_pass(gbuffer_subpass)
+            .build(device.clone())
+            .expect("failed to create G-buffer pipeline");
+
+        let lighting_vs =
+            lighting_vs::load(device.clone()).expect("failed to create shader module");
+        let lighting_fs =
+            lighting_fs::load(device.clone()).expect("failed to create shader module");
+        let lighting_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(lighting_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(lighting_fs.entry_point("main").unwrap(), ())
+            .color_blend_state(ColorBlendState::new(
+                lighting_subpass.num_color_attachments(),
+            ))
+            .render_pass(lighting_subpass)
+            .build(device.clone())
+            .expect("failed to create lighting pipeline");
+
+        Deferred {
+            camera: Camera::new(Vec3::new(0.0, 6.0, 12.0), Vec3::ZERO),
+            uniform_buffer: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            meshes,
+            objects,
+            render_pass,
+            gbuffer_pipeline,
+            lighting_pipeline,
+            gbuffer: None,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            start: Instant::now(),
+            light_count: 16,
+            ambient: 0.05,
+            debug_view: DebugView::Lit,
+        }
+    }
+
+    // Colored lights circling between the pillars
+    fn lights(&self) -> lighting_fs::Lights {
+        let time = self.start.elapsed().as_secs_f32();
+        let mut lights = lighting_fs::Lights {
+            position_radius: [[0.0; 4]; MAX_LIGHTS],
+            color: [[0.0; 4]; MAX_LIGHTS],
+        };
+
+        for i in 0..MAX_LIGHTS {
+            let phase = i as f32 / MAX_LIGHTS as f32 * TAU;
+            let orbit = 2.0 + (i % 4) as f32 * 2.0;
+            let angle = phase + time * (0.2 + (i % 3) as f32 * 0.15);
+            lights.position_radius[i] = [
+                orbit * angle.cos(),
+                0.5 + (time + phase).sin().abs(),
+                orbit * angle.sin(),
+                4.0,
+            ];
+            lights.color[i] = [
+                0.5 + 0.5 * phase.cos(),
+                0.5 + 0.5 * (phase + TAU / 3.0).cos(),
+                0.5 + 0.5 * (phase + 2.0 * TAU / 3.0).cos(),
+                1.0,
+            ];
+        }
+
+        lights
+    }
+}
+
+impl App for Deferred {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        // Transient input attachments: written by one subpass, read by the next, never copied
+        let attachment = |format| {
+            ImageView::new_default(
+                AttachmentImage::transient_input_attachment(
+                    &self.allocators.memory,
+                    extent,
+                    format,
+                )
+                    .unwrap(),
+            )
+                .unwrap()
+        };
+        let gbuffer = GBuffer {
+            albedo: attachment(Format::R8G8B8A8_UNORM),
+            normals: attachment(Format::R16G16B16A16_SFLOAT),
+            depth: attachment(Format::D32_SFLOAT),
+        };
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![
+                            view.clone(),
+                            gbuffer.albedo.clone(),
+                            gbuffer.normals.clone(),
+                            gbuffer.depth.clone(),
+                        ],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+        self.gbuffer = Some(gbuffer);
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let view_projection = self.camera.view_projection(width / height);
+        let gbuffer = self.gbuffer.as_ref().unwrap();
+
+        let frame_subbuffer = self.uniform_buffer.write(gbuffer_vs::Frame {
+            view_projection: view_projection.to_cols_array_2d(),
+        });
+        let gbuffer_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.gbuffer_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::buffer(0, frame_subbuffer)],
+        )
+            .unwrap();
+
+        let lights_subbuffer = self.uniform_buffer.write(self.lights());
+        let lighting_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.lighting_pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view(0, gbuffer.albedo.clone()),
+                WriteDescriptorSet::image_view(1, gbuffer.normals.clone()),
+                WriteDescriptorSet::image_view(2, gbuffer.depth.clone()),
+                WriteDescriptorSet::buffer(3, lights_subbuffer),
+            ],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![
+                        Some([0.0, 0.0, 0.0, 1.0].into()),
+                        Some([0.0, 0.0, 0.0, 0.0].into()),
+                        Some([0.0, 0.0, 0.0, 0.0].into()),
+                        Some(1f32.into()),
+                    ],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.gbuffer_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.gbuffer_pipeline.layout().clone(),
+                0,
+                gbuffer_set,
+            );
+
+        for object in &self.objects {
+            let mesh = &self.meshes[object.mesh];
+            let push_constants = gbuffer_vs::PushConstants {
+                model: object.model.to_cols_array_2d(),
+                albedo: object.albedo,
+            };
+
+            builder
+                .push_constants(self.gbuffer_pipeline.layout().clone(), 0, push_constants)
+                .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                .bind_index_buffer(mesh.index_buffer.clone())
+                .draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0)
+                .unwrap();
+        }
+
+        let push_constants = lighting_fs::PushConstants {
+            inverse_view_projection: view_projection.inverse().to_cols_array_2d(),
+            screen_size: [width, height],
+            light_count: self.light_count,
+            ambient: self.ambient,
+            debug_view: self.debug_view as u32,
+        };
+
+        // The render pass takes care of the dependency between the two subpasses
+        builder
+            .next_subpass(SubpassContents::Inline)
+            .unwrap()
+            .bind_pipeline_graphics(self.lighting_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.lighting_pipeline.layout().clone(),
+                0,
+                lighting_set,
+            )
+            .push_constants(self.lighting_pipeline.layout().clone(), 0, push_constants)
+            .draw(3, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Deferred").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.light_count, 0..=MAX_LIGHTS as u32).text("lights"));
+            ui.add(egui::Slider::new(&mut self.ambient, 0.0..=0.5).text("ambient"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.debug_view, DebugView::Lit, "lit");
+                ui.radio_value(&mut self.debug_view, DebugView::Albedo, "albedo");
+                ui.radio_value(&mut self.debug_view, DebugView::Normals, "normals");
+                ui.radio_value(&mut self.debug_view, DebugView::Depth, "depth");
+            });
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-10",
+        DeviceExtensions::empty(),
+        Deferred::new,
+    );
+}
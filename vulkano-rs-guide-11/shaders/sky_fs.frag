@@ -0,0 +1,11 @@
+#version 460
+
+layout(location = 0) in vec3 v_direction;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 1) uniform samplerCube u_skybox;
+
+void main() {
+    f_color = vec4(texture(u_skybox, v_direction).rgb, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+
+layout(location = 0) out vec3 v_direction;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+    mat4 sky_view_projection;
+    vec4 camera_position;
+} frame;
+
+void main() {
+    v_direction = position;
+    // sky_view_projection ignores the camera position, so the sky never gets
+    // closer. Setting z to w puts every vertex at depth 1 after the perspective
+    // divide, behind everything else in the scene
+    vec4 clip = frame.sky_view_projection * vec4(position, 1.0);
+    gl_Position = clip.xyww;
+}
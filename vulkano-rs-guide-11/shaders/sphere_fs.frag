@@ -0,0 +1,34 @@
+#version 460
+
+layout(location = 0) in vec3 v_position;
+layout(location = 1) in vec3 v_normal;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+    mat4 sky_view_projection;
+    vec4 camera_position;
+} frame;
+layout(set = 0, binding = 1) uniform samplerCube u_skybox;
+
+layout(push_constant) uniform PushConstants {
+    vec4 tint;
+    // 0: reflect, 1: refract
+    uint mode;
+    // Ratio of the refractive indices, air to glass is about 1 / 1.52
+    float eta;
+} pc;
+
+void main() {
+    vec3 view_direction = normalize(v_position - frame.camera_position.xyz);
+    vec3 normal = normalize(v_normal);
+
+    // A cube map is sampled with a direction instead of texture coordinates, so
+    // the sphere just looks up whatever the bounced or bent view ray would hit
+    vec3 direction = pc.mode == 0
+        ? reflect(view_direction, normal)
+        : refract(view_direction, normal, pc.eta);
+
+    f_color = vec4(texture(u_skybox, direction).rgb * pc.tint.rgb, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_position;
+layout(location = 1) out vec3 v_normal;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+    mat4 sky_view_projection;
+    vec4 camera_position;
+} frame;
+
+void main() {
+    v_position = position;
+    v_normal = normal;
+    gl_Position = frame.view_projection * vec4(position, 1.0);
+}
@@ -0,0 +1,544 @@
+//A skybox drawn from a cube map, reflected and refracted by a sphere
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferToImageInfo, PrimaryAutoCommandBuffer,
+    RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::{ImageView, ImageViewCreateInfo, ImageViewType};
+use vulkano::image::{
+    AttachmentImage, ImageCreateFlags, ImageDimensions, ImageLayout, ImageUsage, ImmutableImage,
+    MipmapsCount,
+};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthState, DepthStencilState};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat3, Mat4, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// The order Vulkan expects the layers of a cube map in
+const FACES: [&str; 6] = ["posx", "negx", "posy", "negy", "posz", "negz"];
+
+// A UV sphere of radius 1, on a unit sphere the normal is simply the position
+fn sphere(stacks: u32, sectors: u32) -> (Vec<PosNormalUv>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    for i in 0..=stacks {
+        let phi = PI * i as f32 / stacks as f32;
+        for j in 0..=sectors {
+            let theta = 2.0 * PI * j as f32 / sectors as f32;
+            let position = [phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin()];
+            vertices.push(PosNormalUv {
+                position,
+                normal: position,
+                ..Default::default()
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    for i in 0..stacks {
+        for j in 0..sectors {
+            let a = i * (sectors + 1) + j;
+            let b = a + sectors + 1;
+            // Counter-clockwise when seen from outside
+            indices.extend([a, b + 1, b, a, a + 1, b + 1]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct SkyVertex {
+    #[format(R32G32B32_SFLOAT)]
+    position: [f32; 3],
+}
+
+// Only the directions matter for the skybox, so the cube needs neither normals nor indices
+fn skybox_cube() -> Vec<SkyVertex> {
+    let mut vertices = Vec::new();
+    for axis in 0..3 {
+        for side in [-1.0, 1.0] {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let corner = |a: f32, b: f32| {
+                let mut position = [0.0; 3];
+                position[axis] = side;
+                position[u] = a;
+                position[v] = b;
+                SkyVertex { position }
+            };
+            // The cube is seen from inside and drawn without culling, the winding doesn't matter
+            vertices.extend([
+                corner(-1.0, -1.0),
+                corner(1.0, -1.0),
+                corner(1.0, 1.0),
+                corner(-1.0, -1.0),
+                corner(1.0, 1.0),
+                corner(-1.0, 1.0),
+            ]);
+        }
+    }
+    vertices
+}
+
+// Loads the six faces into the six layers of one image, which must be created as cube
+// compatible so it can be viewed as a cube map
+fn load_cube_map(
+    directory: &str,
+    memory_allocator: &(impl MemoryAllocator + ?Sized),
+    uploads: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+) -> Arc<ImageView<ImmutableImage>> {
+    let mut size = None;
+    let mut pixels = Vec::new();
+    for face in FACES {
+        let path = format!("{directory}/{face}.png");
+        let image = image::open(&path)
+            .unwrap_or_else(|err| panic!("failed to load {path}: {err}"))
+            .to_rgba8();
+        assert_eq!(image.width(), image.height(), "{path} is not square");
+        assert_eq!(
+            *size.get_or_insert(image.width()),
+            image.width(),
+            "all faces must have the same size"
+        );
+        pixels.extend_from_slice(image.as_raw());
+    }
+    let size = size.unwrap();
+
+    let staging_buffer = Buffer::from_iter(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        pixels,
+    )
+        .expect("failed to create staging buffer");
+
+    // ImmutableImage::from_iter doesn't take creation flags, so the image is created
+    // uninitialized and filled by hand
+    let (image, initialization) = ImmutableImage::uninitialized(
+        memory_allocator,
+        ImageDimensions::Dim2d {
+            width: size,
+            height: size,
+            array_layers: 6,
+        },
+        Format::R8G8B8A8_SRGB,
+        MipmapsCount::One,
+        ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+        ImageCreateFlags::CUBE_COMPATIBLE,
+        ImageLayout::ShaderReadOnlyOptimal,
+        [],
+    )
+        .expect("failed to create cube map");
+
+    // The faces are consecutive in the buffer, so one copy fills all six layers
+    uploads
+        .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+            staging_buffer,
+            initialization,
+        ))
+        .unwrap();
+
+    ImageView::new(
+        image.clone(),
+        ImageViewCreateInfo {
+            view_type: ImageViewType::Cube,
+            ..ImageViewCreateInfo::from_image(&image)
+        },
+    )
+        .unwrap()
+}
+
+mod sphere_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/sphere_vs.vert",
+    }
+}
+
+mod sphere_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/sphere_fs.frag",
+    }
+}
+
+mod sky_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/sky_vs.vert",
+    }
+}
+
+mod sky_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/sky_fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Reflect,
+    Refract,
+}
+
+struct Skybox {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    sphere_pipeline: Arc<GraphicsPipeline>,
+    sky_pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    uniform_buffer: StreamingBuffer,
+    cube_map: Arc<ImageView<ImmutableImage>>,
+    sampler: Arc<Sampler>,
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+    sky_vertex_buffer: Subbuffer<[SkyVertex]>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    mode: Mode,
+    refractive_index: f32,
+    tint: [f32; 3],
+}
+
+impl Skybox {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let (vertices, indices) = sphere(32, 64);
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            vertices,
+        )
+            .expect("failed to create vertex buffer");
+        let index_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            indices,
+        )
+            .expect("failed to create index buffer");
+        let sky_vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            skybox_cube(),
+        )
+            .expect("failed to create vertex buffer");
+
+        let directory = args::value::<String>("--skybox")
+            .unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/assets/skybox").into());
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let cube_map = load_cube_map(&directory, &allocators.memory, &mut uploads);
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        // Clamping avoids filtering across the seams between the faces
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..SamplerCreateInfo::simple_repeat_linear_no_mipmap()
+            },
+        )
+            .expect("failed to create sampler");
+
+        let uniform_buffer = StreamingBuffer::new(allocators.memory.clone());
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let sphere_vs = sphere_vs::load(device.clone()).expect("failed to create shader module");
+        let sphere_fs = sphere_fs::load(device.clone()).expect("failed to create shader module");
+        let sphere_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(sphere_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(sphere_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create sphere pipeline");
+
+        // The sky sits exactly at depth 1, where the buffer was cleared to, so the test has to
+        // accept equal depths. Writing depth is pointless since nothing is drawn after it
+        let sky_depth = DepthStencilState {
+            depth: Some(DepthState {
+                enable_dynamic: false,
+                write_enable: false.into(),
+                compare_op: CompareOp::LessOrEqual.into(),
+            }),
+            ..DepthStencilState::disabled()
+        };
+        let sky_vs = sky_vs::load(device.clone()).expect("failed to create shader module");
+        let sky_fs = sky_fs::load(device.clone()).expect("failed to create shader module");
+        let sky_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(SkyVertex::per_vertex())
+            .vertex_shader(sky_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(sky_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(sky_depth)
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create skybox pipeline");
+
+        Skybox {
+            camera: Camera::new(Vec3::new(0.0, 0.5, 4.0), Vec3::ZERO),
+            render_pass,
+            sphere_pipeline,
+            sky_pipeline,
+            allocators,
+            uniform_buffer,
+            cube_map,
+            sampler,
+            vertex_buffer,
+            index_buffer,
+            sky_vertex_buffer,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            mode: Mode::Reflect,
+            refractive_index: 1.52,
+            tint: [0.95, 0.95, 1.0],
+        }
+    }
+
+    fn descriptor_set(
+        &self,
+        pipeline: &GraphicsPipeline,
+        frame: Subbuffer<sphere_vs::Frame>,
+    ) -> Arc<PersistentDescriptorSet> {
+        PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::buffer(0, frame),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    self.cube_map.clone(),
+                    self.sampler.clone(),
+                ),
+            ],
+        )
+            .unwrap()
+    }
+}
+
+impl App for Skybox {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let projection = self.camera.projection(width / height);
+        // Keeping only the rotation of the view leaves the camera at the center of the skybox
+        let sky_view = Mat4::from_mat3(Mat3::from_mat4(self.camera.view()));
+
+        let frame_subbuffer = self.uniform_buffer.write(sphere_vs::Frame {
+            view_projection: (projection * self.camera.view()).to_cols_array_2d(),
+            sky_view_projection: (projection * sky_view).to_cols_array_2d(),
+            camera_position: self.camera.position.extend(1.0).to_array(),
+        });
+        let sphere_set = self.descriptor_set(&self.sphere_pipeline, frame_subbuffer.clone());
+        let sky_set = self.descriptor_set(&self.sky_pipeline, frame_subbuffer);
+
+        let push_constants = sphere_fs::PushConstants {
+            tint: [self.tint[0], self.tint[1], self.tint[2], 1.0],
+            mode: self.mode as u32,
+            eta: 1.0 / self.refractive_index,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.sphere_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.sphere_pipeline.layout().clone(),
+                0,
+                sphere_set,
+            )
+            .push_constants(self.sphere_pipeline.layout().clone(), 0, push_constants)
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .bind_index_buffer(self.index_buffer.clone())
+            .draw_indexed(self.index_buffer.len() as u32, 1, 0, 0, 0)
+            .unwrap()
+            // The sky goes last, so the depth test skips every pixel already covered by the
+            // scene instead of shading the whole screen
+            .bind_pipeline_graphics(self.sky_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.sky_pipeline.layout().clone(),
+                0,
+                sky_set,
+            )
+            .bind_vertex_buffers(0, self.sky_vertex_buffer.clone())
+            .draw(self.sky_vertex_buffer.len() as u32, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Sphere").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.mode, Mode::Reflect, "reflect");
+                ui.radio_value(&mut self.mode, Mode::Refract, "refract");
+            });
+            ui.add_enabled(
+                self.mode == Mode::Refract,
+                egui::Slider::new(&mut self.refractive_index, 1.0..=2.5).text("refractive index"),
+            );
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut self.tint);
+                ui.label("tint");
+            });
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-11",
+        DeviceExtensions::empty(),
+        Skybox::new,
+    );
+}
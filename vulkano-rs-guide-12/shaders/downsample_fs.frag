@@ -0,0 +1,58 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D u_source;
+
+layout(push_constant) uniform PushConstants {
+    float threshold;
+    // Width of the smooth transition around the threshold
+    float knee;
+    // Only the first downsample, straight from the scene, extracts the bright parts
+    uint prefilter;
+} pc;
+
+vec3 sample_source(vec2 offset) {
+    vec2 texel_size = 1.0 / vec2(textureSize(u_source, 0));
+    return texture(u_source, v_tex_coord + offset * texel_size).rgb;
+}
+
+// Keeps what is above the threshold, with a quadratic curve instead of a hard cut
+vec3 bright_pass(vec3 color) {
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - pc.threshold + pc.knee, 0.0, 2.0 * pc.knee);
+    soft = soft * soft / (4.0 * pc.knee + 0.0001);
+    return color * max(soft, brightness - pc.threshold) / max(brightness, 0.0001);
+}
+
+void main() {
+    // 13 bilinear taps from the source, which is twice the size of the target. The
+    // weights favor the center and keep small bright spots from flickering as they
+    // move across pixels
+    vec3 a = sample_source(vec2(-2.0, 2.0));
+    vec3 b = sample_source(vec2(0.0, 2.0));
+    vec3 c = sample_source(vec2(2.0, 2.0));
+    vec3 d = sample_source(vec2(-2.0, 0.0));
+    vec3 e = sample_source(vec2(0.0, 0.0));
+    vec3 f = sample_source(vec2(2.0, 0.0));
+    vec3 g = sample_source(vec2(-2.0, -2.0));
+    vec3 h = sample_source(vec2(0.0, -2.0));
+    vec3 i = sample_source(vec2(2.0, -2.0));
+    vec3 j = sample_source(vec2(-1.0, 1.0));
+    vec3 k = sample_source(vec2(1.0, 1.0));
+    vec3 l = sample_source(vec2(-1.0, -1.0));
+    vec3 m = sample_source(vec2(1.0, -1.0));
+
+    vec3 color = e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+
+    if (pc.prefilter != 0) {
+        color = bright_pass(color);
+    }
+
+    f_color = vec4(color, 1.0);
+}
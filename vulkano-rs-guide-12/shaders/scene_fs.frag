@@ -0,0 +1,37 @@
+#version 460
+
+layout(location = 0) in vec3 v_position;
+layout(location = 1) in vec3 v_normal;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+    vec4 light_position[4];
+    vec4 light_color[4];
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 color;
+    uint emissive;
+} pc;
+
+void main() {
+    if (pc.emissive != 0) {
+        f_color = pc.color;
+        return;
+    }
+
+    vec3 normal = normalize(v_normal);
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < 4; i++) {
+        vec3 to_light = frame.light_position[i].xyz - v_position;
+        float distance_squared = dot(to_light, to_light);
+        float diffuse = max(dot(normal, normalize(to_light)), 0.0);
+        // Physically based falloff, only usable because nothing gets clamped here
+        color += frame.light_color[i].rgb * diffuse / distance_squared;
+    }
+
+    f_color = vec4(color * pc.color.rgb, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_position;
+layout(location = 1) out vec3 v_normal;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+    vec4 light_position[4];
+    vec4 light_color[4];
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 color;
+    uint emissive;
+} pc;
+
+void main() {
+    vec4 world = pc.model * vec4(position, 1.0);
+    v_position = world.xyz;
+    v_normal = transpose(inverse(mat3(pc.model))) * normal;
+    gl_Position = frame.view_projection * world;
+}
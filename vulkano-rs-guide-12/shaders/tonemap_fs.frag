@@ -0,0 +1,46 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D u_hdr;
+layout(set = 0, binding = 1) uniform sampler2D u_bloom;
+
+layout(push_constant) uniform PushConstants {
+    float exposure;
+    // 0 turns bloom off
+    float bloom_strength;
+    // 0: clamp, 1: Reinhard, 2: ACES
+    uint operator;
+    // Whether the swapchain format does the sRGB encoding for us
+    uint srgb_output;
+} pc;
+
+#include <tonemap.glsl>
+
+void main() {
+    vec3 hdr = texture(u_hdr, v_tex_coord).rgb;
+    // The bloom images aren't rendered at all when bloom is off, so they are only
+    // read when needed
+    if (pc.bloom_strength > 0.0) {
+        hdr += texture(u_bloom, v_tex_coord).rgb * pc.bloom_strength;
+    }
+    hdr *= pc.exposure;
+
+    vec3 mapped;
+    if (pc.operator == 0) {
+        // What an 8-bit target would do: everything above 1 is lost
+        mapped = clamp(hdr, 0.0, 1.0);
+    } else if (pc.operator == 1) {
+        mapped = reinhard(hdr);
+    } else {
+        mapped = aces(hdr);
+    }
+
+    if (pc.srgb_output == 0) {
+        mapped = pow(mapped, vec3(1.0 / 2.2));
+    }
+
+    f_color = vec4(mapped, 1.0);
+}
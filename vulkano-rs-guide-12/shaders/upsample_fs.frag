@@ -0,0 +1,29 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D u_source;
+
+layout(push_constant) uniform PushConstants {
+    // In texels of the smaller source level
+    float radius;
+} pc;
+
+void main() {
+    vec2 d = pc.radius / vec2(textureSize(u_source, 0));
+
+    // 3x3 tent filter, the result is added to the target by the blend state
+    vec3 color = texture(u_source, v_tex_coord).rgb * 4.0;
+    color += texture(u_source, v_tex_coord + vec2(-d.x, 0.0)).rgb * 2.0;
+    color += texture(u_source, v_tex_coord + vec2(d.x, 0.0)).rgb * 2.0;
+    color += texture(u_source, v_tex_coord + vec2(0.0, -d.y)).rgb * 2.0;
+    color += texture(u_source, v_tex_coord + vec2(0.0, d.y)).rgb * 2.0;
+    color += texture(u_source, v_tex_coord + vec2(-d.x, -d.y)).rgb;
+    color += texture(u_source, v_tex_coord + vec2(d.x, -d.y)).rgb;
+    color += texture(u_source, v_tex_coord + vec2(-d.x, d.y)).rgb;
+    color += texture(u_source, v_tex_coord + vec2(d.x, d.y)).rgb;
+
+    f_color = vec4(color / 16.0, 1.0);
+}
@@ -0,0 +1,793 @@
+//HDR rendering: lighting goes to a floating point image, bloom spreads the brightest parts and
+//a post pass tonemaps the result for the screen
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::{Format, NumericType};
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendState};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexInputState};
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Quat, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Enough range and precision for light far brighter than 1.0, at half the size of 32-bit floats
+const HDR_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+// How many times the bloom halves the resolution, the last level is 1/64 of the window
+const BLOOM_LEVELS: usize = 6;
+
+// Appends a square with the given center, normal and half-axes, counter-clockwise from outside
+fn push_quad(
+    vertices: &mut Vec<PosNormalUv>,
+    indices: &mut Vec<u32>,
+    center: Vec3,
+    normal: Vec3,
+    u: Vec3,
+    v: Vec3,
+) {
+    let base = vertices.len() as u32;
+    for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        vertices.push(PosNormalUv {
+            position: (center + u * su + v * sv).to_array(),
+            normal: normal.to_array(),
+            ..Default::default()
+        });
+    }
+    indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+// A unit cube with one quad per face, so every face has its own normal
+fn cube() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+    for (normal, u, v) in faces {
+        push_quad(
+            &mut vertices,
+            &mut indices,
+            normal * 0.5,
+            normal,
+            u * 0.5,
+            v * 0.5,
+        );
+    }
+    (vertices, indices)
+}
+
+// The same cube seen from inside: normals point inwards and the winding is reversed
+fn tunnel() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = cube();
+    for vertex in &mut vertices {
+        vertex.normal = vertex.normal.map(|n| -n);
+    }
+    for triangle in indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+    (vertices, indices)
+}
+
+struct Mesh {
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+}
+
+struct Object {
+    mesh: usize,
+    model: Mat4,
+    color: [f32; 3],
+    // Emissive objects show their color as is instead of being lit
+    emissive: bool,
+}
+
+struct Light {
+    position: Vec3,
+    // Not limited to 0..1, the light at the end of the tunnel is hundreds of times brighter
+    color: [f32; 3],
+}
+
+const LIGHTS: [Light; 4] = [
+    Light {
+        position: Vec3::new(0.0, 0.0, -49.5),
+        color: [200.0, 200.0, 200.0],
+    },
+    Light {
+        position: Vec3::new(-1.4, -1.9, -9.0),
+        color: [1.0, 0.0, 0.0],
+    },
+    Light {
+        position: Vec3::new(0.0, -1.8, -4.0),
+        color: [0.0, 0.0, 1.5],
+    },
+    Light {
+        position: Vec3::new(0.8, -1.7, -6.0),
+        color: [0.0, 0.8, 0.0],
+    },
+];
+
+mod scene_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/scene_vs.vert",
+    }
+}
+
+mod scene_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/scene_fs.frag",
+    }
+}
+
+mod fullscreen_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/fullscreen_vs.vert",
+    }
+}
+
+// Bloom runs at lower and lower resolutions: every level is a blurred, half size copy of the
+// one above, the first one being a copy of only the bright parts of the scene
+mod downsample_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/downsample_fs.frag",
+    }
+}
+
+// Going back up, every level is blurred once more and added on top of the level above
+mod upsample_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/upsample_fs.frag",
+    }
+}
+
+mod tonemap_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["../shaders/common"],
+        path: "shaders/tonemap_fs.frag",
+    }
+}
+
+// One step of the bloom chain, half the size of the previous one
+struct BloomLevel {
+    image: Arc<ImageView<AttachmentImage>>,
+    extent: [u32; 2],
+    // Downsampling overwrites the level, upsampling adds to it
+    downsample_framebuffer: Arc<Framebuffer>,
+    upsample_framebuffer: Arc<Framebuffer>,
+    // Reads the previous level, or the scene for the first one
+    downsample_set: Arc<PersistentDescriptorSet>,
+    // Reads this level, to upsample it into the previous one
+    upsample_set: Arc<PersistentDescriptorSet>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+struct Hdr {
+    camera: Camera,
+    allocators: Allocators,
+    meshes: Vec<Mesh>,
+    objects: Vec<Object>,
+    uniform_buffer: StreamingBuffer,
+    sampler: Arc<Sampler>,
+    scene_render_pass: Arc<RenderPass>,
+    downsample_render_pass: Arc<RenderPass>,
+    upsample_render_pass: Arc<RenderPass>,
+    tonemap_render_pass: Arc<RenderPass>,
+    scene_pipeline: Arc<GraphicsPipeline>,
+    downsample_pipeline: Arc<GraphicsPipeline>,
+    upsample_pipeline: Arc<GraphicsPipeline>,
+    tonemap_pipeline: Arc<GraphicsPipeline>,
+    // Recreated with the window size
+    scene_framebuffer: Option<Arc<Framebuffer>>,
+    bloom_levels: Vec<BloomLevel>,
+    tonemap_set: Option<Arc<PersistentDescriptorSet>>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    srgb_output: bool,
+    // Parameters exposed in the overlay
+    exposure: f32,
+    operator: Operator,
+    bloom: bool,
+    bloom_threshold: f32,
+    bloom_strength: f32,
+    bloom_radius: f32,
+}
+
+impl Hdr {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let meshes = [cube(), tunnel()]
+            .into_iter()
+            .map(|(vertices, indices)| Mesh {
+                vertex_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::VERTEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    vertices,
+                )
+                    .expect("failed to create vertex buffer"),
+                index_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::INDEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    indices,
+                )
+                    .expect("failed to create index buffer"),
+            })
+            .collect();
+
+        // A long tunnel with a blinding light at the far end and a few dim ones near the camera
+        let mut objects = vec![Object {
+            mesh: 1,
+            model: Mat4::from_scale_rotation_translation(
+                Vec3::new(5.0, 5.0, 55.0),
+                Quat::IDENTITY,
+                Vec3::new(0.0, 0.0, -22.5),
+            ),
+            color: [0.8, 0.8, 0.8],
+            emissive: false,
+        }];
+        objects.extend(LIGHTS.iter().map(|light| Object {
+            mesh: 0,
+            model: Mat4::from_scale_rotation_translation(
+                Vec3::splat(0.1),
+                Quat::IDENTITY,
+                light.position,
+            ),
+            color: light.color,
+            emissive: true,
+        }));
+
+        // The scene is drawn into the floating point image, which is kept for the next pass
+        let scene_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: HDR_FORMAT,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        // Downsampling overwrites every pixel of a bloom level, upsampling keeps what is there
+        // and blends on top of it
+        let downsample_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: HDR_FORMAT,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+        let upsample_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Load,
+                    store: Store,
+                    format: HDR_FORMAT,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        // Every pixel of the swapchain image gets overwritten, so there's nothing to clear
+        let tonemap_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let scene_vs = scene_vs::load(device.clone()).expect("failed to create shader module");
+        let scene_fs = scene_fs::load(device.clone()).expect("failed to create shader module");
+        let scene_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(scene_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(scene_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(scene_render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create scene pipeline");
+
+        // All the post passes draw the same fullscreen triangle
+        let fullscreen_vs =
+            fullscreen_vs::load(device.clone()).expect("failed to create shader module");
+
+        let downsample_fs =
+            downsample_fs::load(device.clone()).expect("failed to create shader module");
+        let downsample_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(fullscreen_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(downsample_fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(downsample_render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create downsample pipeline");
+
+        let upsample_fs =
+            upsample_fs::load(device.clone()).expect("failed to create shader module");
+        let upsample_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(fullscreen_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(upsample_fs.entry_point("main").unwrap(), ())
+            .color_blend_state(ColorBlendState::new(1).blend(AttachmentBlend::additive()))
+            .render_pass(Subpass::from(upsample_render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create upsample pipeline");
+
+        let tonemap_fs = tonemap_fs::load(device.clone()).expect("failed to create shader module");
+        let tonemap_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(fullscreen_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(tonemap_fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(tonemap_render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create tonemap pipeline");
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..SamplerCreateInfo::simple_repeat_linear_no_mipmap()
+            },
+        )
+            .expect("failed to create sampler");
+
+        // Without an sRGB swapchain the shader has to apply the gamma curve itself
+        let srgb_output = renderer.swapchain.image_format().type_color() == Some(NumericType::SRGB);
+
+        Hdr {
+            camera: Camera::new(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -10.0)),
+            uniform_buffer: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            meshes,
+            objects,
+            sampler,
+            scene_render_pass,
+            downsample_render_pass,
+            upsample_render_pass,
+            tonemap_render_pass,
+            scene_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            tonemap_pipeline,
+            scene_framebuffer: None,
+            bloom_levels: Vec::new(),
+            tonemap_set: None,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            srgb_output,
+            exposure: 1.0,
+            operator: Operator::Aces,
+            bloom: true,
+            bloom_threshold: 1.0,
+            bloom_strength: 0.3,
+            bloom_radius: 1.0,
+        }
+    }
+}
+
+fn level_viewport(extent: [u32; 2]) -> Viewport {
+    Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [extent[0] as f32, extent[1] as f32],
+        depth_range: 0.0..1.0,
+    }
+}
+
+impl App for Hdr {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        // Rendered to in the first pass and sampled in the second
+        let hdr_image = ImageView::new_default(
+            AttachmentImage::sampled(&self.allocators.memory, extent, HDR_FORMAT).unwrap(),
+        )
+            .unwrap();
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.scene_framebuffer = Some(
+            Framebuffer::new(
+                self.scene_render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![hdr_image.clone(), depth_buffer],
+                    ..Default::default()
+                },
+            )
+                .unwrap(),
+        );
+
+        // vulkano 0.33's AttachmentImage has a single mip level, so each level of the chain is
+        // its own image. Rendering to the mip levels of one image works the same way, with one
+        // image view per level
+        let mut source = hdr_image.clone();
+        let mut level_extent = extent;
+        self.bloom_levels.clear();
+        for _ in 0..BLOOM_LEVELS {
+            level_extent = level_extent.map(|size| (size / 2).max(1));
+            let image = ImageView::new_default(
+                AttachmentImage::sampled(&self.allocators.memory, level_extent, HDR_FORMAT)
+                    .unwrap(),
+            )
+                .unwrap();
+            let framebuffer = |render_pass: &Arc<RenderPass>| {
+                Framebuffer::new(
+                    render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![image.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            };
+            let sampling = |pipeline: &GraphicsPipeline, view| {
+                PersistentDescriptorSet::new(
+                    &self.allocators.descriptor,
+                    pipeline.layout().set_layouts()[0].clone(),
+                    [WriteDescriptorSet::image_view_sampler(
+                        0,
+                        view,
+                        self.sampler.clone(),
+                    )],
+                )
+                    .unwrap()
+            };
+
+            self.bloom_levels.push(BloomLevel {
+                image: image.clone(),
+                extent: level_extent,
+                downsample_framebuffer: framebuffer(&self.downsample_render_pass),
+                upsample_framebuffer: framebuffer(&self.upsample_render_pass),
+                downsample_set: sampling(&self.downsample_pipeline, source),
+                upsample_set: sampling(&self.upsample_pipeline, image.clone()),
+            });
+            source = image;
+        }
+
+        // After upsampling, the first level holds the whole blurred result
+        let bloom_image = self.bloom_levels[0].image.clone();
+        self.tonemap_set = Some(
+            PersistentDescriptorSet::new(
+                &self.allocators.descriptor,
+                self.tonemap_pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, hdr_image, self.sampler.clone()),
+                    WriteDescriptorSet::image_view_sampler(1, bloom_image, self.sampler.clone()),
+                ],
+            )
+                .unwrap(),
+        );
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.tonemap_render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let frame_subbuffer = self.uniform_buffer.write(scene_vs::Frame {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+            light_position: LIGHTS.map(|light| light.position.extend(1.0).to_array()),
+            light_color: LIGHTS.map(|light| [light.color[0], light.color[1], light.color[2], 1.0]),
+        });
+        let scene_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.scene_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::buffer(0, frame_subbuffer)],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // With --profile, each of these shows up as its own color in the graph
+        renderer.measure(&mut builder, "scene", |builder| {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into()), Some(1f32.into())],
+                        ..RenderPassBeginInfo::framebuffer(self.scene_framebuffer.clone().unwrap())
+                    },
+                    SubpassContents::Inline,
+                )
+                .unwrap()
+                .set_viewport(0, [self.viewport.clone()])
+                .bind_pipeline_graphics(self.scene_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.scene_pipeline.layout().clone(),
+                    0,
+                    scene_set,
+                );
+
+            for object in &self.objects {
+                let mesh = &self.meshes[object.mesh];
+                let [r, g, b] = object.color;
+                let push_constants = scene_vs::PushConstants {
+                    model: object.model.to_cols_array_2d(),
+                    color: [r, g, b, 1.0],
+                    emissive: object.emissive as u32,
+                };
+
+                builder
+                    .push_constants(self.scene_pipeline.layout().clone(), 0, push_constants)
+                    .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                    .bind_index_buffer(mesh.index_buffer.clone())
+                    .draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0)
+                    .unwrap();
+            }
+
+            builder.end_render_pass().unwrap();
+        });
+
+        if self.bloom {
+            renderer.measure(&mut builder, "bloom", |builder| {
+                for (i, level) in self.bloom_levels.iter().enumerate() {
+                    let push_constants = downsample_fs::PushConstants {
+                        threshold: self.bloom_threshold,
+                        knee: self.bloom_threshold * 0.5,
+                        prefilter: (i == 0) as u32,
+                    };
+                    builder
+                        .begin_render_pass(
+                            RenderPassBeginInfo {
+                                clear_values: vec![None],
+                                ..RenderPassBeginInfo::framebuffer(
+                                    level.downsample_framebuffer.clone(),
+                                )
+                            },
+                            SubpassContents::Inline,
+                        )
+                        .unwrap()
+                        .set_viewport(0, [level_viewport(level.extent)])
+                        .bind_pipeline_graphics(self.downsample_pipeline.clone())
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Graphics,
+                            self.downsample_pipeline.layout().clone(),
+                            0,
+                            level.downsample_set.clone(),
+                        )
+                        .push_constants(
+                            self.downsample_pipeline.layout().clone(),
+                            0,
+                            push_constants,
+                        )
+                        .draw(3, 1, 0, 0)
+                        .unwrap()
+                        .end_render_pass()
+                        .unwrap();
+                }
+
+                // From the smallest level back up, each pass reads the level that the previous
+                // pass just wrote to, vulkano puts a barrier between every one of them
+                for pair in self.bloom_levels.windows(2).rev() {
+                    let (target, source) = (&pair[0], &pair[1]);
+                    let push_constants = upsample_fs::PushConstants {
+                        radius: self.bloom_radius,
+                    };
+                    builder
+                        .begin_render_pass(
+                            RenderPassBeginInfo {
+                                clear_values: vec![None],
+                                ..RenderPassBeginInfo::framebuffer(
+                                    target.upsample_framebuffer.clone(),
+                                )
+                            },
+                            SubpassContents::Inline,
+                        )
+                        .unwrap()
+                        .set_viewport(0, [level_viewport(target.extent)])
+                        .bind_pipeline_graphics(self.upsample_pipeline.clone())
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Graphics,
+                            self.upsample_pipeline.layout().clone(),
+                            0,
+                            source.upsample_set.clone(),
+                        )
+                        .push_constants(self.upsample_pipeline.layout().clone(), 0, push_constants)
+                        .draw(3, 1, 0, 0)
+                        .unwrap()
+                        .end_render_pass()
+                        .unwrap();
+                }
+            });
+        }
+
+        let push_constants = tonemap_fs::PushConstants {
+            exposure: self.exposure,
+            bloom_strength: if self.bloom { self.bloom_strength } else { 0.0 },
+            operator: self.operator as u32,
+            srgb_output: self.srgb_output as u32,
+        };
+
+        // vulkano sees that the next pass samples what the previous one wrote, and inserts the
+        // barrier and layout transition in between
+        renderer.measure(&mut builder, "tonemap", |builder| {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![None],
+                        ..RenderPassBeginInfo::framebuffer(
+                            self.framebuffers[image_index as usize].clone(),
+                        )
+                    },
+                    SubpassContents::Inline,
+                )
+                .unwrap()
+                .set_viewport(0, [self.viewport.clone()])
+                .bind_pipeline_graphics(self.tonemap_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.tonemap_pipeline.layout().clone(),
+                    0,
+                    self.tonemap_set.clone().unwrap(),
+                )
+                .push_constants(self.tonemap_pipeline.layout().clone(), 0, push_constants)
+                .draw(3, 1, 0, 0)
+                .unwrap()
+                .end_render_pass()
+                .unwrap();
+        });
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Tonemapping").show(ctx, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.exposure, 0.01..=16.0)
+                    .logarithmic(true)
+                    .text("exposure"),
+            );
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.operator, Operator::Clamp, "clamp");
+                ui.radio_value(&mut self.operator, Operator::Reinhard, "Reinhard");
+                ui.radio_value(&mut self.operator, Operator::Aces, "ACES");
+            });
+            ui.separator();
+            ui.checkbox(&mut self.bloom, "bloom");
+            ui.add_enabled_ui(self.bloom, |ui| {
+                ui.add(egui::Slider::new(&mut self.bloom_threshold, 0.0..=10.0).text("threshold"));
+                ui.add(egui::Slider::new(&mut self.bloom_strength, 0.0..=1.0).text("strength"));
+                ui.add(egui::Slider::new(&mut self.bloom_radius, 0.5..=3.0).text("radius"));
+            });
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-12", DeviceExtensions::empty(), Hdr::new);
+}
@@ -0,0 +1,19 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out float f_occlusion;
+
+layout(set = 0, binding = 0) uniform sampler2D u_occlusion;
+
+void main() {
+    // Averaging over exactly one tile of the noise texture cancels out its pattern
+    vec2 texel_size = 1.0 / vec2(textureSize(u_occlusion, 0));
+    float sum = 0.0;
+    for (int x = -2; x < 2; x++) {
+        for (int y = -2; y < 2; y++) {
+            sum += texture(u_occlusion, v_tex_coord + vec2(x, y) * texel_size).r;
+        }
+    }
+    f_occlusion = sum / 16.0;
+}
@@ -0,0 +1,24 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view;
+    mat4 projection;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 albedo;
+} pc;
+
+void main() {
+    // Everything after the G-buffer works in view space, where the camera is at the
+    // origin looking down -Z
+    mat4 model_view = frame.view * pc.model;
+    v_normal = transpose(inverse(mat3(model_view))) * normal;
+    gl_Position = frame.projection * model_view * vec4(position, 1.0);
+}
@@ -0,0 +1,40 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D u_albedo;
+layout(set = 0, binding = 1) uniform sampler2D u_normals;
+layout(set = 0, binding = 2) uniform sampler2D u_depth;
+layout(set = 0, binding = 3) uniform sampler2D u_occlusion;
+
+layout(push_constant) uniform PushConstants {
+    // Towards the light, in view space like the normals
+    vec4 light_direction;
+    float ambient;
+    // 0: lit with occlusion, 1: lit without, 2: occlusion only
+    uint debug_view;
+} pc;
+
+void main() {
+    float occlusion = texture(u_occlusion, v_tex_coord).r;
+    if (pc.debug_view == 2) {
+        f_color = vec4(vec3(occlusion), 1.0);
+        return;
+    }
+
+    if (texture(u_depth, v_tex_coord).r >= 1.0) {
+        f_color = vec4(0.55, 0.6, 0.7, 1.0);
+        return;
+    }
+
+    vec3 albedo = texture(u_albedo, v_tex_coord).rgb;
+    vec3 normal = normalize(texture(u_normals, v_tex_coord).xyz);
+
+    // Occlusion only darkens the ambient light, the direct light has shadows for that
+    float ambient = pc.ambient * (pc.debug_view == 0 ? occlusion : 1.0);
+    float diffuse = max(dot(normal, pc.light_direction.xyz), 0.0);
+
+    f_color = vec4(albedo * (ambient + diffuse * (1.0 - pc.ambient)), 1.0);
+}
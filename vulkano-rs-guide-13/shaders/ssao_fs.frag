@@ -0,0 +1,58 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out float f_occlusion;
+
+layout(set = 0, binding = 0) uniform sampler2D u_depth;
+layout(set = 0, binding = 1) uniform sampler2D u_normals;
+layout(set = 0, binding = 2) uniform sampler2D u_noise;
+layout(set = 0, binding = 3) uniform Params {
+    mat4 projection;
+    mat4 inverse_projection;
+    vec4 kernel[32];
+    float radius;
+    float bias;
+} params;
+
+// Undoes the projection to find where the visible surface at tex_coord is
+vec3 view_position(vec2 tex_coord) {
+    float depth = texture(u_depth, tex_coord).r;
+    vec4 position = params.inverse_projection * vec4(tex_coord * 2.0 - 1.0, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+void main() {
+    // Nothing to occlude where the background shows
+    if (texture(u_depth, v_tex_coord).r >= 1.0) {
+        f_occlusion = 1.0;
+        return;
+    }
+
+    vec3 position = view_position(v_tex_coord);
+    vec3 normal = normalize(texture(u_normals, v_tex_coord).xyz);
+
+    // Turn the kernel's +Z towards the normal, rotated by the noise around it
+    vec2 noise_scale = vec2(textureSize(u_depth, 0)) / vec2(textureSize(u_noise, 0));
+    vec3 random = vec3(texture(u_noise, v_tex_coord * noise_scale).xy, 0.0);
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < 32; i++) {
+        vec3 sample_position = position + tbn * params.kernel[i].xyz * params.radius;
+
+        // Where the sample lands on screen, and what the camera sees there
+        vec4 clip = params.projection * vec4(sample_position, 1.0);
+        vec2 sample_coord = clip.xy / clip.w * 0.5 + 0.5;
+        float surface_z = view_position(sample_coord).z;
+
+        // The sample is occluded if the visible surface is in front of it, unless
+        // that surface is much further away than the radius, like a background
+        // object seen past an edge
+        float in_range = smoothstep(0.0, 1.0, params.radius / abs(position.z - surface_z));
+        occlusion += (surface_z >= sample_position.z + params.bias ? 1.0 : 0.0) * in_range;
+    }
+
+    f_occlusion = 1.0 - occlusion / 32.0;
+}
@@ -0,0 +1,828 @@
+//Screen-space ambient occlusion, computed from the G-buffer of the deferred chapter
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::{ImageView, ImageViewAbstract};
+use vulkano::image::{AttachmentImage, ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexInputState};
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::shader::EntryPoint;
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Quat, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Have to match the array size in the SSAO shader and the blur size
+const KERNEL_SIZE: usize = 32;
+const NOISE_SIZE: u32 = 4;
+
+const ALBEDO_FORMAT: Format = Format::R8G8B8A8_UNORM;
+const NORMAL_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+const DEPTH_FORMAT: Format = Format::D32_SFLOAT;
+// Occlusion is a single value per pixel
+const AO_FORMAT: Format = Format::R8_UNORM;
+
+// Appends a square with the given center, normal and half-axes, counter-clockwise from outside
+fn push_quad(
+    vertices: &mut Vec<PosNormalUv>,
+    indices: &mut Vec<u32>,
+    center: Vec3,
+    normal: Vec3,
+    u: Vec3,
+    v: Vec3,
+) {
+    let base = vertices.len() as u32;
+    for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        vertices.push(PosNormalUv {
+            position: (center + u * su + v * sv).to_array(),
+            normal: normal.to_array(),
+            ..Default::default()
+        });
+    }
+    indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+// A unit cube with one quad per face, so every face has its own normal
+fn cube() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+    for (normal, u, v) in faces {
+        push_quad(
+            &mut vertices,
+            &mut indices,
+            normal * 0.5,
+            normal,
+            u * 0.5,
+            v * 0.5,
+        );
+    }
+    (vertices, indices)
+}
+
+fn ground(size: f32) -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    push_quad(
+        &mut vertices,
+        &mut indices,
+        Vec3::ZERO,
+        Vec3::Y,
+        Vec3::X * size,
+        Vec3::NEG_Z * size,
+    );
+    (vertices, indices)
+}
+
+struct Mesh {
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+}
+
+struct Object {
+    mesh: usize,
+    model: Mat4,
+    albedo: [f32; 4],
+}
+
+// A small xorshift generator, the kernel and noise only need to look random, not be random
+struct Random(u32);
+
+impl Random {
+    // Uniform in -1..1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+// Sample offsets inside a unit hemisphere around +Z, more of them close to the center so nearby
+// geometry counts for more
+fn hemisphere_kernel(random: &mut Random) -> [[f32; 4]; KERNEL_SIZE] {
+    let mut kernel = [[0.0; 4]; KERNEL_SIZE];
+    for (i, sample) in kernel.iter_mut().enumerate() {
+        let direction = Vec3::new(random.next(), random.next(), random.next().abs()).normalize();
+        let length = random.next().abs().max(0.05);
+        let t = i as f32 / KERNEL_SIZE as f32;
+        let scale = 0.1 + 0.9 * t * t;
+        *sample = (direction * length * scale).extend(0.0).to_array();
+    }
+    kernel
+}
+
+// Random rotations around the normal, tiled over the screen. Without them every pixel would
+// use the same few sample directions and the result would show bands
+fn upload_noise(
+    random: &mut Random,
+    memory_allocator: &(impl MemoryAllocator + ?Sized),
+    uploads: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+) -> Arc<ImageView<ImmutableImage>> {
+    let pixels: Vec<i8> = (0..NOISE_SIZE * NOISE_SIZE)
+        .flat_map(|_| {
+            let x = (random.next() * 127.0) as i8;
+            let y = (random.next() * 127.0) as i8;
+            [x, y, 0, 0]
+        })
+        .collect();
+
+    let image = ImmutableImage::from_iter(
+        memory_allocator,
+        pixels,
+        ImageDimensions::Dim2d {
+            width: NOISE_SIZE,
+            height: NOISE_SIZE,
+            array_layers: 1,
+        },
+        MipmapsCount::One,
+        // Signed, so the shader reads directions in -1..1
+        Format::R8G8B8A8_SNORM,
+        uploads,
+    )
+        .expect("failed to create noise texture");
+
+    ImageView::new_default(image).unwrap()
+}
+
+mod gbuffer_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/gbuffer_vs.vert",
+    }
+}
+
+mod gbuffer_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/gbuffer_fs.frag",
+    }
+}
+
+mod fullscreen_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/fullscreen_vs.vert",
+    }
+}
+
+mod ssao_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/ssao_fs.frag",
+    }
+}
+
+mod blur_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/blur_fs.frag",
+    }
+}
+
+mod lighting_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/lighting_fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DebugView {
+    Lit,
+    NoOcclusion,
+    Occlusion,
+}
+
+// Every image that depends on the window size
+struct Targets {
+    albedo: Arc<ImageView<AttachmentImage>>,
+    normals: Arc<ImageView<AttachmentImage>>,
+    depth: Arc<ImageView<AttachmentImage>>,
+    occlusion: Arc<ImageView<AttachmentImage>>,
+    blurred_occlusion: Arc<ImageView<AttachmentImage>>,
+    gbuffer_framebuffer: Arc<Framebuffer>,
+    occlusion_framebuffer: Arc<Framebuffer>,
+    blur_framebuffer: Arc<Framebuffer>,
+}
+
+struct Ssao {
+    camera: Camera,
+    allocators: Allocators,
+    uniform_buffer: StreamingBuffer,
+    meshes: Vec<Mesh>,
+    objects: Vec<Object>,
+    kernel: [[f32; 4]; KERNEL_SIZE],
+    noise: Arc<ImageView<ImmutableImage>>,
+    // G-buffer reads must not be filtered, the noise has to repeat
+    nearest_clamp: Arc<Sampler>,
+    nearest_repeat: Arc<Sampler>,
+    gbuffer_render_pass: Arc<RenderPass>,
+    // The occlusion and blur passes write the same kind of image, so they share a render pass
+    occlusion_render_pass: Arc<RenderPass>,
+    lighting_render_pass: Arc<RenderPass>,
+    gbuffer_pipeline: Arc<GraphicsPipeline>,
+    ssao_pipeline: Arc<GraphicsPipeline>,
+    blur_pipeline: Arc<GraphicsPipeline>,
+    lighting_pipeline: Arc<GraphicsPipeline>,
+    targets: Option<Targets>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    radius: f32,
+    bias: f32,
+    ambient: f32,
+    debug_view: DebugView,
+}
+
+impl Ssao {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let meshes = [cube(), ground(10.0)]
+            .into_iter()
+            .map(|(vertices, indices)| Mesh {
+                vertex_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::VERTEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    vertices,
+                )
+                    .expect("failed to create vertex buffer"),
+                index_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::INDEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    indices,
+                )
+                    .expect("failed to create index buffer"),
+            })
+            .collect();
+
+        // The pillars of the deferred chapter, with piles of small cubes at their feet to give
+        // the occlusion plenty of corners and crevices
+        let mut objects = vec![Object {
+            mesh: 1,
+            model: Mat4::IDENTITY,
+            albedo: [0.8, 0.8, 0.8, 1.0],
+        }];
+        for x in -3..=3 {
+            for z in -3..=3 {
+                let height = 0.5 + ((x * 7 + z * 3) as f32).sin().abs() * 1.5;
+                let center = Vec3::new(x as f32 * 2.5, 0.0, z as f32 * 2.5);
+                objects.push(Object {
+                    mesh: 0,
+                    model: Mat4::from_scale_rotation_translation(
+                        Vec3::new(0.5, height, 0.5),
+                        Quat::from_rotation_y((x + z) as f32 * 0.3),
+                        center + Vec3::Y * height / 2.0,
+                    ),
+                    albedo: [0.9, 0.9, 0.9, 1.0],
+                });
+                for i in 0..3 {
+                    let angle = (x * 5 + z * 11 + i * 2) as f32;
+                    let offset = Vec3::new(angle.cos(), 0.0, angle.sin()) * 0.6;
+                    objects.push(Object {
+                        mesh: 0,
+                        model: Mat4::from_scale_rotation_translation(
+                            Vec3::splat(0.25),
+                            Quat::from_rotation_y(angle),
+                            center + offset + Vec3::Y * 0.125,
+                        ),
+                        albedo: [0.9, 0.6, 0.4, 1.0],
+                    });
+                }
+            }
+        }
+
+        let mut random = Random(0x2545_f491);
+        let kernel = hemisphere_kernel(&mut random);
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let noise = upload_noise(&mut random, &allocators.memory, &mut uploads);
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let nearest_clamp = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        )
+            .expect("failed to create sampler");
+        let nearest_repeat = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                address_mode: [SamplerAddressMode::Repeat; 3],
+                ..Default::default()
+            },
+        )
+            .expect("failed to create sampler");
+
+        // Unlike the deferred chapter, every G-buffer image is stored: the occlusion needs the
+        // depth and normals of neighboring pixels, which input attachments can't provide
+        let gbuffer_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                albedo: {
+                    load: Clear,
+                    store: Store,
+                    format: ALBEDO_FORMAT,
+                    samples: 1,
+                },
+                normals: {
+                    load: Clear,
+                    store: Store,
+                    format: NORMAL_FORMAT,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: Store,
+                    format: DEPTH_FORMAT,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [albedo, normals],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let occlusion_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                occlusion: {
+                    load: DontCare,
+                    store: Store,
+                    format: AO_FORMAT,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [occlusion],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let lighting_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let gbuffer_subpass = Subpass::from(gbuffer_render_pass.clone(), 0).unwrap();
+        let gbuffer_vs = gbuffer_vs::load(device.clone()).expect("failed to create shader module");
+        let gbuffer_fs = gbuffer_fs::load(device.clone()).expect("failed to create shader module");
+        let gbuffer_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(gbuffer_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(gbuffer_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .color_blend_state(ColorBlendState::new(
+                gbuffer_subpass.num_color_attachments(),
+            ))
+            .render_pass(gbuffer_subpass)
+            .build(device.clone())
+            .expect("failed to create G-buffer pipeline");
+
+        // The three other passes draw a fullscreen triangle, only the fragment shader differs
+        let fullscreen_vs =
+            fullscreen_vs::load(device.clone()).expect("failed to create shader module");
+        let fullscreen_pipeline = |fs: EntryPoint, render_pass: &Arc<RenderPass>| {
+            GraphicsPipeline::start()
+                .vertex_input_state(VertexInputState::new())
+                .vertex_shader(fullscreen_vs.entry_point("main").unwrap(), ())
+                .input_assembly_state(InputAssemblyState::new())
+                .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+                .fragment_shader(fs, ())
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .expect("failed to create fullscreen pipeline")
+        };
+
+        let ssao_fs = ssao_fs::load(device.clone()).expect("failed to create shader module");
+        let blur_fs = blur_fs::load(device.clone()).expect("failed to create shader module");
+        let lighting_fs =
+            lighting_fs::load(device.clone()).expect("failed to create shader module");
+        let ssao_pipeline =
+            fullscreen_pipeline(ssao_fs.entry_point("main").unwrap(), &occlusion_render_pass);
+        let blur_pipeline =
+            fullscreen_pipeline(blur_fs.entry_point("main").unwrap(), &occlusion_render_pass);
+        let lighting_pipeline = fullscreen_pipeline(
+            lighting_fs.entry_point("main").unwrap(),
+            &lighting_render_pass,
+        );
+
+        Ssao {
+            camera: Camera::new(Vec3::new(0.0, 4.0, 9.0), Vec3::ZERO),
+            uniform_buffer: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            meshes,
+            objects,
+            kernel,
+            noise,
+            nearest_clamp,
+            nearest_repeat,
+            gbuffer_render_pass,
+            occlusion_render_pass,
+            lighting_render_pass,
+            gbuffer_pipeline,
+            ssao_pipeline,
+            blur_pipeline,
+            lighting_pipeline,
+            targets: None,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            radius: 0.5,
+            bias: 0.025,
+            ambient: 0.6,
+            debug_view: DebugView::Lit,
+        }
+    }
+}
+
+impl App for Ssao {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        // Written by one pass and sampled by the next ones
+        let target = |format| {
+            ImageView::new_default(
+                AttachmentImage::sampled(&self.allocators.memory, extent, format).unwrap(),
+            )
+                .unwrap()
+        };
+        let albedo = target(ALBEDO_FORMAT);
+        let normals = target(NORMAL_FORMAT);
+        let depth = target(DEPTH_FORMAT);
+        let occlusion = target(AO_FORMAT);
+        let blurred_occlusion = target(AO_FORMAT);
+
+        let framebuffer = |render_pass: &Arc<RenderPass>,
+                           attachments: Vec<Arc<dyn ImageViewAbstract>>| {
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments,
+                    ..Default::default()
+                },
+            )
+                .unwrap()
+        };
+        self.targets = Some(Targets {
+            gbuffer_framebuffer: framebuffer(
+                &self.gbuffer_render_pass,
+                vec![albedo.clone(), normals.clone(), depth.clone()],
+            ),
+            occlusion_framebuffer: framebuffer(
+                &self.occlusion_render_pass,
+                vec![occlusion.clone()],
+            ),
+            blur_framebuffer: framebuffer(
+                &self.occlusion_render_pass,
+                vec![blurred_occlusion.clone()],
+            ),
+            albedo,
+            normals,
+            depth,
+            occlusion,
+            blurred_occlusion,
+        });
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| framebuffer(&self.lighting_render_pass, vec![view.clone()]))
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let view = self.camera.view();
+        let projection = self.camera.projection(width / height);
+        let targets = self.targets.as_ref().unwrap();
+
+        let frame_subbuffer = self.uniform_buffer.write(gbuffer_vs::Frame {
+            view: view.to_cols_array_2d(),
+            projection: projection.to_cols_array_2d(),
+        });
+        let gbuffer_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.gbuffer_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::buffer(0, frame_subbuffer)],
+        )
+            .unwrap();
+
+        let params_subbuffer = self.uniform_buffer.write(ssao_fs::Params {
+            projection: projection.to_cols_array_2d(),
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            kernel: self.kernel,
+            radius: self.radius,
+            bias: self.bias,
+        });
+        let ssao_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.ssao_pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(
+                    0,
+                    targets.depth.clone(),
+                    self.nearest_clamp.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    targets.normals.clone(),
+                    self.nearest_clamp.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    2,
+                    self.noise.clone(),
+                    self.nearest_repeat.clone(),
+                ),
+                WriteDescriptorSet::buffer(3, params_subbuffer),
+            ],
+        )
+            .unwrap();
+
+        let blur_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.blur_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                targets.occlusion.clone(),
+                self.nearest_clamp.clone(),
+            )],
+        )
+            .unwrap();
+
+        let lighting_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.lighting_pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(
+                    0,
+                    targets.albedo.clone(),
+                    self.nearest_clamp.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    targets.normals.clone(),
+                    self.nearest_clamp.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    2,
+                    targets.depth.clone(),
+                    self.nearest_clamp.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    3,
+                    targets.blurred_occlusion.clone(),
+                    self.nearest_clamp.clone(),
+                ),
+            ],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // Each pass is timed on its own, --profile graphs how the frame splits between them
+        renderer.measure(&mut builder, "gbuffer", |builder| {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![
+                            Some([0.0, 0.0, 0.0, 0.0].into()),
+                            Some([0.0, 0.0, 0.0, 0.0].into()),
+                            Some(1f32.into()),
+                        ],
+                        ..RenderPassBeginInfo::framebuffer(targets.gbuffer_framebuffer.clone())
+                    },
+                    SubpassContents::Inline,
+                )
+                .unwrap()
+                .set_viewport(0, [self.viewport.clone()])
+                .bind_pipeline_graphics(self.gbuffer_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.gbuffer_pipeline.layout().clone(),
+                    0,
+                    gbuffer_set,
+                );
+
+            for object in &self.objects {
+                let mesh = &self.meshes[object.mesh];
+                let push_constants = gbuffer_vs::PushConstants {
+                    model: object.model.to_cols_array_2d(),
+                    albedo: object.albedo,
+                };
+
+                builder
+                    .push_constants(self.gbuffer_pipeline.layout().clone(), 0, push_constants)
+                    .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                    .bind_index_buffer(mesh.index_buffer.clone())
+                    .draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0)
+                    .unwrap();
+            }
+
+            builder.end_render_pass().unwrap();
+        });
+
+        // Occlusion from the depth and normals
+        renderer.measure(&mut builder, "ssao", |builder| {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![None],
+                        ..RenderPassBeginInfo::framebuffer(targets.occlusion_framebuffer.clone())
+                    },
+                    SubpassContents::Inline,
+                )
+                .unwrap()
+                .bind_pipeline_graphics(self.ssao_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.ssao_pipeline.layout().clone(),
+                    0,
+                    ssao_set,
+                )
+                .draw(3, 1, 0, 0)
+                .unwrap()
+                .end_render_pass()
+                .unwrap();
+        });
+
+        // Blur away the noise pattern
+        renderer.measure(&mut builder, "blur", |builder| {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![None],
+                        ..RenderPassBeginInfo::framebuffer(targets.blur_framebuffer.clone())
+                    },
+                    SubpassContents::Inline,
+                )
+                .unwrap()
+                .bind_pipeline_graphics(self.blur_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.blur_pipeline.layout().clone(),
+                    0,
+                    blur_set,
+                )
+                .draw(3, 1, 0, 0)
+                .unwrap()
+                .end_render_pass()
+                .unwrap();
+        });
+
+        // The light comes from above and to the side, turned into view space like the normals
+        let light_direction = view.transform_vector3(Vec3::new(0.4, 1.0, 0.3).normalize());
+        let push_constants = lighting_fs::PushConstants {
+            light_direction: light_direction.extend(0.0).to_array(),
+            ambient: self.ambient,
+            debug_view: self.debug_view as u32,
+        };
+
+        // And finally light the scene with it
+        renderer.measure(&mut builder, "lighting", |builder| {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![None],
+                        ..RenderPassBeginInfo::framebuffer(
+                            self.framebuffers[image_index as usize].clone(),
+                        )
+                    },
+                    SubpassContents::Inline,
+                )
+                .unwrap()
+                .bind_pipeline_graphics(self.lighting_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.lighting_pipeline.layout().clone(),
+                    0,
+                    lighting_set,
+                )
+                .push_constants(self.lighting_pipeline.layout().clone(), 0, push_constants)
+                .draw(3, 1, 0, 0)
+                .unwrap()
+                .end_render_pass()
+                .unwrap();
+        });
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("SSAO").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.radius, 0.05..=2.0).text("radius"));
+            ui.add(
+                egui::Slider::new(&mut self.bias, 0.0..=0.1)
+                    .step_by(0.005)
+                    .text("bias"),
+            );
+            ui.add(egui::Slider::new(&mut self.ambient, 0.0..=1.0).text("ambient"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.debug_view, DebugView::Lit, "lit");
+                ui.radio_value(&mut self.debug_view, DebugView::NoOcclusion, "no SSAO");
+                ui.radio_value(&mut self.debug_view, DebugView::Occlusion, "SSAO only");
+            });
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-13", DeviceExtensions::empty(), Ssao::new);
+}
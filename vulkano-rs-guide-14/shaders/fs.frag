@@ -0,0 +1,12 @@
+#version 460
+
+layout(location = 0) in vec2 v_corner;
+layout(location = 1) in vec4 v_color;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    // A soft round dot instead of a square
+    float falloff = max(1.0 - dot(v_corner, v_corner), 0.0);
+    f_color = vec4(v_color.rgb * v_color.a * falloff, 1.0);
+}
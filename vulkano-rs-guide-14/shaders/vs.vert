@@ -0,0 +1,40 @@
+#version 460
+
+// Per instance: one instance is one particle
+layout(location = 0) in vec4 position;
+layout(location = 1) in vec4 velocity;
+
+layout(location = 0) out vec2 v_corner;
+layout(location = 1) out vec4 v_color;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    // The quads always face the camera
+    vec4 camera_right;
+    vec4 camera_up;
+    float size;
+    float lifetime;
+} pc;
+
+// Per vertex: the two triangles of the quad
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+    // Dead particles collapse to nothing
+    float size = position.w > 0.0 ? pc.size : 0.0;
+    vec3 world = position.xyz
+        + (pc.camera_right.xyz * corner.x + pc.camera_up.xyz * corner.y) * size;
+
+    // Fast particles are hot and yellow, slow ones turn red, and all of them fade
+    // out at the end of their life
+    float heat = clamp(length(velocity.xyz) / 8.0, 0.0, 1.0);
+    vec3 color = mix(vec3(1.0, 0.25, 0.05), vec3(1.0, 0.9, 0.5), heat);
+    v_color = vec4(color, clamp(position.w / pc.lifetime * 2.0, 0.0, 1.0));
+    v_corner = corner;
+
+    gl_Position = pc.view_projection * vec4(world, 1.0);
+}
@@ -0,0 +1,386 @@
+//GPU particles: a compute shader moves them, the graphics pipeline draws the same buffer
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendState};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Has to match local_size_x in the compute shader
+const WORK_GROUP_SIZE: u32 = 256;
+
+// The same memory is a storage buffer for the compute shader and a per-instance vertex buffer
+// for drawing, so the layout has to suit both: two vec4s need no padding in either
+#[derive(BufferContents, Vertex, Clone, Copy)]
+#[repr(C)]
+struct Particle {
+    // w is the remaining life in seconds, the particle is dead at 0
+    #[format(R32G32B32A32_SFLOAT)]
+    position: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    velocity: [f32; 4],
+}
+
+// A small xorshift generator, the starting particles only need to look random
+struct Random(u32);
+
+impl Random {
+    // Uniform in 0..1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        include: ["../shaders/common"],
+        path: "shaders/cs.comp",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Particles {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    compute_pipeline: Arc<ComputePipeline>,
+    graphics_pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    particle_buffer: Subbuffer<[Particle]>,
+    particle_set: Arc<PersistentDescriptorSet>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    start: Instant,
+    last_frame: Instant,
+    // Parameters exposed in the overlay
+    paused: bool,
+    gravity: f32,
+    speed: f32,
+    spread: f32,
+    lifetime: f32,
+    size: f32,
+}
+
+impl Particles {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let count = args::value::<u32>("--particles").unwrap_or(65536);
+        let lifetime = 4.0;
+
+        // Start as if the fountain had been running for a while, every particle in a different
+        // stage of its life, so they don't all die and respawn in the same frame
+        let mut random = Random(0x9e37_79b9);
+        let particles: Vec<Particle> = (0..count)
+            .map(|_| {
+                let angle = random.next() * TAU;
+                let sideways = random.next();
+                Particle {
+                    position: [0.0, 0.0, 0.0, random.next() * lifetime],
+                    velocity: [angle.cos() * sideways, 8.0, angle.sin() * sideways, 0.0],
+                }
+            })
+            .collect();
+
+        // Only the GPU touches the particles after this, so they live in device memory and are
+        // copied there once through a staging buffer
+        let staging_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            particles,
+        )
+            .expect("failed to create staging buffer");
+        let particle_buffer = Buffer::new_slice::<Particle>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER
+                    | BufferUsage::VERTEX_BUFFER
+                    | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            count as u64,
+        )
+            .expect("failed to create particle buffer");
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        uploads
+            .copy_buffer(CopyBufferInfo::buffers(
+                staging_buffer,
+                particle_buffer.clone(),
+            ))
+            .unwrap();
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let cs = cs::load(device.clone()).expect("failed to create shader module");
+        let compute_pipeline = ComputePipeline::new(
+            device.clone(),
+            cs.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let particle_set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            compute_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::buffer(0, particle_buffer.clone())],
+        )
+            .unwrap();
+
+        // No depth buffer: the particles are blended additively, so the order they are drawn in
+        // doesn't matter
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let graphics_pipeline = GraphicsPipeline::start()
+            // The particle buffer advances once per instance, not once per vertex
+            .vertex_input_state(Particle::per_instance())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .color_blend_state(ColorBlendState::new(1).blend(AttachmentBlend::additive()))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        Particles {
+            camera: Camera::new(Vec3::new(0.0, 3.0, 10.0), Vec3::new(0.0, 2.0, 0.0)),
+            render_pass,
+            compute_pipeline,
+            graphics_pipeline,
+            allocators,
+            particle_buffer,
+            particle_set,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            start: Instant::now(),
+            last_frame: Instant::now(),
+            paused: false,
+            gravity: 9.8,
+            speed: 8.0,
+            spread: 1.0,
+            lifetime,
+            size: 0.03,
+        }
+    }
+}
+
+impl App for Particles {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let now = Instant::now();
+        // Clamped so a long hitch, like dragging the window, doesn't make everything jump
+        let dt = (now - self.last_frame).as_secs_f32().min(0.05);
+        self.last_frame = now;
+
+        let count = self.particle_buffer.len() as u32;
+        let [width, height] = self.viewport.dimensions;
+        let right = self.camera.right();
+        let up = right.cross(self.camera.forward());
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        if !self.paused {
+            let push_constants = cs::PushConstants {
+                dt,
+                time: self.start.elapsed().as_secs_f32(),
+                gravity: self.gravity,
+                speed: self.speed,
+                spread: self.spread,
+                lifetime: self.lifetime,
+                count,
+            };
+            // Shows up in the statistics logged with --pipeline-stats
+            renderer.measure(&mut builder, "simulate", |builder| {
+                builder
+                    .bind_pipeline_compute(self.compute_pipeline.clone())
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        self.compute_pipeline.layout().clone(),
+                        0,
+                        self.particle_set.clone(),
+                    )
+                    .push_constants(self.compute_pipeline.layout().clone(), 0, push_constants)
+                    .dispatch([(count + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE, 1, 1])
+                    .unwrap();
+            });
+        }
+
+        let push_constants = vs::PushConstants {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+            camera_right: right.extend(0.0).to_array(),
+            camera_up: up.extend(0.0).to_array(),
+            size: self.size,
+            lifetime: self.lifetime,
+        };
+
+        // The draw reads what the dispatch just wrote. vulkano tracks that both use the same
+        // buffer and adds a barrier from the compute shader writes to the vertex input reads
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.01, 0.01, 0.02, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap();
+        renderer.measure(&mut builder, "draw", |builder| {
+            builder
+                .set_viewport(0, [self.viewport.clone()])
+                .bind_pipeline_graphics(self.graphics_pipeline.clone())
+                .push_constants(self.graphics_pipeline.layout().clone(), 0, push_constants)
+                .bind_vertex_buffers(0, self.particle_buffer.clone())
+                // Six vertices for the quad, one instance per particle
+                .draw(6, count, 0, 0)
+                .unwrap();
+        });
+        builder.end_render_pass().unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Particles").show(ctx, |ui| {
+            ui.label(format!("{} particles", self.particle_buffer.len()));
+            ui.checkbox(&mut self.paused, "paused");
+            ui.add(egui::Slider::new(&mut self.gravity, 0.0..=20.0).text("gravity"));
+            ui.add(egui::Slider::new(&mut self.speed, 0.0..=20.0).text("speed"));
+            ui.add(egui::Slider::new(&mut self.spread, 0.0..=5.0).text("spread"));
+            ui.add(egui::Slider::new(&mut self.lifetime, 0.5..=10.0).text("lifetime"));
+            ui.add(egui::Slider::new(&mut self.size, 0.005..=0.2).text("size"));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-14",
+        DeviceExtensions::empty(),
+        Particles::new,
+    );
+}
@@ -0,0 +1,278 @@
+//Conway's Game of Life in a compute shader, ping-ponging between two storage images
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::winit::event::{ElementState, MouseButton, WindowEvent};
+
+// A slow machine shouldn't fall further and further behind
+const MAX_GENERATIONS_PER_FRAME: u32 = 64;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        include: ["../shaders/common"],
+        path: "shaders/cs.comp",
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Command {
+    Step,
+    // Used to apply the brush while paused, without advancing
+    Copy,
+    Randomize,
+    Clear,
+}
+
+struct Life {
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // Each generation reads one image and writes the other, sets[i] reads images[i]
+    images: [Arc<StorageImage>; 2],
+    sets: [Arc<PersistentDescriptorSet>; 2],
+    // Index of the image holding the latest generation
+    current: usize,
+    size: [u32; 2],
+    window_size: [f32; 2],
+    cursor: [f32; 2],
+    drawing: bool,
+    queued: Vec<Command>,
+    last_frame: Instant,
+    // Fractional generations carried over to the next frame
+    pending: f32,
+    seed: u32,
+    // Parameters exposed in the overlay
+    paused: bool,
+    generations_per_second: f32,
+    brush_radius: i32,
+}
+
+impl Life {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        // The grid doesn't follow the window size, it is stretched over the window instead
+        let side = args::value::<u32>("--size").unwrap_or(512);
+        let images = [(); 2].map(|_| {
+            StorageImage::new(
+                &allocators.memory,
+                ImageDimensions::Dim2d {
+                    width: side,
+                    height: side,
+                    array_layers: 1,
+                },
+                Format::R8G8B8A8_UNORM,
+                Some(renderer.queue().queue_family_index()),
+            )
+                .unwrap()
+        });
+
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let sets = [0, 1].map(|i| {
+            PersistentDescriptorSet::new(
+                &allocators.descriptor,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view(
+                        0,
+                        ImageView::new_default(images[i].clone()).unwrap(),
+                    ),
+                    WriteDescriptorSet::image_view(
+                        1,
+                        ImageView::new_default(images[1 - i].clone()).unwrap(),
+                    ),
+                ],
+            )
+                .unwrap()
+        });
+
+        Life {
+            pipeline,
+            allocators,
+            images,
+            sets,
+            current: 0,
+            size: [side, side],
+            window_size: [1.0, 1.0],
+            cursor: [0.0, 0.0],
+            drawing: false,
+            // The images start with garbage in them
+            queued: vec![Command::Randomize],
+            last_frame: Instant::now(),
+            pending: 0.0,
+            seed: 1,
+            paused: false,
+            generations_per_second: 30.0,
+            brush_radius: 4,
+        }
+    }
+
+    // The cell under the mouse, while the left button is held
+    fn brush(&self) -> Option<[i32; 2]> {
+        self.drawing.then(|| {
+            [0, 1].map(|axis| {
+                (self.cursor[axis] / self.window_size[axis] * self.size[axis] as f32) as i32
+            })
+        })
+    }
+}
+
+impl App for Life {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+        self.window_size = [width as f32, height as f32];
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let now = Instant::now();
+        let dt = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+
+        let mut commands = std::mem::take(&mut self.queued);
+        if !self.paused {
+            self.pending += dt * self.generations_per_second;
+            let generations = (self.pending as u32).min(MAX_GENERATIONS_PER_FRAME);
+            self.pending = self.pending.fract();
+            commands.extend((0..generations).map(|_| Command::Step));
+        }
+        let brush = self.brush();
+        if commands.is_empty() && brush.is_some() {
+            commands.push(Command::Copy);
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder.bind_pipeline_compute(self.pipeline.clone());
+
+        let [width, height] = self.size;
+        for command in commands {
+            if let Command::Randomize = command {
+                self.seed = self
+                    .seed
+                    .wrapping_mul(747_796_405)
+                    .wrapping_add(2_891_336_453);
+            }
+            let push_constants = cs::PushConstants {
+                mode: command as u32,
+                seed: self.seed,
+                brush: brush.unwrap_or_default(),
+                brush_radius: if brush.is_some() {
+                    self.brush_radius
+                } else {
+                    0
+                },
+            };
+
+            // Every dispatch reads what the previous one wrote, vulkano puts a barrier between
+            // them because the images swap roles each time
+            builder
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    self.pipeline.layout().clone(),
+                    0,
+                    self.sets[self.current].clone(),
+                )
+                .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+                .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+                .unwrap();
+            self.current = 1 - self.current;
+        }
+
+        // Nearest filtering by default, so every cell stays a sharp square
+        builder
+            .blit_image(BlitImageInfo::images(
+                self.images[self.current].clone(),
+                renderer.images[image_index as usize].clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Game of Life").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.paused, "paused");
+                if ui
+                    .add_enabled(self.paused, egui::Button::new("step"))
+                    .clicked()
+                {
+                    self.queued.push(Command::Step);
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut self.generations_per_second, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("generations/s"),
+            );
+            ui.add(egui::Slider::new(&mut self.brush_radius, 1..=32).text("brush radius"));
+            ui.horizontal(|ui| {
+                if ui.button("randomize").clicked() {
+                    self.queued.push(Command::Randomize);
+                }
+                if ui.button("clear").clicked() {
+                    self.queued.push(Command::Clear);
+                }
+            });
+            ui.label("Hold the left mouse button to draw cells");
+        });
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = [position.x as f32, position.y as f32];
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Left,
+                state,
+                ..
+            } => {
+                self.drawing = *state == ElementState::Pressed;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-15", DeviceExtensions::empty(), Life::new);
+}
@@ -0,0 +1,20 @@
+#version 460
+
+layout(location = 0) in vec4 position;
+layout(location = 1) in vec4 velocity;
+
+layout(location = 0) out vec3 v_color;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+} pc;
+
+void main() {
+    // Slow outer bodies are red, fast inner ones blue-white
+    float speed = clamp(length(velocity.xyz) / 40.0, 0.0, 1.0);
+    v_color = mix(vec3(0.6, 0.2, 0.1), vec3(0.6, 0.8, 1.0), speed) * 0.5;
+
+    gl_Position = pc.view_projection * vec4(position.xyz, 1.0);
+    // Larger points need the large_points feature, one pixel works everywhere
+    gl_PointSize = 1.0;
+}
@@ -0,0 +1,525 @@
+//N-body gravity: every body pulls on every other one, computed in a compute shader
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendState};
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::{self, GpuFuture, PipelineStage};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Has to match local_size_x and the shared tile size in the compute shader
+const WORK_GROUP_SIZE: u32 = 256;
+
+// Stored once for the compute shader and drawn directly as the vertex buffer
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct Body {
+    // w is the mass
+    #[format(R32G32B32A32_SFLOAT)]
+    position: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    velocity: [f32; 4],
+}
+
+// A small xorshift generator, the starting galaxy only needs to look random
+struct Random(u32);
+
+impl Random {
+    // Uniform in 0..1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+}
+
+// A heavy body in the middle and a thin disk of light ones, each moving at about the speed of a
+// circular orbit around the mass inside its radius
+fn galaxy(count: u32) -> Vec<Body> {
+    const CENTER_MASS: f32 = 4000.0;
+    const DISK_MASS: f32 = 1000.0;
+    const INNER_RADIUS: f32 = 2.0;
+    const OUTER_RADIUS: f32 = 20.0;
+
+    let mut random = Random(0x1234_5678);
+    let body_mass = DISK_MASS / (count - 1) as f32;
+    let mut bodies = vec![Body {
+        position: [0.0, 0.0, 0.0, CENTER_MASS],
+        velocity: [0.0; 4],
+    }];
+
+    for _ in 1..count {
+        // The square root spreads the bodies evenly over the area of the disk
+        let t = random.next().sqrt();
+        let radius = INNER_RADIUS + (OUTER_RADIUS - INNER_RADIUS) * t;
+        let angle = random.next() * TAU;
+        let height = (random.next() - 0.5) * 0.5;
+
+        let enclosed_mass = CENTER_MASS + DISK_MASS * t * t;
+        let speed = (enclosed_mass / radius).sqrt();
+
+        bodies.push(Body {
+            position: [
+                radius * angle.cos(),
+                height,
+                radius * angle.sin(),
+                body_mass,
+            ],
+            velocity: [-speed * angle.sin(), 0.0, speed * angle.cos(), 0.0],
+        });
+    }
+
+    bodies
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+// Timestamps around the simulation dispatches, one pair per frame in flight
+struct SimulationTimer {
+    query_pool: Arc<QueryPool>,
+    timestamp_period: f32,
+    // Steps timed by the queries of each slot, 0 when nothing was written
+    slot_steps: Vec<u32>,
+    // Accumulated since the last report
+    total: Duration,
+    steps: u32,
+    last_report: Instant,
+    // The last reported time per step
+    per_step: Option<Duration>,
+}
+
+struct NBody {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    compute_pipeline: Arc<ComputePipeline>,
+    graphics_pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    // Each step reads one buffer and writes the other, sets[i] reads buffers[i]
+    buffers: [Subbuffer<[Body]>; 2],
+    sets: [Arc<PersistentDescriptorSet>; 2],
+    // Index of the buffer holding the latest positions
+    current: usize,
+    timer: Option<SimulationTimer>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    paused: bool,
+    tiled: bool,
+    steps_per_frame: u32,
+    dt: f32,
+    gravity: f32,
+    softening: f32,
+}
+
+impl NBody {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        // The work is quadratic in the number of bodies, so this is the knob to benchmark with
+        let count = args::value::<u32>("--bodies").unwrap_or(16384).max(2);
+
+        let staging_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            galaxy(count),
+        )
+            .expect("failed to create staging buffer");
+        let buffers = [(); 2].map(|_| {
+            Buffer::new_slice::<Body>(
+                &allocators.memory,
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER
+                        | BufferUsage::VERTEX_BUFFER
+                        | BufferUsage::TRANSFER_DST,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::DeviceOnly,
+                    ..Default::default()
+                },
+                count as u64,
+            )
+                .expect("failed to create body buffer")
+        });
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        uploads
+            .copy_buffer(CopyBufferInfo::buffers(staging_buffer, buffers[0].clone()))
+            .unwrap();
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let cs = cs::load(device.clone()).expect("failed to create shader module");
+        let compute_pipeline = ComputePipeline::new(
+            device.clone(),
+            cs.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let layout = compute_pipeline.layout().set_layouts().get(0).unwrap();
+        let sets = [0, 1].map(|i| {
+            PersistentDescriptorSet::new(
+                &allocators.descriptor,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::buffer(0, buffers[i].clone()),
+                    WriteDescriptorSet::buffer(1, buffers[1 - i].clone()),
+                ],
+            )
+                .unwrap()
+        });
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let graphics_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(Body::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::PointList))
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            // Dense regions glow brighter as the points add up
+            .color_blend_state(ColorBlendState::new(1).blend(AttachmentBlend::additive()))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // Same check as the frame statistics: not every queue family can write timestamps
+        let physical_device = device.physical_device();
+        let supports_timestamps = physical_device.queue_family_properties()
+            [renderer.queue().queue_family_index() as usize]
+            .timestamp_valid_bits
+            .is_some();
+        let frames_in_flight = renderer.images.len() as u32;
+        let timer = supports_timestamps.then(|| SimulationTimer {
+            query_pool: QueryPool::new(
+                device.clone(),
+                QueryPoolCreateInfo {
+                    query_count: frames_in_flight * 2,
+                    ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+                },
+            )
+                .expect("failed to create query pool"),
+            timestamp_period: physical_device.properties().timestamp_period,
+            slot_steps: vec![0; frames_in_flight as usize],
+            total: Duration::ZERO,
+            steps: 0,
+            last_report: Instant::now(),
+            per_step: None,
+        });
+
+        NBody {
+            camera: Camera::new(Vec3::new(0.0, 25.0, 35.0), Vec3::ZERO),
+            render_pass,
+            compute_pipeline,
+            graphics_pipeline,
+            allocators,
+            buffers,
+            sets,
+            current: 0,
+            timer,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            paused: false,
+            tiled: true,
+            steps_per_frame: 1,
+            dt: 0.005,
+            gravity: 1.0,
+            softening: 0.3,
+        }
+    }
+
+    fn count(&self) -> u32 {
+        self.buffers[0].len() as u32
+    }
+
+    // Collects the timestamps of the last frame that used this slot, and prints a summary
+    // about once per second
+    fn read_timer(&mut self, slot: usize) {
+        let count = self.count();
+        let timer = match &mut self.timer {
+            Some(timer) => timer,
+            None => return,
+        };
+
+        if timer.slot_steps[slot] > 0 {
+            let mut timestamps = [0u64; 2];
+            let range = slot as u32 * 2..slot as u32 * 2 + 2;
+            let available = timer
+                .query_pool
+                .queries_range(range)
+                .unwrap()
+                .get_results(&mut timestamps, QueryResultFlags::empty())
+                .unwrap();
+
+            if available {
+                let ticks = timestamps[1].saturating_sub(timestamps[0]);
+                let nanos = ticks as f64 * timer.timestamp_period as f64;
+                timer.total += Duration::from_nanos(nanos as u64);
+                timer.steps += timer.slot_steps[slot];
+            }
+        }
+
+        if timer.last_report.elapsed() >= Duration::from_secs(1) && timer.steps > 0 {
+            let per_step = timer.total / timer.steps;
+            let interactions = count as f64 * count as f64 / per_step.as_secs_f64();
+            println!(
+                "{count} bodies: {:.3} ms per step, {:.2} billion interactions/s",
+                per_step.as_secs_f64() * 1000.0,
+                interactions / 1e9,
+            );
+            timer.per_step = Some(per_step);
+            timer.total = Duration::ZERO;
+            timer.steps = 0;
+            timer.last_report = Instant::now();
+        }
+    }
+}
+
+impl App for NBody {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let count = self.count();
+        let steps = if self.paused { 0 } else { self.steps_per_frame };
+        // The results are read before the slot is reused, by then that frame has finished
+        self.read_timer(image_index as usize);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        if steps > 0 {
+            let push_constants = cs::PushConstants {
+                dt: self.dt,
+                gravity: self.gravity,
+                softening: self.softening,
+                count,
+                tiled: self.tiled as u32,
+            };
+
+            let query_index = image_index * 2;
+            if let Some(timer) = &mut self.timer {
+                // Writing timestamps is unsafe in vulkano: nothing checks that the queries
+                // were reset, or that they aren't still in use
+                unsafe {
+                    builder
+                        .reset_query_pool(timer.query_pool.clone(), query_index..query_index + 2)
+                        .unwrap()
+                        .write_timestamp(
+                            timer.query_pool.clone(),
+                            query_index,
+                            PipelineStage::TopOfPipe,
+                        )
+                        .unwrap();
+                }
+                timer.slot_steps[image_index as usize] = steps;
+            }
+
+            builder
+                .bind_pipeline_compute(self.compute_pipeline.clone())
+                .push_constants(self.compute_pipeline.layout().clone(), 0, push_constants);
+            for _ in 0..steps {
+                builder
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        self.compute_pipeline.layout().clone(),
+                        0,
+                        self.sets[self.current].clone(),
+                    )
+                    .dispatch([(count + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE, 1, 1])
+                    .unwrap();
+                self.current = 1 - self.current;
+            }
+
+            if let Some(timer) = &self.timer {
+                unsafe {
+                    builder
+                        .write_timestamp(
+                            timer.query_pool.clone(),
+                            query_index + 1,
+                            PipelineStage::ComputeShader,
+                        )
+                        .unwrap();
+                }
+            }
+        } else if let Some(timer) = &mut self.timer {
+            // Nothing was timed this frame
+            timer.slot_steps[image_index as usize] = 0;
+        }
+
+        let [width, height] = self.viewport.dimensions;
+        let push_constants = vs::PushConstants {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+        };
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.01, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.graphics_pipeline.clone())
+            .push_constants(self.graphics_pipeline.layout().clone(), 0, push_constants)
+            .bind_vertex_buffers(0, self.buffers[self.current].clone())
+            .draw(count, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("N-body").show(ctx, |ui| {
+            ui.label(format!("{} bodies", self.count()));
+            match self.timer.as_ref().map(|timer| timer.per_step) {
+                Some(Some(per_step)) => ui.label(format!(
+                    "simulation: {:.3} ms per step",
+                    per_step.as_secs_f64() * 1000.0
+                )),
+                Some(None) => ui.label("simulation: measuring..."),
+                None => ui.label("simulation: timestamps unsupported"),
+            };
+            ui.checkbox(&mut self.paused, "paused");
+            ui.checkbox(&mut self.tiled, "shared memory tiles");
+            ui.add(egui::Slider::new(&mut self.steps_per_frame, 1..=16).text("steps per frame"));
+            ui.add(
+                egui::Slider::new(&mut self.dt, 0.0001..=0.02)
+                    .logarithmic(true)
+                    .text("time step"),
+            );
+            ui.add(egui::Slider::new(&mut self.gravity, 0.0..=5.0).text("gravity"));
+            ui.add(egui::Slider::new(&mut self.softening, 0.01..=2.0).text("softening"));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-16", DeviceExtensions::empty(), NBody::new);
+}
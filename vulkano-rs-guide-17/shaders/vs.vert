@@ -0,0 +1,28 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 velocity;
+
+layout(location = 0) out vec3 v_color;
+
+layout(push_constant) uniform PushConstants {
+    // Fits the square world into the window
+    vec2 scale;
+    float size;
+} pc;
+
+// A thin arrow head pointing along +x
+const vec2 SHAPE[3] = vec2[](vec2(1.0, 0.0), vec2(-0.6, 0.5), vec2(-0.6, -0.5));
+
+void main() {
+    vec2 heading = normalize(velocity + vec2(1e-6, 0.0));
+    vec2 side = vec2(-heading.y, heading.x);
+    vec2 corner = SHAPE[gl_VertexIndex];
+    vec2 world = position + (heading * corner.x + side * corner.y) * pc.size;
+
+    // Color by direction, so the flocks stand out from each other
+    float angle = atan(heading.y, heading.x);
+    v_color = 0.55 + 0.45 * cos(angle + vec3(0.0, 2.094, 4.189));
+
+    gl_Position = vec4(world * pc.scale, 0.0, 1.0);
+}
@@ -0,0 +1,428 @@
+//Boids: flocking from three local rules, with the neighbours found through a grid of bins
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Has to match local_size_x in the compute shader
+const WORK_GROUP_SIZE: u32 = 256;
+// The world is the square -1..1 in both directions, cut into at most this many bins per side
+const MAX_GRID_SIZE: u32 = 64;
+
+// Stored once for the compute shader and drawn as one instance per boid
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct Boid {
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+    #[format(R32G32_SFLOAT)]
+    velocity: [f32; 2],
+}
+
+// A small xorshift generator, the starting flock only needs to look random
+struct Random(u32);
+
+impl Random {
+    // Uniform in 0..1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+}
+
+// The passes of one simulation step, all in the same compute shader
+#[derive(Clone, Copy)]
+enum Pass {
+    ClearBins,
+    CountBins,
+    // Prefix sum of the bin sizes, which gives the first sorted index of each bin
+    ScanBins,
+    // Writes each boid to its slot in the sorted buffer, grouped by bin
+    Scatter,
+    // Without binning the sorted buffer is a plain copy
+    Copy,
+    Update,
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Boids {
+    render_pass: Arc<RenderPass>,
+    compute_pipeline: Arc<ComputePipeline>,
+    graphics_pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    boids: Subbuffer<[Boid]>,
+    set: Arc<PersistentDescriptorSet>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    last_frame: Instant,
+    // Parameters exposed in the overlay
+    paused: bool,
+    binned: bool,
+    view_radius: f32,
+    separation_radius: f32,
+    separation: f32,
+    alignment: f32,
+    cohesion: f32,
+    max_speed: f32,
+    size: f32,
+}
+
+impl Boids {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let count = args::value::<u32>("--boids").unwrap_or(8192).max(1);
+
+        let mut random = Random(0x9e37_79b9);
+        let flock = (0..count).map(|_| {
+            let angle = random.next() * TAU;
+            Boid {
+                position: [random.next() * 2.0 - 1.0, random.next() * 2.0 - 1.0],
+                velocity: [angle.cos() * 0.2, angle.sin() * 0.2],
+            }
+        });
+        let staging_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            flock,
+        )
+            .expect("failed to create staging buffer");
+
+        // Everything else only ever lives on the GPU
+        let storage = |usage: BufferUsage| BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | usage,
+            ..Default::default()
+        };
+        let device_only = || AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        };
+        let boids = Buffer::new_slice::<Boid>(
+            &allocators.memory,
+            storage(BufferUsage::VERTEX_BUFFER | BufferUsage::TRANSFER_DST),
+            device_only(),
+            count as u64,
+        )
+            .expect("failed to create boid buffer");
+        let sorted = Buffer::new_slice::<Boid>(
+            &allocators.memory,
+            storage(BufferUsage::empty()),
+            device_only(),
+            count as u64,
+        )
+            .expect("failed to create sorted buffer");
+        let bins = (MAX_GRID_SIZE * MAX_GRID_SIZE) as u64;
+        let [bin_counts, bin_starts] = [(); 2].map(|_| {
+            Buffer::new_slice::<u32>(
+                &allocators.memory,
+                storage(BufferUsage::empty()),
+                device_only(),
+                bins,
+            )
+                .expect("failed to create bin buffer")
+        });
+        let bin_slots = Buffer::new_slice::<u32>(
+            &allocators.memory,
+            storage(BufferUsage::empty()),
+            device_only(),
+            count as u64,
+        )
+            .expect("failed to create bin buffer");
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        uploads
+            .copy_buffer(CopyBufferInfo::buffers(staging_buffer, boids.clone()))
+            .unwrap();
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let cs = cs::load(device.clone()).expect("failed to create shader module");
+        let compute_pipeline = ComputePipeline::new(
+            device.clone(),
+            cs.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            compute_pipeline
+                .layout()
+                .set_layouts()
+                .get(0)
+                .unwrap()
+                .clone(),
+            [
+                WriteDescriptorSet::buffer(0, boids.clone()),
+                WriteDescriptorSet::buffer(1, sorted),
+                WriteDescriptorSet::buffer(2, bin_counts),
+                WriteDescriptorSet::buffer(3, bin_starts),
+                WriteDescriptorSet::buffer(4, bin_slots),
+            ],
+        )
+            .unwrap();
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let graphics_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(Boid::per_instance())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        Boids {
+            render_pass,
+            compute_pipeline,
+            graphics_pipeline,
+            allocators,
+            boids,
+            set,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            last_frame: Instant::now(),
+            paused: false,
+            binned: true,
+            view_radius: 0.05,
+            separation_radius: 0.015,
+            separation: 1.0,
+            alignment: 0.5,
+            cohesion: 0.3,
+            max_speed: 0.3,
+            size: 0.006,
+        }
+    }
+}
+
+impl App for Boids {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let now = Instant::now();
+        let dt = (now - self.last_frame).as_secs_f32().min(0.05);
+        self.last_frame = now;
+
+        let count = self.boids.len() as u32;
+        // Bins no smaller than the view radius, so every neighbour is in one of the 3x3 around
+        let grid_size = ((2.0 / self.view_radius) as u32).clamp(3, MAX_GRID_SIZE);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        if !self.paused {
+            let passes: &[Pass] = if self.binned {
+                &[
+                    Pass::ClearBins,
+                    Pass::CountBins,
+                    Pass::ScanBins,
+                    Pass::Scatter,
+                    Pass::Update,
+                ]
+            } else {
+                &[Pass::Copy, Pass::Update]
+            };
+
+            builder
+                .bind_pipeline_compute(self.compute_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    self.compute_pipeline.layout().clone(),
+                    0,
+                    self.set.clone(),
+                );
+            for &pass in passes {
+                let push_constants = cs::PushConstants {
+                    pass: pass as u32,
+                    count,
+                    grid_size,
+                    binned: self.binned as u32,
+                    dt,
+                    view_radius: self.view_radius,
+                    separation_radius: self.separation_radius,
+                    separation: self.separation,
+                    alignment: self.alignment,
+                    cohesion: self.cohesion,
+                    max_speed: self.max_speed,
+                };
+                let invocations = match pass {
+                    Pass::ClearBins => grid_size * grid_size,
+                    Pass::ScanBins => WORK_GROUP_SIZE,
+                    _ => count,
+                };
+
+                // Each pass reads what the previous one wrote, vulkano puts the barriers in
+                builder
+                    .push_constants(self.compute_pipeline.layout().clone(), 0, push_constants)
+                    .dispatch([(invocations + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE, 1, 1])
+                    .unwrap();
+            }
+        }
+
+        let [width, height] = self.viewport.dimensions;
+        let push_constants = vs::PushConstants {
+            scale: [height.min(width) / width, height.min(width) / height],
+            size: self.size,
+        };
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.02, 0.02, 0.03, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.graphics_pipeline.clone())
+            .push_constants(self.graphics_pipeline.layout().clone(), 0, push_constants)
+            .bind_vertex_buffers(0, self.boids.clone())
+            .draw(3, count, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Boids").show(ctx, |ui| {
+            ui.label(format!("{} boids", self.boids.len()));
+            ui.checkbox(&mut self.paused, "paused");
+            ui.checkbox(&mut self.binned, "spatial binning");
+            ui.add(egui::Slider::new(&mut self.view_radius, 0.035..=0.2).text("view radius"));
+            ui.add(
+                egui::Slider::new(&mut self.separation_radius, 0.0..=0.05)
+                    .text("separation radius"),
+            );
+            ui.add(egui::Slider::new(&mut self.separation, 0.0..=5.0).text("separation"));
+            ui.add(egui::Slider::new(&mut self.alignment, 0.0..=2.0).text("alignment"));
+            ui.add(egui::Slider::new(&mut self.cohesion, 0.0..=2.0).text("cohesion"));
+            ui.add(egui::Slider::new(&mut self.max_speed, 0.05..=1.0).text("max speed"));
+            ui.add(egui::Slider::new(&mut self.size, 0.002..=0.02).text("size"));
+        });
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-17", DeviceExtensions::empty(), Boids::new);
+}
@@ -0,0 +1,491 @@
+//Stable fluids: advection, diffusion and pressure projection as a chain of compute dispatches
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BlitImageInfo, ClearColorImageInfo, CommandBufferUsage,
+    CopyImageInfo, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sampler::Filter;
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::winit::event::{ElementState, MouseButton, WindowEvent};
+
+// Half floats can be stored to, filtered and blitted on every Vulkan device, full floats can't
+const FIELD_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+// What one dispatch of the compute shader does
+#[derive(Clone, Copy)]
+enum Mode {
+    // Adds a blob of `value` around the cursor
+    Splat,
+    // Moves the source along the velocity field
+    Advect,
+    // One Jacobi iteration of the diffusion equation
+    Diffuse,
+    Divergence,
+    // One Jacobi iteration of the pressure equation
+    Pressure,
+    // Subtracts the pressure gradient, leaving a velocity field without divergence
+    Project,
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// A quantity stored over the grid, in two images so a pass can read one while writing the other
+struct Field {
+    views: [Arc<ImageView<StorageImage>>; 2],
+    current: usize,
+}
+
+impl Field {
+    fn read(&self) -> Arc<ImageView<StorageImage>> {
+        self.views[self.current].clone()
+    }
+
+    fn write(&self) -> Arc<ImageView<StorageImage>> {
+        self.views[1 - self.current].clone()
+    }
+
+    // After a pass has written the other image, that one becomes current
+    fn swap(&mut self) {
+        self.current = 1 - self.current;
+    }
+}
+
+struct Fluid {
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    velocity: Field,
+    pressure: Field,
+    dye: Field,
+    // Holds the starting point of a diffusion, or the divergence during projection
+    scratch: Arc<ImageView<StorageImage>>,
+    size: [u32; 2],
+    window_size: [f32; 2],
+    cursor: [f32; 2],
+    last_cursor: [f32; 2],
+    dragging: bool,
+    clear: bool,
+    start: Instant,
+    last_frame: Instant,
+    // Parameters exposed in the overlay
+    paused: bool,
+    viscosity: f32,
+    diffusion: f32,
+    diffusion_iterations: u32,
+    pressure_iterations: u32,
+    velocity_dissipation: f32,
+    dye_dissipation: f32,
+    force: f32,
+    splat_radius: f32,
+}
+
+impl Fluid {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        // Like the Game of Life grid, stretched over the window
+        let side = args::value::<u32>("--size").unwrap_or(256);
+        let view = || {
+            let image = StorageImage::new(
+                &allocators.memory,
+                ImageDimensions::Dim2d {
+                    width: side,
+                    height: side,
+                    array_layers: 1,
+                },
+                FIELD_FORMAT,
+                Some(renderer.queue().queue_family_index()),
+            )
+                .unwrap();
+            ImageView::new_default(image).unwrap()
+        };
+        let mut field = || Field {
+            views: [view(), view()],
+            current: 0,
+        };
+
+        Fluid {
+            pipeline,
+            allocators,
+            velocity: field(),
+            pressure: field(),
+            dye: field(),
+            scratch: view(),
+            size: [side, side],
+            window_size: [1.0, 1.0],
+            cursor: [0.0, 0.0],
+            last_cursor: [0.0, 0.0],
+            dragging: false,
+            // The images start with garbage in them
+            clear: true,
+            start: Instant::now(),
+            last_frame: Instant::now(),
+            paused: false,
+            viscosity: 0.0,
+            diffusion: 0.0,
+            diffusion_iterations: 20,
+            pressure_iterations: 40,
+            velocity_dissipation: 0.2,
+            dye_dissipation: 0.3,
+            force: 1.0,
+            splat_radius: 6.0,
+        }
+    }
+
+    // Records one pass. Descriptor sets are cheap to allocate, and which images a pass uses
+    // changes with every swap, so a new set is made each time
+    #[allow(clippy::too_many_arguments)]
+    fn dispatch(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        mode: Mode,
+        source: Arc<ImageView<StorageImage>>,
+        target: Arc<ImageView<StorageImage>>,
+        velocity: Arc<ImageView<StorageImage>>,
+        aux: Arc<ImageView<StorageImage>>,
+        push_constants: cs::PushConstants,
+    ) {
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::image_view(0, source),
+                WriteDescriptorSet::image_view(1, target),
+                WriteDescriptorSet::image_view(2, velocity),
+                WriteDescriptorSet::image_view(3, aux),
+            ],
+        )
+            .unwrap();
+
+        let [width, height] = self.size;
+        builder
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                cs::PushConstants {
+                    mode: mode as u32,
+                    ..push_constants
+                },
+            )
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap();
+    }
+
+    // Implicit diffusion of a field: the starting values are kept in scratch while the field
+    // is refined in place
+    fn diffuse(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        dye: bool,
+        alpha: f32,
+    ) {
+        if alpha <= 0.0 || self.diffusion_iterations == 0 {
+            return;
+        }
+
+        let field = if dye { &self.dye } else { &self.velocity };
+        builder
+            .copy_image(CopyImageInfo::images(
+                field.read().image().clone(),
+                self.scratch.image().clone(),
+            ))
+            .unwrap();
+
+        for _ in 0..self.diffusion_iterations {
+            let field = if dye { &self.dye } else { &self.velocity };
+            self.dispatch(
+                builder,
+                Mode::Diffuse,
+                field.read(),
+                field.write(),
+                self.velocity.read(),
+                self.scratch.clone(),
+                cs::PushConstants {
+                    alpha,
+                    ..push_constants()
+                },
+            );
+            if dye {
+                self.dye.swap();
+            } else {
+                self.velocity.swap();
+            }
+        }
+    }
+
+    fn step(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, dt: f32) {
+        let base = || cs::PushConstants {
+            dt,
+            ..push_constants()
+        };
+
+        // Dragging pushes the fluid along with the mouse and drops dye in it
+        if self.dragging {
+            let scale = [0, 1].map(|axis| self.size[axis] as f32 / self.window_size[axis]);
+            let point = [0, 1].map(|axis| self.cursor[axis] * scale[axis]);
+            let moved =
+                [0, 1].map(|axis| (self.cursor[axis] - self.last_cursor[axis]) * scale[axis]);
+            let hue = self.start.elapsed().as_secs_f32() * 0.3;
+            let color = [0.0, 1.0, 2.0].map(|phase| 0.5 + 0.5 * (TAU * (hue + phase / 3.0)).cos());
+
+            let splats = [
+                (
+                    &self.velocity,
+                    [
+                        moved[0] / dt * self.force,
+                        moved[1] / dt * self.force,
+                        0.0,
+                        0.0,
+                    ],
+                ),
+                (&self.dye, [color[0], color[1], color[2], 1.0]),
+            ];
+            for (field, value) in splats {
+                self.dispatch(
+                    builder,
+                    Mode::Splat,
+                    field.read(),
+                    field.write(),
+                    self.velocity.read(),
+                    self.scratch.clone(),
+                    cs::PushConstants {
+                        value,
+                        point,
+                        radius: self.splat_radius,
+                        ..base()
+                    },
+                );
+            }
+            self.velocity.swap();
+            self.dye.swap();
+        }
+        self.last_cursor = self.cursor;
+
+        // The velocity carries itself along
+        self.dispatch(
+            builder,
+            Mode::Advect,
+            self.velocity.read(),
+            self.velocity.write(),
+            self.velocity.read(),
+            self.scratch.clone(),
+            cs::PushConstants {
+                dissipation: self.velocity_dissipation,
+                ..base()
+            },
+        );
+        self.velocity.swap();
+        self.diffuse(builder, false, self.viscosity * dt);
+
+        // Projection: find the pressure whose gradient cancels the divergence, then remove it.
+        // Starting from last frame's pressure lets the few iterations go further
+        self.dispatch(
+            builder,
+            Mode::Divergence,
+            self.velocity.read(),
+            self.scratch.clone(),
+            self.velocity.read(),
+            self.pressure.read(),
+            base(),
+        );
+        for _ in 0..self.pressure_iterations {
+            self.dispatch(
+                builder,
+                Mode::Pressure,
+                self.pressure.read(),
+                self.pressure.write(),
+                self.velocity.read(),
+                self.scratch.clone(),
+                base(),
+            );
+            self.pressure.swap();
+        }
+        self.dispatch(
+            builder,
+            Mode::Project,
+            self.pressure.read(),
+            self.velocity.write(),
+            self.velocity.read(),
+            self.scratch.clone(),
+            base(),
+        );
+        self.velocity.swap();
+
+        // The dye is carried by the now divergence-free velocity
+        self.dispatch(
+            builder,
+            Mode::Advect,
+            self.dye.read(),
+            self.dye.write(),
+            self.velocity.read(),
+            self.scratch.clone(),
+            cs::PushConstants {
+                dissipation: self.dye_dissipation,
+                ..base()
+            },
+        );
+        self.dye.swap();
+        self.diffuse(builder, true, self.diffusion * dt);
+    }
+}
+
+// Everything zeroed, passes fill in what they use
+fn push_constants() -> cs::PushConstants {
+    cs::PushConstants {
+        value: [0.0; 4],
+        point: [0.0; 2],
+        mode: 0,
+        dt: 0.0,
+        alpha: 0.0,
+        dissipation: 0.0,
+        radius: 1.0,
+    }
+}
+
+impl App for Fluid {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+        self.window_size = [width as f32, height as f32];
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let now = Instant::now();
+        // A long frame would let the advection jump over several cells at once
+        let dt = (now - self.last_frame).as_secs_f32().min(1.0 / 30.0);
+        self.last_frame = now;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder.bind_pipeline_compute(self.pipeline.clone());
+
+        if std::mem::take(&mut self.clear) {
+            let fields = [&self.velocity, &self.pressure, &self.dye];
+            let views = fields
+                .into_iter()
+                .flat_map(|field| &field.views)
+                .chain([&self.scratch]);
+            for view in views {
+                builder
+                    .clear_color_image(ClearColorImageInfo::image(view.image().clone()))
+                    .unwrap();
+            }
+        }
+
+        if !self.paused {
+            self.step(&mut builder, dt);
+        }
+
+        // Linear filtering smooths out the grid when it is stretched over the window
+        builder
+            .blit_image(BlitImageInfo {
+                filter: Filter::Linear,
+                ..BlitImageInfo::images(
+                    self.dye.read().image().clone(),
+                    renderer.images[image_index as usize].clone(),
+                )
+            })
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Fluid").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.paused, "paused");
+                if ui.button("clear").clicked() {
+                    self.clear = true;
+                }
+            });
+            ui.add(egui::Slider::new(&mut self.viscosity, 0.0..=50.0).text("viscosity"));
+            ui.add(egui::Slider::new(&mut self.diffusion, 0.0..=50.0).text("dye diffusion"));
+            ui.add(
+                egui::Slider::new(&mut self.diffusion_iterations, 1..=80)
+                    .text("diffusion iterations"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.pressure_iterations, 1..=200)
+                    .text("pressure iterations"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.velocity_dissipation, 0.0..=5.0)
+                    .text("velocity dissipation"),
+            );
+            ui.add(egui::Slider::new(&mut self.dye_dissipation, 0.0..=5.0).text("dye dissipation"));
+            ui.add(egui::Slider::new(&mut self.force, 0.0..=5.0).text("force"));
+            ui.add(egui::Slider::new(&mut self.splat_radius, 1.0..=32.0).text("splat radius"));
+            ui.label("Drag with the left mouse button to stir");
+        });
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = [position.x as f32, position.y as f32];
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Left,
+                state,
+                ..
+            } => {
+                self.dragging = *state == ElementState::Pressed;
+                // No jump from wherever the cursor was when the button was last released
+                self.last_cursor = self.cursor;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-18", DeviceExtensions::empty(), Fluid::new);
+}
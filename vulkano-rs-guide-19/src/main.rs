@@ -0,0 +1,290 @@
+//Parallel prefix sum: a work-efficient exclusive scan, checked against the CPU
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::{self, GpuFuture, PipelineStage};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::MemoryReport;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// Each work group scans two elements per invocation, and has to match the shader
+const WORK_GROUP_SIZE: u32 = 256;
+const BLOCK_SIZE: u32 = WORK_GROUP_SIZE * 2;
+
+// Which half of the algorithm a dispatch runs
+#[derive(Clone, Copy)]
+enum Mode {
+    // Scans every block on its own and writes the block totals
+    ScanBlocks,
+    // Adds the scanned block totals back onto every element of their block
+    AddOffsets,
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// A small xorshift generator for the input values
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+fn main() {
+    // No window, so no surface for the device to support
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    // --memory-report prints the heaps around the allocations, the levels add little to the input
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    let count = args::value::<u32>("--count").unwrap_or(1 << 22).max(1);
+    // Small values, so the total of a few million of them still fits in 32 bits
+    let mut random = Random(0x2545_f491);
+    let input: Vec<u32> = (0..count).map(|_| random.next() % 16).collect();
+
+    // The scan itself works on device memory, the data goes in and out through host buffers
+    let upload_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        input.iter().copied(),
+    )
+        .expect("failed to create buffer");
+    let download_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        count as u64,
+    )
+        .expect("failed to create buffer");
+
+    // One scan covers a single block. For more, the block totals are scanned in turn, and so on
+    // until they fit in one block: 512 elements, then 262144, then 134 million
+    let storage_buffer = |length: u64| {
+        Buffer::new_slice::<u32>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER
+                    | BufferUsage::TRANSFER_SRC
+                    | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            length,
+        )
+            .expect("failed to create buffer")
+    };
+    let mut levels: Vec<Subbuffer<[u32]>> = vec![storage_buffer(count as u64)];
+    let mut length = count as u64;
+    // The last level's single total is the sum of the whole input
+    while length > 1 || levels.len() == 1 {
+        length = (length + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
+        levels.push(storage_buffer(length));
+    }
+
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("after the buffers");
+    }
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+
+    // sets[i] scans levels[i] and writes its block totals to levels[i + 1]
+    let layout = pipeline.layout().set_layouts().get(0).unwrap();
+    let sets: Vec<Arc<PersistentDescriptorSet>> = levels
+        .windows(2)
+        .map(|pair| {
+            PersistentDescriptorSet::new(
+                &allocators.descriptor,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::buffer(0, pair[0].clone()),
+                    WriteDescriptorSet::buffer(1, pair[1].clone()),
+                ],
+            )
+                .unwrap()
+        })
+        .collect();
+
+    let supports_timestamps = device.physical_device().queue_family_properties()
+        [queue.queue_family_index() as usize]
+        .timestamp_valid_bits
+        .is_some();
+    let query_pool = QueryPool::new(
+        device.clone(),
+        QueryPoolCreateInfo {
+            query_count: 2,
+            ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+        },
+    )
+        .expect("failed to create query pool");
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(upload_buffer, levels[0].clone()))
+        .unwrap()
+        .bind_pipeline_compute(pipeline.clone());
+    if supports_timestamps {
+        unsafe {
+            builder
+                .reset_query_pool(query_pool.clone(), 0..2)
+                .unwrap()
+                .write_timestamp(query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                .unwrap();
+        }
+    }
+
+    // Down the levels scanning blocks, then back up adding each level's scanned totals onto the
+    // level below it
+    let passes = (0..sets.len())
+        .map(|level| (Mode::ScanBlocks, level))
+        .chain(
+            (0..sets.len() - 1)
+                .rev()
+                .map(|level| (Mode::AddOffsets, level)),
+        );
+    for (mode, level) in passes {
+        let length = levels[level].len() as u32;
+        builder
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                sets[level].clone(),
+            )
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                cs::PushConstants {
+                    mode: mode as u32,
+                    count: length,
+                },
+            )
+            .dispatch([(length + BLOCK_SIZE - 1) / BLOCK_SIZE, 1, 1])
+            .unwrap();
+    }
+
+    if supports_timestamps {
+        unsafe {
+            builder
+                .write_timestamp(query_pool.clone(), 1, PipelineStage::ComputeShader)
+                .unwrap();
+        }
+    }
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(
+            levels[0].clone(),
+            download_buffer.clone(),
+        ))
+        .unwrap();
+
+    let start = Instant::now();
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+    let submission_time = start.elapsed();
+
+    // The same scan on the CPU, one element after another
+    let start = Instant::now();
+    let expected: Vec<u32> = input
+        .iter()
+        .scan(0u32, |sum, &value| {
+            let before = *sum;
+            *sum += value;
+            Some(before)
+        })
+        .collect();
+    let cpu_time = start.elapsed();
+
+    let result = download_buffer.read().unwrap();
+    if let Some(i) = (0..count as usize).find(|&i| result[i] != expected[i]) {
+        panic!(
+            "mismatch at index {i}: gpu {}, cpu {}",
+            result[i], expected[i]
+        );
+    }
+
+    println!(
+        "Scanned {count} values in {} levels, total {}",
+        sets.len(),
+        expected[count as usize - 1] + input[count as usize - 1],
+    );
+    if supports_timestamps {
+        let mut timestamps = [0u64; 2];
+        query_pool
+            .queries_range(0..2)
+            .unwrap()
+            .get_results(&mut timestamps, QueryResultFlags::WAIT)
+            .unwrap();
+        let period = device.physical_device().properties().timestamp_period as f64;
+        let gpu_millis = (timestamps[1] - timestamps[0]) as f64 * period / 1e6;
+        println!("GPU scan: {gpu_millis:.3} ms");
+    }
+    println!(
+        "GPU submission including copies: {:.3} ms",
+        submission_time.as_secs_f64() * 1000.0
+    );
+    println!("CPU scan: {:.3} ms", cpu_time.as_secs_f64() * 1000.0);
+    tracing::info!("everything succeeded");
+}
@@ -6,14 +6,18 @@ use vulkano::command_buffer::allocator::{
 };
 use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
-use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, QueueCreateInfo, QueueFlags};
 use vulkano::instance::{Instance, InstanceCreateInfo};
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
 use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
 use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::{logging, tracing};
 
 fn main() {
+    // Messages go through tracing, as in the rest of the chapters
+    logging::init();
+
     // Initialization
     // The instance maps vulkano to the local vulkan instalation
     let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
@@ -87,45 +91,12 @@ fn main() {
 
     // Compute pipelines
     // We are going to multiply the 65536 values on the data buffer by 12
-    // GLSL shader to program the actual parallel computing
-    /*
-        #version 460 -> The GLSL version to be used
-
-                layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in; -> Defining the
-                    local size of the work groups, 1024 work groups * local size of 64 = 65536
-                    x, y, z for convenience if working with 2d or 3d data structure
-
-                layout(set = 0, binding = 0) buffer Data {
-                    uint data[];
-                } buf; -> Creating a slot for a descriptor set, descriptors describe the resources
-                          that shaders will use during execution (data buffer in this case),
-                          provide a way to bind resources to shaders and specify how they can be
-                          accessed by the shaders
-
-
-                void main() { -> shader entry point
-                    uint idx = gl_GlobalInvocationID.x; -> represents the indices of the buffer
-                                                            0..65536
-                    buf.data[idx] *= 12; -> multiply each index by 12
-                }
-     */
+    // The GLSL shader that does the actual parallel computing is in shaders/cs.comp, with a
+    // walkthrough of what each line does
     mod cs {
         vulkano_shaders::shader! {
             ty: "compute",
-            src: "
-                #version 460
-
-                layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
-
-                layout(set = 0, binding = 0) buffer Data {
-                    uint data[];
-                } buf;
-
-                void main() {
-                    uint idx = gl_GlobalInvocationID.x;
-                    buf.data[idx] *= 12;
-                }
-            "
+            path: "shaders/cs.comp"
         }
     }
 
@@ -144,21 +115,17 @@ fn main() {
     // Just like buffers and command buffers, descriptor sets need allocators
     let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
 
-    // Before creating the descriptor set, the layout its targeting is needed
-    let pipeline_layout = compute_pipeline.layout();
-    let descriptor_set_layouts = pipeline_layout.set_layouts();
+    // The descriptor set targets one of the layouts of the pipeline, which took them from the
+    // shader, so only the set index and the resource for each binding are needed
     let descriptor_set_layout_index = 0;
-    let descriptor_set_layout = descriptor_set_layouts
-        .get(descriptor_set_layout_index)
-        .unwrap();
 
-    // Create the descriptor set
-    let descriptor_set = PersistentDescriptorSet::new(
+    // Create the descriptor set, the data buffer goes to binding 0 as declared in the shader
+    let descriptor_set = bind_resources(
         &descriptor_set_allocator,
-        descriptor_set_layout.clone(),
-        [WriteDescriptorSet::buffer(0, data_buffer.clone())], // 0 is the binding
-    )
-        .unwrap();
+        &compute_pipeline,
+        descriptor_set_layout_index,
+        [(0, Resource::buffer(data_buffer.clone()))],
+    );
 
     // Create command buffer allocator
     let command_buffer_allocator = StandardCommandBufferAllocator::new(
@@ -181,7 +148,7 @@ fn main() {
         .bind_descriptor_sets(
             PipelineBindPoint::Compute,
             compute_pipeline.layout().clone(),
-            descriptor_set_layout_index as u32,
+            descriptor_set_layout_index,
             descriptor_set,
         )
         .dispatch(work_group_counts)
@@ -206,5 +173,5 @@ fn main() {
         assert_eq!(*val, n as u32 * 12);
     }
 
-    println!("Everything succeeded!");
+    tracing::info!("everything succeeded");
 }
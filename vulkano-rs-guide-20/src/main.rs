@@ -0,0 +1,126 @@
+//Reduction: the sum, minimum and maximum of a large buffer through a tree of work groups
+
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::MemoryReport;
+use vulkano_rs_common::reduce::{GpuReducer, ReduceOp};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// A small xorshift generator for the input values
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    // --memory-report prints how much of each heap the buffers take
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    let count = args::value::<u32>("--count").unwrap_or(1 << 24).max(1);
+    let mut random = Random(0x68e3_1da4);
+    let input: Vec<u32> = (0..count).map(|_| random.next()).collect();
+
+    // The reduction reads device memory, the values are copied there once up front
+    let upload_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        input.iter().copied(),
+    )
+        .expect("failed to create buffer");
+    let data_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        count as u64,
+    )
+        .expect("failed to create buffer");
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(upload_buffer, data_buffer.clone()))
+        .unwrap();
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("after the buffers");
+    }
+
+    // The pipeline is built once and reused for all three reductions
+    let reducer = GpuReducer::new(&allocators, &queue);
+
+    println!("Reducing {count} values");
+    for op in [ReduceOp::Sum, ReduceOp::Min, ReduceOp::Max] {
+        let start = Instant::now();
+        let gpu = reducer.reduce(data_buffer.clone(), op);
+        let gpu_time = start.elapsed();
+
+        let start = Instant::now();
+        let cpu = input.iter().fold(op.identity(), |a, &b| match op {
+            ReduceOp::Sum => a.wrapping_add(b),
+            ReduceOp::Min => a.min(b),
+            ReduceOp::Max => a.max(b),
+        });
+        let cpu_time = start.elapsed();
+
+        assert_eq!(gpu, cpu, "{op:?} doesn't match the CPU");
+        // The GPU time includes recording and submitting the passes and waiting for the result
+        println!(
+            "{op:?}: {gpu} (GPU {:.3} ms, CPU {:.3} ms)",
+            gpu_time.as_secs_f64() * 1000.0,
+            cpu_time.as_secs_f64() * 1000.0,
+        );
+    }
+
+    tracing::info!("everything succeeded");
+}
@@ -0,0 +1,268 @@
+//Bitonic sort: a fixed network of compare-and-swap passes, sorting a whole buffer on the GPU
+
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, FillBufferInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::{self, GpuFuture, PipelineStage};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::MemoryReport;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// Has to match local_size_x in the shader
+const WORK_GROUP_SIZE: u32 = 256;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// A small xorshift generator for the keys
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    // --memory-report shows the padded buffer landing in device memory
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    let count = args::value::<u32>("--count").unwrap_or(1 << 22).max(1);
+    // Every padded key gets its own invocation, so the padding has to fit in one dispatch. The
+    // stage size is doubled past the padded count in a u32, which caps it at 2^30 too
+    let max_groups = device
+        .physical_device()
+        .properties()
+        .max_compute_work_group_count[0];
+    let max_count = (1u64 << (max_groups as u64 * WORK_GROUP_SIZE as u64).ilog2()).min(1 << 30);
+    if count as u64 > max_count {
+        tracing::error!("--count can be at most {max_count} on this device, got {count}");
+        std::process::exit(1);
+    }
+    let mut random = Random(0x7f4a_7c15);
+    let mut keys: Vec<u32> = (0..count).map(|_| random.next()).collect();
+
+    // The network only works on powers of two. The padding is the largest key, so it ends up
+    // after everything else and is simply not copied back
+    let padded = count.next_power_of_two();
+
+    let upload_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        keys.iter().copied(),
+    )
+        .expect("failed to create buffer");
+    let data_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER
+                | BufferUsage::TRANSFER_SRC
+                | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        padded as u64,
+    )
+        .expect("failed to create buffer");
+    let download_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        count as u64,
+    )
+        .expect("failed to create buffer");
+
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("after the buffers");
+    }
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    let set = PersistentDescriptorSet::new(
+        &allocators.descriptor,
+        pipeline.layout().set_layouts().get(0).unwrap().clone(),
+        [WriteDescriptorSet::buffer(0, data_buffer.clone())],
+    )
+        .unwrap();
+
+    let supports_timestamps = device.physical_device().queue_family_properties()
+        [queue.queue_family_index() as usize]
+        .timestamp_valid_bits
+        .is_some();
+    let query_pool = QueryPool::new(
+        device.clone(),
+        QueryPoolCreateInfo {
+            query_count: 2,
+            ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+        },
+    )
+        .expect("failed to create query pool");
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(
+            upload_buffer,
+            data_buffer.clone().slice(0..count as u64),
+        ))
+        .unwrap();
+    if padded > count {
+        builder
+            .fill_buffer(FillBufferInfo {
+                data: u32::MAX,
+                ..FillBufferInfo::dst_buffer(data_buffer.clone().slice(count as u64..))
+            })
+            .unwrap();
+    }
+    builder
+        .bind_pipeline_compute(pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            pipeline.layout().clone(),
+            0,
+            set,
+        );
+    if supports_timestamps {
+        unsafe {
+            builder
+                .reset_query_pool(query_pool.clone(), 0..2)
+                .unwrap()
+                .write_timestamp(query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                .unwrap();
+        }
+    }
+
+    // log2(n) stages, stage k merges bitonic sequences of length k through log2(k) passes of
+    // shrinking distance j. Every pass depends on the previous one, so each is its own dispatch
+    let mut passes = 0;
+    let mut k = 2;
+    while k <= padded {
+        let mut j = k / 2;
+        while j > 0 {
+            builder
+                .push_constants(pipeline.layout().clone(), 0, cs::PushConstants { k, j })
+                .dispatch([(padded + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE, 1, 1])
+                .unwrap();
+            passes += 1;
+            j /= 2;
+        }
+        k *= 2;
+    }
+
+    if supports_timestamps {
+        unsafe {
+            builder
+                .write_timestamp(query_pool.clone(), 1, PipelineStage::ComputeShader)
+                .unwrap();
+        }
+    }
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(
+            data_buffer.slice(0..count as u64),
+            download_buffer.clone(),
+        ))
+        .unwrap();
+
+    let start = Instant::now();
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+    let submission_time = start.elapsed();
+
+    let start = Instant::now();
+    keys.sort();
+    let cpu_time = start.elapsed();
+
+    assert!(
+        *download_buffer.read().unwrap() == keys[..],
+        "the GPU result doesn't match the CPU sort"
+    );
+
+    println!("Sorted {count} keys ({padded} padded) in {passes} passes");
+    if supports_timestamps {
+        let mut timestamps = [0u64; 2];
+        query_pool
+            .queries_range(0..2)
+            .unwrap()
+            .get_results(&mut timestamps, QueryResultFlags::WAIT)
+            .unwrap();
+        let period = device.physical_device().properties().timestamp_period as f64;
+        let seconds = (timestamps[1] - timestamps[0]) as f64 * period / 1e9;
+        println!(
+            "GPU sort: {:.3} ms, {:.1} million keys/s",
+            seconds * 1000.0,
+            count as f64 / seconds / 1e6,
+        );
+    }
+    println!(
+        "GPU submission including copies: {:.3} ms",
+        submission_time.as_secs_f64() * 1000.0
+    );
+    println!(
+        "CPU sort: {:.3} ms, {:.1} million keys/s",
+        cpu_time.as_secs_f64() * 1000.0,
+        count as f64 / cpu_time.as_secs_f64() / 1e6,
+    );
+    tracing::info!("everything succeeded");
+}
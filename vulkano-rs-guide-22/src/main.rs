@@ -0,0 +1,304 @@
+//Histogram: counting with atomics, and why many threads hitting the same counter is slow
+
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, FillBufferInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::{self, GpuFuture, PipelineStage};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::MemoryReport;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+const BINS: usize = 256;
+// Has to match local_size_x in the shader
+const WORK_GROUP_SIZE: u32 = 256;
+// Words of input per invocation, more means fewer work groups merging into the global bins
+const WORDS_PER_INVOCATION: u32 = 16;
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    // Every byte is an atomic add straight to the global bins
+    GlobalAtomics,
+    // Each work group counts into its own copy of the bins in shared memory, and merges them
+    // into the global bins once at the end
+    Privatized,
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// A small xorshift generator for the input bytes
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    // --memory-report prints the heaps again after each dataset's buffers
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+
+    let supports_timestamps = device.physical_device().queue_family_properties()
+        [queue.queue_family_index() as usize]
+        .timestamp_valid_bits
+        .is_some();
+    let timestamp_period = device.physical_device().properties().timestamp_period as f64;
+    let query_pool = QueryPool::new(
+        device.clone(),
+        QueryPoolCreateInfo {
+            query_count: 2,
+            ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+        },
+    )
+        .expect("failed to create query pool");
+
+    let bins_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER
+                | BufferUsage::TRANSFER_SRC
+                | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        BINS as u64,
+    )
+        .expect("failed to create buffer");
+    let download_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        BINS as u64,
+    )
+        .expect("failed to create buffer");
+
+    // Runs one version over the data, returns the bins and the GPU time in milliseconds
+    let run = |mode: Mode, data_buffer: &Subbuffer<[u32]>| {
+        let set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::buffer(0, data_buffer.clone()),
+                WriteDescriptorSet::buffer(1, bins_buffer.clone()),
+            ],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .fill_buffer(FillBufferInfo::dst_buffer(bins_buffer.clone()))
+            .unwrap()
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                cs::PushConstants { mode: mode as u32 },
+            );
+        if supports_timestamps {
+            unsafe {
+                builder
+                    .reset_query_pool(query_pool.clone(), 0..2)
+                    .unwrap()
+                    .write_timestamp(query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                    .unwrap();
+            }
+        }
+
+        let words_per_group = WORK_GROUP_SIZE * WORDS_PER_INVOCATION;
+        let groups = (data_buffer.len() as u32 + words_per_group - 1) / words_per_group;
+        builder.dispatch([groups, 1, 1]).unwrap();
+
+        if supports_timestamps {
+            unsafe {
+                builder
+                    .write_timestamp(query_pool.clone(), 1, PipelineStage::ComputeShader)
+                    .unwrap();
+            }
+        }
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                bins_buffer.clone(),
+                download_buffer.clone(),
+            ))
+            .unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let millis = supports_timestamps.then(|| {
+            let mut timestamps = [0u64; 2];
+            query_pool
+                .queries_range(0..2)
+                .unwrap()
+                .get_results(&mut timestamps, QueryResultFlags::WAIT)
+                .unwrap();
+            (timestamps[1] - timestamps[0]) as f64 * timestamp_period / 1e6
+        });
+        let bins = download_buffer.read().unwrap().to_vec();
+        (bins, millis)
+    };
+
+    // Four bytes per word
+    let words = args::value::<u32>("--count").unwrap_or(1 << 26).max(4) / 4;
+    let mut random = Random(0x3c6e_f372);
+    // Random bytes spread the atomics over all 256 bins. In the skewed data nine bytes in ten
+    // are the same value, like the background of an image, and the adds pile up on one counter
+    let datasets: [(&str, Vec<u32>); 2] = [
+        ("uniform", (0..words).map(|_| random.next()).collect()),
+        (
+            "skewed",
+            (0..words)
+                .map(|_| {
+                    let mut word = 0;
+                    for byte in 0..4 {
+                        let value = if random.next() % 10 == 0 {
+                            random.next() & 0xff
+                        } else {
+                            0x80
+                        };
+                        word |= value << (byte * 8);
+                    }
+                    word
+                })
+                .collect(),
+        ),
+    ];
+
+    for (name, data) in datasets {
+        // Device memory, so the timings measure the atomics rather than reads over the bus
+        let staging_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            data.iter().copied(),
+        )
+            .expect("failed to create buffer");
+        let data_buffer = Buffer::new_slice::<u32>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            data.len() as u64,
+        )
+            .expect("failed to create buffer");
+
+        if let Some(memory_report) = &mut memory_report {
+            memory_report.print(&format!("after the {name} data"));
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(staging_buffer, data_buffer.clone()))
+            .unwrap();
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let start = Instant::now();
+        let mut expected = vec![0u32; BINS];
+        for word in &data {
+            for byte in word.to_le_bytes() {
+                expected[byte as usize] += 1;
+            }
+        }
+        let cpu_time = start.elapsed();
+
+        println!("{name} data, {} bytes:", data.len() * 4);
+        for mode in [Mode::GlobalAtomics, Mode::Privatized] {
+            let (bins, millis) = run(mode, &data_buffer);
+            assert_eq!(bins, expected, "{mode:?} histogram doesn't match the CPU");
+            match millis {
+                Some(millis) => println!("  {mode:?}: {millis:.3} ms"),
+                None => println!("  {mode:?}: correct, timestamps unsupported"),
+            }
+        }
+        println!("  CPU: {:.3} ms", cpu_time.as_secs_f64() * 1000.0);
+    }
+
+    tracing::info!("everything succeeded");
+}
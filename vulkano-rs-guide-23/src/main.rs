@@ -0,0 +1,212 @@
+//Gaussian blur: a separable convolution as two compute passes over storage images
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage, CopyBufferToImageInfo,
+    CopyImageToBufferInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::wait;
+
+// Colors have to be averaged in linear space, and half floats keep the precision between passes
+const WORK_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+// Every pass reads 2 * radius + 1 pixels for each one it writes, this keeps it reasonable
+const MAX_RADIUS: i32 = 64;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// Rings of color on a checkerboard, for when no input is given: sharp edges show the blur well
+fn test_pattern(size: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        let checker = ((x / 32) + (y / 32)) % 2 == 0;
+        let dx = x as f32 - size as f32 / 2.0;
+        let dy = y as f32 - size as f32 / 2.0;
+        let ring = ((dx * dx + dy * dy).sqrt() / 24.0) as u32 % 3;
+        match (checker, ring) {
+            (_, 0) => image::Rgba([230, 60, 40, 255]),
+            (true, _) => image::Rgba([250, 250, 250, 255]),
+            (false, 1) => image::Rgba([40, 90, 220, 255]),
+            (false, _) => image::Rgba([20, 20, 20, 255]),
+        }
+    })
+}
+
+fn main() {
+    let input = match args::value::<String>("--input") {
+        Some(path) => image::open(&path)
+            .unwrap_or_else(|err| panic!("failed to load {path}: {err}"))
+            .to_rgba8(),
+        None => test_pattern(512),
+    };
+    let output = args::value::<String>("--output").unwrap_or_else(|| "blurred.png".into());
+    let radius = args::value::<i32>("--radius")
+        .unwrap_or(8)
+        .clamp(0, MAX_RADIUS);
+    // By default the kernel ends where the curve is down to about 1%
+    let sigma = args::value::<f32>("--sigma")
+        .unwrap_or(radius as f32 / 3.0)
+        .max(0.1);
+    let (width, height) = input.dimensions();
+
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    let dimensions = ImageDimensions::Dim2d {
+        width,
+        height,
+        array_layers: 1,
+    };
+    // The PNG's bytes are sRGB encoded. sRGB formats can't be storage images, but blitting from
+    // one to a float image decodes to linear on the way, and blitting back encodes again
+    let srgb_image = StorageImage::with_usage(
+        &allocators.memory,
+        dimensions,
+        Format::R8G8B8A8_SRGB,
+        ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+        ImageCreateFlags::empty(),
+        [queue.queue_family_index()],
+    )
+        .unwrap();
+    // The first pass reads images[0] and writes images[1], the second goes back
+    let images = [(); 2].map(|_| {
+        StorageImage::new(
+            &allocators.memory,
+            dimensions,
+            WORK_FORMAT,
+            Some(queue.queue_family_index()),
+        )
+            .unwrap()
+    });
+
+    let buffer = |usage, memory_usage| {
+        Buffer::new_slice::<u8>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: memory_usage,
+                ..Default::default()
+            },
+            (width * height * 4) as u64,
+        )
+            .expect("failed to create buffer")
+    };
+    let upload_buffer = buffer(BufferUsage::TRANSFER_SRC, MemoryUsage::Upload);
+    upload_buffer
+        .write()
+        .unwrap()
+        .copy_from_slice(input.as_raw());
+    let download_buffer = buffer(BufferUsage::TRANSFER_DST, MemoryUsage::Download);
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    let layout = pipeline.layout().set_layouts().get(0).unwrap();
+    let sets = [0, 1].map(|i| {
+        PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(
+                    0,
+                    ImageView::new_default(images[i].clone()).unwrap(),
+                ),
+                WriteDescriptorSet::image_view(
+                    1,
+                    ImageView::new_default(images[1 - i].clone()).unwrap(),
+                ),
+            ],
+        )
+            .unwrap()
+    });
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+            upload_buffer,
+            srgb_image.clone(),
+        ))
+        .unwrap()
+        .blit_image(BlitImageInfo::images(srgb_image.clone(), images[0].clone()))
+        .unwrap()
+        .bind_pipeline_compute(pipeline.clone());
+
+    for (set, direction) in sets.into_iter().zip([[1, 0], [0, 1]]) {
+        builder
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                cs::PushConstants {
+                    direction,
+                    radius,
+                    sigma,
+                },
+            )
+            .dispatch([(width + 15) / 16, (height + 15) / 16, 1])
+            .unwrap();
+    }
+
+    builder
+        .blit_image(BlitImageInfo::images(images[0].clone(), srgb_image.clone()))
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            srgb_image,
+            download_buffer.clone(),
+        ))
+        .unwrap();
+
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let pixels = download_buffer.read().unwrap().to_vec();
+    image::RgbaImage::from_raw(width, height, pixels)
+        .unwrap()
+        .save(&output)
+        .unwrap_or_else(|err| panic!("failed to save {output}: {err}"));
+
+    println!("Blurred {width}x{height} with radius {radius} and sigma {sigma:.2} into {output}");
+}
@@ -0,0 +1,228 @@
+//A Julia set explorer: the mouse picks the complex parameter, the wheel zooms in
+
+use std::sync::Arc;
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+struct Julia {
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // Recreated with the swapchain so the fractal is computed at the window resolution
+    image: Option<(Arc<StorageImage>, Arc<PersistentDescriptorSet>)>,
+    window_size: [f32; 2],
+    // In 0..1 across the window
+    cursor: [f32; 2],
+    // Parameters exposed in the overlay
+    follow_mouse: bool,
+    c: [f32; 2],
+    center: [f32; 2],
+    scale: f32,
+    max_iterations: u32,
+    color_shift: f32,
+}
+
+impl Julia {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        Julia {
+            pipeline,
+            allocators: renderer.allocators.clone(),
+            image: None,
+            window_size: [1.0, 1.0],
+            cursor: [0.5, 0.5],
+            follow_mouse: true,
+            c: [-0.8, 0.156],
+            center: [0.0, 0.0],
+            scale: 3.0,
+            max_iterations: 300,
+            color_shift: 0.0,
+        }
+    }
+
+    // The point of the plane under the cursor, the same mapping as in the shader
+    fn under_cursor(&self, scale: f32, center: [f32; 2]) -> [f32; 2] {
+        let aspect = [self.window_size[0] / self.window_size[1], 1.0];
+        [0, 1].map(|axis| (self.cursor[axis] - 0.5) * scale * aspect[axis] + center[axis])
+    }
+}
+
+impl App for Julia {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+        self.window_size = [width as f32, height as f32];
+
+        let image = StorageImage::new(
+            &self.allocators.memory,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(renderer.queue().queue_family_index()),
+        )
+            .unwrap();
+
+        let view = ImageView::new_default(image.clone()).unwrap();
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [WriteDescriptorSet::image_view(0, view)],
+        )
+            .unwrap();
+
+        self.image = Some((image, set));
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let (image, set) = self.image.clone().unwrap();
+        let [width, height] = renderer.swapchain.image_extent();
+
+        // Moving across the window sweeps c over the interesting part of the plane, where the
+        // Mandelbrot set is
+        if self.follow_mouse {
+            self.c = [
+                (self.cursor[0] * 2.0 - 1.0) * 1.6 - 0.4,
+                (self.cursor[1] * 2.0 - 1.0) * 1.2,
+            ];
+        }
+
+        let push_constants = cs::PushConstants {
+            center: self.center,
+            c: self.c,
+            scale: self.scale,
+            max_iterations: self.max_iterations,
+            color_shift: self.color_shift,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap()
+            .blit_image(BlitImageInfo::images(
+                image,
+                renderer.images[image_index as usize].clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Julia").show(ctx, |ui| {
+            ui.checkbox(&mut self.follow_mouse, "c follows the mouse");
+            ui.add_enabled(
+                !self.follow_mouse,
+                egui::Slider::new(&mut self.c[0], -2.0..=1.0)
+                    .text("c real")
+                    .fixed_decimals(4),
+            );
+            ui.add_enabled(
+                !self.follow_mouse,
+                egui::Slider::new(&mut self.c[1], -1.5..=1.5)
+                    .text("c imaginary")
+                    .fixed_decimals(4),
+            );
+            ui.add(egui::Slider::new(&mut self.max_iterations, 10..=2000).text("iterations"));
+            ui.add(
+                egui::Slider::new(&mut self.scale, 0.00001..=4.0)
+                    .logarithmic(true)
+                    .text("zoom"),
+            );
+            ui.add(egui::Slider::new(&mut self.color_shift, 0.0..=1.0).text("color shift"));
+            if ui.button("reset view").clicked() {
+                self.center = [0.0, 0.0];
+                self.scale = 3.0;
+            }
+            ui.label("Click to freeze c, scroll to zoom at the cursor");
+        });
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = [
+                    position.x as f32 / self.window_size[0],
+                    position.y as f32 / self.window_size[1],
+                ];
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Left,
+                state: ElementState::Pressed,
+                ..
+            } => {
+                self.follow_mouse = !self.follow_mouse;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+                };
+                // Zoom around the cursor: the point under it stays put
+                let before = self.under_cursor(self.scale, self.center);
+                let scale = (self.scale * 0.85f32.powf(lines)).clamp(0.00001, 4.0);
+                let after = self.under_cursor(scale, self.center);
+                self.center = [0, 1].map(|axis| self.center[axis] + before[axis] - after[axis]);
+                self.scale = scale;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-24", DeviceExtensions::empty(), Julia::new);
+}
@@ -0,0 +1,175 @@
+//Raymarching signed distance functions: a scene with no triangles, drawn by a compute shader
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+struct Raymarching {
+    camera: Camera,
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // Recreated with the swapchain so the scene is traced at the window resolution
+    image: Option<(Arc<StorageImage>, Arc<PersistentDescriptorSet>)>,
+    start: Instant,
+    // Parameters exposed in the overlay
+    max_steps: u32,
+    soft_shadows: bool,
+    show_steps: bool,
+    smoothness: f32,
+}
+
+impl Raymarching {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        Raymarching {
+            camera: Camera::new(Vec3::new(0.0, 2.5, 7.0), Vec3::new(0.0, 1.0, 0.0)),
+            pipeline,
+            allocators: renderer.allocators.clone(),
+            image: None,
+            start: Instant::now(),
+            max_steps: 128,
+            soft_shadows: true,
+            show_steps: false,
+            smoothness: 0.4,
+        }
+    }
+}
+
+impl App for Raymarching {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+
+        let image = StorageImage::new(
+            &self.allocators.memory,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(renderer.queue().queue_family_index()),
+        )
+            .unwrap();
+
+        let view = ImageView::new_default(image.clone()).unwrap();
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [WriteDescriptorSet::image_view(0, view)],
+        )
+            .unwrap();
+
+        self.image = Some((image, set));
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let (image, set) = self.image.clone().unwrap();
+        let [width, height] = renderer.swapchain.image_extent();
+
+        let forward = self.camera.forward();
+        let right = self.camera.right();
+        let up = right.cross(forward);
+        let push_constants = cs::PushConstants {
+            position: self
+                .camera
+                .position
+                .extend(self.start.elapsed().as_secs_f32())
+                .to_array(),
+            forward: forward.extend((self.camera.fov_y / 2.0).tan()).to_array(),
+            right: right.extend(0.0).to_array(),
+            up: up.extend(0.0).to_array(),
+            max_steps: self.max_steps,
+            soft_shadows: self.soft_shadows as u32,
+            show_steps: self.show_steps as u32,
+            smoothness: self.smoothness,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap()
+            .blit_image(BlitImageInfo::images(
+                image,
+                renderer.images[image_index as usize].clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Raymarching").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.max_steps, 8..=512).text("max steps"));
+            ui.add(egui::Slider::new(&mut self.smoothness, 0.01..=1.0).text("blend distance"));
+            ui.checkbox(&mut self.soft_shadows, "soft shadows");
+            ui.checkbox(&mut self.show_steps, "show step count");
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-25",
+        DeviceExtensions::empty(),
+        Raymarching::new,
+    );
+}
@@ -0,0 +1,209 @@
+//A progressive path tracer: every frame adds a few samples per pixel to a running average
+
+use std::sync::Arc;
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        include: ["../shaders/common"],
+        path: "shaders/cs.comp",
+    }
+}
+
+struct PathTracer {
+    camera: Camera,
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // Recreated with the swapchain: the display image, and the set binding it with the
+    // accumulation image
+    image: Option<(Arc<StorageImage>, Arc<PersistentDescriptorSet>)>,
+    accumulated: u32,
+    // What the accumulated samples were traced with, any change starts over
+    traced: Option<([f32; 3], f32, f32, u32)>,
+    // Parameters exposed in the overlay
+    samples_per_frame: u32,
+    max_bounces: u32,
+    exposure: f32,
+}
+
+impl PathTracer {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        PathTracer {
+            camera: Camera::new(Vec3::new(0.0, 2.0, 7.0), Vec3::new(0.0, 1.0, 0.0)),
+            pipeline,
+            allocators: renderer.allocators.clone(),
+            image: None,
+            accumulated: 0,
+            traced: None,
+            samples_per_frame: 4,
+            max_bounces: 6,
+            exposure: 1.0,
+        }
+    }
+}
+
+impl App for PathTracer {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+        let dimensions = ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        };
+        let queue_family_index = Some(renderer.queue().queue_family_index());
+
+        let accumulation = StorageImage::new(
+            &self.allocators.memory,
+            dimensions,
+            Format::R32G32B32A32_SFLOAT,
+            queue_family_index,
+        )
+            .unwrap();
+        let display = StorageImage::new(
+            &self.allocators.memory,
+            dimensions,
+            Format::R8G8B8A8_UNORM,
+            queue_family_index,
+        )
+            .unwrap();
+
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, ImageView::new_default(accumulation).unwrap()),
+                WriteDescriptorSet::image_view(1, ImageView::new_default(display.clone()).unwrap()),
+            ],
+        )
+            .unwrap();
+
+        self.image = Some((display, set));
+        // The new accumulation image starts out empty
+        self.accumulated = 0;
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let (image, set) = self.image.clone().unwrap();
+        let [width, height] = renderer.swapchain.image_extent();
+
+        // The average only converges while nothing changes: moving the camera restarts it
+        let state = Some((
+            self.camera.position.to_array(),
+            self.camera.yaw,
+            self.camera.pitch,
+            self.max_bounces,
+        ));
+        if state != self.traced {
+            self.traced = state;
+            self.accumulated = 0;
+        }
+
+        let forward = self.camera.forward();
+        let right = self.camera.right();
+        let up = right.cross(forward);
+        let push_constants = cs::PushConstants {
+            position: self.camera.position.extend(0.0).to_array(),
+            forward: forward.extend((self.camera.fov_y / 2.0).tan()).to_array(),
+            right: right.extend(0.0).to_array(),
+            up: up.extend(0.0).to_array(),
+            accumulated: self.accumulated,
+            samples_per_frame: self.samples_per_frame,
+            max_bounces: self.max_bounces,
+            exposure: self.exposure,
+        };
+        self.accumulated += self.samples_per_frame;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap()
+            .blit_image(BlitImageInfo::images(
+                image,
+                renderer.images[image_index as usize].clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Path tracer").show(ctx, |ui| {
+            ui.label(format!("{} samples per pixel", self.accumulated));
+            ui.add(
+                egui::Slider::new(&mut self.samples_per_frame, 1..=32).text("samples per frame"),
+            );
+            ui.add(egui::Slider::new(&mut self.max_bounces, 1..=16).text("max bounces"));
+            // Exposure is applied when displaying, no need to start over
+            ui.add(
+                egui::Slider::new(&mut self.exposure, 0.1..=4.0)
+                    .logarithmic(true)
+                    .text("exposure"),
+            );
+            if ui.button("restart").clicked() {
+                self.accumulated = 0;
+            }
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-26",
+        DeviceExtensions::empty(),
+        PathTracer::new,
+    );
+}
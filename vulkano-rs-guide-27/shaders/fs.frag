@@ -0,0 +1,33 @@
+#version 460
+
+layout(location = 0) in vec3 v_world;
+layout(location = 1) in float v_level;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 camera_position;
+    float detail;
+    float max_level;
+    float height_scale;
+    uint show_levels;
+} pc;
+
+void main() {
+    // The normal of the triangle actually drawn: coarse patches look faceted
+    vec3 normal = normalize(cross(dFdx(v_world), dFdy(v_world)));
+    // Screen space Y points down, which flips the cross product
+    normal = -normal;
+    float light = max(dot(normal, normalize(vec3(0.4, 1.0, 0.3))), 0.0) * 0.8 + 0.2;
+
+    vec3 color = mix(vec3(0.25, 0.45, 0.2), vec3(0.6, 0.55, 0.45), smoothstep(0.5, 2.0, v_world.y));
+    if (pc.show_levels != 0) {
+        // Blue for a single quad, through green to red at the maximum level
+        float t = log2(v_level) / log2(max(pc.max_level, 2.0));
+        color = mix(vec3(0.1, 0.2, 1.0), vec3(0.1, 1.0, 0.2), clamp(t * 2.0, 0.0, 1.0));
+        color = mix(color, vec3(1.0, 0.2, 0.1), clamp(t * 2.0 - 1.0, 0.0, 1.0));
+    }
+
+    f_color = vec4(color * light, 1.0);
+}
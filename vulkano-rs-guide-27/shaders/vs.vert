@@ -0,0 +1,10 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+
+layout(location = 0) out vec2 v_position;
+
+// Nothing to transform yet, the vertices only become real after tessellation
+void main() {
+    v_position = position;
+}
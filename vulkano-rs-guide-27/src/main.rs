@@ -0,0 +1,333 @@
+//Tessellation: patches of terrain subdivided on the GPU, finer the closer they are to the camera
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::rasterization::{PolygonMode, RasterizationState};
+use vulkano::pipeline::graphics::tessellation::TessellationState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+// The terrain is PATCHES x PATCHES squares of PATCH_SIZE units, centered on the origin
+const PATCHES: u32 = 16;
+const PATCH_SIZE: f32 = 4.0;
+
+// Only the corners of each patch, the rest of the vertices are made by the tessellator
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct PatchVertex {
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+}
+
+// Four control points per patch, in the order the evaluation shader interpolates them
+fn terrain_patches() -> Vec<PatchVertex> {
+    let half = PATCHES as f32 * PATCH_SIZE / 2.0;
+    let mut vertices = Vec::new();
+    for z in 0..PATCHES {
+        for x in 0..PATCHES {
+            let x0 = x as f32 * PATCH_SIZE - half;
+            let z0 = z as f32 * PATCH_SIZE - half;
+            let (x1, z1) = (x0 + PATCH_SIZE, z0 + PATCH_SIZE);
+            for position in [[x0, z0], [x1, z0], [x1, z1], [x0, z1]] {
+                vertices.push(PatchVertex { position });
+            }
+        }
+    }
+    vertices
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod tcs {
+    vulkano_shaders::shader! {
+        ty: "tess_ctrl",
+        path: "shaders/tcs.tesc",
+    }
+}
+
+mod tes {
+    vulkano_shaders::shader! {
+        ty: "tess_eval",
+        path: "shaders/tes.tese",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Tessellation {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    // Filled and wireframe versions, otherwise identical. The polygon mode is baked into a
+    // pipeline unless it is dynamic state, which needs VK_EXT_extended_dynamic_state3 and
+    // vulkano doesn't support that yet. Switching between two pipelines costs nothing more
+    // than the bind
+    pipelines: [Arc<GraphicsPipeline>; 2],
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[PatchVertex]>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Holding a key sends repeated presses, only the first one toggles the wireframe
+    tab_held: bool,
+    // Parameters exposed in the overlay
+    wireframe: bool,
+    show_levels: bool,
+    detail: f32,
+    max_level: f32,
+    height_scale: f32,
+}
+
+impl Tessellation {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            terrain_patches(),
+        )
+            .expect("failed to create vertex buffer");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let tcs = tcs::load(device.clone()).expect("failed to create shader module");
+        let tes = tes::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let pipelines = [PolygonMode::Fill, PolygonMode::Line].map(|polygon_mode| {
+            GraphicsPipeline::start()
+                .vertex_input_state(PatchVertex::per_vertex())
+                .vertex_shader(vs.entry_point("main").unwrap(), ())
+                // Patches instead of triangles: every 4 vertices go to the control shader
+                .input_assembly_state(
+                    InputAssemblyState::new().topology(PrimitiveTopology::PatchList),
+                )
+                .tessellation_shaders(
+                    tcs.entry_point("main").unwrap(),
+                    (),
+                    tes.entry_point("main").unwrap(),
+                    (),
+                )
+                .tessellation_state(TessellationState::new().patch_control_points(4))
+                .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+                .fragment_shader(fs.entry_point("main").unwrap(), ())
+                .depth_stencil_state(DepthStencilState::simple_depth_test())
+                // Line mode is what needs the fill_mode_non_solid feature
+                .rasterization_state(RasterizationState::new().polygon_mode(polygon_mode))
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .expect("failed to create graphics pipeline")
+        });
+
+        Tessellation {
+            camera: Camera::new(Vec3::new(0.0, 8.0, 30.0), Vec3::ZERO),
+            render_pass,
+            pipelines,
+            allocators,
+            vertex_buffer,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            tab_held: false,
+            wireframe: true,
+            show_levels: false,
+            detail: 80.0,
+            max_level: 32.0,
+            height_scale: 2.0,
+        }
+    }
+}
+
+impl App for Tessellation {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        // The same block is declared in every stage that reads it
+        let push_constants = tcs::PushConstants {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+            camera_position: self.camera.position.extend(1.0).to_array(),
+            detail: self.detail,
+            max_level: self.max_level,
+            height_scale: self.height_scale,
+            show_levels: self.show_levels as u32,
+        };
+        let pipeline = self.pipelines[self.wireframe as usize].clone();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.55, 0.7, 0.85, 1.0].into()), Some(1.0.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(pipeline.clone())
+            .push_constants(pipeline.layout().clone(), 0, push_constants)
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .draw(self.vertex_buffer.len() as u32, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Tessellation").show(ctx, |ui| {
+            ui.checkbox(&mut self.wireframe, "wireframe (Tab)");
+            ui.checkbox(&mut self.show_levels, "color by level");
+            ui.add(
+                egui::Slider::new(&mut self.detail, 5.0..=400.0)
+                    .logarithmic(true)
+                    .text("detail"),
+            );
+            // 64 is the least every implementation supports
+            ui.add(egui::Slider::new(&mut self.max_level, 1.0..=64.0).text("max level"));
+            ui.add(egui::Slider::new(&mut self.height_scale, 0.0..=6.0).text("height"));
+        });
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state,
+                    virtual_keycode: Some(VirtualKeyCode::Tab),
+                    ..
+                },
+            ..
+        } = event
+        {
+            let pressed = *state == ElementState::Pressed;
+            if pressed && !self.tab_held {
+                self.wireframe = !self.wireframe;
+            }
+            self.tab_held = pressed;
+        }
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run_with_features(
+        "vulkano-rs-guide-27",
+        DeviceExtensions::empty(),
+        Features {
+            tessellation_shader: true,
+            fill_mode_non_solid: true,
+            ..Features::empty()
+        },
+        Tessellation::new,
+    );
+}
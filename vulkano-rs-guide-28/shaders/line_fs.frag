@@ -0,0 +1,9 @@
+#version 460
+
+layout(location = 0) in vec3 g_color;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    f_color = vec4(g_color, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 460
+
+layout(location = 0) in vec3 v_position;
+layout(location = 1) in vec3 v_normal;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    float diffuse = max(dot(normalize(v_normal), normalize(vec3(0.5, 1.0, 0.3))), 0.0);
+    f_color = vec4(vec3(0.6, 0.3, 0.35) * (diffuse * 0.8 + 0.2), 1.0);
+}
@@ -0,0 +1,20 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_position;
+layout(location = 1) out vec3 v_normal;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    float normal_length;
+    uint face_normals;
+    uint vertex_normals;
+} pc;
+
+void main() {
+    v_position = position;
+    v_normal = normal;
+    gl_Position = pc.view_projection * vec4(position, 1.0);
+}
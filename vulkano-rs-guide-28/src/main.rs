@@ -0,0 +1,336 @@
+//Geometry shaders: drawing the normals of a mesh as lines, generated on the GPU from its triangles
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// A coarse torus, so every face and its normal is easy to make out. It is built like the
+// sphere of chapter 8, only pushed away from the center by `major_radius`
+fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    rings: u32,
+    sides: u32,
+) -> (Vec<PosNormalUv>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    for i in 0..=sides {
+        let phi = 2.0 * PI * i as f32 / sides as f32;
+        for j in 0..=rings {
+            let theta = 2.0 * PI * j as f32 / rings as f32;
+            let normal = [phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin()];
+            let position = [
+                major_radius * theta.cos() + minor_radius * normal[0],
+                minor_radius * normal[1],
+                major_radius * theta.sin() + minor_radius * normal[2],
+            ];
+            vertices.push(PosNormalUv {
+                position,
+                normal,
+                ..Default::default()
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    for i in 0..sides {
+        for j in 0..rings {
+            let a = i * (rings + 1) + j;
+            let b = a + rings + 1;
+            // Counter-clockwise when seen from outside
+            indices.extend([a, b + 1, b, a, a + 1, b + 1]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+// Shared by both pipelines: the mesh uses gl_Position, the geometry shader ignores it and
+// works from the world space outputs instead
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod mesh_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/mesh_fs.frag",
+    }
+}
+
+mod gs {
+    vulkano_shaders::shader! {
+        ty: "geometry",
+        path: "shaders/gs.geom",
+    }
+}
+
+mod line_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/line_fs.frag",
+    }
+}
+
+struct GeometryShader {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    mesh_pipeline: Arc<GraphicsPipeline>,
+    normals_pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    face_normals: bool,
+    vertex_normals: bool,
+    normal_length: f32,
+}
+
+impl GeometryShader {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let (vertices, indices) = torus(1.0, 0.4, 24, 12);
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            vertices,
+        )
+            .expect("failed to create vertex buffer");
+        let index_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            indices,
+        )
+            .expect("failed to create index buffer");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let mesh_fs = mesh_fs::load(device.clone()).expect("failed to create shader module");
+        let gs = gs::load(device.clone()).expect("failed to create shader module");
+        let line_fs = line_fs::load(device.clone()).expect("failed to create shader module");
+
+        let mesh_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(mesh_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // Same vertices and indices, the triangles go through the geometry shader and come out
+        // as lines. Without the geometry_shader feature, building this pipeline fails
+        let normals_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .geometry_shader(gs.entry_point("main").unwrap(), ())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(line_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        GeometryShader {
+            camera: Camera::new(Vec3::new(0.0, 1.5, 3.5), Vec3::ZERO),
+            render_pass,
+            mesh_pipeline,
+            normals_pipeline,
+            allocators,
+            vertex_buffer,
+            index_buffer,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            face_normals: true,
+            vertex_normals: false,
+            normal_length: 0.15,
+        }
+    }
+}
+
+impl App for GeometryShader {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        // Both pipelines get the same block, a closure builds it twice
+        let push_constants = || vs::PushConstants {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+            normal_length: self.normal_length,
+            face_normals: self.face_normals as u32,
+            vertex_normals: self.vertex_normals as u32,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.05, 0.05, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .bind_index_buffer(self.index_buffer.clone())
+            .bind_pipeline_graphics(self.mesh_pipeline.clone())
+            .push_constants(self.mesh_pipeline.layout().clone(), 0, push_constants())
+            .draw_indexed(self.index_buffer.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+
+        if self.face_normals || self.vertex_normals {
+            builder
+                .bind_pipeline_graphics(self.normals_pipeline.clone())
+                .push_constants(self.normals_pipeline.layout().clone(), 0, push_constants())
+                .draw_indexed(self.index_buffer.len() as u32, 1, 0, 0, 0)
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Normals").show(ctx, |ui| {
+            ui.checkbox(&mut self.face_normals, "face normals");
+            ui.checkbox(&mut self.vertex_normals, "vertex normals");
+            ui.add(egui::Slider::new(&mut self.normal_length, 0.01..=0.5).text("length"));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    // Geometry shaders are an optional feature: it has to be supported by the device, then
+    // turned on in DeviceCreateInfo::enabled_features when the device is created (see
+    // VulkanContext::with_features). Extensions are enabled the same way, next to it
+    window::run_with_features(
+        "vulkano-rs-guide-28",
+        DeviceExtensions::empty(),
+        Features {
+            geometry_shader: true,
+            ..Features::empty()
+        },
+        GeometryShader::new,
+    );
+}
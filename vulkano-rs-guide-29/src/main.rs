@@ -0,0 +1,268 @@
+//A triangle drawn two ways: with render pass and framebuffer objects, and with dynamic rendering
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, RenderingAttachmentInfo,
+    RenderingInfo, SubpassContents,
+};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::render_pass::PipelineRenderingCreateInfo;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{
+    Framebuffer, FramebufferCreateInfo, LoadOp, RenderPass, StoreOp, Subpass,
+};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::requirements::DeviceRequirements;
+use vulkano_rs_common::types::PosColor;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Rendering {
+    RenderPass,
+    Dynamic,
+}
+
+const CLEAR_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 1.0];
+
+struct Triangle {
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[PosColor]>,
+    viewport: Viewport,
+    // The classic way: the attachments are described up front in a render pass, every
+    // swapchain image gets a framebuffer, and the pipeline is built for one subpass of it
+    render_pass: Arc<RenderPass>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    render_pass_pipeline: Arc<GraphicsPipeline>,
+    // With dynamic rendering the pipeline only needs the attachment formats, the image views
+    // are handed over when recording
+    dynamic_pipeline: Arc<GraphicsPipeline>,
+    // Parameters exposed in the overlay
+    rendering: Rendering,
+}
+
+impl Triangle {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [
+                PosColor {
+                    position: [0.0, -0.5],
+                    color: [1.0, 0.2, 0.2],
+                },
+                PosColor {
+                    position: [0.5, 0.5],
+                    color: [0.2, 1.0, 0.2],
+                },
+                PosColor {
+                    position: [-0.5, 0.5],
+                    color: [0.2, 0.2, 1.0],
+                },
+            ],
+        )
+            .expect("failed to create vertex buffer");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        // Everything but the last step is the same for both pipelines
+        let pipeline = || {
+            GraphicsPipeline::start()
+                .vertex_input_state(PosColor::per_vertex())
+                .vertex_shader(vs.entry_point("main").unwrap(), ())
+                .input_assembly_state(InputAssemblyState::new())
+                .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+                .fragment_shader(fs.entry_point("main").unwrap(), ())
+        };
+
+        let render_pass_pipeline = pipeline()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let dynamic_pipeline = pipeline()
+            .render_pass(PipelineRenderingCreateInfo {
+                color_attachment_formats: vec![Some(renderer.swapchain.image_format())],
+                ..Default::default()
+            })
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        Triangle {
+            allocators,
+            vertex_buffer,
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            render_pass,
+            framebuffers: Vec::new(),
+            render_pass_pipeline,
+            dynamic_pipeline,
+            rendering: Rendering::Dynamic,
+        }
+    }
+}
+
+impl App for Triangle {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        // Only the render pass path needs these, dynamic rendering has nothing to recreate
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        match self.rendering {
+            Rendering::RenderPass => {
+                builder
+                    .begin_render_pass(
+                        RenderPassBeginInfo {
+                            clear_values: vec![Some(CLEAR_COLOR.into())],
+                            ..RenderPassBeginInfo::framebuffer(
+                                self.framebuffers[image_index as usize].clone(),
+                            )
+                        },
+                        SubpassContents::Inline,
+                    )
+                    .unwrap()
+                    .set_viewport(0, [self.viewport.clone()])
+                    .bind_pipeline_graphics(self.render_pass_pipeline.clone())
+                    .bind_vertex_buffers(0, self.vertex_buffer.clone())
+                    .draw(self.vertex_buffer.len() as u32, 1, 0, 0)
+                    .unwrap()
+                    .end_render_pass()
+                    .unwrap();
+            }
+            Rendering::Dynamic => {
+                // What the render pass described ahead of time (load and store operations,
+                // clear value) is given here, next to the image view it applies to
+                builder
+                    .begin_rendering(RenderingInfo {
+                        color_attachments: vec![Some(RenderingAttachmentInfo {
+                            load_op: LoadOp::Clear,
+                            store_op: StoreOp::Store,
+                            clear_value: Some(CLEAR_COLOR.into()),
+                            ..RenderingAttachmentInfo::image_view(
+                                renderer.image_views[image_index as usize].clone(),
+                            )
+                        })],
+                        ..Default::default()
+                    })
+                    .unwrap()
+                    .set_viewport(0, [self.viewport.clone()])
+                    .bind_pipeline_graphics(self.dynamic_pipeline.clone())
+                    .bind_vertex_buffers(0, self.vertex_buffer.clone())
+                    .draw(self.vertex_buffer.len() as u32, 1, 0, 0)
+                    .unwrap()
+                    .end_rendering()
+                    .unwrap();
+            }
+        }
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Triangle").show(ctx, |ui| {
+            ui.radio_value(&mut self.rendering, Rendering::RenderPass, "render pass");
+            ui.radio_value(&mut self.rendering, Rendering::Dynamic, "dynamic rendering");
+        });
+    }
+}
+
+fn main() {
+    // Dynamic rendering is core in Vulkan 1.3, older drivers expose it through the extension.
+    // Either way the feature has to be turned on
+    let requirements = DeviceRequirements::new()
+        .extensions(DeviceExtensions {
+            khr_dynamic_rendering: true,
+            ..DeviceExtensions::empty()
+        })
+        .features(Features {
+            dynamic_rendering: true,
+            ..Features::empty()
+        });
+    window::run_with_requirements("vulkano-rs-guide-29", requirements, Triangle::new);
+}
@@ -13,8 +13,12 @@ use vulkano::instance::{Instance, InstanceCreateInfo};
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
 use vulkano::sync;
 use vulkano::sync::GpuFuture;
+use vulkano_rs_common::{logging, tracing};
 
 pub fn main() {
+    // Messages go through tracing, as in the rest of the chapters
+    logging::init();
+
     let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
     let instance =
         Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
@@ -110,5 +114,5 @@ pub fn main() {
     let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
     image.save("image.png").unwrap();
 
-    println!("Everything succeeded!");
+    tracing::info!("everything succeeded");
 }
@@ -0,0 +1,17 @@
+#version 460
+#extension GL_EXT_nonuniform_qualifier : require
+
+layout(location = 0) in vec2 v_tex_coord;
+layout(location = 1) flat in uint v_texture_index;
+
+layout(location = 0) out vec4 f_color;
+
+// No size: the number of textures is only decided when the descriptor set is
+// allocated
+layout(set = 0, binding = 0) uniform sampler2D textures[];
+
+void main() {
+    // nonuniformEXT tells the compiler the index may differ between invocations,
+    // which is always safe when the index comes from an input
+    f_color = texture(textures[nonuniformEXT(v_texture_index)], v_tex_coord);
+}
@@ -0,0 +1,19 @@
+#version 460
+
+layout(location = 0) out vec2 v_tex_coord;
+layout(location = 1) flat out uint v_texture_index;
+
+// Changed before every draw, this is all that differs between two quads
+layout(push_constant) uniform PushConstants {
+    vec2 offset;
+    float scale;
+    uint texture_index;
+} pc;
+
+// A quad as a 4 vertex triangle strip, no vertex buffer needed
+void main() {
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+    v_tex_coord = corner;
+    v_texture_index = pc.texture_index;
+    gl_Position = vec4(pc.offset + corner * pc.scale, 0.0, 1.0);
+}
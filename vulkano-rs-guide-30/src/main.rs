@@ -0,0 +1,380 @@
+//Bindless textures: one descriptor set holding every texture, each draw picks its own by index
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::layout::{DescriptorSetLayout, DescriptorSetLayoutCreateInfo};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::{ImageView, ImageViewAbstract};
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::memory::allocator::MemoryAllocator;
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::VertexInputState;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::layout::{PipelineLayout, PipelineLayoutCreateInfo};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::{args, egui, tracing};
+
+const TEXTURE_SIZE: u32 = 64;
+
+// A color from a hue in 0..1, at full saturation and value
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let channel = |offset: f32| {
+        let k = (hue * 6.0 + offset) % 6.0;
+        1.0 - (k.min(4.0 - k).clamp(0.0, 1.0))
+    };
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
+
+// Every texture gets its own hue, one of four patterns and a frequency, so that any two of them
+// are easy to tell apart on screen
+fn pattern_texture(index: u32) -> Vec<u8> {
+    // Stepping by the golden ratio spreads consecutive hues far apart
+    let [r, g, b] = hue_to_rgb((index as f32 * 0.618_034) % 1.0);
+    let frequency = 2 + (index / 4) % 6;
+
+    let mut pixels = Vec::with_capacity((TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize);
+    for y in 0..TEXTURE_SIZE {
+        for x in 0..TEXTURE_SIZE {
+            let u = x as f32 / TEXTURE_SIZE as f32 * frequency as f32;
+            let v = y as f32 / TEXTURE_SIZE as f32 * frequency as f32;
+            let lit = match index % 4 {
+                0 => (u as u32 + v as u32) % 2 == 0,
+                1 => (u + v) as u32 % 2 == 0,
+                2 => {
+                    let (du, dv) = (u.fract() - 0.5, v.fract() - 0.5);
+                    du * du + dv * dv < 0.1
+                }
+                _ => {
+                    let center = frequency as f32 / 2.0;
+                    ((u - center).hypot(v - center) * 2.0) as u32 % 2 == 0
+                }
+            };
+            let shade = if lit { 1.0 } else { 0.2 };
+            pixels.extend([
+                (r * shade * 255.0) as u8,
+                (g * shade * 255.0) as u8,
+                (b * shade * 255.0) as u8,
+                255,
+            ]);
+        }
+    }
+
+    pixels
+}
+
+fn upload_textures(
+    memory_allocator: &(impl MemoryAllocator + ?Sized),
+    uploads: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    count: u32,
+) -> Vec<Arc<ImageView<ImmutableImage>>> {
+    (0..count)
+        .map(|index| {
+            let image = ImmutableImage::from_iter(
+                memory_allocator,
+                pattern_texture(index),
+                ImageDimensions::Dim2d {
+                    width: TEXTURE_SIZE,
+                    height: TEXTURE_SIZE,
+                    array_layers: 1,
+                },
+                MipmapsCount::One,
+                Format::R8G8B8A8_SRGB,
+                uploads,
+            )
+                .expect("failed to create texture");
+            ImageView::new_default(image).unwrap()
+        })
+        .collect()
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Bindless {
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    // Bound once per frame, however many different textures are drawn
+    set: Arc<PersistentDescriptorSet>,
+    texture_count: u32,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    start: Instant,
+    // Parameters exposed in the overlay
+    grid: u32,
+    scroll: bool,
+}
+
+impl Bindless {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        // Without update-after-bind, every texture counts against the per-stage limits
+        let properties = device.physical_device().properties();
+        let limit = properties
+            .max_per_stage_descriptor_samplers
+            .min(properties.max_per_stage_descriptor_sampled_images)
+            .min(properties.max_descriptor_set_sampled_images);
+        let requested = args::value::<u32>("--textures").unwrap_or(256).max(1);
+        let texture_count = requested.min(limit);
+        if texture_count < requested {
+            tracing::warn!("this device allows at most {limit} textures per stage");
+        }
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let textures = upload_textures(&allocators.memory, &mut uploads, texture_count);
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::simple_repeat_linear())
+            .expect("failed to create sampler");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        // The layout can't be derived from the shaders alone this time: the texture array has
+        // to be marked as variable-count, with the most it will ever hold as its size
+        let layout = {
+            let mut set_layout_infos = DescriptorSetLayoutCreateInfo::from_requirements(
+                fs.entry_point("main")
+                    .unwrap()
+                    .descriptor_binding_requirements(),
+            );
+            let binding = set_layout_infos[0].bindings.get_mut(&0).unwrap();
+            binding.variable_descriptor_count = true;
+            binding.descriptor_count = texture_count;
+
+            let set_layouts = set_layout_infos
+                .into_iter()
+                .map(|info| DescriptorSetLayout::new(device.clone(), info))
+                .collect::<Result<Vec<_>, _>>()
+                .expect("failed to create descriptor set layout");
+
+            PipelineLayout::new(
+                device.clone(),
+                PipelineLayoutCreateInfo {
+                    set_layouts,
+                    push_constant_ranges: vs
+                        .entry_point("main")
+                        .unwrap()
+                        .push_constant_requirements()
+                        .cloned()
+                        .into_iter()
+                        .collect(),
+                    ..Default::default()
+                },
+            )
+                .expect("failed to create pipeline layout")
+        };
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(
+                InputAssemblyState::new().topology(PrimitiveTopology::TriangleStrip),
+            )
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .with_pipeline_layout(device.clone(), layout)
+            .expect("failed to create graphics pipeline");
+
+        // The actual number of descriptors is given when allocating the set
+        let set = PersistentDescriptorSet::new_variable(
+            &allocators.descriptor,
+            pipeline.layout().set_layouts()[0].clone(),
+            texture_count,
+            [WriteDescriptorSet::image_view_sampler_array(
+                0,
+                0,
+                textures
+                    .into_iter()
+                    .map(|view| (view as Arc<dyn ImageViewAbstract>, sampler.clone())),
+            )],
+        )
+            .unwrap();
+
+        Bindless {
+            render_pass,
+            pipeline,
+            allocators,
+            set,
+            texture_count,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            start: Instant::now(),
+            grid: 16,
+            scroll: true,
+        }
+    }
+}
+
+impl App for Bindless {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // Shift every quad to the next texture a few times per second
+        let shift = if self.scroll {
+            (self.start.elapsed().as_secs_f32() * 4.0) as u32
+        } else {
+            0
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.05, 0.05, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            );
+
+        // One draw per quad, and no descriptor set changes in between: only the index does
+        let cell = 2.0 / self.grid as f32;
+        for y in 0..self.grid {
+            for x in 0..self.grid {
+                let push_constants = vs::PushConstants {
+                    offset: [x as f32 * cell - 1.0, y as f32 * cell - 1.0],
+                    scale: cell * 0.9,
+                    texture_index: (y * self.grid + x + shift) % self.texture_count,
+                };
+                builder
+                    .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+                    .draw(4, 1, 0, 0)
+                    .unwrap();
+            }
+        }
+
+        builder.end_render_pass().unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Bindless").show(ctx, |ui| {
+            ui.label(format!(
+                "{} textures in one descriptor set",
+                self.texture_count
+            ));
+            ui.add(egui::Slider::new(&mut self.grid, 1..=32).text("grid"));
+            ui.checkbox(&mut self.scroll, "scroll");
+        });
+    }
+}
+
+fn main() {
+    // Descriptor indexing is core in Vulkan 1.2, but each of its parts is a separate feature
+    window::run_with_features(
+        "vulkano-rs-guide-30",
+        DeviceExtensions::empty(),
+        Features {
+            // Arrays without a size in the shader
+            runtime_descriptor_array: true,
+            // Choosing that size when allocating the set
+            descriptor_binding_variable_descriptor_count: true,
+            // Indexing them with nonuniformEXT
+            shader_sampled_image_array_non_uniform_indexing: true,
+            ..Features::empty()
+        },
+        Bindless::new,
+    );
+}
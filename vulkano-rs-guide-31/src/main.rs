@@ -0,0 +1,184 @@
+//Buffer device addresses: linked lists on the GPU, followed through raw pointers in the shader
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::Pipeline;
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::args;
+use vulkano_rs_common::compute::ComputeContext;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+const WORK_GROUP_SIZE: u32 = 64;
+const MAX_LIST_LENGTH: u32 = 64;
+
+// Has to match the shader's Node, including its 16 byte alignment
+#[derive(BufferContents, Clone, Copy, Default)]
+#[repr(C)]
+struct ListNode {
+    // Address of the next node, 0 at the end of the list
+    next: [u32; 2],
+    value: u32,
+    _padding: u32,
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+// The shader takes addresses as two 32 bit halves, low first
+fn split_address(address: u64) -> [u32; 2] {
+    [address as u32, (address >> 32) as u32]
+}
+
+// Host visible to keep the example short, the GPU reads and writes it right in system memory
+fn address_buffer<T: BufferContents>(
+    memory_allocator: &(impl MemoryAllocator + ?Sized),
+    length: u32,
+    usage: MemoryUsage,
+) -> Subbuffer<[T]> {
+    Buffer::new_slice::<T>(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::SHADER_DEVICE_ADDRESS,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage,
+            ..Default::default()
+        },
+        length as u64,
+    )
+        .expect("failed to create buffer")
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    // Without this feature buffers have no address to give out
+    let context = VulkanContext::with_features(
+        instance,
+        DeviceExtensions::empty(),
+        Features {
+            buffer_device_address: true,
+            ..Features::empty()
+        },
+        None,
+    );
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    // Memory for buffers with SHADER_DEVICE_ADDRESS usage has to be allocated with the
+    // matching flag, the standard allocator does that once the feature is enabled
+    let compute = ComputeContext::new(&context);
+    let allocators = &compute.allocators;
+
+    let list_count = args::value::<u32>("--lists").unwrap_or(4096).max(1);
+    let mut random = Random(0x2545_f491);
+    let lengths: Vec<u32> = (0..list_count)
+        .map(|_| 1 + random.next() % MAX_LIST_LENGTH)
+        .collect();
+    let node_count: u32 = lengths.iter().sum();
+
+    let nodes = address_buffer::<ListNode>(&allocators.memory, node_count, MemoryUsage::Upload);
+    let heads = address_buffer::<[u32; 2]>(&allocators.memory, list_count, MemoryUsage::Upload);
+    let sums = address_buffer::<u32>(&allocators.memory, list_count, MemoryUsage::Download);
+
+    let nodes_address = nodes.device_address().unwrap().get();
+    let node_address =
+        |slot: u32| nodes_address + slot as u64 * std::mem::size_of::<ListNode>() as u64;
+
+    // Shuffle the node slots, so that following a list jumps all over the buffer
+    let mut slots: Vec<u32> = (0..node_count).collect();
+    for i in (1..slots.len()).rev() {
+        slots.swap(i, random.next() as usize % (i + 1));
+    }
+
+    let mut expected = Vec::with_capacity(list_count as usize);
+    {
+        let mut nodes = nodes.write().unwrap();
+        let mut heads = heads.write().unwrap();
+        let mut cursor = 0;
+        for (list, &length) in lengths.iter().enumerate() {
+            let list_slots = &slots[cursor..cursor + length as usize];
+            cursor += length as usize;
+
+            let mut sum = 0u32;
+            for (i, &slot) in list_slots.iter().enumerate() {
+                let value = random.next() % 1000;
+                sum += value;
+                nodes[slot as usize] = ListNode {
+                    next: list_slots
+                        .get(i + 1)
+                        .map_or([0, 0], |&next| split_address(node_address(next))),
+                    value,
+                    ..Default::default()
+                };
+            }
+            heads[list] = split_address(node_address(list_slots[0]));
+            expected.push(sum);
+        }
+    }
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    // Every buffer is reached through an address, so there is no descriptor set to bind
+    let pipeline = compute.bind(&mut builder, &shader, "main", &[]);
+    builder
+        .push_constants(
+            pipeline.layout().clone(),
+            0,
+            cs::PushConstants {
+                heads: split_address(heads.device_address().unwrap().get()),
+                sums: split_address(sums.device_address().unwrap().get()),
+                list_count,
+            },
+        )
+        .dispatch([(list_count + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE, 1, 1])
+        .unwrap();
+
+    // vulkano only sees a few numbers in the push constants: it can't know which buffers the
+    // shader touches, so it adds no barriers for them and doesn't keep them alive. Waiting for
+    // the fence, while they are still in scope, covers both here
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let result = sums.read().unwrap();
+    if let Some(i) = (0..list_count as usize).find(|&i| result[i] != expected[i]) {
+        panic!(
+            "mismatch in list {i}: gpu {}, cpu {}",
+            result[i], expected[i]
+        );
+    }
+
+    println!("Summed {list_count} linked lists, {node_count} nodes shuffled through one buffer");
+    tracing::info!("everything succeeded");
+}
@@ -0,0 +1,125 @@
+//The compute chapter again, with push descriptors instead of allocated descriptor sets
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::WriteDescriptorSet;
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+const LENGTH: u32 = 65536;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(
+        instance,
+        DeviceExtensions {
+            khr_push_descriptor: true,
+            ..DeviceExtensions::empty()
+        },
+        None,
+    );
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    // Instead of one buffer multiplied by 12, several buffers each multiplied by their own
+    // factor: one dispatch per buffer, each with different bindings
+    let factors = [2u32, 3, 5, 7, 11, 12, 13, 17];
+    let buffers: Vec<Subbuffer<[u32]>> = factors
+        .iter()
+        .map(|_| {
+            Buffer::from_iter(
+                &allocators.memory,
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::Upload,
+                    ..Default::default()
+                },
+                0..LENGTH,
+            )
+                .expect("failed to create buffer")
+        })
+        .collect();
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+    // The last argument can change the set layouts before they are created. Marking set 0 as a
+    // push descriptor set means no descriptor set will ever be allocated from it, its bindings
+    // are recorded straight into the command buffer instead
+    let compute_pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |layout_create_infos| {
+            layout_create_infos[0].push_descriptor = true;
+        },
+    )
+        .expect("failed to create compute pipeline");
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder.bind_pipeline_compute(compute_pipeline.clone());
+
+    // With persistent sets this loop would need a descriptor set allocator and one set per
+    // buffer, created up front and kept alive until the GPU is done. Here the writes are
+    // given right where they are used, and vulkano keeps the buffers alive with the command
+    // buffer. Sets that are built once and reused every frame are still better off allocated
+    for (buffer, &factor) in buffers.iter().zip(&factors) {
+        builder
+            .push_descriptor_set(
+                PipelineBindPoint::Compute,
+                compute_pipeline.layout().clone(),
+                0,
+                [WriteDescriptorSet::buffer(0, buffer.clone())],
+            )
+            .push_constants(
+                compute_pipeline.layout().clone(),
+                0,
+                cs::PushConstants { factor },
+            )
+            .dispatch([LENGTH / 64, 1, 1])
+            .unwrap();
+    }
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    for (buffer, &factor) in buffers.iter().zip(&factors) {
+        let content = buffer.read().unwrap();
+        for (n, val) in content.iter().enumerate() {
+            assert_eq!(*val, n as u32 * factor);
+        }
+    }
+
+    tracing::info!("everything succeeded");
+}
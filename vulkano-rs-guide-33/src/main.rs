@@ -0,0 +1,445 @@
+//Occlusion culling: bounding boxes are tested against the depth buffer, hidden meshes are skipped
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::{ColorBlendState, ColorComponents};
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthState, DepthStencilState};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, StateMode};
+use vulkano::query::{
+    QueryControlFlags, QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType,
+};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::{cube, sphere};
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// The spheres hidden behind the wall, GRID_X by GRID_Z of them
+const GRID_X: u32 = 8;
+const GRID_Z: u32 = 6;
+const SPHERE_RADIUS: f32 = 0.6;
+// Far more triangles than a sphere this size needs, to make skipping it worth it
+const SPHERE_STACKS: u32 = 192;
+const SPHERE_SECTORS: u32 = 384;
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Object {
+    center: Vec3,
+    color: [f32; 4],
+}
+
+// Boxes that hide the spheres: the ground and a wall
+struct Occluder {
+    center: Vec3,
+    half_size: Vec3,
+}
+
+struct OcclusionCulling {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    scene_pipeline: Arc<GraphicsPipeline>,
+    // Tests depth but writes nothing, only the query sees what it draws
+    query_pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    cube: Mesh,
+    sphere: Mesh,
+    objects: Vec<Object>,
+    occluders: Vec<Occluder>,
+    // One query per object for every swapchain image, read back when that image comes around
+    // again
+    query_pool: Arc<QueryPool>,
+    written: Vec<bool>,
+    visible: Vec<bool>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    culling: bool,
+}
+
+impl OcclusionCulling {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let cube = Mesh::new(&allocators.memory, cube());
+        let sphere = Mesh::new(&allocators.memory, sphere(SPHERE_STACKS, SPHERE_SECTORS));
+
+        let objects: Vec<Object> = (0..GRID_Z)
+            .flat_map(|z| (0..GRID_X).map(move |x| (x, z)))
+            .map(|(x, z)| Object {
+                center: Vec3::new(
+                    (x as f32 - (GRID_X - 1) as f32 / 2.0) * 2.5,
+                    SPHERE_RADIUS,
+                    -4.0 - z as f32 * 2.5,
+                ),
+                color: [
+                    0.3 + 0.7 * x as f32 / GRID_X as f32,
+                    0.4,
+                    0.3 + 0.7 * z as f32 / GRID_Z as f32,
+                    1.0,
+                ],
+            })
+            .collect();
+        let occluders = vec![
+            Occluder {
+                center: Vec3::new(0.0, -0.5, -8.0),
+                half_size: Vec3::new(30.0, 0.5, 30.0),
+            },
+            Occluder {
+                center: Vec3::new(0.0, 2.5, -2.0),
+                half_size: Vec3::new(8.0, 2.5, 0.25),
+            },
+        ];
+
+        let slots = renderer.images.len() as u32;
+        let query_pool = QueryPool::new(
+            device.clone(),
+            QueryPoolCreateInfo {
+                query_count: objects.len() as u32 * slots,
+                ..QueryPoolCreateInfo::query_type(QueryType::Occlusion)
+            },
+        )
+            .expect("failed to create query pool");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let scene_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // A box must not hide anything itself, so it leaves both the color and the depth
+        // buffer alone. Back faces are kept, in case the camera is inside the box
+        let query_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState {
+                depth: Some(DepthState {
+                    enable_dynamic: false,
+                    write_enable: StateMode::Fixed(false),
+                    compare_op: StateMode::Fixed(CompareOp::Less),
+                }),
+                ..DepthStencilState::disabled()
+            })
+            .color_blend_state(ColorBlendState::new(1).color_write_mask(ColorComponents::empty()))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        OcclusionCulling {
+            camera: Camera::new(Vec3::new(0.0, 2.0, 8.0), Vec3::new(0.0, 1.0, 0.0)),
+            render_pass,
+            scene_pipeline,
+            query_pipeline,
+            allocators,
+            cube,
+            sphere,
+            visible: vec![true; objects.len()],
+            objects,
+            occluders,
+            query_pool,
+            written: vec![false; slots as usize],
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            culling: true,
+        }
+    }
+
+    // Updates `visible` from the queries this slot recorded last time
+    fn read_queries(&mut self, slot: u32) {
+        if !self.written[slot as usize] {
+            return;
+        }
+
+        let count = self.objects.len() as u32;
+        // With availability, every query gives two values: the sample count, then whether the
+        // count is ready yet
+        let mut results = vec![0u64; count as usize * 2];
+        self.query_pool
+            .queries_range(slot * count..(slot + 1) * count)
+            .unwrap()
+            .get_results(&mut results, QueryResultFlags::WITH_AVAILABILITY)
+            .unwrap();
+
+        // A result that isn't ready keeps the previous answer rather than waiting for the GPU
+        for (visible, result) in self.visible.iter_mut().zip(results.chunks(2)) {
+            if result[1] != 0 {
+                *visible = result[0] > 0;
+            }
+        }
+    }
+}
+
+impl App for OcclusionCulling {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // The answers are a few frames old: objects that just came into view can pop in late
+        self.read_queries(image_index);
+
+        let [width, height] = self.viewport.dimensions;
+        let view_projection = self
+            .camera
+            .view_projection(width / height)
+            .to_cols_array_2d();
+        let push_constants = |center: Vec3, scale: Vec3, color: [f32; 4]| vs::PushConstants {
+            view_projection,
+            center: center.extend(0.0).to_array(),
+            scale: scale.extend(0.0).to_array(),
+            color,
+        };
+
+        let count = self.objects.len() as u32;
+        let first_query = image_index * count;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // Queries have to be reset before they are used again, and outside of a render pass
+        unsafe {
+            builder
+                .reset_query_pool(self.query_pool.clone(), first_query..first_query + count)
+                .unwrap();
+        }
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.55, 0.7, 0.85, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.scene_pipeline.clone());
+
+        // The occluders go first, so that the depth buffer is ready for the tests
+        for occluder in &self.occluders {
+            builder.push_constants(
+                self.scene_pipeline.layout().clone(),
+                0,
+                push_constants(occluder.center, occluder.half_size, [0.6, 0.6, 0.6, 1.0]),
+            );
+            self.cube.draw(&mut builder);
+        }
+
+        // Each query counts the samples of one bounding box that pass the depth test. 12
+        // triangles stand in for the hundreds of thousands of the sphere
+        builder.bind_pipeline_graphics(self.query_pipeline.clone());
+        for (i, object) in self.objects.iter().enumerate() {
+            let query = first_query + i as u32;
+            builder.push_constants(
+                self.query_pipeline.layout().clone(),
+                0,
+                push_constants(object.center, Vec3::splat(SPHERE_RADIUS), object.color),
+            );
+            unsafe {
+                builder
+                    .begin_query(self.query_pool.clone(), query, QueryControlFlags::empty())
+                    .unwrap();
+                self.cube.draw(&mut builder);
+                builder.end_query(self.query_pool.clone(), query).unwrap();
+            }
+        }
+        self.written[image_index as usize] = true;
+
+        builder.bind_pipeline_graphics(self.scene_pipeline.clone());
+        for (object, &visible) in self.objects.iter().zip(&self.visible) {
+            if self.culling && !visible {
+                continue;
+            }
+            builder.push_constants(
+                self.scene_pipeline.layout().clone(),
+                0,
+                push_constants(object.center, Vec3::splat(SPHERE_RADIUS), object.color),
+            );
+            self.sphere.draw(&mut builder);
+        }
+
+        builder.end_render_pass().unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        let visible = self.visible.iter().filter(|&&visible| visible).count();
+        let drawn = if self.culling {
+            visible
+        } else {
+            self.objects.len()
+        };
+        let triangles = drawn as u64 * (SPHERE_STACKS * SPHERE_SECTORS * 2) as u64;
+
+        egui::Window::new("Occlusion").show(ctx, |ui| {
+            ui.checkbox(&mut self.culling, "occlusion culling");
+            ui.label(format!("visible: {visible} of {}", self.objects.len()));
+            ui.label(format!("spheres drawn: {drawn}"));
+            ui.label(format!("sphere triangles: {:.1} M", triangles as f64 / 1e6));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-33",
+        DeviceExtensions::empty(),
+        OcclusionCulling::new,
+    );
+}
@@ -0,0 +1,344 @@
+//Subgroup operations: the reduction of chapter 20 again, with whole subgroups adding at once
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::SubgroupFeatures;
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::shader::ShaderStages;
+use vulkano::sync::{self, GpuFuture, PipelineStage};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::MemoryReport;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// Both versions fold two elements per invocation while loading, and have to match the shaders
+const WORK_GROUP_SIZE: u32 = 256;
+const BLOCK_SIZE: u32 = WORK_GROUP_SIZE * 2;
+// Each version runs this many times, the fastest run is reported
+const RUNS: u32 = 5;
+
+// What the first pass computes, the passes after it add up its partial results
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    Sum,
+    // How many values are at least the threshold
+    CountAbove,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kernel {
+    // The tree of chapter 20, through shared memory with a barrier per level
+    SharedMemory,
+    // Subgroup instructions, shared memory only to combine the subgroups of a work group
+    Subgroups,
+}
+
+mod shared_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/shared_cs.comp",
+    }
+}
+
+mod subgroup_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        // Subgroup operations came with Vulkan 1.1
+        vulkan_version: "1.1",
+        path: "shaders/subgroup_cs.comp",
+    }
+}
+
+// A small xorshift generator for the input values
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    // Subgroups are as wide as the hardware runs invocations together: 32 on most desktop GPUs,
+    // 64 on older AMD ones, 4 to 128 elsewhere
+    let properties = device.physical_device().properties();
+    let subgroups_supported = properties
+        .subgroup_supported_stages
+        .map_or(false, |stages| stages.intersects(ShaderStages::COMPUTE))
+        && properties
+            .subgroup_supported_operations
+            .map_or(false, |operations| {
+                operations.contains(SubgroupFeatures::ARITHMETIC | SubgroupFeatures::BALLOT)
+            });
+    match properties.subgroup_size {
+        Some(size) => println!("Subgroup size: {size}"),
+        None => println!("Subgroup size: unknown, the device predates Vulkan 1.1"),
+    }
+    if !subgroups_supported {
+        tracing::warn!("no arithmetic and ballot subgroup operations in compute shaders, only the shared memory version runs");
+    }
+
+    let allocators = Allocators::new(&device);
+
+    // --memory-report prints the heaps around the allocations, like chapter 19
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    let count = args::value::<u32>("--count").unwrap_or(1 << 24).max(1);
+    // A quarter of the values are at or above the threshold
+    let threshold = 768;
+    let mut random = Random(0x68e3_1da4);
+    let input: Vec<u32> = (0..count).map(|_| random.next() % 1024).collect();
+
+    let upload_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        input.iter().copied(),
+    )
+        .expect("failed to create buffer");
+    let download_buffer = Buffer::from_data(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        0u32,
+    )
+        .expect("failed to create buffer");
+
+    // levels[0] is the input, every pass writes one partial result per block to the next level
+    // until a single value is left
+    let storage_buffer = |length: u64| {
+        Buffer::new_slice::<u32>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER
+                    | BufferUsage::TRANSFER_SRC
+                    | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            length,
+        )
+            .expect("failed to create buffer")
+    };
+    let mut levels: Vec<Subbuffer<[u32]>> = vec![storage_buffer(count as u64)];
+    let mut length = count as u64;
+    while length > 1 || levels.len() == 1 {
+        length = (length + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
+        levels.push(storage_buffer(length));
+    }
+
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("after the buffers");
+    }
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(upload_buffer, levels[0].clone()))
+        .unwrap();
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let mut kernels = vec![(
+        Kernel::SharedMemory,
+        shared_cs::load(device.clone()).expect("failed to create shader module"),
+    )];
+    if subgroups_supported {
+        kernels.push((
+            Kernel::Subgroups,
+            subgroup_cs::load(device.clone()).expect("failed to create shader module"),
+        ));
+    }
+
+    let supports_timestamps = device.physical_device().queue_family_properties()
+        [queue.queue_family_index() as usize]
+        .timestamp_valid_bits
+        .is_some();
+    let timestamp_period = properties.timestamp_period as f64;
+    let query_pool = QueryPool::new(
+        device.clone(),
+        QueryPoolCreateInfo {
+            query_count: 2,
+            ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+        },
+    )
+        .expect("failed to create query pool");
+
+    // Runs every pass of one reduction, returns the result and the GPU time in milliseconds
+    let run =
+        |pipeline: &Arc<ComputePipeline>, sets: &[Arc<PersistentDescriptorSet>], mode: Mode| {
+            let mut builder = AutoCommandBufferBuilder::primary(
+                &*allocators.command,
+                queue.queue_family_index(),
+                CommandBufferUsage::OneTimeSubmit,
+            )
+                .unwrap();
+            builder.bind_pipeline_compute(pipeline.clone());
+            if supports_timestamps {
+                unsafe {
+                    builder
+                        .reset_query_pool(query_pool.clone(), 0..2)
+                        .unwrap()
+                        .write_timestamp(query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                        .unwrap();
+                }
+            }
+
+            for (level, set) in sets.iter().enumerate() {
+                let length = levels[level].len() as u32;
+                let mode = if level == 0 { mode } else { Mode::Sum };
+                builder
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        pipeline.layout().clone(),
+                        0,
+                        set.clone(),
+                    )
+                    .push_constants(
+                        pipeline.layout().clone(),
+                        0,
+                        shared_cs::PushConstants {
+                            mode: mode as u32,
+                            count: length,
+                            threshold,
+                        },
+                    )
+                    .dispatch([(length + BLOCK_SIZE - 1) / BLOCK_SIZE, 1, 1])
+                    .unwrap();
+            }
+
+            if supports_timestamps {
+                unsafe {
+                    builder
+                        .write_timestamp(query_pool.clone(), 1, PipelineStage::ComputeShader)
+                        .unwrap();
+                }
+            }
+            builder
+                .copy_buffer(CopyBufferInfo::buffers(
+                    levels.last().unwrap().clone(),
+                    download_buffer.clone(),
+                ))
+                .unwrap();
+
+            let future = sync::now(device.clone())
+                .then_execute(queue.clone(), builder.build().unwrap())
+                .unwrap()
+                .then_signal_fence_and_flush()
+                .unwrap();
+            wait::fence(&future);
+
+            let millis = supports_timestamps.then(|| {
+                let mut timestamps = [0u64; 2];
+                query_pool
+                    .queries_range(0..2)
+                    .unwrap()
+                    .get_results(&mut timestamps, QueryResultFlags::WAIT)
+                    .unwrap();
+                (timestamps[1] - timestamps[0]) as f64 * timestamp_period / 1e6
+            });
+            let result = *download_buffer.read().unwrap();
+            (result, millis)
+        };
+
+    // Wraps around like the GPU does, past 4 million values or so
+    let expected_sum = input
+        .iter()
+        .fold(0u32, |sum, &value| sum.wrapping_add(value));
+    let expected_count = input.iter().filter(|&&value| value >= threshold).count() as u32;
+
+    println!("Reducing {count} values");
+    for (kernel, shader) in kernels {
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+        let sets: Vec<Arc<PersistentDescriptorSet>> = levels
+            .windows(2)
+            .map(|pair| {
+                PersistentDescriptorSet::new(
+                    &allocators.descriptor,
+                    pipeline.layout().set_layouts().get(0).unwrap().clone(),
+                    [
+                        WriteDescriptorSet::buffer(0, pair[0].clone()),
+                        WriteDescriptorSet::buffer(1, pair[1].clone()),
+                    ],
+                )
+                    .unwrap()
+            })
+            .collect();
+
+        println!("{kernel:?}:");
+        for (mode, expected) in [
+            (Mode::Sum, expected_sum),
+            (Mode::CountAbove, expected_count),
+        ] {
+            let mut fastest: Option<f64> = None;
+            for _ in 0..RUNS {
+                let (result, millis) = run(&pipeline, &sets, mode);
+                assert_eq!(
+                    result, expected,
+                    "{kernel:?} {mode:?} doesn't match the CPU"
+                );
+                if let Some(millis) = millis {
+                    fastest = Some(fastest.map_or(millis, |fastest| fastest.min(millis)));
+                }
+            }
+            match fastest {
+                Some(millis) => println!("  {mode:?}: {expected} in {millis:.3} ms"),
+                None => println!("  {mode:?}: {expected}, timestamps unsupported"),
+            }
+        }
+    }
+
+    tracing::info!("everything succeeded");
+}
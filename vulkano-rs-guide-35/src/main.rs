@@ -0,0 +1,322 @@
+//16-bit floats and 8-bit integers in storage buffers: less memory traffic, at some cost in accuracy
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::device::Features;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::Pipeline;
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::{self, GpuFuture, PipelineStage};
+use vulkano_rs_common::args;
+use vulkano_rs_common::compute::ComputeContext;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::Resource;
+use vulkano_rs_common::memory::MemoryReport;
+use vulkano_rs_common::requirements::DeviceRequirements;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// Has to match local_size_x in the shader
+const WORK_GROUP_SIZE: u32 = 256;
+
+// How x, y and z are stored while the kernel runs
+#[derive(Clone, Copy, Debug)]
+enum Storage {
+    F32,
+    F16,
+    // Fixed point, -127..127 standing for -1..1
+    Int8,
+}
+
+impl Storage {
+    fn bytes(self) -> u64 {
+        match self {
+            Storage::F32 => 4,
+            Storage::F16 => 2,
+            Storage::Int8 => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    // Converts the f32 inputs to the storage type
+    Pack,
+    // The timed kernel, reading and writing only the storage type
+    Run,
+    // Converts the result back to f32 so it can be compared
+    Unpack,
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        vulkan_version: "1.2",
+        path: "shaders/cs.comp",
+    }
+}
+
+// A small xorshift generator for the input values
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    // Uniform in -1..1
+    fn next_signed(&mut self) -> f32 {
+        self.next() as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+fn device_buffer<T: BufferContents>(
+    memory_allocator: &(impl MemoryAllocator + ?Sized),
+    length: u32,
+) -> Subbuffer<[T]> {
+    Buffer::new_slice::<T>(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER
+                | BufferUsage::TRANSFER_SRC
+                | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        length as u64,
+    )
+        .expect("failed to create buffer")
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    // Storing the small types and computing with them are separate features. Here they are only
+    // converted to and from f32, but naming the types at all needs the arithmetic ones too
+    let requirements = DeviceRequirements::new().features(Features {
+        storage_buffer16_bit_access: true,
+        storage_buffer8_bit_access: true,
+        shader_float16: true,
+        shader_int8: true,
+        ..Features::empty()
+    });
+    let context = VulkanContext::with_requirements(instance, &requirements, None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let compute = ComputeContext::new(&context);
+    let allocators = &compute.allocators;
+
+    // --memory-report prints the heaps around the allocations. All three storage types are
+    // allocated whichever one runs
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    let count = args::value::<u32>("--count").unwrap_or(1 << 24).max(1);
+    let mut random = Random(0x1f83_d9ab);
+    let x: Vec<f32> = (0..count).map(|_| random.next_signed()).collect();
+    let y: Vec<f32> = (0..count).map(|_| random.next_signed()).collect();
+
+    let upload = |data: &[f32]| {
+        Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            data.iter().copied(),
+        )
+            .expect("failed to create buffer")
+    };
+    let download_buffer = Buffer::new_slice::<f32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        count as u64,
+    )
+        .expect("failed to create buffer");
+
+    let [x32, y32, z32] = [(); 3].map(|_| device_buffer::<f32>(&allocators.memory, count));
+    // float16_t has no Rust counterpart, the GPU does all the conversions so u16 is enough
+    let [x16, y16, z16] = [(); 3].map(|_| device_buffer::<u16>(&allocators.memory, count));
+    let [x8, y8, z8] = [(); 3].map(|_| device_buffer::<i8>(&allocators.memory, count));
+
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("after the buffers");
+    }
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(upload(&x), x32.clone()))
+        .unwrap()
+        .copy_buffer(CopyBufferInfo::buffers(upload(&y), y32.clone()))
+        .unwrap();
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let bindings = [
+        (0, Resource::buffer(x32)),
+        (1, Resource::buffer(y32)),
+        (2, Resource::buffer(z32.clone())),
+        (3, Resource::buffer(x16)),
+        (4, Resource::buffer(y16)),
+        (5, Resource::buffer(z16)),
+        (6, Resource::buffer(x8)),
+        (7, Resource::buffer(y8)),
+        (8, Resource::buffer(z8)),
+    ];
+
+    let supports_timestamps = device.physical_device().queue_family_properties()
+        [queue.queue_family_index() as usize]
+        .timestamp_valid_bits
+        .is_some();
+    let timestamp_period = device.physical_device().properties().timestamp_period as f64;
+    let query_pool = QueryPool::new(
+        device.clone(),
+        QueryPoolCreateInfo {
+            query_count: 2,
+            ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+        },
+    )
+        .expect("failed to create query pool");
+
+    // Runs the kernel with one kind of storage, returns z as f32 and the kernel's GPU time in
+    // milliseconds
+    let run = |storage: Storage| {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let pipeline = compute.bind(&mut builder, &shader, "main", &bindings);
+
+        let groups = (count + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE;
+        let dispatch = |builder: &mut AutoCommandBufferBuilder<_>, mode: Mode| {
+            builder
+                .push_constants(
+                    pipeline.layout().clone(),
+                    0,
+                    cs::PushConstants {
+                        storage: storage as u32,
+                        mode: mode as u32,
+                        count,
+                    },
+                )
+                .dispatch([groups, 1, 1])
+                .unwrap();
+        };
+
+        dispatch(&mut builder, Mode::Pack);
+        if supports_timestamps {
+            unsafe {
+                builder
+                    .reset_query_pool(query_pool.clone(), 0..2)
+                    .unwrap()
+                    .write_timestamp(query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                    .unwrap();
+            }
+        }
+        dispatch(&mut builder, Mode::Run);
+        if supports_timestamps {
+            unsafe {
+                builder
+                    .write_timestamp(query_pool.clone(), 1, PipelineStage::ComputeShader)
+                    .unwrap();
+            }
+        }
+        dispatch(&mut builder, Mode::Unpack);
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                z32.clone(),
+                download_buffer.clone(),
+            ))
+            .unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let millis = supports_timestamps.then(|| {
+            let mut timestamps = [0u64; 2];
+            query_pool
+                .queries_range(0..2)
+                .unwrap()
+                .get_results(&mut timestamps, QueryResultFlags::WAIT)
+                .unwrap();
+            (timestamps[1] - timestamps[0]) as f64 * timestamp_period / 1e6
+        });
+        let z = download_buffer.read().unwrap().to_vec();
+        (z, millis)
+    };
+
+    // The exact answer, to measure every version's error against
+    let expected: Vec<f64> = x
+        .iter()
+        .zip(&y)
+        .map(|(&x, &y)| 0.75 * x as f64 + 0.25 * y as f64)
+        .collect();
+
+    println!("z = 0.75 x + 0.25 y over {count} values:");
+    for storage in [Storage::F32, Storage::F16, Storage::Int8] {
+        let (z, millis) = run(storage);
+
+        let errors = z
+            .iter()
+            .zip(&expected)
+            .map(|(&z, &expected)| (z as f64 - expected).abs());
+        let (max_error, total_error) = errors.fold((0.0f64, 0.0), |(max, total), error| {
+            (max.max(error), total + error)
+        });
+        let mean_error = total_error / count as f64;
+
+        // x and y read, z written
+        let bytes = count as u64 * 3 * storage.bytes();
+        let timing = match millis {
+            Some(millis) => format!(
+                "{millis:.3} ms, {:.1} GB/s",
+                bytes as f64 / (millis / 1000.0) / 1e9
+            ),
+            None => "timestamps unsupported".into(),
+        };
+        println!(
+            "  {storage:?}: {} MB moved, {timing}, max error {max_error:.2e}, mean error {mean_error:.2e}",
+            bytes / 1_000_000,
+        );
+    }
+
+    tracing::info!("everything succeeded");
+}
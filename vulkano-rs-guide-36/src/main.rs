@@ -0,0 +1,341 @@
+//Matrix multiplication: from one invocation per element to tiles in shared memory and registers
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::{self, GpuFuture, PipelineStage};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::MemoryReport;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// The size of C each work group computes, and have to match the shaders. The register tiled
+// version has the same 16x16 invocations, each computing 4x4 elements
+const TILE: u32 = 16;
+const REGISTER_TILE: u32 = 64;
+// Each version runs this many times, the fastest run is reported
+const RUNS: u32 = 5;
+// Elements of C checked against the CPU, computing all of them would take a while
+const SAMPLES: usize = 256;
+
+#[derive(Clone, Copy, Debug)]
+enum Kernel {
+    // Every invocation reads a whole row of A and column of B from global memory
+    Naive,
+    // The work group stages 16x16 blocks of A and B in shared memory, so each value loaded
+    // from global memory is used 16 times
+    Tiled,
+    // Bigger blocks, and every invocation keeps a 4x4 block of C in registers so each value read
+    // from shared memory is used 4 times
+    RegisterTiled,
+}
+
+impl Kernel {
+    fn tile(self) -> u32 {
+        match self {
+            Kernel::Naive | Kernel::Tiled => TILE,
+            Kernel::RegisterTiled => REGISTER_TILE,
+        }
+    }
+}
+
+mod naive_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/naive_cs.comp",
+    }
+}
+
+mod tiled_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/tiled_cs.comp",
+    }
+}
+
+mod register_tiled_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/register_tiled_cs.comp",
+    }
+}
+
+// A small xorshift generator for the matrices
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    // Uniform in -1..1
+    fn next_signed(&mut self) -> f32 {
+        self.next() as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    // --memory-report prints the heaps around the allocations, three matrices of n² floats
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    // Rounded up so every kernel's blocks cover the matrices exactly, the shaders don't check
+    // for edges
+    let n = args::value::<u32>("--size").unwrap_or(1024).max(1);
+    let n = (n + REGISTER_TILE - 1) / REGISTER_TILE * REGISTER_TILE;
+    let elements = n as u64 * n as u64;
+
+    let mut random = Random(0x5be0_cd19);
+    let a: Vec<f32> = (0..elements).map(|_| random.next_signed()).collect();
+    let b: Vec<f32> = (0..elements).map(|_| random.next_signed()).collect();
+
+    let upload = |data: &[f32]| {
+        Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            data.iter().copied(),
+        )
+            .expect("failed to create buffer")
+    };
+    let [a_buffer, b_buffer, c_buffer] = [(); 3].map(|_| {
+        Buffer::new_slice::<f32>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER
+                    | BufferUsage::TRANSFER_SRC
+                    | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            elements,
+        )
+            .expect("failed to create buffer")
+    });
+    let download_buffer = Buffer::new_slice::<f32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        elements,
+    )
+        .expect("failed to create buffer");
+
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("after the buffers");
+    }
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(upload(&a), a_buffer.clone()))
+        .unwrap()
+        .copy_buffer(CopyBufferInfo::buffers(upload(&b), b_buffer.clone()))
+        .unwrap();
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let kernels = [
+        (
+            Kernel::Naive,
+            naive_cs::load(device.clone()).expect("failed to create shader module"),
+        ),
+        (
+            Kernel::Tiled,
+            tiled_cs::load(device.clone()).expect("failed to create shader module"),
+        ),
+        (
+            Kernel::RegisterTiled,
+            register_tiled_cs::load(device.clone()).expect("failed to create shader module"),
+        ),
+    ];
+
+    let supports_timestamps = device.physical_device().queue_family_properties()
+        [queue.queue_family_index() as usize]
+        .timestamp_valid_bits
+        .is_some();
+    let timestamp_period = device.physical_device().properties().timestamp_period as f64;
+    let query_pool = QueryPool::new(
+        device.clone(),
+        QueryPoolCreateInfo {
+            query_count: 2,
+            ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+        },
+    )
+        .expect("failed to create query pool");
+
+    // Multiplies A by B into C once, returns the GPU time in milliseconds
+    let run = |kernel: Kernel, pipeline: &Arc<ComputePipeline>| {
+        let set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::buffer(0, a_buffer.clone()),
+                WriteDescriptorSet::buffer(1, b_buffer.clone()),
+                WriteDescriptorSet::buffer(2, c_buffer.clone()),
+            ],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            // The three shaders declare the same push constants
+            .push_constants(pipeline.layout().clone(), 0, naive_cs::PushConstants { n });
+        if supports_timestamps {
+            unsafe {
+                builder
+                    .reset_query_pool(query_pool.clone(), 0..2)
+                    .unwrap()
+                    .write_timestamp(query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                    .unwrap();
+            }
+        }
+
+        let groups = n / kernel.tile();
+        builder.dispatch([groups, groups, 1]).unwrap();
+
+        if supports_timestamps {
+            unsafe {
+                builder
+                    .write_timestamp(query_pool.clone(), 1, PipelineStage::ComputeShader)
+                    .unwrap();
+            }
+        }
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                c_buffer.clone(),
+                download_buffer.clone(),
+            ))
+            .unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        supports_timestamps.then(|| {
+            let mut timestamps = [0u64; 2];
+            query_pool
+                .queries_range(0..2)
+                .unwrap()
+                .get_results(&mut timestamps, QueryResultFlags::WAIT)
+                .unwrap();
+            (timestamps[1] - timestamps[0]) as f64 * timestamp_period / 1e6
+        })
+    };
+
+    // A few elements of C computed on the CPU, in f64 so only the GPU's rounding shows
+    let samples: Vec<(usize, usize, f64)> = (0..SAMPLES)
+        .map(|_| {
+            let row = (random.next() % n) as usize;
+            let col = (random.next() % n) as usize;
+            let n = n as usize;
+            let value = (0..n)
+                .map(|k| a[row * n + k] as f64 * b[k * n + col] as f64)
+                .sum();
+            (row, col, value)
+        })
+        .collect();
+    // Each element adds up n products of values below 1, the f32 rounding grows with n
+    let tolerance = n as f64 * 1e-5;
+
+    // A multiply and an add for every k of every element
+    let flops = 2.0 * n as f64 * n as f64 * n as f64;
+
+    println!("Multiplying two {n}x{n} matrices");
+    for (kernel, shader) in kernels {
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let mut fastest: Option<f64> = None;
+        for _ in 0..RUNS {
+            if let Some(millis) = run(kernel, &pipeline) {
+                fastest = Some(fastest.map_or(millis, |fastest| fastest.min(millis)));
+            }
+        }
+
+        let c = download_buffer.read().unwrap();
+        for &(row, col, expected) in &samples {
+            let value = c[row * n as usize + col] as f64;
+            assert!(
+                (value - expected).abs() <= tolerance,
+                "{kernel:?} C[{row}][{col}] is {value}, the CPU says {expected}"
+            );
+        }
+
+        match fastest {
+            Some(millis) => println!(
+                "  {kernel:?}: {millis:.3} ms, {:.1} GFLOPs",
+                flops / (millis / 1000.0) / 1e9
+            ),
+            None => println!("  {kernel:?}: correct, timestamps unsupported"),
+        }
+    }
+
+    tracing::info!("everything succeeded");
+}
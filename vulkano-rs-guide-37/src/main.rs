@@ -0,0 +1,120 @@
+//Reading results back: the same copy into cached and uncached host memory, and how fast the CPU
+//reads each
+
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, FillBufferInfo,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::{host_buffer, HostMemory, MemoryReport};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// Each read runs this many times, the fastest is reported
+const RUNS: u32 = 5;
+// What the GPU fills the buffer with, so the CPU has something to check
+const PATTERN: u32 = 0x0102_0304;
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    // --track-memory logs each buffer's allocation, and the peak usage at the end
+    let allocators = Allocators::new(&device);
+
+    // --memory-report shows which heap each host buffer comes from
+    let mut memory_report =
+        args::flag("--memory-report").then(|| MemoryReport::new(device.physical_device()));
+    if let Some(memory_report) = &mut memory_report {
+        memory_report.print("before the buffers");
+    }
+
+    let megabytes = args::value::<u64>("--size").unwrap_or(256).max(1);
+    let count = megabytes * 1024 * 1024 / 4;
+
+    // The results the CPU wants, written by the GPU into its own memory
+    let device_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        count,
+    )
+        .expect("failed to create buffer");
+
+    println!("Reading back {megabytes} MiB:");
+    for memory in [HostMemory::Cached, HostMemory::Uncached] {
+        // Many integrated GPUs only have cached memory, some discrete ones only uncached
+        let Some(host_buffer) = host_buffer::<u32>(&allocators.memory, count, memory) else {
+            println!("  {memory:?}: the device has no such memory");
+            continue;
+        };
+        if let Some(memory_report) = &mut memory_report {
+            memory_report.print(&format!("after the {memory:?} buffer"));
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .fill_buffer(FillBufferInfo {
+                data: PATTERN,
+                ..FillBufferInfo::dst_buffer(device_buffer.clone())
+            })
+            .unwrap()
+            .copy_buffer(CopyBufferInfo::buffers(
+                device_buffer.clone(),
+                host_buffer.clone(),
+            ))
+            .unwrap();
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        // Summing touches every word, like any real use of the results would. The GPU is done
+        // with the buffer, so the time is all CPU reads
+        let mut fastest = f64::MAX;
+        for _ in 0..RUNS {
+            let start = Instant::now();
+            let sum = host_buffer
+                .read()
+                .unwrap()
+                .iter()
+                .fold(0u32, |sum, &value| sum.wrapping_add(value));
+            fastest = fastest.min(start.elapsed().as_secs_f64());
+
+            let expected = (count as u32).wrapping_mul(PATTERN);
+            assert_eq!(sum, expected, "{memory:?} memory read back the wrong data");
+        }
+
+        println!(
+            "  {memory:?}: {:.3} ms, {:.2} GB/s",
+            fastest * 1000.0,
+            (count * 4) as f64 / fastest / 1e9
+        );
+    }
+
+    tracing::info!("everything succeeded");
+}
@@ -0,0 +1,427 @@
+//Secondary command buffers: the draws of a render pass split into chunks, recorded separately and
+//optionally in parallel on a rayon thread pool
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+
+use rayon::prelude::*;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferInheritanceInfo, CommandBufferInheritanceRenderPassInfo,
+    CommandBufferInheritanceRenderPassType, CommandBufferUsage, RenderPassBeginInfo,
+    SecondaryAutoCommandBuffer, SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::cube;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// GRID by GRID cubes, every one its own draw call so that recording takes a noticeable time
+const GRID: u32 = 64;
+const SPACING: f32 = 1.5;
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    // Primary and secondary builders record draws the same way
+    fn draw<L>(&self, builder: &mut AutoCommandBufferBuilder<L>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Recording {
+    // Every draw straight into the primary command buffer
+    Inline,
+    // The draws split between secondary command buffers, recorded one after the other
+    Secondary,
+    // The same, with the secondary command buffers recorded in parallel by rayon's workers
+    Threads,
+}
+
+struct SecondaryCommandBuffers {
+    camera: Camera,
+    start: Instant,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    // One per rayon worker. The standard allocator already keeps a command pool per thread, a
+    // separate allocator makes that explicit, like the one pool per thread raw Vulkan requires
+    worker_allocators: Vec<StandardCommandBufferAllocator>,
+    cube: Mesh,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // CPU time spent recording the draws in each mode, smoothed over the frames it was used
+    record_millis: [f64; 3],
+    // Parameters exposed in the overlay
+    recording: Recording,
+    chunks: u32,
+}
+
+impl SecondaryCommandBuffers {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+        let cube = Mesh::new(&allocators.memory, cube());
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let extent = GRID as f32 * SPACING / 2.0;
+        SecondaryCommandBuffers {
+            camera: Camera::new(Vec3::new(0.0, extent * 0.6, extent * 1.4), Vec3::ZERO),
+            start: Instant::now(),
+            render_pass,
+            pipeline,
+            allocators,
+            worker_allocators: (0..rayon::current_num_threads())
+                .map(|_| StandardCommandBufferAllocator::new(device.clone(), Default::default()))
+                .collect(),
+            cube,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            record_millis: [0.0; 3],
+            recording: Recording::Secondary,
+            chunks: 8,
+        }
+    }
+
+    // Records the cubes with indices in `range`. A rolling wave moves them up and down, which
+    // is why the draws can't simply be recorded once and replayed
+    fn draw_cubes<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        range: Range<u32>,
+        view_projection: [[f32; 4]; 4],
+        time: f32,
+    ) {
+        let offset = (GRID - 1) as f32 / 2.0;
+        for i in range {
+            let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+            let height = 1.0 + (time * 2.0 + x * 0.25 + z * 0.15).sin() * 0.6;
+            builder.push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    view_projection,
+                    center: [(x - offset) * SPACING, height, (z - offset) * SPACING, 0.0],
+                    scale: [0.5, height, 0.5, 0.0],
+                    color: [
+                        x / GRID as f32,
+                        0.3 + 0.5 * height / 1.6,
+                        z / GRID as f32,
+                        1.0,
+                    ],
+                },
+            );
+            self.cube.draw(builder);
+        }
+    }
+
+    // One chunk of the draws in a secondary command buffer. It runs inside the primary's render
+    // pass, so it has to be told which subpass, and it inherits no state: the pipeline and the
+    // viewport are set again
+    fn record_secondary(
+        &self,
+        allocator: &StandardCommandBufferAllocator,
+        queue_family_index: u32,
+        framebuffer: &Arc<Framebuffer>,
+        range: Range<u32>,
+        view_projection: [[f32; 4]; 4],
+        time: f32,
+    ) -> SecondaryAutoCommandBuffer {
+        let mut builder = AutoCommandBufferBuilder::secondary(
+            allocator,
+            queue_family_index,
+            CommandBufferUsage::OneTimeSubmit,
+            CommandBufferInheritanceInfo {
+                render_pass: Some(CommandBufferInheritanceRenderPassType::BeginRenderPass(
+                    CommandBufferInheritanceRenderPassInfo {
+                        subpass: Subpass::from(self.render_pass.clone(), 0).unwrap(),
+                        // Optional, naming the framebuffer may let the driver optimize
+                        framebuffer: Some(framebuffer.clone()),
+                    },
+                )),
+                ..Default::default()
+            },
+        )
+            .unwrap();
+        builder
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone());
+        self.draw_cubes(&mut builder, range, view_projection, time);
+        builder.build().unwrap()
+    }
+}
+
+impl App for SecondaryCommandBuffers {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let view_projection = self
+            .camera
+            .view_projection(width / height)
+            .to_cols_array_2d();
+        let time = self.start.elapsed().as_secs_f32();
+        let framebuffer = &self.framebuffers[image_index as usize];
+        // The renderer can't be shared between threads, the workers only get the queue family
+        let queue_family_index = renderer.queue().queue_family_index();
+
+        let start = Instant::now();
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            queue_family_index,
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // The subpass contents say up front whether draws come inline or from secondary
+        // command buffers, a subpass can't mix both
+        let contents = match self.recording {
+            Recording::Inline => SubpassContents::Inline,
+            Recording::Secondary | Recording::Threads => SubpassContents::SecondaryCommandBuffers,
+        };
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.1, 0.1, 0.15, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                },
+                contents,
+            )
+            .unwrap();
+
+        let count = GRID * GRID;
+        let chunk_size = (count + self.chunks - 1) / self.chunks;
+        let range = |chunk: u32| chunk * chunk_size..((chunk + 1) * chunk_size).min(count);
+        match self.recording {
+            Recording::Inline => {
+                builder
+                    .set_viewport(0, [self.viewport.clone()])
+                    .bind_pipeline_graphics(self.pipeline.clone());
+                self.draw_cubes(&mut builder, 0..count, view_projection, time);
+            }
+            Recording::Secondary => {
+                let secondaries: Vec<SecondaryAutoCommandBuffer> = (0..self.chunks)
+                    .map(|chunk| {
+                        self.record_secondary(
+                            &*self.allocators.command,
+                            queue_family_index,
+                            framebuffer,
+                            range(chunk),
+                            view_projection,
+                            time,
+                        )
+                    })
+                    .collect();
+                builder.execute_commands_from_vec(secondaries).unwrap();
+            }
+            Recording::Threads => {
+                // Secondary command buffers are independent of each other, nothing stops them
+                // from being recorded at the same time. Rayon's collect keeps them in chunk
+                // order, whichever worker finished first
+                let secondaries: Vec<SecondaryAutoCommandBuffer> = (0..self.chunks)
+                    .into_par_iter()
+                    .map(|chunk| {
+                        let worker = rayon::current_thread_index().unwrap();
+                        self.record_secondary(
+                            &self.worker_allocators[worker],
+                            queue_family_index,
+                            framebuffer,
+                            range(chunk),
+                            view_projection,
+                            time,
+                        )
+                    })
+                    .collect();
+                builder.execute_commands_from_vec(secondaries).unwrap();
+            }
+        }
+
+        builder.end_render_pass().unwrap();
+        let command_buffer = builder.build().unwrap();
+        let millis = start.elapsed().as_secs_f64() * 1000.0;
+        let average = &mut self.record_millis[self.recording as usize];
+        *average = *average * 0.95 + millis * 0.05;
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Recording").show(ctx, |ui| {
+            ui.radio_value(&mut self.recording, Recording::Inline, "inline");
+            ui.radio_value(&mut self.recording, Recording::Secondary, "secondary");
+            ui.radio_value(
+                &mut self.recording,
+                Recording::Threads,
+                "secondary on rayon workers",
+            );
+            ui.add_enabled(
+                self.recording != Recording::Inline,
+                egui::Slider::new(&mut self.chunks, 1..=32).text("command buffers"),
+            );
+            ui.label(format!("draws: {}", GRID * GRID));
+            ui.label("recording time:");
+            for (recording, name) in ["inline", "secondary", "threads"].iter().enumerate() {
+                let millis = self.record_millis[recording];
+                if millis > 0.0 {
+                    ui.label(format!("  {name}: {millis:.2} ms"));
+                }
+            }
+            ui.label(format!("rayon workers: {}", self.worker_allocators.len()));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-38",
+        DeviceExtensions::empty(),
+        SecondaryCommandBuffers::new,
+    );
+}
@@ -0,0 +1,144 @@
+//The compute chapter again, with a command buffer recorded once and submitted over and over
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+const LENGTH: u32 = 65536;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    let iterations = args::value::<u32>("--iterations").unwrap_or(1000).max(1);
+
+    let data_buffer: Subbuffer<[u32]> = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        0..LENGTH,
+    )
+        .expect("failed to create buffer");
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let compute_pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    // The pipeline knows the layout from the shader, only the buffer for binding 0 is needed
+    let set = bind_resources(
+        &allocators.descriptor,
+        &compute_pipeline,
+        0,
+        [(0, Resource::buffer(data_buffer.clone()))],
+    );
+
+    // The same commands in both cases, only the usage differs
+    let record = |usage: CommandBufferUsage| {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            usage,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(compute_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                compute_pipeline.layout().clone(),
+                0,
+                set.clone(),
+            )
+            .dispatch([LENGTH / 64, 1, 1])
+            .unwrap();
+        Arc::new(builder.build().unwrap())
+    };
+    let submit = |command_buffer: Arc<PrimaryAutoCommandBuffer>| {
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+    };
+
+    // OneTimeSubmit: a fresh command buffer every iteration, like the other chapters do. The
+    // driver may optimize it for a single use, but recording costs CPU time each time
+    let mut record_time = Duration::ZERO;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let record_start = Instant::now();
+        let command_buffer = record(CommandBufferUsage::OneTimeSubmit);
+        record_time += record_start.elapsed();
+        submit(command_buffer);
+    }
+    let one_time = start.elapsed();
+
+    // MultipleSubmit: recorded once, submitted as often as needed. It can't be pending twice
+    // at the same time though, that would take SimultaneousUse, which drivers may make slower
+    // to execute. Every submission here is waited for, so MultipleSubmit is enough
+    let start = Instant::now();
+    let command_buffer = record(CommandBufferUsage::MultipleSubmit);
+    for _ in 0..iterations {
+        submit(command_buffer.clone());
+    }
+    let reused = start.elapsed();
+
+    let content = data_buffer.read().unwrap();
+    for (n, val) in content.iter().enumerate() {
+        assert_eq!(*val, n as u32 + 2 * iterations);
+    }
+
+    // The time per iteration includes waiting for the GPU, which is the same in both cases,
+    // so the difference is what recording costs
+    let per_iteration = |total: Duration| total.as_secs_f64() * 1e6 / iterations as f64;
+    println!("{iterations} submissions of one dispatch:");
+    println!(
+        "  OneTimeSubmit: {:.1} µs per iteration, {:.1} µs of it recording",
+        per_iteration(one_time),
+        per_iteration(record_time),
+    );
+    println!(
+        "  MultipleSubmit: {:.1} µs per iteration, recorded once",
+        per_iteration(reused),
+    );
+
+    tracing::info!("everything succeeded");
+}
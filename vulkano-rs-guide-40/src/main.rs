@@ -0,0 +1,253 @@
+//Streaming data to the GPU: a scrolling waterfall plot, one new row uploaded through a mapped
+//staging buffer every frame
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::BufferUsage;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::staging::PersistentStaging;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// The history kept on the GPU, as a ring of rows: the newest overwrites the oldest
+const ROWS: u32 = 512;
+const COLUMNS: u32 = 512;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// A small xorshift generator for the noise
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    // Uniform in 0..1
+    fn next_unit(&mut self) -> f32 {
+        self.next() as f32 / u32::MAX as f32
+    }
+}
+
+struct Waterfall {
+    start: Instant,
+    random: Random,
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // The history lives in device memory, rows reach it through the staging regions
+    staging: PersistentStaging<f32>,
+    head: u32,
+    // Recreated with the swapchain so the plot is drawn at the window resolution
+    image: Option<(Arc<StorageImage>, Arc<PersistentDescriptorSet>)>,
+    // Parameters exposed in the overlay
+    paused: bool,
+    noise: f32,
+    drift: f32,
+}
+
+impl Waterfall {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let allocators = renderer.allocators.clone();
+        // One staging region per swapchain image, as that bounds the number of frames in flight
+        let mut staging = PersistentStaging::new(
+            &allocators,
+            renderer.queue(),
+            ROWS as u64 * COLUMNS as u64,
+            BufferUsage::STORAGE_BUFFER,
+            renderer.images.len() as u32,
+        );
+        // Device memory starts out with whatever was there before, the first upload clears all
+        // of it before anything reads the history
+        let future = staging
+            .upload(
+                sync::now(device.clone()).boxed(),
+                0..ROWS as u64 * COLUMNS as u64,
+                |values| values.fill(0.0),
+            )
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        Waterfall {
+            start: Instant::now(),
+            random: Random(0x9e37_79b9),
+            pipeline,
+            allocators,
+            staging,
+            head: 0,
+            image: None,
+            paused: false,
+            noise: 0.15,
+            drift: 1.0,
+        }
+    }
+
+    // A made-up spectrum: three peaks that wander, pulse and fade, over some noise
+    fn next_row(&mut self) -> Vec<f32> {
+        let t = self.start.elapsed().as_secs_f32() * self.drift;
+        let peaks = [
+            (0.2 + 0.1 * (t * 0.7).sin(), 0.02, 0.9),
+            (0.5 + 0.25 * (t * 0.31).sin(), 0.04, 0.7),
+            (0.8, 0.01, 0.5 + 0.5 * (t * 3.0).sin()),
+        ];
+
+        (0..COLUMNS)
+            .map(|column| {
+                let x = column as f32 / COLUMNS as f32;
+                let signal: f32 = peaks
+                    .iter()
+                    .map(|&(center, width, amplitude)| {
+                        amplitude * (-((x - center) / width).powi(2)).exp()
+                    })
+                    .sum();
+                (signal + self.noise * self.random.next_unit()).min(1.0)
+            })
+            .collect()
+    }
+}
+
+impl App for Waterfall {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+
+        let image = StorageImage::new(
+            &self.allocators.memory,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(renderer.queue().queue_family_index()),
+        )
+            .unwrap();
+
+        let view = ImageView::new_default(image.clone()).unwrap();
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, view),
+                WriteDescriptorSet::buffer(1, self.staging.device_buffer().clone()),
+            ],
+        )
+            .unwrap();
+
+        self.image = Some((image, set));
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        mut before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // Only the new row is written and copied, the rest of the history stays where it is
+        if !self.paused {
+            self.head = (self.head + 1) % ROWS;
+            let row = self.next_row();
+            let first = self.head as u64 * COLUMNS as u64;
+            before = self
+                .staging
+                .upload(before, first..first + COLUMNS as u64, |values| {
+                    values.copy_from_slice(&row)
+                });
+        }
+
+        let (image, set) = self.image.clone().unwrap();
+        let [width, height] = renderer.swapchain.image_extent();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                cs::PushConstants {
+                    head: self.head,
+                    rows: ROWS,
+                    columns: COLUMNS,
+                },
+            )
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap()
+            .blit_image(BlitImageInfo::images(
+                image,
+                renderer.images[image_index as usize].clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        let row_bytes = COLUMNS as usize * 4;
+        let history_bytes = ROWS as usize * row_bytes;
+
+        egui::Window::new("Waterfall").show(ctx, |ui| {
+            ui.checkbox(&mut self.paused, "paused");
+            ui.add(egui::Slider::new(&mut self.noise, 0.0..=0.6).text("noise"));
+            ui.add(egui::Slider::new(&mut self.drift, 0.0..=4.0).text("drift"));
+            ui.label(format!(
+                "uploaded per frame: {} KiB of {} KiB",
+                row_bytes / 1024,
+                history_bytes / 1024
+            ));
+        });
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-40",
+        DeviceExtensions::empty(),
+        Waterfall::new,
+    );
+}
@@ -0,0 +1,13 @@
+#version 460
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform PushConstants {
+    vec3 color;
+    float angle;
+    float aspect;
+} pc;
+
+void main() {
+    f_color = vec4(pc.color, 1.0);
+}
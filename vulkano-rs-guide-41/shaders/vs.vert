@@ -0,0 +1,16 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+
+layout(push_constant) uniform PushConstants {
+    vec3 color;
+    float angle;
+    float aspect;
+} pc;
+
+void main() {
+    mat2 rotation = mat2(cos(pc.angle), sin(pc.angle), -sin(pc.angle), cos(pc.angle));
+    vec2 rotated = rotation * position;
+    // Keeps the triangle from stretching with the window
+    gl_Position = vec4(rotated.x / pc.aspect, rotated.y, 0.0, 1.0);
+}
@@ -0,0 +1,453 @@
+//Two windows, each with its own surface and swapchain, drawn from one device and queue and
+//presented from whichever queue can present to them
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::{Device, DeviceExtensions, DeviceOwned, Queue};
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageUsage, SwapchainImage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::shader::ShaderModule;
+use vulkano::swapchain::{
+    acquire_next_image, AcquireError, Surface, Swapchain, SwapchainCreateInfo,
+    SwapchainCreationError, SwapchainPresentInfo,
+};
+use vulkano::sync::{self, FlushError, GpuFuture, Sharing};
+use vulkano::VulkanLibrary;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::queues::Queues;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::winit::dpi::LogicalSize;
+use vulkano_rs_common::winit::event::{Event, WindowEvent};
+use vulkano_rs_common::winit::event_loop::{ControlFlow, EventLoop};
+use vulkano_rs_common::winit::window::{Window, WindowBuilder, WindowId};
+use vulkano_win::VkSurfaceBuild;
+
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct PlainVertex {
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+const CLEAR_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 1.0];
+
+// What tells the windows apart: their title, triangle color and spinning direction
+const WINDOWS: [(&str, [f32; 3], f32); 2] = [
+    ("vulkano-rs-guide-41: left", [1.0, 0.4, 0.2], 1.0),
+    ("vulkano-rs-guide-41: right", [0.2, 0.6, 1.0], -1.0),
+];
+
+// Everything that belongs to a single window. The device, graphics queue, vertex buffer and
+// shaders are shared by all of them
+struct WindowTarget {
+    surface: Arc<Surface>,
+    // The graphics queue, unless its family can't present to this surface
+    present_queue: Arc<Queue>,
+    swapchain: Arc<Swapchain>,
+    // The surfaces may prefer different formats, so each window has its own render pass and a
+    // pipeline built for it
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    recreate_swapchain: bool,
+    // Every window presents on its own schedule, so each one keeps its own last frame
+    previous_frame_end: Option<Box<dyn GpuFuture>>,
+    color: [f32; 3],
+    direction: f32,
+}
+
+impl WindowTarget {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &Arc<Device>,
+        graphics_queue: &Arc<Queue>,
+        present_queue: Arc<Queue>,
+        surface: Arc<Surface>,
+        vs: &Arc<ShaderModule>,
+        fs: &Arc<ShaderModule>,
+        color: [f32; 3],
+        direction: f32,
+    ) -> Self {
+        let physical_device = device.physical_device();
+        let surface_capabilities = physical_device
+            .surface_capabilities(&surface, Default::default())
+            .unwrap();
+        let image_format = physical_device
+            .surface_formats(&surface, Default::default())
+            .unwrap()[0]
+            .0;
+
+        // Rendered to by one family and presented by the other, without ownership transfers
+        let image_sharing = if Arc::ptr_eq(&present_queue, graphics_queue) {
+            Sharing::Exclusive
+        } else {
+            Sharing::Concurrent(
+                vec![
+                    graphics_queue.queue_family_index(),
+                    present_queue.queue_family_index(),
+                ]
+                .into(),
+            )
+        };
+
+        let (swapchain, images) = Swapchain::new(
+            device.clone(),
+            surface.clone(),
+            SwapchainCreateInfo {
+                min_image_count: surface_capabilities.min_image_count,
+                image_format: Some(image_format),
+                image_extent: window(&surface).inner_size().into(),
+                image_usage: ImageUsage::COLOR_ATTACHMENT,
+                image_sharing,
+                composite_alpha: surface_capabilities
+                    .supported_composite_alpha
+                    .into_iter()
+                    .next()
+                    .unwrap(),
+                ..Default::default()
+            },
+        )
+            .expect("failed to create swapchain");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: image_format,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PlainVertex::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let mut target = WindowTarget {
+            surface,
+            present_queue,
+            swapchain,
+            render_pass,
+            pipeline,
+            framebuffers: Vec::new(),
+            recreate_swapchain: false,
+            previous_frame_end: Some(sync::now(device.clone()).boxed()),
+            color,
+            direction,
+        };
+        target.create_framebuffers(images);
+        target
+    }
+
+    fn create_framebuffers(&mut self, images: Vec<Arc<SwapchainImage>>) {
+        self.framebuffers = images
+            .into_iter()
+            .map(|image| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![ImageView::new_default(image).unwrap()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        queue: &Arc<Queue>,
+        command_buffer_allocator: &StandardCommandBufferAllocator,
+        vertex_buffer: &Subbuffer<[PlainVertex]>,
+        seconds: f32,
+    ) {
+        // Nothing to draw while the window is minimized
+        let dimensions = window(&self.surface).inner_size();
+        if dimensions.width == 0 || dimensions.height == 0 {
+            return;
+        }
+
+        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+
+        if self.recreate_swapchain {
+            let (swapchain, images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: dimensions.into(),
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.swapchain = swapchain;
+            self.create_framebuffers(images);
+            self.recreate_swapchain = false;
+        }
+
+        let (image_index, suboptimal, acquire_future) =
+            match acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        let [width, height] = self.swapchain.image_extent();
+        let push_constants = vs::PushConstants {
+            color: self.color,
+            angle: seconds * self.direction,
+            aspect: width as f32 / height as f32,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(CLEAR_COLOR.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [width as f32, height as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            )
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+            .bind_vertex_buffers(0, vertex_buffer.clone())
+            .draw(vertex_buffer.len() as u32, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let future = self
+            .previous_frame_end
+            .take()
+            .unwrap()
+            .join(acquire_future)
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap();
+        // Presenting from another queue has to wait on a semaphore for the frame
+        let future = if Arc::ptr_eq(&self.present_queue, queue) {
+            future.boxed()
+        } else {
+            future.then_signal_semaphore().boxed()
+        };
+        let future = future
+            .then_swapchain_present(
+                self.present_queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_index),
+            )
+            .then_signal_fence_and_flush();
+
+        self.previous_frame_end = match future {
+            Ok(future) => Some(future.boxed()),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                Some(sync::now(queue.device().clone()).boxed())
+            }
+            Err(e) => {
+                tracing::error!("failed to flush future: {e}");
+                Some(sync::now(queue.device().clone()).boxed())
+            }
+        };
+    }
+}
+
+fn window(surface: &Surface) -> &Window {
+    surface.object().unwrap().downcast_ref::<Window>().unwrap()
+}
+
+// Support for presenting is a property of a queue family and a surface together. The graphics
+// queue is tried first so the swapchain images stay on one family, as QueuePlan does for the
+// surface the device was picked for
+fn present_queue(queues: &Queues, surface: &Surface) -> Option<Arc<Queue>> {
+    let physical_device = queues.graphics.device().physical_device();
+    [
+        &queues.graphics,
+        &queues.present,
+        &queues.compute,
+        &queues.transfer,
+    ]
+    .into_iter()
+    .find(|queue| {
+        physical_device
+            .surface_support(queue.queue_family_index(), surface)
+            .unwrap_or(false)
+    })
+    .cloned()
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+
+    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let required_extensions = vulkano_win::required_extensions(&library);
+    let instance = context::create_instance(library, required_extensions);
+
+    // Each window gets its own surface, they are all created from the same instance
+    let surfaces: Vec<Arc<Surface>> = WINDOWS
+        .iter()
+        .map(|(title, _, _)| {
+            WindowBuilder::new()
+                .with_title(*title)
+                .with_inner_size(LogicalSize::new(480, 480))
+                .build_vk_surface(&event_loop, instance.clone())
+                .expect("failed to create window")
+        })
+        .collect();
+
+    // The device is picked for the first surface, so the queues it is created with can present
+    // to that one. The windows are on the same display and the others almost always work too
+    let context = VulkanContext::new(
+        instance,
+        DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        },
+        Some(&surfaces[0]),
+    );
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+
+    let vertex_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [
+            PlainVertex {
+                position: [0.0, -0.6],
+            },
+            PlainVertex {
+                position: [0.52, 0.3],
+            },
+            PlainVertex {
+                position: [-0.52, 0.3],
+            },
+        ],
+    )
+        .expect("failed to create vertex buffer");
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+    // A window none of the device's queues can present to is closed, the others still run
+    let mut targets: Vec<(WindowId, WindowTarget)> = surfaces
+        .into_iter()
+        .zip(WINDOWS)
+        .filter_map(|(surface, (title, color, direction))| {
+            let Some(present_queue) = present_queue(&context.queues, &surface) else {
+                tracing::warn!("no queue of the device can present to \"{title}\", closing it");
+                return None;
+            };
+            let target = WindowTarget::new(
+                &device,
+                &queue,
+                present_queue,
+                surface,
+                &vs,
+                &fs,
+                color,
+                direction,
+            );
+            Some((window(&target.surface).id(), target))
+        })
+        .collect();
+
+    let start = Instant::now();
+
+    event_loop.run(move |event, _, control_flow| match event {
+        // Closing one window only drops its swapchain and surface, the other keeps going
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            window_id,
+        } => {
+            targets.retain(|(id, _)| *id != window_id);
+            if targets.is_empty() {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            window_id,
+        } => {
+            if let Some((_, target)) = targets.iter_mut().find(|(id, _)| *id == window_id) {
+                target.recreate_swapchain = true;
+            }
+        }
+        Event::RedrawEventsCleared => {
+            let seconds = start.elapsed().as_secs_f32();
+            for (_, target) in &mut targets {
+                target.render(&queue, &*allocators.command, &vertex_buffer, seconds);
+            }
+        }
+        // The loop never returns, so the allocator isn't dropped to print it
+        Event::LoopDestroyed => allocators.memory.print_summary(),
+        _ => (),
+    });
+}
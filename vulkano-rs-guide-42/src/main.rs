@@ -0,0 +1,380 @@
+//Split screen: one scene drawn from two cameras into two halves of the same swapchain image, with
+//the viewport and scissor set per half
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Scissor, Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Vec3};
+use vulkano_rs_common::mesh::cube;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// A GRID by GRID field of pillars for the two players to move around in
+const GRID: u32 = 12;
+const SPACING: f32 = 3.0;
+// Pixels left uncovered between the two views
+const DIVIDER: u32 = 4;
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Split {
+    // Left and right halves, each as tall as the window
+    SideBySide,
+    // Top and bottom halves, each as wide as the window
+    Stacked,
+}
+
+const PLAYER_COLORS: [[f32; 4]; 2] = [[1.0, 0.3, 0.2, 1.0], [0.2, 0.5, 1.0, 1.0]];
+
+struct SplitScreen {
+    // The first player is moved with the keyboard and mouse, the second flies in circles
+    camera: Camera,
+    orbit: Camera,
+    start: Instant,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    cube: Mesh,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    // Parameters exposed in the overlay
+    split: Split,
+}
+
+impl SplitScreen {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+        let cube = Mesh::new(&allocators.memory, cube());
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        // Both the viewport and the scissor are dynamic, so the one pipeline draws into either
+        // half. With them baked in, each half would need its own pipeline
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_dynamic(1))
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let extent = GRID as f32 * SPACING / 2.0;
+        SplitScreen {
+            camera: Camera::new(Vec3::new(0.0, 2.0, extent + 4.0), Vec3::new(0.0, 1.0, 0.0)),
+            orbit: Camera::new(Vec3::new(extent, 6.0, 0.0), Vec3::ZERO),
+            start: Instant::now(),
+            render_pass,
+            pipeline,
+            allocators,
+            cube,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            split: Split::SideBySide,
+        }
+    }
+
+    // The two rectangles the players see the scene in, as scissors. The divider between them
+    // keeps the clear color
+    fn halves(&self) -> [Scissor; 2] {
+        let [width, height] = self.extent;
+        match self.split {
+            Split::SideBySide => {
+                let half = width.saturating_sub(DIVIDER) / 2;
+                [
+                    Scissor {
+                        origin: [0, 0],
+                        dimensions: [half, height],
+                    },
+                    Scissor {
+                        origin: [width - half, 0],
+                        dimensions: [half, height],
+                    },
+                ]
+            }
+            Split::Stacked => {
+                let half = height.saturating_sub(DIVIDER) / 2;
+                [
+                    Scissor {
+                        origin: [0, 0],
+                        dimensions: [width, half],
+                    },
+                    Scissor {
+                        origin: [0, height - half],
+                        dimensions: [width, half],
+                    },
+                ]
+            }
+        }
+    }
+
+    // The whole scene as seen through `view_projection`: the pillars, and a cube for each
+    // player so they can see each other
+    fn draw_scene(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        view_projection: Mat4,
+    ) {
+        let view_projection = view_projection.to_cols_array_2d();
+        let draw = |builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+                    center: Vec3,
+                    scale: Vec3,
+                    color: [f32; 4]| {
+            builder.push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    view_projection,
+                    center: center.extend(0.0).to_array(),
+                    scale: scale.extend(0.0).to_array(),
+                    color,
+                },
+            );
+            self.cube.draw(builder);
+        };
+
+        // The floor, a flattened cube
+        let extent = GRID as f32 * SPACING / 2.0;
+        draw(
+            builder,
+            Vec3::new(0.0, -0.1, 0.0),
+            Vec3::new(extent + 2.0, 0.1, extent + 2.0),
+            [0.35, 0.35, 0.35, 1.0],
+        );
+
+        let offset = (GRID - 1) as f32 / 2.0;
+        for i in 0..GRID * GRID {
+            let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+            let height = 0.5 + ((x * 1.7 + z * 2.3).sin() * 0.5 + 0.5) * 2.5;
+            draw(
+                builder,
+                Vec3::new((x - offset) * SPACING, height, (z - offset) * SPACING),
+                Vec3::new(0.4, height, 0.4),
+                [0.6, 0.65 + 0.1 * (x / GRID as f32), 0.5, 1.0],
+            );
+        }
+
+        for (player, color) in [&self.camera, &self.orbit].iter().zip(PLAYER_COLORS) {
+            draw(builder, player.position, Vec3::splat(0.3), color);
+        }
+    }
+}
+
+impl App for SplitScreen {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.extent = extent;
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // The second player circles the field, looking at its center
+        let time = self.start.elapsed().as_secs_f32() * 0.3;
+        let radius = GRID as f32 * SPACING / 2.0 + 2.0;
+        let fov_y = self.orbit.fov_y;
+        self.orbit = Camera::new(
+            Vec3::new(time.cos() * radius, 6.0, time.sin() * radius),
+            Vec3::ZERO,
+        );
+        self.orbit.fov_y = fov_y;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // One render pass for both views: the clear covers the whole image, the halves only
+        // differ in the dynamic state set before their draws
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.02, 0.02, 0.02, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(self.pipeline.clone());
+
+        for (camera, half) in [&self.camera, &self.orbit].into_iter().zip(self.halves()) {
+            if half.dimensions[0] == 0 || half.dimensions[1] == 0 {
+                continue;
+            }
+            // The viewport maps the camera's image onto the half, the scissor makes sure
+            // nothing is drawn outside of it. Here they are the same rectangle, a viewport
+            // larger than its scissor would crop the view instead of squeezing it
+            let viewport = Viewport {
+                origin: [half.origin[0] as f32, half.origin[1] as f32],
+                dimensions: [half.dimensions[0] as f32, half.dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            };
+            let aspect = viewport.dimensions[0] / viewport.dimensions[1];
+            builder.set_viewport(0, [viewport]).set_scissor(0, [half]);
+            self.draw_scene(&mut builder, camera.view_projection(aspect));
+        }
+
+        builder.end_render_pass().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Split screen").show(ctx, |ui| {
+            ui.radio_value(&mut self.split, Split::SideBySide, "side by side");
+            ui.radio_value(&mut self.split, Split::Stacked, "stacked");
+            ui.add(egui::Slider::new(&mut self.orbit.fov_y, 0.3..=2.0).text("second player fov"));
+            ui.label("The first view follows the keyboard and mouse");
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-42",
+        DeviceExtensions::empty(),
+        SplitScreen::new,
+    );
+}
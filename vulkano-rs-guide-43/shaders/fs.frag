@@ -0,0 +1,16 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform PushConstants {
+    vec4 center;
+    vec4 scale;
+    vec4 color;
+} pc;
+
+void main() {
+    float diffuse = max(dot(normalize(v_normal), normalize(vec3(0.4, 1.0, 0.6))), 0.0);
+    f_color = vec4(pc.color.rgb * (diffuse * 0.8 + 0.2), 1.0);
+}
@@ -0,0 +1,25 @@
+#version 460
+#extension GL_EXT_multiview : require
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+
+// One camera per eye, the only thing that differs between the two views
+layout(set = 0, binding = 0) uniform Eyes {
+    mat4 view_projection[2];
+} eyes;
+
+layout(push_constant) uniform PushConstants {
+    vec4 center;
+    vec4 scale;
+    vec4 color;
+} pc;
+
+void main() {
+    v_normal = normal;
+    // Every draw runs once per view, gl_ViewIndex says which one this is
+    vec4 world = vec4(pc.center.xyz + position * pc.scale.xyz, 1.0);
+    gl_Position = eyes.view_projection[gl_ViewIndex] * world;
+}
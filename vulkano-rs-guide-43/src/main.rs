@@ -0,0 +1,408 @@
+//Stereo rendering with multiview: both eyes drawn by one render pass into the two layers of an
+//image, then placed side by side in the window
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage, ImageBlit,
+    PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{
+    ImageAccess, ImageCreateFlags, ImageDimensions, ImageLayout, ImageSubresourceLayers,
+    ImageUsage, SampleCount, StorageImage,
+};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{
+    AttachmentDescription, AttachmentReference, Framebuffer, FramebufferCreateInfo, LoadOp,
+    RenderPass, RenderPassCreateInfo, StoreOp, Subpass, SubpassDescription,
+};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Vec3};
+use vulkano_rs_common::mesh::cube;
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+const GRID: u32 = 8;
+const SPACING: f32 = 2.5;
+// Both views in one pass: bit i of a view mask stands for layer i of the attachments
+const VIEW_MASK: u32 = 0b11;
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Stereo {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    uniform_buffer: StreamingBuffer,
+    cube: Mesh,
+    // Half the window wide, with a layer per eye
+    eyes: Option<(Arc<StorageImage>, Arc<Framebuffer>)>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    eye_distance: f32,
+    cross_eyed: bool,
+}
+
+impl Stereo {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+        let cube = Mesh::new(&allocators.memory, cube());
+
+        // The render pass macros have no way to give a view mask, so the render pass is
+        // described in full
+        let render_pass = RenderPass::new(
+            device.clone(),
+            RenderPassCreateInfo {
+                attachments: vec![
+                    AttachmentDescription {
+                        format: Some(renderer.swapchain.image_format()),
+                        samples: SampleCount::Sample1,
+                        load_op: LoadOp::Clear,
+                        store_op: StoreOp::Store,
+                        initial_layout: ImageLayout::Undefined,
+                        final_layout: ImageLayout::ColorAttachmentOptimal,
+                        ..Default::default()
+                    },
+                    AttachmentDescription {
+                        format: Some(Format::D16_UNORM),
+                        samples: SampleCount::Sample1,
+                        load_op: LoadOp::Clear,
+                        store_op: StoreOp::DontCare,
+                        initial_layout: ImageLayout::Undefined,
+                        final_layout: ImageLayout::DepthStencilAttachmentOptimal,
+                        ..Default::default()
+                    },
+                ],
+                subpasses: vec![SubpassDescription {
+                    view_mask: VIEW_MASK,
+                    color_attachments: vec![Some(AttachmentReference {
+                        attachment: 0,
+                        layout: ImageLayout::ColorAttachmentOptimal,
+                        ..Default::default()
+                    })],
+                    depth_stencil_attachment: Some(AttachmentReference {
+                        attachment: 1,
+                        layout: ImageLayout::DepthStencilAttachmentOptimal,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                // Tells the driver the two views see nearly the same thing, which lets it share
+                // work between them
+                correlated_view_masks: vec![VIEW_MASK],
+                ..Default::default()
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let extent = GRID as f32 * SPACING / 2.0;
+        Stereo {
+            camera: Camera::new(Vec3::new(0.0, 3.0, extent + 4.0), Vec3::new(0.0, 1.0, 0.0)),
+            render_pass,
+            pipeline,
+            uniform_buffer: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            cube,
+            eyes: None,
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            eye_distance: 0.065,
+            cross_eyed: false,
+        }
+    }
+
+    fn draw_scene(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        let offset = (GRID - 1) as f32 / 2.0;
+        let objects = (0..GRID * GRID)
+            .map(|i| {
+                let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+                let height = 0.3 + ((x * 1.3 + z * 2.1).sin() * 0.5 + 0.5) * 1.5;
+                (
+                    Vec3::new((x - offset) * SPACING, height, (z - offset) * SPACING),
+                    Vec3::new(0.4, height, 0.4),
+                    [x / GRID as f32, 0.6, z / GRID as f32, 1.0],
+                )
+            })
+            .chain([(
+                Vec3::new(0.0, -0.1, 0.0),
+                Vec3::new(
+                    GRID as f32 * SPACING / 2.0 + 1.0,
+                    0.1,
+                    GRID as f32 * SPACING / 2.0 + 1.0,
+                ),
+                [0.35, 0.35, 0.35, 1.0],
+            )]);
+
+        for (center, scale, color) in objects {
+            builder.push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    center: center.extend(0.0).to_array(),
+                    scale: scale.extend(0.0).to_array(),
+                    color,
+                },
+            );
+            self.cube.draw(builder);
+        }
+    }
+}
+
+impl App for Stereo {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+        let eye_width = (width / 2).max(1);
+        self.viewport.dimensions = [eye_width as f32, height as f32];
+
+        // Multiview renders to layers of array images, one per view
+        let dimensions = ImageDimensions::Dim2d {
+            width: eye_width,
+            height,
+            array_layers: 2,
+        };
+        let queue_family_index = renderer.queue().queue_family_index();
+        let eyes = StorageImage::with_usage(
+            &self.allocators.memory,
+            dimensions,
+            renderer.swapchain.image_format(),
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+            ImageCreateFlags::empty(),
+            [queue_family_index],
+        )
+            .unwrap();
+        let depth = StorageImage::with_usage(
+            &self.allocators.memory,
+            dimensions,
+            Format::D16_UNORM,
+            ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            ImageCreateFlags::empty(),
+            [queue_family_index],
+        )
+            .unwrap();
+
+        // The views cover both layers, the framebuffer itself has a single layer as the view
+        // mask picks the layers
+        let framebuffer = Framebuffer::new(
+            self.render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![
+                    ImageView::new_default(eyes.clone()).unwrap(),
+                    ImageView::new_default(depth).unwrap(),
+                ],
+                ..Default::default()
+            },
+        )
+            .unwrap();
+
+        self.eyes = Some((eyes, framebuffer));
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let (eyes, framebuffer) = self.eyes.clone().unwrap();
+        let [eye_width, height] = self.viewport.dimensions;
+
+        // Each eye sits half the eye distance to the side of the camera. Moving an eye to the
+        // left moves the world to the right in its view
+        let view = self.camera.view();
+        let projection = self.camera.projection(eye_width / height);
+        let half = self.eye_distance / 2.0;
+        let eye = |shift: f32| {
+            (projection * Mat4::from_translation(Vec3::new(shift, 0.0, 0.0)) * view)
+                .to_cols_array_2d()
+        };
+        let uniform_subbuffer = self.uniform_buffer.write(vs::Eyes {
+            view_projection: [eye(half), eye(-half)],
+        });
+
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [WriteDescriptorSet::buffer(0, uniform_subbuffer)],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.1, 0.1, 0.15, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            );
+        // Recorded once, drawn into both layers
+        self.draw_scene(&mut builder);
+        builder.end_render_pass().unwrap();
+
+        // Layer 0 holds the left eye and layer 1 the right one. Crossing them puts each eye's
+        // view on the side of the other eye, to be seen by crossing the eyes
+        let swapchain_image = renderer.images[image_index as usize].clone();
+        let (eye_width, height) = (eye_width as u32, height as u32);
+        let regions = (0..2u32)
+            .map(|layer| {
+                let side = if self.cross_eyed { 1 - layer } else { layer };
+                ImageBlit {
+                    src_subresource: ImageSubresourceLayers {
+                        array_layers: layer..layer + 1,
+                        ..eyes.subresource_layers()
+                    },
+                    src_offsets: [[0, 0, 0], [eye_width, height, 1]],
+                    dst_subresource: swapchain_image.subresource_layers(),
+                    dst_offsets: [
+                        [side * eye_width, 0, 0],
+                        [(side + 1) * eye_width, height, 1],
+                    ],
+                    ..Default::default()
+                }
+            })
+            .collect();
+        builder
+            .blit_image(BlitImageInfo {
+                regions,
+                ..BlitImageInfo::images(eyes, swapchain_image)
+            })
+            .unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Stereo").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.eye_distance, 0.0..=0.5).text("eye distance"));
+            ui.checkbox(&mut self.cross_eyed, "cross-eyed");
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run_with_features(
+        "vulkano-rs-guide-43",
+        DeviceExtensions::empty(),
+        Features {
+            multiview: true,
+            ..Features::empty()
+        },
+        Stereo::new,
+    );
+}
@@ -0,0 +1,300 @@
+//Dynamic viewport and scissor: one pipeline for every window size, next to a pipeline with the
+//viewport baked in that has to be built again whenever it changes
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::{DeviceExtensions, DeviceOwned};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Scissor, Viewport, ViewportState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::types::PosColor;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    // Viewport and scissor are part of the pipeline, like every other piece of its state
+    Baked,
+    // Both are left out of the pipeline and set in the command buffer instead
+    Dynamic,
+}
+
+struct ViewportScissor {
+    render_pass: Arc<RenderPass>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[PosColor]>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    // Built once and good for any window size
+    dynamic_pipeline: Arc<GraphicsPipeline>,
+    // Built for one window size and inset, which are kept to notice when they change
+    baked_pipeline: Option<(Arc<GraphicsPipeline>, [u32; 2], f32)>,
+    // How often the baked pipeline was built, and how long the last build took
+    rebuilds: u32,
+    rebuild_millis: f64,
+    // Parameters exposed in the overlay
+    state: State,
+    // Fraction of the window cut off on every side by the scissor
+    inset: f32,
+}
+
+impl ViewportScissor {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [
+                PosColor {
+                    position: [0.0, -0.9],
+                    color: [1.0, 0.2, 0.2],
+                },
+                PosColor {
+                    position: [0.9, 0.9],
+                    color: [0.2, 1.0, 0.2],
+                },
+                PosColor {
+                    position: [-0.9, 0.9],
+                    color: [0.2, 0.2, 1.0],
+                },
+            ],
+        )
+            .expect("failed to create vertex buffer");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let dynamic_pipeline = build_pipeline(
+            &render_pass,
+            &vs,
+            &fs,
+            ViewportState::viewport_dynamic_scissor_dynamic(1),
+        );
+
+        ViewportScissor {
+            render_pass,
+            vs,
+            fs,
+            allocators,
+            vertex_buffer,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            dynamic_pipeline,
+            baked_pipeline: None,
+            rebuilds: 0,
+            rebuild_millis: 0.0,
+            state: State::Dynamic,
+            inset: 0.1,
+        }
+    }
+
+    // The whole window for the viewport, and a rectangle `inset` away from its edges for the
+    // scissor. Only the pixels inside both are drawn
+    fn viewport_scissor(&self) -> (Viewport, Scissor) {
+        let [width, height] = self.extent;
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        };
+        let [inset_x, inset_y] = [width, height].map(|size| (size as f32 * self.inset) as u32);
+        let scissor = Scissor {
+            origin: [inset_x, inset_y],
+            dimensions: [
+                width.saturating_sub(2 * inset_x).max(1),
+                height.saturating_sub(2 * inset_y).max(1),
+            ],
+        };
+        (viewport, scissor)
+    }
+}
+
+// The two pipelines only differ in their viewport state
+fn build_pipeline(
+    render_pass: &Arc<RenderPass>,
+    vs: &ShaderModule,
+    fs: &ShaderModule,
+    viewport_state: ViewportState,
+) -> Arc<GraphicsPipeline> {
+    GraphicsPipeline::start()
+        .vertex_input_state(PosColor::per_vertex())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(viewport_state)
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(render_pass.device().clone())
+        .expect("failed to create graphics pipeline")
+}
+
+impl App for ViewportScissor {
+    fn resize(&mut self, renderer: &Renderer) {
+        self.extent = renderer.swapchain.image_extent();
+
+        // The framebuffers follow the swapchain images either way. With dynamic state they are
+        // all there is to recreate
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let (viewport, scissor) = self.viewport_scissor();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.05, 0.05, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap();
+
+        match self.state {
+            State::Baked => {
+                // Any change to the window size or the inset makes the pipeline stale. Building
+                // one compiles its shaders for the driver, which is why it is avoided per frame
+                let stale = match &self.baked_pipeline {
+                    Some((_, extent, inset)) => *extent != self.extent || *inset != self.inset,
+                    None => true,
+                };
+                if stale {
+                    let start = Instant::now();
+                    let pipeline = build_pipeline(
+                        &self.render_pass,
+                        &self.vs,
+                        &self.fs,
+                        ViewportState::viewport_fixed_scissor_fixed([(viewport, scissor)]),
+                    );
+                    self.rebuild_millis = start.elapsed().as_secs_f64() * 1000.0;
+                    self.rebuilds += 1;
+                    self.baked_pipeline = Some((pipeline, self.extent, self.inset));
+                }
+
+                let (pipeline, _, _) = self.baked_pipeline.as_ref().unwrap();
+                builder.bind_pipeline_graphics(pipeline.clone());
+            }
+            State::Dynamic => {
+                // Setting them costs no more than any other command, any time and as often as
+                // needed
+                builder
+                    .bind_pipeline_graphics(self.dynamic_pipeline.clone())
+                    .set_viewport(0, [viewport])
+                    .set_scissor(0, [scissor]);
+            }
+        }
+
+        builder
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .draw(self.vertex_buffer.len() as u32, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Viewport and scissor").show(ctx, |ui| {
+            ui.radio_value(&mut self.state, State::Baked, "baked into the pipeline");
+            ui.radio_value(&mut self.state, State::Dynamic, "dynamic state");
+            ui.add(egui::Slider::new(&mut self.inset, 0.0..=0.45).text("scissor inset"));
+            ui.label(format!("baked pipeline builds: {}", self.rebuilds));
+            if self.rebuilds > 0 {
+                ui.label(format!("last build: {:.2} ms", self.rebuild_millis));
+            }
+            ui.label("Resize the window or drag the slider to make the baked pipeline stale");
+        });
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-44",
+        DeviceExtensions::empty(),
+        ViewportScissor::new,
+    );
+}
@@ -0,0 +1,20 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 center;
+    vec4 scale;
+    vec4 color;
+} pc;
+
+void main() {
+    // Lit from both sides, since the quads are seen from either
+    float diffuse = abs(dot(normalize(v_normal), normalize(vec3(0.4, 1.0, 0.6))));
+    // The alpha is what the blend state weighs this color against the one already in
+    // the image with
+    f_color = vec4(pc.color.rgb * (diffuse * 0.6 + 0.4), pc.color.a);
+}
@@ -0,0 +1,410 @@
+//Alpha blending: translucent quads blended over an opaque scene, sorted back to front on the CPU
+//every frame, with the artifacts that show up when they aren't
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthState, DepthStencilState};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, StateMode};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::cube;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+// A square from -1 to 1 in the XY plane, facing +Z. It is drawn from both sides, so there is no
+// back face to cull
+fn quad() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let normal = [0.0, 0.0, 1.0];
+    let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .map(|(x, y)| PosNormalUv {
+            position: [x, y, 0.0],
+            normal,
+            ..Default::default()
+        })
+        .to_vec();
+    (vertices, vec![0, 1, 2, 0, 2, 3])
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Order {
+    // Farthest first, so every quad is blended over everything behind it
+    BackToFront,
+    // In the order they were created in, whatever the camera does
+    Unsorted,
+    // Nearest first, the worst case: each quad ends up under the ones behind it
+    FrontToBack,
+}
+
+// A translucent quad, standing on the floor
+struct Pane {
+    center: Vec3,
+    color: [f32; 3],
+}
+
+struct Blending {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    opaque_pipeline: Arc<GraphicsPipeline>,
+    // The same blending pipeline without and with depth writes
+    translucent_pipelines: [Arc<GraphicsPipeline>; 2],
+    allocators: Allocators,
+    cube: Mesh,
+    quad: Mesh,
+    panes: Vec<Pane>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    // Parameters exposed in the overlay
+    order: Order,
+    depth_write: bool,
+    alpha: f32,
+}
+
+impl Blending {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+        let cube = Mesh::new(&allocators.memory, cube());
+        let quad = Mesh::new(&allocators.memory, quad());
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = || {
+            GraphicsPipeline::start()
+                .vertex_input_state(PosNormalUv::per_vertex())
+                .vertex_shader(vs.entry_point("main").unwrap(), ())
+                .input_assembly_state(InputAssemblyState::new())
+                .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+                .fragment_shader(fs.entry_point("main").unwrap(), ())
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        };
+
+        let opaque_pipeline = pipeline()
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // Blending mixes each fragment into the image as color * alpha + destination * (1 -
+        // alpha). The depth test still hides the quads behind opaque objects, but a quad
+        // writing its depth would also hide the quads behind it that are drawn later
+        let translucent_pipelines = [false, true].map(|write| {
+            pipeline()
+                .color_blend_state(ColorBlendState::new(1).blend_alpha())
+                .depth_stencil_state(DepthStencilState {
+                    depth: Some(DepthState {
+                        enable_dynamic: false,
+                        write_enable: StateMode::Fixed(write),
+                        compare_op: StateMode::Fixed(CompareOp::Less),
+                    }),
+                    ..DepthStencilState::disabled()
+                })
+                .rasterization_state(RasterizationState::new().cull_mode(CullMode::None))
+                .build(device.clone())
+                .expect("failed to create graphics pipeline")
+        });
+
+        // A staggered column of panes leading away from the camera, created nearest first so
+        // that leaving them unsorted is wrong from the start
+        let colors = [
+            [1.0, 0.2, 0.2],
+            [1.0, 0.8, 0.1],
+            [0.2, 0.9, 0.3],
+            [0.1, 0.7, 1.0],
+            [0.5, 0.3, 1.0],
+            [1.0, 0.3, 0.8],
+        ];
+        let panes = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| Pane {
+                center: Vec3::new(
+                    if i % 2 == 0 { -0.6 } else { 0.6 },
+                    1.0,
+                    3.0 - i as f32 * 1.5,
+                ),
+                color,
+            })
+            .collect();
+
+        Blending {
+            camera: Camera::new(Vec3::new(1.5, 2.0, 8.0), Vec3::new(0.0, 1.0, 0.0)),
+            render_pass,
+            opaque_pipeline,
+            translucent_pipelines,
+            allocators,
+            cube,
+            quad,
+            panes,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            order: Order::BackToFront,
+            depth_write: false,
+            alpha: 0.5,
+        }
+    }
+}
+
+impl App for Blending {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.extent = extent;
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.extent;
+        let view_projection = self
+            .camera
+            .view_projection(width as f32 / height as f32)
+            .to_cols_array_2d();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.02, 0.02, 0.02, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [width as f32, height as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            );
+
+        let draw = |builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+                    pipeline: &Arc<GraphicsPipeline>,
+                    mesh: &Mesh,
+                    center: Vec3,
+                    scale: Vec3,
+                    color: [f32; 4]| {
+            builder.push_constants(
+                pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    view_projection,
+                    center: center.extend(0.0).to_array(),
+                    scale: scale.extend(0.0).to_array(),
+                    color,
+                },
+            );
+            mesh.draw(builder);
+        };
+
+        // Everything opaque goes first, in any order, so the panes have all of it to blend over
+        builder.bind_pipeline_graphics(self.opaque_pipeline.clone());
+        draw(
+            &mut builder,
+            &self.opaque_pipeline,
+            &self.cube,
+            Vec3::new(0.0, -0.1, 0.0),
+            Vec3::new(6.0, 0.1, 6.0),
+            [0.35, 0.35, 0.35, 1.0],
+        );
+        draw(
+            &mut builder,
+            &self.opaque_pipeline,
+            &self.cube,
+            Vec3::new(0.0, 0.5, -6.0),
+            Vec3::new(3.0, 0.5, 0.3),
+            [0.8, 0.8, 0.75, 1.0],
+        );
+
+        // The blend is not commutative, so the panes have to reach the image in the right order.
+        // Sorting by distance from the camera is redone every frame because moving the camera
+        // changes it
+        let mut panes: Vec<&Pane> = self.panes.iter().collect();
+        let distance = |pane: &Pane| pane.center.distance_squared(self.camera.position);
+        match self.order {
+            Order::BackToFront => panes.sort_by(|a, b| distance(b).total_cmp(&distance(a))),
+            Order::Unsorted => {}
+            Order::FrontToBack => panes.sort_by(|a, b| distance(a).total_cmp(&distance(b))),
+        }
+
+        let pipeline = &self.translucent_pipelines[self.depth_write as usize];
+        builder.bind_pipeline_graphics(pipeline.clone());
+        for pane in panes {
+            let [r, g, b] = pane.color;
+            draw(
+                &mut builder,
+                pipeline,
+                &self.quad,
+                pane.center,
+                Vec3::ONE,
+                [r, g, b, self.alpha],
+            );
+        }
+
+        builder.end_render_pass().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Alpha blending").show(ctx, |ui| {
+            ui.radio_value(&mut self.order, Order::BackToFront, "back to front");
+            ui.radio_value(&mut self.order, Order::Unsorted, "unsorted");
+            ui.radio_value(&mut self.order, Order::FrontToBack, "front to back");
+            ui.checkbox(&mut self.depth_write, "write depth for translucent quads");
+            ui.add(egui::Slider::new(&mut self.alpha, 0.0..=1.0).text("alpha"));
+            ui.label(
+                "With depth writes on, a quad drawn too early cuts holes into the ones behind it",
+            );
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-45",
+        DeviceExtensions::empty(),
+        Blending::new,
+    );
+}
@@ -0,0 +1,18 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 center;
+    vec4 scale;
+    vec4 color;
+} pc;
+
+void main() {
+    v_normal = normal;
+    gl_Position = pc.view_projection * vec4(pc.center.xyz + position * pc.scale.xyz, 1.0);
+}
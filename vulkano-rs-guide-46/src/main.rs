@@ -0,0 +1,471 @@
+//Stencil outlines: the selected object marks its pixels in the stencil buffer, then a scaled-up
+//copy of it is drawn only where nothing was marked, leaving a rim around it
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::DeviceExtensions;
+use vulkano::format::{ClearValue, Format, FormatFeatures};
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::{
+    CompareOp, DepthState, DepthStencilState, StencilOp, StencilOpState, StencilOps, StencilState,
+};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, StateMode};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::cube;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+// The value the selected object leaves in the stencil buffer
+const SELECTED: u32 = 1;
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+// The outline is a flat color, unlit
+mod outline_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/outline_fs.frag",
+    }
+}
+
+// The first depth/stencil format the device can render to. Unlike depth-only formats, none of
+// these is guaranteed to be supported
+fn depth_stencil_format(physical_device: &PhysicalDevice) -> Format {
+    [
+        Format::D24_UNORM_S8_UINT,
+        Format::D32_SFLOAT_S8_UINT,
+        Format::D16_UNORM_S8_UINT,
+    ]
+    .into_iter()
+    .find(|&format| {
+        physical_device
+            .format_properties(format)
+            .map_or(false, |properties| {
+                properties
+                    .optimal_tiling_features
+                    .intersects(FormatFeatures::DEPTH_STENCIL_ATTACHMENT)
+            })
+    })
+    .expect("no depth/stencil format supported")
+}
+
+// The same stencil behaviour for front and back faces
+fn stencil(ops: StencilOps, write_mask: u32) -> Option<StencilState> {
+    let state = StencilOpState {
+        ops: StateMode::Fixed(ops),
+        compare_mask: StateMode::Fixed(u32::MAX),
+        write_mask: StateMode::Fixed(write_mask),
+        reference: StateMode::Fixed(SELECTED),
+    };
+    Some(StencilState {
+        enable_dynamic: false,
+        front: state.clone(),
+        back: state,
+    })
+}
+
+// Objects standing on the floor, one of which is selected at a time
+const OBJECTS: [([f32; 3], [f32; 3], [f32; 3]); 5] = [
+    ([-3.0, 0.6, 0.0], [0.6, 0.6, 0.6], [0.9, 0.4, 0.3]),
+    ([-1.0, 1.0, -1.5], [0.5, 1.0, 0.5], [0.4, 0.8, 0.4]),
+    ([1.0, 0.4, 0.5], [0.8, 0.4, 0.4], [0.3, 0.5, 0.9]),
+    ([3.0, 0.8, -0.5], [0.4, 0.8, 0.8], [0.9, 0.8, 0.3]),
+    ([0.0, 0.5, 2.5], [1.5, 0.5, 0.2], [0.7, 0.7, 0.7]),
+];
+
+struct Outline {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    depth_stencil_format: Format,
+    scene_pipeline: Arc<GraphicsPipeline>,
+    mark_pipeline: Arc<GraphicsPipeline>,
+    // The outline with and without the depth test, to hide it behind other objects or not
+    outline_pipelines: [Arc<GraphicsPipeline>; 2],
+    allocators: Allocators,
+    cube: Mesh,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    // Holding a key sends repeated presses, only the first one moves the selection
+    tab_held: bool,
+    // Parameters exposed in the overlay
+    selected: usize,
+    width: f32,
+    color: [f32; 3],
+    through_objects: bool,
+}
+
+impl Outline {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+        let cube = Mesh::new(&allocators.memory, cube());
+
+        let depth_stencil_format = depth_stencil_format(device.physical_device());
+        tracing::info!("depth/stencil format: {depth_stencil_format:?}");
+
+        // The stencil buffer shares an attachment with the depth buffer. It is cleared along with
+        // it, and doesn't have to outlive the render pass either
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth_stencil: {
+                    load: Clear,
+                    store: DontCare,
+                    format: depth_stencil_format,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth_stencil},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let outline_fs = outline_fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = |fs: &Arc<ShaderModule>, depth_stencil_state: DepthStencilState| {
+            GraphicsPipeline::start()
+                .vertex_input_state(PosNormalUv::per_vertex())
+                .vertex_shader(vs.entry_point("main").unwrap(), ())
+                .input_assembly_state(InputAssemblyState::new())
+                .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+                .fragment_shader(fs.entry_point("main").unwrap(), ())
+                .depth_stencil_state(depth_stencil_state)
+                .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .expect("failed to create graphics pipeline")
+        };
+
+        // Everything but the selected object leaves the stencil buffer alone
+        let scene_pipeline = pipeline(&fs, DepthStencilState::simple_depth_test());
+
+        // The selected object replaces the stencil value with SELECTED wherever it covers a
+        // pixel, even where it fails the depth test, so the marked area is its whole silhouette
+        // and not just the parts in view
+        let mark_pipeline = pipeline(
+            &fs,
+            DepthStencilState {
+                stencil: stencil(
+                    StencilOps {
+                        fail_op: StencilOp::Keep,
+                        pass_op: StencilOp::Replace,
+                        depth_fail_op: StencilOp::Replace,
+                        compare_op: CompareOp::Always,
+                    },
+                    u32::MAX,
+                ),
+                ..DepthStencilState::simple_depth_test()
+            },
+        );
+
+        // The scaled-up copy only passes the stencil test where the value isn't SELECTED, which
+        // is the rim around the silhouette. It never writes the stencil or depth buffer itself
+        let outline_pipelines = [true, false].map(|depth_test| {
+            pipeline(
+                &outline_fs,
+                DepthStencilState {
+                    depth: depth_test.then_some(DepthState {
+                        enable_dynamic: false,
+                        write_enable: StateMode::Fixed(false),
+                        compare_op: StateMode::Fixed(CompareOp::Less),
+                    }),
+                    stencil: stencil(
+                        StencilOps {
+                            fail_op: StencilOp::Keep,
+                            pass_op: StencilOp::Keep,
+                            depth_fail_op: StencilOp::Keep,
+                            compare_op: CompareOp::NotEqual,
+                        },
+                        0,
+                    ),
+                    ..DepthStencilState::disabled()
+                },
+            )
+        });
+
+        Outline {
+            camera: Camera::new(Vec3::new(0.0, 4.0, 9.0), Vec3::new(0.0, 0.5, 0.0)),
+            render_pass,
+            depth_stencil_format,
+            scene_pipeline,
+            mark_pipeline,
+            outline_pipelines,
+            allocators,
+            cube,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            tab_held: false,
+            selected: 0,
+            width: 0.08,
+            color: [1.0, 0.6, 0.1],
+            through_objects: true,
+        }
+    }
+}
+
+impl App for Outline {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.extent = extent;
+
+        let depth_stencil_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, self.depth_stencil_format)
+                .unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_stencil_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.extent;
+        let view_projection = self
+            .camera
+            .view_projection(width as f32 / height as f32)
+            .to_cols_array_2d();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![
+                        Some([0.02, 0.02, 0.02, 1.0].into()),
+                        Some(ClearValue::DepthStencil((1.0, 0))),
+                    ],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [width as f32, height as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            );
+
+        let draw = |builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+                    pipeline: &Arc<GraphicsPipeline>,
+                    center: Vec3,
+                    scale: Vec3,
+                    color: [f32; 4]| {
+            builder
+                .bind_pipeline_graphics(pipeline.clone())
+                .push_constants(
+                    pipeline.layout().clone(),
+                    0,
+                    vs::PushConstants {
+                        view_projection,
+                        center: center.extend(0.0).to_array(),
+                        scale: scale.extend(0.0).to_array(),
+                        color,
+                    },
+                );
+            self.cube.draw(builder);
+        };
+
+        // The floor and every object, the selected one marking the stencil buffer as it goes
+        draw(
+            &mut builder,
+            &self.scene_pipeline,
+            Vec3::new(0.0, -0.1, 0.0),
+            Vec3::new(6.0, 0.1, 6.0),
+            [0.35, 0.35, 0.35, 1.0],
+        );
+        for (i, (center, scale, [r, g, b])) in OBJECTS.into_iter().enumerate() {
+            let pipeline = if i == self.selected {
+                &self.mark_pipeline
+            } else {
+                &self.scene_pipeline
+            };
+            draw(
+                &mut builder,
+                pipeline,
+                Vec3::from(center),
+                Vec3::from(scale),
+                [r, g, b, 1.0],
+            );
+        }
+
+        // The selected object again, grown by the outline width on every side. It has to come
+        // after everything that could still mark the stencil buffer
+        let (center, scale, _) = OBJECTS[self.selected];
+        let [r, g, b] = self.color;
+        draw(
+            &mut builder,
+            &self.outline_pipelines[self.through_objects as usize],
+            Vec3::from(center),
+            Vec3::from(scale) + Vec3::splat(self.width),
+            [r, g, b, 1.0],
+        );
+
+        builder.end_render_pass().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Stencil outline").show(ctx, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.selected, 0..=OBJECTS.len() - 1)
+                    .text("selected object (Tab)"),
+            );
+            ui.add(egui::Slider::new(&mut self.width, 0.0..=0.3).text("outline width"));
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut self.color);
+                ui.label("outline color");
+            });
+            ui.checkbox(&mut self.through_objects, "show outline through objects");
+        });
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state,
+                    virtual_keycode: Some(VirtualKeyCode::Tab),
+                    ..
+                },
+            ..
+        } = event
+        {
+            let pressed = *state == ElementState::Pressed;
+            if pressed && !self.tab_held {
+                self.selected = (self.selected + 1) % OBJECTS.len();
+            }
+            self.tab_held = pressed;
+        }
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-46",
+        DeviceExtensions::empty(),
+        Outline::new,
+    );
+}
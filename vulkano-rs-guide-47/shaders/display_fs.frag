@@ -0,0 +1,45 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D u_color;
+layout(set = 0, binding = 1) uniform sampler2D u_normals;
+// Integer images are read through an integer sampler type
+layout(set = 0, binding = 2) uniform usampler2D u_ids;
+
+layout(push_constant) uniform PushConstants {
+    uint target;
+} pc;
+
+// Neighboring IDs get very different colors, 0 stays black
+vec3 id_color(uint id) {
+    uint hash = id * 2654435761u;
+    vec3 color = vec3(hash >> 24, (hash >> 16) & 255u, (hash >> 8) & 255u) / 255.0;
+    return id == 0 ? vec3(0.0) : color;
+}
+
+void main() {
+    vec4 color = texture(u_color, v_tex_coord);
+    vec3 normal = texture(u_normals, v_tex_coord).xyz;
+    uint id = texture(u_ids, v_tex_coord).r;
+
+    switch (pc.target) {
+        case 0:
+            f_color = color;
+            break;
+        case 1:
+            f_color = vec4(normal * 0.5 + 0.5, 1.0);
+            break;
+        case 2:
+            f_color = vec4(id_color(id), 1.0);
+            break;
+        default:
+            // What the deferred chapter does with them: light the color with the
+            // normals, after the fact
+            float diffuse = max(dot(normal, normalize(vec3(0.4, 1.0, 0.6))), 0.0);
+            f_color = vec4(color.rgb * (diffuse * 0.8 + 0.2), 1.0);
+            break;
+    }
+}
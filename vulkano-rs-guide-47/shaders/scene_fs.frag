@@ -0,0 +1,22 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+
+// One output per color attachment of the subpass, matched by location
+layout(location = 0) out vec4 f_color;
+layout(location = 1) out vec4 f_normal;
+layout(location = 2) out uint f_id;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 center;
+    vec4 scale;
+    vec4 color;
+    uint id;
+} pc;
+
+void main() {
+    f_color = pc.color;
+    f_normal = vec4(normalize(v_normal), 0.0);
+    f_id = pc.id;
+}
@@ -0,0 +1,533 @@
+//Multiple render targets: one fragment shader writing color, normals and object IDs to three
+//attachments at once, shown side by side in the quadrants of the screen
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexInputState};
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::cube;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+const COLOR_FORMAT: Format = Format::R8G8B8A8_UNORM;
+const NORMAL_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+// An integer format, so IDs come back exactly as written. It can't be blended or filtered
+const ID_FORMAT: Format = Format::R32_UINT;
+const DEPTH_FORMAT: Format = Format::D16_UNORM;
+
+const GRID: u32 = 6;
+const SPACING: f32 = 2.5;
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+mod scene_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/scene_vs.vert",
+    }
+}
+
+mod scene_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/scene_fs.frag",
+    }
+}
+
+mod fullscreen_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/fullscreen_vs.vert",
+    }
+}
+
+mod display_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/display_fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Color,
+    Normals,
+    Ids,
+    Lit,
+}
+
+const TARGETS: [(Target, &str); 4] = [
+    (Target::Color, "color"),
+    (Target::Normals, "normals"),
+    (Target::Ids, "object IDs"),
+    (Target::Lit, "lit"),
+];
+
+// Every image that depends on the window size
+struct Targets {
+    color: Arc<ImageView<AttachmentImage>>,
+    normals: Arc<ImageView<AttachmentImage>>,
+    ids: Arc<ImageView<AttachmentImage>>,
+    framebuffer: Arc<Framebuffer>,
+}
+
+struct RenderTargets {
+    camera: Camera,
+    allocators: Allocators,
+    cube: Mesh,
+    sampler: Arc<Sampler>,
+    scene_render_pass: Arc<RenderPass>,
+    display_render_pass: Arc<RenderPass>,
+    scene_pipeline: Arc<GraphicsPipeline>,
+    display_pipeline: Arc<GraphicsPipeline>,
+    targets: Option<Targets>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    // Parameters exposed in the overlay
+    fullscreen: Option<Target>,
+}
+
+impl RenderTargets {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+        let cube = Mesh::new(&allocators.memory, cube());
+
+        // Nearest filtering throughout, since the ID image can't be filtered any other way
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        )
+            .expect("failed to create sampler");
+
+        // The three color attachments are all written by the same subpass, in the order they
+        // are listed in `color`, which is the order of the shader's output locations
+        let scene_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: COLOR_FORMAT,
+                    samples: 1,
+                },
+                normals: {
+                    load: Clear,
+                    store: Store,
+                    format: NORMAL_FORMAT,
+                    samples: 1,
+                },
+                ids: {
+                    load: Clear,
+                    store: Store,
+                    format: ID_FORMAT,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: DEPTH_FORMAT,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color, normals, ids],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let display_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let scene_subpass = Subpass::from(scene_render_pass.clone(), 0).unwrap();
+        let scene_vs = scene_vs::load(device.clone()).expect("failed to create shader module");
+        let scene_fs = scene_fs::load(device.clone()).expect("failed to create shader module");
+        // The blend state needs an entry per color attachment. None of them blends, which the
+        // integer attachment wouldn't allow anyway
+        let scene_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(scene_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(scene_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .color_blend_state(ColorBlendState::new(scene_subpass.num_color_attachments()))
+            .render_pass(scene_subpass)
+            .build(device.clone())
+            .expect("failed to create scene pipeline");
+
+        let fullscreen_vs =
+            fullscreen_vs::load(device.clone()).expect("failed to create shader module");
+        let display_fs = display_fs::load(device.clone()).expect("failed to create shader module");
+        let display_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(fullscreen_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(display_fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(display_render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create display pipeline");
+
+        RenderTargets {
+            camera: Camera::new(Vec3::new(0.0, 5.0, 12.0), Vec3::ZERO),
+            allocators,
+            cube,
+            sampler,
+            scene_render_pass,
+            display_render_pass,
+            scene_pipeline,
+            display_pipeline,
+            targets: None,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            fullscreen: None,
+        }
+    }
+
+    // The floor and a field of cubes, each with its own ID
+    fn draw_scene(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        let [width, height] = self.extent;
+        let view_projection = self
+            .camera
+            .view_projection(width as f32 / height as f32)
+            .to_cols_array_2d();
+        let layout = self.scene_pipeline.layout();
+
+        let offset = (GRID - 1) as f32 / 2.0;
+        let floor = (
+            Vec3::new(0.0, -0.1, 0.0),
+            Vec3::new(
+                GRID as f32 * SPACING / 2.0 + 1.0,
+                0.1,
+                GRID as f32 * SPACING / 2.0 + 1.0,
+            ),
+            [0.5, 0.5, 0.5, 1.0],
+        );
+        let cubes = (0..GRID * GRID).map(|i| {
+            let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+            let size = 0.4 + 0.3 * (x * 1.3 + z * 2.1).sin().abs();
+            (
+                Vec3::new((x - offset) * SPACING, size, (z - offset) * SPACING),
+                Vec3::splat(size),
+                [
+                    0.3 + 0.6 * x / GRID as f32,
+                    0.6,
+                    0.3 + 0.6 * z / GRID as f32,
+                    1.0,
+                ],
+            )
+        });
+
+        // IDs start at 1, the clear value 0 is the background
+        for (id, (center, scale, color)) in (1..).zip([floor].into_iter().chain(cubes)) {
+            builder.push_constants(
+                layout.clone(),
+                0,
+                scene_vs::PushConstants {
+                    view_projection,
+                    center: center.extend(0.0).to_array(),
+                    scale: scale.extend(0.0).to_array(),
+                    color,
+                    id,
+                },
+            );
+            self.cube.draw(builder);
+        }
+    }
+}
+
+impl App for RenderTargets {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.extent = extent;
+
+        // Written by the scene pass and sampled by the display pass
+        let target = |format| {
+            ImageView::new_default(
+                AttachmentImage::sampled(&self.allocators.memory, extent, format).unwrap(),
+            )
+                .unwrap()
+        };
+        let color = target(COLOR_FORMAT);
+        let normals = target(NORMAL_FORMAT);
+        let ids = target(ID_FORMAT);
+        let depth = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, DEPTH_FORMAT).unwrap(),
+        )
+            .unwrap();
+
+        let framebuffer = Framebuffer::new(
+            self.scene_render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![color.clone(), normals.clone(), ids.clone(), depth],
+                ..Default::default()
+            },
+        )
+            .unwrap();
+        self.targets = Some(Targets {
+            color,
+            normals,
+            ids,
+            framebuffer,
+        });
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.display_render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.extent;
+        let targets = self.targets.as_ref().unwrap();
+
+        let display_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.display_pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(
+                    0,
+                    targets.color.clone(),
+                    self.sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    targets.normals.clone(),
+                    self.sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    2,
+                    targets.ids.clone(),
+                    self.sampler.clone(),
+                ),
+            ],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // Each attachment gets its own clear value, of its own type
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![
+                        Some([0.1, 0.1, 0.12, 1.0].into()),
+                        Some([0.0, 0.0, 0.0, 0.0].into()),
+                        Some([0u32; 4].into()),
+                        Some(1f32.into()),
+                    ],
+                    ..RenderPassBeginInfo::framebuffer(targets.framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [width as f32, height as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            )
+            .bind_pipeline_graphics(self.scene_pipeline.clone());
+        self.draw_scene(&mut builder);
+        builder.end_render_pass().unwrap();
+
+        // Either one target over the whole window, or all four with each in its own quadrant
+        let [half_width, half_height] = [width as f32 / 2.0, height as f32 / 2.0];
+        let views: Vec<(Target, Viewport)> = match self.fullscreen {
+            Some(target) => vec![(
+                target,
+                Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [width as f32, height as f32],
+                    depth_range: 0.0..1.0,
+                },
+            )],
+            None => TARGETS
+                .iter()
+                .enumerate()
+                .map(|(i, &(target, _))| {
+                    let (column, row) = ((i % 2) as f32, (i / 2) as f32);
+                    (
+                        target,
+                        Viewport {
+                            origin: [column * half_width, row * half_height],
+                            dimensions: [half_width, half_height],
+                            depth_range: 0.0..1.0,
+                        },
+                    )
+                })
+                .collect(),
+        };
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(self.display_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.display_pipeline.layout().clone(),
+                0,
+                display_set,
+            );
+        for (target, viewport) in views {
+            builder
+                .set_viewport(0, [viewport])
+                .push_constants(
+                    self.display_pipeline.layout().clone(),
+                    0,
+                    display_fs::PushConstants {
+                        target: target as u32,
+                    },
+                )
+                .draw(3, 1, 0, 0)
+                .unwrap();
+        }
+        builder.end_render_pass().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Render targets").show(ctx, |ui| {
+            ui.radio_value(&mut self.fullscreen, None, "all four");
+            for (target, name) in TARGETS {
+                ui.radio_value(&mut self.fullscreen, Some(target), name);
+            }
+            ui.label("Top row: color and normals. Bottom row: object IDs and lit");
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-47",
+        DeviceExtensions::empty(),
+        RenderTargets::new,
+    );
+}
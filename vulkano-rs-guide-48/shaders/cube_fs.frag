@@ -0,0 +1,14 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+// The image the first pass rendered, sampled like any other texture
+layout(set = 0, binding = 0) uniform sampler2D u_texture;
+
+void main() {
+    float diffuse = max(dot(normalize(v_normal), normalize(vec3(0.4, 1.0, 0.6))), 0.0);
+    f_color = vec4(texture(u_texture, v_tex_coord).rgb * (diffuse * 0.7 + 0.3), 1.0);
+}
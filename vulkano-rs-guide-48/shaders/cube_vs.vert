@@ -0,0 +1,19 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 tex_coord;
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec2 v_tex_coord;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    mat4 model;
+} pc;
+
+void main() {
+    v_normal = mat3(pc.model) * normal;
+    v_tex_coord = tex_coord;
+    gl_Position = pc.view_projection * pc.model * vec4(position, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec3 color;
+
+layout(location = 0) out vec3 v_color;
+
+layout(push_constant) uniform PushConstants {
+    float angle;
+} pc;
+
+void main() {
+    v_color = color;
+    mat2 rotation = mat2(cos(pc.angle), sin(pc.angle), -sin(pc.angle), cos(pc.angle));
+    gl_Position = vec4(rotation * position, 0.0, 1.0);
+}
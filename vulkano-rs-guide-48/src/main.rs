@@ -0,0 +1,438 @@
+//Render to texture: a spinning triangle is drawn into an offscreen image, which the main pass then
+//samples as the texture of a cube
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Vec3};
+use vulkano_rs_common::types::{PosColor, PosNormalUv};
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// The offscreen image doesn't follow the window, its size is up to us
+const TEXTURE_SIZE: u32 = 512;
+const TEXTURE_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+// A cube from -1 to 1 with the whole texture on each face
+fn textured_cube() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for axis in 0..3 {
+        for sign in [-1.0f32, 1.0] {
+            let mut normal = [0.0; 3];
+            normal[axis] = sign;
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let base = vertices.len() as u32;
+            for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let mut position = normal;
+                position[u] = a * sign;
+                position[v] = b;
+                vertices.push(PosNormalUv {
+                    position,
+                    normal,
+                    tex_coord: [(a + 1.0) / 2.0, (1.0 - b) / 2.0],
+                });
+            }
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    (vertices, indices)
+}
+
+mod triangle_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/triangle_vs.vert",
+    }
+}
+
+mod triangle_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/triangle_fs.frag",
+    }
+}
+
+mod cube_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/cube_vs.vert",
+    }
+}
+
+mod cube_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/cube_fs.frag",
+    }
+}
+
+struct RenderToTexture {
+    camera: Camera,
+    start: Instant,
+    allocators: Allocators,
+    triangle: Subbuffer<[PosColor]>,
+    cube_vertices: Subbuffer<[PosNormalUv]>,
+    cube_indices: Subbuffer<[u32]>,
+    texture_render_pass: Arc<RenderPass>,
+    main_render_pass: Arc<RenderPass>,
+    texture_framebuffer: Arc<Framebuffer>,
+    triangle_pipeline: Arc<GraphicsPipeline>,
+    cube_pipeline: Arc<GraphicsPipeline>,
+    texture_set: Arc<PersistentDescriptorSet>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    // Parameters exposed in the overlay
+    triangle_speed: f32,
+    cube_speed: f32,
+    background: [f32; 3],
+}
+
+impl RenderToTexture {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+        let triangle = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::VERTEX_BUFFER),
+            allocation_info(),
+            (0..3).map(|i| {
+                let angle = i as f32 * TAU / 3.0;
+                let mut color = [0.2; 3];
+                color[i] = 1.0;
+                PosColor {
+                    position: [angle.sin() * 0.8, -angle.cos() * 0.8],
+                    color,
+                }
+            }),
+        )
+            .expect("failed to create vertex buffer");
+        let (vertices, indices) = textured_cube();
+        let cube_vertices = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::VERTEX_BUFFER),
+            allocation_info(),
+            vertices,
+        )
+            .expect("failed to create vertex buffer");
+        let cube_indices = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::INDEX_BUFFER),
+            allocation_info(),
+            indices,
+        )
+            .expect("failed to create index buffer");
+
+        // Rendered to as a color attachment, then sampled, so it needs both usages. The
+        // contents have to be stored at the end of the pass for the second one to read them
+        let texture = ImageView::new_default(
+            AttachmentImage::sampled(
+                &allocators.memory,
+                [TEXTURE_SIZE, TEXTURE_SIZE],
+                TEXTURE_FORMAT,
+            )
+                .unwrap(),
+        )
+            .unwrap();
+
+        let texture_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: TEXTURE_FORMAT,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let main_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        // Unlike the swapchain images, there is only one texture, so only one framebuffer
+        let texture_framebuffer = Framebuffer::new(
+            texture_render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![texture.clone()],
+                ..Default::default()
+            },
+        )
+            .unwrap();
+
+        let triangle_vs =
+            triangle_vs::load(device.clone()).expect("failed to create shader module");
+        let triangle_fs =
+            triangle_fs::load(device.clone()).expect("failed to create shader module");
+        // The texture never changes size, so its viewport can be baked into the pipeline
+        let triangle_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosColor::per_vertex())
+            .vertex_shader(triangle_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+                Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [TEXTURE_SIZE as f32, TEXTURE_SIZE as f32],
+                    depth_range: 0.0..1.0,
+                },
+            ]))
+            .fragment_shader(triangle_fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(texture_render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let cube_vs = cube_vs::load(device.clone()).expect("failed to create shader module");
+        let cube_fs = cube_fs::load(device.clone()).expect("failed to create shader module");
+        let cube_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(cube_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(cube_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(main_render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // The descriptor set points at the image, not at what's in it, so it is written once and
+        // sees every frame's new contents
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::simple_repeat_linear())
+            .expect("failed to create sampler");
+        let texture_set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            cube_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(0, texture, sampler)],
+        )
+            .unwrap();
+
+        RenderToTexture {
+            camera: Camera::new(Vec3::new(0.0, 1.5, 4.0), Vec3::ZERO),
+            start: Instant::now(),
+            allocators,
+            triangle,
+            cube_vertices,
+            cube_indices,
+            texture_render_pass,
+            main_render_pass,
+            texture_framebuffer,
+            triangle_pipeline,
+            cube_pipeline,
+            texture_set,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            triangle_speed: 1.0,
+            cube_speed: 0.3,
+            background: [0.1, 0.1, 0.3],
+        }
+    }
+}
+
+impl App for RenderToTexture {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.extent = extent;
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.main_render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let time = self.start.elapsed().as_secs_f32();
+        let [width, height] = self.extent;
+        let [r, g, b] = self.background;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        // First pass: the triangle into the texture. For it, the image is in the color
+        // attachment layout
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([r, g, b, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(self.texture_framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(self.triangle_pipeline.clone())
+            .push_constants(
+                self.triangle_pipeline.layout().clone(),
+                0,
+                triangle_vs::PushConstants {
+                    angle: time * self.triangle_speed,
+                },
+            )
+            .bind_vertex_buffers(0, self.triangle.clone())
+            .draw(3, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        // Second pass: the cube, sampling the texture. Sampling needs the image in the shader
+        // read-only layout, and the triangle's writes finished and visible to the fragment
+        // shader. The command buffer builder sees the image go from attachment to descriptor
+        // and records the barrier with that layout transition between the two passes. In raw
+        // Vulkan it would be a pipeline barrier, or the first pass's final layout and a subpass
+        // dependency
+        let model = Mat4::from_rotation_y(time * self.cube_speed)
+            * Mat4::from_rotation_x(time * self.cube_speed * 0.7);
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.02, 0.02, 0.02, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [width as f32, height as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            )
+            .bind_pipeline_graphics(self.cube_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.cube_pipeline.layout().clone(),
+                0,
+                self.texture_set.clone(),
+            )
+            .push_constants(
+                self.cube_pipeline.layout().clone(),
+                0,
+                cube_vs::PushConstants {
+                    view_projection: self
+                        .camera
+                        .view_projection(width as f32 / height as f32)
+                        .to_cols_array_2d(),
+                    model: model.to_cols_array_2d(),
+                },
+            )
+            .bind_vertex_buffers(0, self.cube_vertices.clone())
+            .bind_index_buffer(self.cube_indices.clone())
+            .draw_indexed(self.cube_indices.len() as u32, 1, 0, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Render to texture").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.triangle_speed, -5.0..=5.0).text("triangle speed"));
+            ui.add(egui::Slider::new(&mut self.cube_speed, -2.0..=2.0).text("cube speed"));
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut self.background);
+                ui.label("texture background");
+            });
+            ui.label(format!(
+                "texture: {TEXTURE_SIZE}x{TEXTURE_SIZE} {TEXTURE_FORMAT:?}"
+            ));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-48",
+        DeviceExtensions::empty(),
+        RenderToTexture::new,
+    );
+}
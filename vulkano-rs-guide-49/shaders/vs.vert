@@ -0,0 +1,26 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 center;
+    vec4 scale;
+    vec4 color;
+    // Around the vertical axis, in radians
+    float angle;
+} pc;
+
+void main() {
+    mat3 rotation = mat3(
+        cos(pc.angle), 0.0, -sin(pc.angle),
+        0.0, 1.0, 0.0,
+        sin(pc.angle), 0.0, cos(pc.angle)
+    );
+    v_normal = rotation * normal;
+    vec3 world = pc.center.xyz + rotation * (position * pc.scale.xyz);
+    gl_Position = pc.view_projection * vec4(world, 1.0);
+}
@@ -0,0 +1,294 @@
+//Offline rendering: an animation drawn frame by frame into an offscreen image without a window,
+//and written out as numbered PNGs or piped into ffmpeg as a video
+
+use std::f32::consts::TAU;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::cube;
+use vulkano_rs_common::record::{self, Recorder};
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::wait;
+
+// Cubes circling the one in the middle
+const RING: u32 = 8;
+
+// A vertex and index buffer pair, drawn whole
+struct Mesh {
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+}
+
+impl Mesh {
+    fn new(
+        memory_allocator: &(impl MemoryAllocator + ?Sized),
+        (vertices, indices): (Vec<PosNormalUv>, Vec<u32>),
+    ) -> Self {
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+
+        Mesh {
+            vertices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::VERTEX_BUFFER),
+                allocation_info(),
+                vertices,
+            )
+                .expect("failed to create vertex buffer"),
+            indices: Buffer::from_iter(
+                memory_allocator,
+                buffer_info(BufferUsage::INDEX_BUFFER),
+                allocation_info(),
+                indices,
+            )
+                .expect("failed to create index buffer"),
+        }
+    }
+
+    fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed(self.indices.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+// Everything that moves is a function of the time alone, so any frame can be drawn on its own
+fn draw_scene(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipeline: &GraphicsPipeline,
+    cube: &Mesh,
+    view_projection: [[f32; 4]; 4],
+    time: f32,
+) {
+    let mut draw = |center: Vec3, scale: Vec3, color: [f32; 4], angle: f32| {
+        builder.push_constants(
+            pipeline.layout().clone(),
+            0,
+            vs::PushConstants {
+                view_projection,
+                center: center.extend(0.0).to_array(),
+                scale: scale.extend(0.0).to_array(),
+                color,
+                angle,
+            },
+        );
+        cube.draw(builder);
+    };
+
+    draw(
+        Vec3::new(0.0, -0.1, 0.0),
+        Vec3::new(6.0, 0.1, 6.0),
+        [0.35, 0.35, 0.35, 1.0],
+        0.0,
+    );
+    draw(
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::splat(1.0),
+        [0.9, 0.9, 0.9, 1.0],
+        time,
+    );
+
+    for i in 0..RING {
+        let phase = i as f32 / RING as f32;
+        let angle = phase * TAU + time * 0.5;
+        let bounce = (time * 3.0 + phase * TAU).sin().abs();
+        draw(
+            Vec3::new(angle.cos() * 3.5, 0.4 + bounce, angle.sin() * 3.5),
+            Vec3::splat(0.4),
+            [
+                0.5 + 0.5 * (phase * TAU).cos(),
+                0.5,
+                0.5 + 0.5 * (phase * TAU).sin(),
+                1.0,
+            ],
+            -time * 2.0,
+        );
+    }
+}
+
+fn main() {
+    let width = args::value::<u32>("--width").unwrap_or(1280);
+    let height = args::value::<u32>("--height").unwrap_or(720);
+
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let allocators = Allocators::new(&device);
+    let cube = Mesh::new(&allocators.memory, cube());
+    let mut recorder = Recorder::from_args(&allocators.memory, [width, height]);
+
+    // Stands in for the swapchain image: rendered to, then copied out instead of presented
+    let color_image = AttachmentImage::with_usage(
+        &allocators.memory,
+        [width, height],
+        record::FORMAT,
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    )
+        .unwrap();
+    let depth_image =
+        AttachmentImage::transient(&allocators.memory, [width, height], Format::D16_UNORM).unwrap();
+
+    let render_pass = vulkano::single_pass_renderpass!(
+        device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: record::FORMAT,
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: DontCare,
+                format: Format::D16_UNORM,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {depth},
+        },
+    )
+        .unwrap();
+
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![
+                ImageView::new_default(color_image.clone()).unwrap(),
+                ImageView::new_default(depth_image).unwrap(),
+            ],
+            ..Default::default()
+        },
+    )
+        .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+    // The size is known up front and never changes, so the viewport is baked in
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(PosNormalUv::per_vertex())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+            Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [width as f32, height as f32],
+                depth_range: 0.0..1.0,
+            },
+        ]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .expect("failed to create graphics pipeline");
+
+    let camera = Camera::new(Vec3::new(0.0, 4.0, 9.0), Vec3::new(0.0, 0.8, 0.0));
+    let view_projection = camera
+        .view_projection(width as f32 / height as f32)
+        .to_cols_array_2d();
+
+    println!(
+        "Rendering {} frames of {width}x{height} at {} fps",
+        recorder.frames, recorder.fps
+    );
+    let start = Instant::now();
+    for frame in 0..recorder.frames {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.02, 0.02, 0.02, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(pipeline.clone());
+        draw_scene(
+            &mut builder,
+            &pipeline,
+            &cube,
+            view_projection,
+            recorder.time(frame),
+        );
+        builder.end_render_pass().unwrap();
+        recorder.copy(&mut builder, color_image.clone());
+
+        // There is only one image and one readback buffer, so each frame is finished and
+        // written out before the next one starts. Nothing is waiting to see it, the time it
+        // takes only makes the whole run longer
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+        recorder.write(frame);
+
+        if (frame + 1) % recorder.fps == 0 {
+            println!("{} / {} frames", frame + 1, recorder.frames);
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "Took {elapsed:.2} s, {:.1} frames per second",
+        recorder.frames as f64 / elapsed
+    );
+    recorder.finish();
+}
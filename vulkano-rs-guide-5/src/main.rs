@@ -0,0 +1,165 @@
+//The Mandelbrot set from the previous chapter, drawn live in a window with tweakable parameters
+
+use std::sync::Arc;
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+struct Mandelbrot {
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // Recreated with the swapchain so the fractal is computed at the window resolution
+    image: Option<(Arc<StorageImage>, Arc<PersistentDescriptorSet>)>,
+    // Parameters exposed in the overlay
+    center: [f32; 2],
+    scale: f32,
+    max_iterations: u32,
+}
+
+impl Mandelbrot {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        Mandelbrot {
+            pipeline,
+            allocators: renderer.allocators.clone(),
+            image: None,
+            center: [-0.5, 0.0],
+            scale: 3.0,
+            max_iterations: 200,
+        }
+    }
+}
+
+impl App for Mandelbrot {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+
+        let image = StorageImage::new(
+            &self.allocators.memory,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(renderer.queue().queue_family_index()),
+        )
+            .unwrap();
+
+        let view = ImageView::new_default(image.clone()).unwrap();
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [WriteDescriptorSet::image_view(0, view)],
+        )
+            .unwrap();
+
+        self.image = Some((image, set));
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let (image, set) = self.image.clone().unwrap();
+        let [width, height] = renderer.swapchain.image_extent();
+
+        let push_constants = cs::PushConstants {
+            center: self.center,
+            scale: self.scale,
+            max_iterations: self.max_iterations,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+            // Round up so the edges of the window are covered too
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap()
+            // The swapchain image can't be a storage image on every platform, so copy the result
+            // over, the blit also converts between the two formats
+            .blit_image(BlitImageInfo::images(
+                image,
+                renderer.images[image_index as usize].clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Mandelbrot").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.max_iterations, 10..=2000).text("iterations"));
+            ui.add(
+                egui::Slider::new(&mut self.scale, 0.00001..=4.0)
+                    .logarithmic(true)
+                    .text("zoom"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.center[0], -2.0..=1.0)
+                    .text("center x")
+                    .fixed_decimals(6),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.center[1], -1.5..=1.5)
+                    .text("center y")
+                    .fixed_decimals(6),
+            );
+        });
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-5",
+        DeviceExtensions::empty(),
+        Mandelbrot::new,
+    );
+}
@@ -0,0 +1,28 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D u_texture;
+
+layout(push_constant) uniform PushConstants {
+    float level;
+    // Set for BC5, which only has the X and Y of a normal
+    uint two_channel;
+} pc;
+
+void main() {
+    // Sampling doesn't care whether the texels were compressed. The hardware
+    // decodes the blocks it reads on the fly
+    vec4 texel = textureLod(u_texture, v_tex_coord, pc.level);
+
+    if (pc.two_channel != 0) {
+        // A unit normal's Z follows from its X and Y
+        vec2 xy = texel.rg * 2.0 - 1.0;
+        float z = sqrt(max(1.0 - dot(xy, xy), 0.0));
+        f_color = vec4(vec3(xy, z) * 0.5 + 0.5, 1.0);
+    } else {
+        f_color = texel;
+    }
+}
@@ -0,0 +1,9 @@
+#version 460
+
+layout(location = 0) out vec2 v_tex_coord;
+
+void main() {
+    // Vertex 0, 1 and 2 become a triangle that contains the whole image
+    v_tex_coord = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(v_tex_coord * 2.0 - 1.0, 0.0, 1.0);
+}
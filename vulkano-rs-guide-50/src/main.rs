@@ -0,0 +1,425 @@
+//Compressed textures: BC7 and BC5 data loaded from a KTX2 container straight into an image of the
+//matching block-compressed format, or decompressed on the CPU when the device can't sample it
+
+use std::fs;
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BufferImageCopy, CommandBufferUsage, CopyBufferToImageInfo,
+    CopyImageToBufferInfo, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::view::ImageView;
+use vulkano::image::{
+    AttachmentImage, ImageAccess, ImageCreateFlags, ImageDimensions, ImageLayout,
+    ImageSubresourceLayers, ImageUsage, ImmutableImage, MipmapsCount,
+};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::VertexInputState;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerCreateInfo, SamplerMipmapMode};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::wait;
+
+// The block-compressed formats this chapter reads. BC7 is the high quality choice for color,
+// BC5 stores two independent channels, typically the X and Y of a normal map
+#[derive(Clone, Copy, Debug)]
+enum Compression {
+    Bc7 { srgb: bool },
+    Bc5,
+}
+
+impl Compression {
+    fn from_ktx2(format: ktx2::Format) -> Option<Self> {
+        match format {
+            ktx2::Format::BC7_UNORM_BLOCK => Some(Compression::Bc7 { srgb: false }),
+            ktx2::Format::BC7_SRGB_BLOCK => Some(Compression::Bc7 { srgb: true }),
+            ktx2::Format::BC5_UNORM_BLOCK => Some(Compression::Bc5),
+            _ => None,
+        }
+    }
+
+    // The format the blocks can be uploaded to as they are
+    fn native_format(self) -> Format {
+        match self {
+            Compression::Bc7 { srgb: false } => Format::BC7_UNORM_BLOCK,
+            Compression::Bc7 { srgb: true } => Format::BC7_SRGB_BLOCK,
+            Compression::Bc5 => Format::BC5_UNORM_BLOCK,
+        }
+    }
+
+    // The uncompressed format holding the same channels, for when the native one isn't
+    // supported
+    fn fallback_format(self) -> Format {
+        match self {
+            Compression::Bc7 { srgb: false } => Format::R8G8B8A8_UNORM,
+            Compression::Bc7 { srgb: true } => Format::R8G8B8A8_SRGB,
+            Compression::Bc5 => Format::R8G8_UNORM,
+        }
+    }
+
+    // Decodes one mip level into pixels of the fallback format
+    fn decompress(self, blocks: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = vec![0u32; width as usize * height as usize];
+        let (width, height) = (width as usize, height as usize);
+        match self {
+            Compression::Bc7 { .. } => {
+                texture2ddecoder::decode_bc7(blocks, width, height, &mut pixels)
+            }
+            Compression::Bc5 => texture2ddecoder::decode_bc5(blocks, width, height, &mut pixels),
+        }
+        .unwrap_or_else(|err| panic!("failed to decompress {self:?} data: {err}"));
+
+        // The decoder hands out BGRA pixels packed in a u32
+        pixels
+            .into_iter()
+            .flat_map(|pixel| {
+                let [b, g, r, a] = pixel.to_le_bytes();
+                match self {
+                    Compression::Bc7 { .. } => vec![r, g, b, a],
+                    Compression::Bc5 => vec![r, g],
+                }
+            })
+            .collect()
+    }
+}
+
+// Whether images of `format` can be sampled with linear filtering on this device. For the BC
+// formats that also takes the texture_compression_bc feature, which this reports as supported
+// whether or not it was enabled
+fn can_sample(physical_device: &PhysicalDevice, format: Format) -> bool {
+    physical_device
+        .format_properties(format)
+        .map_or(false, |properties| {
+            properties.optimal_tiling_features.contains(
+                FormatFeatures::SAMPLED_IMAGE | FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR,
+            )
+        })
+}
+
+mod fullscreen_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/fullscreen_vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+fn main() {
+    let path = args::value::<String>("--texture").unwrap_or_else(|| {
+        eprintln!(
+            "Usage: --texture <file.ktx2> [--level N] [--output decoded.png] [--no-bc]\n\
+             The file must hold a single 2D image in BC7 or BC5 without supercompression, as \
+             written by e.g. `ktx create` or Compressonator"
+        );
+        std::process::exit(1);
+    });
+    let output = args::value::<String>("--output").unwrap_or_else(|| "decoded.png".into());
+
+    let data = fs::read(&path).unwrap_or_else(|err| panic!("failed to read {path}: {err}"));
+    let reader =
+        ktx2::Reader::new(&data[..]).unwrap_or_else(|err| panic!("{path} is not KTX2: {err:?}"));
+    let header = reader.header();
+    assert!(
+        header.supercompression_scheme.is_none(),
+        "{path} is supercompressed, only plain BC data is supported"
+    );
+    assert!(
+        header.pixel_depth <= 1 && header.layer_count <= 1 && header.face_count == 1,
+        "{path} is not a single 2D image"
+    );
+    let compression = header
+        .format
+        .and_then(Compression::from_ktx2)
+        .unwrap_or_else(|| panic!("{path} is {:?}, not BC7 or BC5", header.format));
+    let (width, height) = (header.pixel_width, header.pixel_height);
+    let levels: Vec<&[u8]> = reader.levels().collect();
+    let level = args::value::<u32>("--level")
+        .unwrap_or(0)
+        .min(levels.len() as u32 - 1);
+
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+
+    // BC formats can only be used with the texture_compression_bc feature. Most desktop GPUs
+    // have it and most mobile ones don't, so it is asked for only when there is a device that
+    // has it, instead of turning the others away. --no-bc leaves it off to try the fallback
+    let texture_compression_bc = !args::flag("--no-bc")
+        && instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .any(|p| p.supported_features().texture_compression_bc);
+    let context = VulkanContext::with_features(
+        instance,
+        DeviceExtensions::empty(),
+        Features {
+            texture_compression_bc,
+            ..Features::empty()
+        },
+        None,
+    );
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let native = texture_compression_bc
+        && device.enabled_features().texture_compression_bc
+        && can_sample(device.physical_device(), compression.native_format());
+    let format = if native {
+        compression.native_format()
+    } else {
+        compression.fallback_format()
+    };
+    assert!(
+        can_sample(device.physical_device(), format),
+        "{format:?} can't be sampled either"
+    );
+
+    let allocators = Allocators::new(&device);
+
+    // The mip levels come from the file, so the image is created uninitialized and each level
+    // copied in by hand
+    let (texture, initialization) = ImmutableImage::uninitialized(
+        &allocators.memory,
+        ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        },
+        format,
+        MipmapsCount::Specific(levels.len() as u32),
+        ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+        ImageCreateFlags::empty(),
+        ImageLayout::ShaderReadOnlyOptimal,
+        [],
+    )
+        .expect("failed to create texture");
+
+    // Mip level i is the full size halved i times, but never less than a pixel
+    let level_extent = |i: u32| [(width >> i).max(1), (height >> i).max(1)];
+
+    // Either way the levels go into one staging buffer back to back, with a copy region each
+    let subresource = initialization.subresource_layers();
+    let mut pixels = Vec::new();
+    let mut regions = Vec::new();
+    for (i, blocks) in levels.iter().enumerate() {
+        let [level_width, level_height] = level_extent(i as u32);
+        regions.push(BufferImageCopy {
+            buffer_offset: pixels.len() as u64,
+            image_subresource: ImageSubresourceLayers {
+                mip_level: i as u32,
+                ..subresource.clone()
+            },
+            image_extent: [level_width, level_height, 1],
+            ..Default::default()
+        });
+        if native {
+            pixels.extend_from_slice(blocks);
+        } else {
+            pixels.extend(compression.decompress(blocks, level_width, level_height));
+        }
+    }
+
+    let rgba_size: u64 = (0..levels.len() as u32)
+        .map(|i| level_extent(i).iter().product::<u32>() as u64 * 4)
+        .sum();
+    println!(
+        "{path}: {width}x{height} {compression:?}, {} mip levels",
+        levels.len()
+    );
+    println!(
+        "Uploaded as {format:?}{}: {} KiB, against {} KiB as uncompressed RGBA8",
+        if native {
+            ""
+        } else {
+            " after decompressing on the CPU"
+        },
+        pixels.len() / 1024,
+        rgba_size / 1024
+    );
+
+    let staging_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        pixels,
+    )
+        .expect("failed to create staging buffer");
+
+    // The result is written in the texture's own color space, so the PNG gets the values as
+    // they are stored
+    let srgb = matches!(compression, Compression::Bc7 { srgb: true });
+    let output_format = if srgb {
+        Format::R8G8B8A8_SRGB
+    } else {
+        Format::R8G8B8A8_UNORM
+    };
+    let [output_width, output_height] = level_extent(level);
+    let output_image = AttachmentImage::with_usage(
+        &allocators.memory,
+        [output_width, output_height],
+        output_format,
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    )
+        .unwrap();
+    let download_buffer = Buffer::new_slice::<u8>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        output_width as u64 * output_height as u64 * 4,
+    )
+        .expect("failed to create download buffer");
+
+    let render_pass = vulkano::single_pass_renderpass!(
+        device.clone(),
+        attachments: {
+            color: {
+                load: DontCare,
+                store: Store,
+                format: output_format,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {},
+        },
+    )
+        .unwrap();
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![ImageView::new_default(output_image.clone()).unwrap()],
+            ..Default::default()
+        },
+    )
+        .unwrap();
+
+    let fullscreen_vs =
+        fullscreen_vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(VertexInputState::new())
+        .vertex_shader(fullscreen_vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+            Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [output_width as f32, output_height as f32],
+                depth_range: 0.0..1.0,
+            },
+        ]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .expect("failed to create graphics pipeline");
+
+    let sampler = Sampler::new(
+        device.clone(),
+        SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            mipmap_mode: SamplerMipmapMode::Linear,
+            lod: 0.0..=levels.len() as f32,
+            ..Default::default()
+        },
+    )
+        .expect("failed to create sampler");
+    let set = PersistentDescriptorSet::new(
+        &allocators.descriptor,
+        pipeline.layout().set_layouts()[0].clone(),
+        [WriteDescriptorSet::image_view_sampler(
+            0,
+            ImageView::new_default(texture).unwrap(),
+            sampler,
+        )],
+    )
+        .unwrap();
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer_to_image(CopyBufferToImageInfo {
+            regions: regions.into(),
+            ..CopyBufferToImageInfo::buffer_image(staging_buffer, initialization)
+        })
+        .unwrap()
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: vec![None],
+                ..RenderPassBeginInfo::framebuffer(framebuffer)
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            set,
+        )
+        .push_constants(
+            pipeline.layout().clone(),
+            0,
+            fs::PushConstants {
+                level: level as f32,
+                two_channel: matches!(compression, Compression::Bc5) as u32,
+            },
+        )
+        .draw(3, 1, 0, 0)
+        .unwrap()
+        .end_render_pass()
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            output_image,
+            download_buffer.clone(),
+        ))
+        .unwrap();
+
+    let future = sync::now(device.clone())
+        .then_execute(queue, builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let pixels = download_buffer.read().unwrap().to_vec();
+    image::RgbaImage::from_raw(output_width, output_height, pixels)
+        .unwrap()
+        .save(&output)
+        .unwrap_or_else(|err| panic!("failed to save {output}: {err}"));
+    println!("Sampled mip level {level} ({output_width}x{output_height}) into {output}");
+}
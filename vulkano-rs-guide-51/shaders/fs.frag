@@ -0,0 +1,14 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+layout(location = 1) flat in uint v_layer;
+
+layout(location = 0) out vec4 f_color;
+
+// One descriptor for all the layers. The layer is just a third texture coordinate,
+// so unlike an array of descriptors any value may be used without qualifiers
+layout(set = 0, binding = 0) uniform sampler2DArray u_textures;
+
+void main() {
+    f_color = texture(u_textures, vec3(v_tex_coord, float(v_layer)));
+}
@@ -0,0 +1,22 @@
+#version 460
+
+layout(location = 0) out vec2 v_tex_coord;
+layout(location = 1) flat out uint v_layer;
+
+layout(push_constant) uniform PushConstants {
+    uint grid;
+    uint shift;
+    uint layer_count;
+} pc;
+
+// A quad as a 4 vertex triangle strip, placed in the grid by its instance index
+void main() {
+    uint x = gl_InstanceIndex % pc.grid;
+    uint y = gl_InstanceIndex / pc.grid;
+    float cell = 2.0 / float(pc.grid);
+
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+    v_tex_coord = corner;
+    v_layer = (gl_InstanceIndex + pc.shift) % pc.layer_count;
+    gl_Position = vec4(vec2(x, y) * cell - 1.0 + corner * cell * 0.9, 0.0, 1.0);
+}
@@ -0,0 +1,313 @@
+//Texture arrays: several images of the same size as the layers of one image, a single descriptor
+//that each instance picks its layer from
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::VertexInputState;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::{args, egui, tracing};
+
+// Every layer of an array image has the same size, format and mip count
+const TEXTURE_SIZE: u32 = 64;
+
+// A color from a hue in 0..1, at full saturation and value
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let channel = |offset: f32| {
+        let k = (hue * 6.0 + offset) % 6.0;
+        1.0 - (k.min(4.0 - k).clamp(0.0, 1.0))
+    };
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
+
+// Every texture gets its own hue, one of four patterns and a frequency, so that any two of them
+// are easy to tell apart on screen
+fn pattern_texture(index: u32) -> Vec<u8> {
+    // Stepping by the golden ratio spreads consecutive hues far apart
+    let [r, g, b] = hue_to_rgb((index as f32 * 0.618_034) % 1.0);
+    let frequency = 2 + (index / 4) % 6;
+
+    let mut pixels = Vec::with_capacity((TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize);
+    for y in 0..TEXTURE_SIZE {
+        for x in 0..TEXTURE_SIZE {
+            let u = x as f32 / TEXTURE_SIZE as f32 * frequency as f32;
+            let v = y as f32 / TEXTURE_SIZE as f32 * frequency as f32;
+            let lit = match index % 4 {
+                0 => (u as u32 + v as u32) % 2 == 0,
+                1 => (u + v) as u32 % 2 == 0,
+                2 => {
+                    let (du, dv) = (u.fract() - 0.5, v.fract() - 0.5);
+                    du * du + dv * dv < 0.1
+                }
+                _ => {
+                    let center = frequency as f32 / 2.0;
+                    ((u - center).hypot(v - center) * 2.0) as u32 % 2 == 0
+                }
+            };
+            let shade = if lit { 1.0 } else { 0.2 };
+            pixels.extend([
+                (r * shade * 255.0) as u8,
+                (g * shade * 255.0) as u8,
+                (b * shade * 255.0) as u8,
+                255,
+            ]);
+        }
+    }
+
+    pixels
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct TextureArray {
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    set: Arc<PersistentDescriptorSet>,
+    layer_count: u32,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    start: Instant,
+    // Parameters exposed in the overlay
+    grid: u32,
+    scroll: bool,
+}
+
+impl TextureArray {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        // The number of layers has a limit of its own, at least 256, instead of the descriptor
+        // limits an array of separate textures runs into
+        let limit = device.physical_device().properties().max_image_array_layers;
+        let requested = args::value::<u32>("--layers").unwrap_or(64).max(1);
+        let layer_count = requested.min(limit);
+        if layer_count < requested {
+            tracing::warn!("this device allows at most {limit} array layers");
+        }
+
+        // The layers are consecutive in the source data, so one upload fills all of them
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let image = ImmutableImage::from_iter(
+            &allocators.memory,
+            (0..layer_count)
+                .flat_map(pattern_texture)
+                .collect::<Vec<_>>(),
+            ImageDimensions::Dim2d {
+                width: TEXTURE_SIZE,
+                height: TEXTURE_SIZE,
+                array_layers: layer_count,
+            },
+            MipmapsCount::One,
+            Format::R8G8B8A8_SRGB,
+            &mut uploads,
+        )
+            .expect("failed to create texture array");
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        // With more than one layer, the default view is a 2D array view over all of them
+        let textures = ImageView::new_default(image).unwrap();
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::simple_repeat_linear())
+            .expect("failed to create sampler");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        // Nothing special in the layout this time, it comes straight from the shaders
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(
+                InputAssemblyState::new().topology(PrimitiveTopology::TriangleStrip),
+            )
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(0, textures, sampler)],
+        )
+            .unwrap();
+
+        TextureArray {
+            render_pass,
+            pipeline,
+            allocators,
+            set,
+            layer_count,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            start: Instant::now(),
+            grid: 16,
+            scroll: true,
+        }
+    }
+}
+
+impl App for TextureArray {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // Shift every quad to the next layer a few times per second
+        let shift = if self.scroll {
+            (self.start.elapsed().as_secs_f32() * 4.0) as u32
+        } else {
+            0
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // A single instanced draw covers the whole grid, since no quad needs anything bound
+        // that another one doesn't
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.05, 0.05, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    grid: self.grid,
+                    shift,
+                    layer_count: self.layer_count,
+                },
+            )
+            .draw(4, self.grid * self.grid, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Texture array").show(ctx, |ui| {
+            ui.label(format!(
+                "{} layers of {TEXTURE_SIZE}x{TEXTURE_SIZE} in one image",
+                self.layer_count
+            ));
+            ui.add(egui::Slider::new(&mut self.grid, 1..=32).text("grid"));
+            ui.checkbox(&mut self.scroll, "scroll");
+            ui.label("Unlike the bindless chapter, every layer must have the same size");
+        });
+    }
+}
+
+fn main() {
+    // No features needed: array images and sampler2DArray are core Vulkan 1.0
+    window::run(
+        "vulkano-rs-guide-51",
+        DeviceExtensions::empty(),
+        TextureArray::new,
+    );
+}
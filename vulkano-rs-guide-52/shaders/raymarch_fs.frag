@@ -0,0 +1,68 @@
+#version 460
+
+layout(location = 0) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+// Sampled with normalized coordinates in all three dimensions, and filtered between
+// the eight nearest voxels
+layout(set = 0, binding = 0) uniform sampler3D u_volume;
+
+layout(push_constant) uniform PushConstants {
+    mat4 inverse_view_projection;
+    vec4 camera_position;
+    float density_scale;
+    int steps;
+} pc;
+
+const vec3 BACKGROUND = vec3(0.02, 0.02, 0.03);
+
+// Where the ray enters and leaves the -1 to 1 box, entering after leaving if it
+// misses
+vec2 intersect_box(vec3 origin, vec3 direction) {
+    vec3 inverse = 1.0 / direction;
+    vec3 t0 = (-1.0 - origin) * inverse;
+    vec3 t1 = (1.0 - origin) * inverse;
+    vec3 near = min(t0, t1);
+    vec3 far = max(t0, t1);
+    return vec2(max(max(near.x, near.y), near.z), min(min(far.x, far.y), far.z));
+}
+
+void main() {
+    // The view ray through this pixel, from the camera to the far plane
+    vec4 far = pc.inverse_view_projection * vec4(v_tex_coord * 2.0 - 1.0, 1.0, 1.0);
+    vec3 origin = pc.camera_position.xyz;
+    vec3 direction = normalize(far.xyz / far.w - origin);
+
+    vec2 span = intersect_box(origin, direction);
+    if (span.x > span.y || span.y < 0.0) {
+        f_color = vec4(BACKGROUND, 1.0);
+        return;
+    }
+
+    // Fixed steps across the box's diagonal, whichever way the ray crosses it
+    float step_size = 2.0 * sqrt(3.0) / float(pc.steps);
+    float t = max(span.x, 0.0);
+    vec3 color = vec3(0.0);
+    float transmittance = 1.0;
+    for (int i = 0; i < pc.steps && t < span.y; i++) {
+        vec3 position = origin + direction * t;
+        float density = texture(u_volume, position * 0.5 + 0.5).r * pc.density_scale;
+
+        // Each step absorbs part of the light behind it and glows a little itself,
+        // hotter where it is denser
+        float absorbed = 1.0 - exp(-density * step_size);
+        float heat = clamp(density * 0.2, 0.0, 1.0);
+        vec3 glow = mix(vec3(0.1, 0.3, 1.0), vec3(1.0, 0.7, 0.3), heat);
+        color += transmittance * absorbed * glow;
+        transmittance *= 1.0 - absorbed;
+
+        // Nothing further back can show through anymore
+        if (transmittance < 0.01) {
+            break;
+        }
+        t += step_size;
+    }
+
+    f_color = vec4(color + transmittance * BACKGROUND, 1.0);
+}
@@ -0,0 +1,326 @@
+//Volume rendering: a compute shader fills a 3D image with a density field, which the fragment
+//shader raymarches through and samples along every view ray
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::VertexInputState;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::{args, egui};
+
+// One channel of half floats is plenty for a density, and can be both written as a storage image
+// and filtered when sampled on the common desktop GPUs
+const VOLUME_FORMAT: Format = Format::R16_SFLOAT;
+
+mod density_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        include: ["../shaders/common"],
+        path: "shaders/density_cs.comp",
+    }
+}
+
+mod fullscreen_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/fullscreen_vs.vert",
+    }
+}
+
+mod raymarch_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/raymarch_fs.frag",
+    }
+}
+
+struct Volume {
+    camera: Camera,
+    start: Instant,
+    size: u32,
+    allocators: Allocators,
+    density_pipeline: Arc<ComputePipeline>,
+    raymarch_pipeline: Arc<GraphicsPipeline>,
+    density_set: Arc<PersistentDescriptorSet>,
+    raymarch_set: Arc<PersistentDescriptorSet>,
+    render_pass: Arc<RenderPass>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    animate: bool,
+    time: f32,
+    threshold: f32,
+    density_scale: f32,
+    steps: i32,
+}
+
+impl Volume {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let needed = FormatFeatures::STORAGE_IMAGE
+            | FormatFeatures::SAMPLED_IMAGE
+            | FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR;
+        let supported = device
+            .physical_device()
+            .format_properties(VOLUME_FORMAT)
+            .map_or(false, |properties| {
+                properties.optimal_tiling_features.contains(needed)
+            });
+        assert!(
+            supported,
+            "{VOLUME_FORMAT:?} can't be both stored to and filtered on this device"
+        );
+
+        // A size cubed voxels, so memory grows fast: 128 is already 4 MiB at 2 bytes each
+        let size = args::value::<u32>("--size").unwrap_or(96).clamp(8, 256);
+        let volume = StorageImage::with_usage(
+            &allocators.memory,
+            ImageDimensions::Dim3d {
+                width: size,
+                height: size,
+                depth: size,
+            },
+            VOLUME_FORMAT,
+            ImageUsage::STORAGE | ImageUsage::SAMPLED,
+            ImageCreateFlags::empty(),
+            [renderer.queue().queue_family_index()],
+        )
+            .expect("failed to create volume");
+        let volume_view = ImageView::new_default(volume).unwrap();
+
+        // Clamping keeps the edges from wrapping around to the opposite side
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..SamplerCreateInfo::simple_repeat_linear_no_mipmap()
+            },
+        )
+            .expect("failed to create sampler");
+
+        let density_cs = density_cs::load(device.clone()).expect("failed to create shader module");
+        let density_pipeline = ComputePipeline::new(
+            device.clone(),
+            density_cs.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let fullscreen_vs =
+            fullscreen_vs::load(device.clone()).expect("failed to create shader module");
+        let raymarch_fs =
+            raymarch_fs::load(device.clone()).expect("failed to create shader module");
+        let raymarch_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(fullscreen_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(raymarch_fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // The same image is bound twice: as a storage image to write, and with a sampler to
+        // read it filtered
+        let density_set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            density_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view(0, volume_view.clone())],
+        )
+            .unwrap();
+        let raymarch_set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            raymarch_pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                volume_view,
+                sampler,
+            )],
+        )
+            .unwrap();
+
+        Volume {
+            camera: Camera::new(Vec3::new(0.0, 0.8, 3.0), Vec3::ZERO),
+            start: Instant::now(),
+            size,
+            allocators,
+            density_pipeline,
+            raymarch_pipeline,
+            density_set,
+            raymarch_set,
+            render_pass,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            animate: true,
+            time: 0.0,
+            threshold: 0.5,
+            density_scale: 4.0,
+            steps: 128,
+        }
+    }
+}
+
+impl App for Volume {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        if self.animate {
+            self.time = self.start.elapsed().as_secs_f32();
+        }
+        let [width, height] = self.viewport.dimensions;
+        let view_projection = self.camera.view_projection(width / height);
+        let groups = (self.size + 7) / 8;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // Regenerate the field, then march through it. The builder puts a barrier between the
+        // compute writes and the fragment shader reads, with the move from the general layout
+        // storage images use to the one for sampling
+        builder
+            .bind_pipeline_compute(self.density_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.density_pipeline.layout().clone(),
+                0,
+                self.density_set.clone(),
+            )
+            .push_constants(
+                self.density_pipeline.layout().clone(),
+                0,
+                density_cs::PushConstants {
+                    time: self.time,
+                    threshold: self.threshold,
+                },
+            )
+            .dispatch([groups, groups, groups])
+            .unwrap()
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![None],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.raymarch_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.raymarch_pipeline.layout().clone(),
+                0,
+                self.raymarch_set.clone(),
+            )
+            .push_constants(
+                self.raymarch_pipeline.layout().clone(),
+                0,
+                raymarch_fs::PushConstants {
+                    inverse_view_projection: view_projection.inverse().to_cols_array_2d(),
+                    camera_position: self.camera.position.extend(1.0).to_array(),
+                    density_scale: self.density_scale,
+                    steps: self.steps,
+                },
+            )
+            .draw(3, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Volume").show(ctx, |ui| {
+            ui.label(format!("{0}x{0}x{0} voxels", self.size));
+            ui.checkbox(&mut self.animate, "animate");
+            ui.add(egui::Slider::new(&mut self.threshold, 0.0..=3.0).text("threshold"));
+            ui.add(egui::Slider::new(&mut self.density_scale, 0.1..=20.0).text("density"));
+            ui.add(egui::Slider::new(&mut self.steps, 16..=512).text("steps"));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-52",
+        DeviceExtensions::empty(),
+        Volume::new,
+    );
+}
@@ -0,0 +1,215 @@
+//Compute to screen: a compute shader writes a storage image every frame, which is then blitted or
+//copied into the swapchain image, the pattern behind the fractal and simulation chapters
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage, CopyImageInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageAccess, ImageDimensions, StorageImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sampler::Filter;
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Storage images need a format the shader can write with imageStore. The swapchain formats are
+// mostly BGRA and sRGB, which often can't be
+const IMAGE_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Transfer {
+    // Scales and converts between formats, like a texture lookup would
+    Blit,
+    // Moves the bytes as they are: same size, and formats with the same texel size
+    Copy,
+}
+
+struct ComputeToScreen {
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    start: Instant,
+    // The storage image with the descriptor set pointing at it, created at whatever size the
+    // resolution asks for
+    image: Option<(Arc<StorageImage>, Arc<PersistentDescriptorSet>)>,
+    // Whether the swapchain format has the same texel size as IMAGE_FORMAT
+    can_copy: bool,
+    // Parameters exposed in the overlay
+    transfer: Transfer,
+    resolution: f32,
+    filter: Filter,
+}
+
+impl ComputeToScreen {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        ComputeToScreen {
+            pipeline,
+            allocators: renderer.allocators.clone(),
+            start: Instant::now(),
+            image: None,
+            can_copy: renderer.swapchain.image_format().block_size() == IMAGE_FORMAT.block_size(),
+            transfer: Transfer::Blit,
+            resolution: 1.0,
+            filter: Filter::Linear,
+        }
+    }
+
+    // The size the storage image should have for the current settings. A copy can't scale, so
+    // it always needs the full window size
+    fn image_extent(&self, renderer: &Renderer) -> [u32; 2] {
+        let extent = renderer.swapchain.image_extent();
+        match self.transfer {
+            Transfer::Blit => extent.map(|size| ((size as f32 * self.resolution) as u32).max(1)),
+            Transfer::Copy => extent,
+        }
+    }
+
+    fn create_image(&mut self, renderer: &Renderer, [width, height]: [u32; 2]) {
+        let image = StorageImage::new(
+            &self.allocators.memory,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            IMAGE_FORMAT,
+            Some(renderer.queue().queue_family_index()),
+        )
+            .unwrap();
+
+        let view = ImageView::new_default(image.clone()).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            self.pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view(0, view)],
+        )
+            .unwrap();
+
+        self.image = Some((image, set));
+    }
+}
+
+impl App for ComputeToScreen {
+    fn resize(&mut self, renderer: &Renderer) {
+        self.create_image(renderer, self.image_extent(renderer));
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // The overlay may have changed the size since the last frame
+        let extent = self.image_extent(renderer);
+        if self.image.as_ref().unwrap().0.dimensions().width_height() != extent {
+            self.create_image(renderer, extent);
+        }
+        let (image, set) = self.image.clone().unwrap();
+        let [width, height] = extent;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                cs::PushConstants {
+                    time: self.start.elapsed().as_secs_f32(),
+                },
+            )
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap();
+
+        // Between the dispatch and the transfer, the builder inserts the barrier that waits for
+        // the shader's writes and moves the image into the transfer source layout
+        let swapchain_image = renderer.images[image_index as usize].clone();
+        match self.transfer {
+            Transfer::Blit => {
+                // The region defaults to the whole of both images, stretching the smaller one
+                builder
+                    .blit_image(BlitImageInfo {
+                        filter: self.filter,
+                        ..BlitImageInfo::images(image, swapchain_image)
+                    })
+                    .unwrap();
+            }
+            Transfer::Copy => {
+                // No conversion at all: RGBA bytes land in what is usually a BGRA sRGB image,
+                // so red and blue trade places and the colors get the sRGB curve on top
+                builder
+                    .copy_image(CopyImageInfo::images(image, swapchain_image))
+                    .unwrap();
+            }
+        }
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Compute to screen").show(ctx, |ui| {
+            ui.radio_value(&mut self.transfer, Transfer::Blit, "blit");
+            ui.add_enabled_ui(self.can_copy, |ui| {
+                ui.radio_value(&mut self.transfer, Transfer::Copy, "copy");
+            });
+            ui.add_enabled_ui(self.transfer == Transfer::Blit, |ui| {
+                ui.add(egui::Slider::new(&mut self.resolution, 0.05..=1.0).text("resolution"));
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.filter, Filter::Nearest, "nearest");
+                    ui.radio_value(&mut self.filter, Filter::Linear, "linear");
+                });
+            });
+            if let Some((image, _)) = &self.image {
+                let [width, height] = image.dimensions().width_height();
+                ui.label(format!("storage image: {width}x{height}"));
+            }
+        });
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-53",
+        DeviceExtensions::empty(),
+        ComputeToScreen::new,
+    );
+}
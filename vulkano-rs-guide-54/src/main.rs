@@ -0,0 +1,222 @@
+//Texel buffers: a buffer read through texelFetch and another written in place through
+//imageLoad and imageStore, with the format conversion done by the hardware
+
+use vulkano::buffer::view::{BufferView, BufferViewCreateInfo};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::DeviceExtensions;
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::wait;
+
+// Both views read the same bytes as the PNGs, four normalized bytes per pixel. Unlike sRGB, this
+// format has to work for uniform and storage texel buffers on every device
+const TEXEL_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// Whether buffer views of `format` can be created for `features` on this device. Buffers have
+// their own list of supported features, separate from the image tilings
+fn supports(physical_device: &PhysicalDevice, format: Format, features: FormatFeatures) -> bool {
+    physical_device
+        .format_properties(format)
+        .map_or(false, |properties| {
+            properties.buffer_features.contains(features)
+        })
+}
+
+// Rings of color, as the picture faded in
+fn rings(size: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        let dx = x as f32 - size as f32 / 2.0;
+        let dy = y as f32 - size as f32 / 2.0;
+        match ((dx * dx + dy * dy).sqrt() / 16.0) as u32 % 3 {
+            0 => image::Rgba([230, 60, 40, 255]),
+            1 => image::Rgba([250, 250, 250, 255]),
+            _ => image::Rgba([40, 90, 220, 255]),
+        }
+    })
+}
+
+// A checkerboard over a gradient, as the picture faded out
+fn checkerboard(width: u32, height: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let shade = if ((x / 32) + (y / 32)) % 2 == 0 {
+            1.0
+        } else {
+            0.4
+        };
+        image::Rgba([
+            (shade * 255.0 * x as f32 / width as f32) as u8,
+            (shade * 160.0) as u8,
+            (shade * 255.0 * y as f32 / height as f32) as u8,
+            255,
+        ])
+    })
+}
+
+fn main() {
+    // 256x256 is exactly the 65536 texels every device has to allow in a texel buffer
+    let foreground = match args::value::<String>("--input") {
+        Some(path) => image::open(&path)
+            .unwrap_or_else(|err| panic!("failed to load {path}: {err}"))
+            .to_rgba8(),
+        None => rings(256),
+    };
+    let output = args::value::<String>("--output").unwrap_or_else(|| "crossfaded.png".into());
+    let mix_factor = args::value::<f32>("--mix").unwrap_or(0.5).clamp(0.0, 1.0);
+    let (width, height) = foreground.dimensions();
+    let background = checkerboard(width, height);
+    let texels = width * height;
+
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+
+    let physical_device = device.physical_device();
+    assert!(
+        supports(
+            physical_device,
+            TEXEL_FORMAT,
+            FormatFeatures::UNIFORM_TEXEL_BUFFER | FormatFeatures::STORAGE_TEXEL_BUFFER,
+        ),
+        "{TEXEL_FORMAT:?} can't be used for texel buffers on this device"
+    );
+    // Texel buffers are addressed by the texture unit, which caps how many elements a view can
+    // cover. Storage buffers have no such limit, only one on their size in bytes
+    let limit = physical_device.properties().max_texel_buffer_elements;
+    assert!(
+        texels <= limit,
+        "{width}x{height} is {texels} texels, but this device allows {limit} in a texel buffer"
+    );
+
+    let allocators = Allocators::new(&device);
+
+    let buffer = |usage, memory_usage| {
+        Buffer::new_slice::<u8>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: memory_usage,
+                ..Default::default()
+            },
+            (texels * 4) as u64,
+        )
+            .expect("failed to create buffer")
+    };
+    // The foreground is only ever read, so it can live where the host writes it
+    let foreground_buffer = buffer(BufferUsage::UNIFORM_TEXEL_BUFFER, MemoryUsage::Upload);
+    foreground_buffer
+        .write()
+        .unwrap()
+        .copy_from_slice(foreground.as_raw());
+    // The background is read and written on the device, then copied out for the PNG
+    let upload_buffer = buffer(BufferUsage::TRANSFER_SRC, MemoryUsage::Upload);
+    upload_buffer
+        .write()
+        .unwrap()
+        .copy_from_slice(background.as_raw());
+    let background_buffer = buffer(
+        BufferUsage::STORAGE_TEXEL_BUFFER | BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+        MemoryUsage::DeviceOnly,
+    );
+    let download_buffer = buffer(BufferUsage::TRANSFER_DST, MemoryUsage::Download);
+
+    // The view is what gives the raw bytes a format, the same way an image view does for an
+    // image. The buffers themselves are just bytes
+    let view = |buffer: Subbuffer<[u8]>| {
+        BufferView::new(
+            buffer,
+            BufferViewCreateInfo {
+                format: Some(TEXEL_FORMAT),
+                ..Default::default()
+            },
+        )
+            .expect("failed to create buffer view")
+    };
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    let layout = pipeline.layout().set_layouts().get(0).unwrap();
+    let set = PersistentDescriptorSet::new(
+        &allocators.descriptor,
+        layout.clone(),
+        [
+            WriteDescriptorSet::buffer_view(0, view(foreground_buffer)),
+            WriteDescriptorSet::buffer_view(1, view(background_buffer.clone())),
+        ],
+    )
+        .unwrap();
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(
+            upload_buffer,
+            background_buffer.clone(),
+        ))
+        .unwrap()
+        .bind_pipeline_compute(pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            pipeline.layout().clone(),
+            0,
+            set,
+        )
+        .push_constants(
+            pipeline.layout().clone(),
+            0,
+            cs::PushConstants { mix_factor },
+        )
+        .dispatch([(texels + 63) / 64, 1, 1])
+        .unwrap()
+        .copy_buffer(CopyBufferInfo::buffers(
+            background_buffer,
+            download_buffer.clone(),
+        ))
+        .unwrap();
+
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let pixels = download_buffer.read().unwrap().to_vec();
+    image::RgbaImage::from_raw(width, height, pixels)
+        .unwrap()
+        .save(&output)
+        .unwrap_or_else(|err| panic!("failed to save {output}: {err}"));
+
+    println!("Faded {width}x{height} texels {mix_factor:.2} of the way into {output}");
+}
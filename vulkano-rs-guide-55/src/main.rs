@@ -0,0 +1,398 @@
+//Indirect drawing: the draw parameters are read from a buffer of commands instead of being passed
+//to the draw call, the first step towards letting the GPU decide what gets drawn
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, DrawIndexedIndirectCommand, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::{cube, sphere};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// A GRID by GRID field of cubes and spheres
+const GRID: u32 = 32;
+const SPACING: f32 = 2.5;
+
+// Where an object is and what it looks like, one per instance. An indirect command can't carry
+// push constants, so what used to be pushed before every draw comes from a vertex buffer
+// stepped per instance, and first_instance in the command picks the object
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct Object {
+    #[format(R32G32B32A32_SFLOAT)]
+    center: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    scale: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    color: [f32; 4],
+}
+
+// Where one mesh lives in the shared vertex and index buffers. All meshes have to share them,
+// since a single indirect draw can't switch buffers between its commands
+#[derive(Clone, Copy)]
+struct MeshRange {
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: u32,
+}
+
+const CUBE: usize = 0;
+const SPHERE: usize = 1;
+
+// Appends every mesh to one vertex and one index list. The indices stay relative to their own
+// mesh, vertex_offset is added to them when drawing
+fn merge(
+    meshes: Vec<(Vec<PosNormalUv>, Vec<u32>)>,
+) -> (Vec<PosNormalUv>, Vec<u32>, Vec<MeshRange>) {
+    let mut all_vertices = Vec::new();
+    let mut all_indices = Vec::new();
+    let mut ranges = Vec::new();
+    for (vertices, indices) in meshes {
+        ranges.push(MeshRange {
+            first_index: all_indices.len() as u32,
+            index_count: indices.len() as u32,
+            vertex_offset: all_vertices.len() as u32,
+        });
+        all_vertices.extend(vertices);
+        all_indices.extend(indices);
+    }
+    (all_vertices, all_indices, ranges)
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Batching {
+    // One command per object, each drawing a single instance
+    PerObject,
+    // One command per mesh, drawing all of its objects as instances
+    PerMesh,
+}
+
+struct IndirectDraw {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    commands: StreamingBuffer,
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+    meshes: Vec<MeshRange>,
+    objects: Subbuffer<[Object]>,
+    // Which mesh every object is drawn with. The objects are sorted by it, so the ones sharing
+    // a mesh are next to each other and can be drawn as one run of instances
+    shapes: Vec<usize>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    batching: Batching,
+    visible: usize,
+    // How many commands the last frame's draw read
+    command_count: usize,
+}
+
+impl IndirectDraw {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let (vertices, indices, meshes) = merge(vec![cube(), sphere(12, 24)]);
+
+        let offset = (GRID - 1) as f32 / 2.0;
+        let mut objects: Vec<_> = (0..GRID * GRID)
+            .map(|i| {
+                let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+                let shape = if (i % GRID + i / GRID) % 2 == 0 {
+                    CUBE
+                } else {
+                    SPHERE
+                };
+                let size = 0.4 + ((x * 1.3 + z * 0.7).sin() * 0.5 + 0.5) * 0.6;
+                let color = match shape {
+                    CUBE => [0.9, 0.5 + 0.4 * x / GRID as f32, 0.3, 1.0],
+                    _ => [0.3, 0.5 + 0.4 * z / GRID as f32, 0.9, 1.0],
+                };
+                let object = Object {
+                    center: [(x - offset) * SPACING, size, (z - offset) * SPACING, 0.0],
+                    scale: [size, size, size, 0.0],
+                    color,
+                };
+                (shape, object)
+            })
+            .collect();
+        objects.sort_by_key(|(shape, _)| *shape);
+        let (shapes, objects): (Vec<_>, Vec<_>) = objects.into_iter().unzip();
+
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+        let vertices = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::VERTEX_BUFFER),
+            allocation_info(),
+            vertices,
+        )
+            .expect("failed to create vertex buffer");
+        let indices = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::INDEX_BUFFER),
+            allocation_info(),
+            indices,
+        )
+            .expect("failed to create index buffer");
+        let objects = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::VERTEX_BUFFER),
+            allocation_info(),
+            objects,
+        )
+            .expect("failed to create object buffer");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state([PosNormalUv::per_vertex(), Object::per_instance()])
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let visible = shapes.len();
+        IndirectDraw {
+            camera: Camera::new(Vec3::new(0.0, 12.0, 30.0), Vec3::ZERO),
+            render_pass,
+            pipeline,
+            commands: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            vertices,
+            indices,
+            meshes,
+            objects,
+            shapes,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            batching: Batching::PerObject,
+            visible,
+            command_count: 0,
+        }
+    }
+
+    // The commands for drawing the first `visible` objects. This is the list of arguments the
+    // draw_indexed calls would have taken, only written to memory instead of recorded
+    fn build_commands(&self) -> Vec<DrawIndexedIndirectCommand> {
+        let command =
+            |mesh: MeshRange, instance_count, first_instance| DrawIndexedIndirectCommand {
+                index_count: mesh.index_count,
+                instance_count,
+                first_index: mesh.first_index,
+                vertex_offset: mesh.vertex_offset,
+                first_instance,
+            };
+
+        match self.batching {
+            Batching::PerObject => self.shapes[..self.visible]
+                .iter()
+                .enumerate()
+                .map(|(i, &shape)| command(self.meshes[shape], 1, i as u32))
+                .collect(),
+            Batching::PerMesh => (0..self.meshes.len())
+                .filter_map(|shape| {
+                    // The objects are sorted by shape, so each one is a contiguous run
+                    let visible = &self.shapes[..self.visible];
+                    let first = visible.iter().position(|&s| s == shape)?;
+                    let count = visible.iter().filter(|&&s| s == shape).count();
+                    Some(command(self.meshes[shape], count as u32, first as u32))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl App for IndirectDraw {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let aspect = self.viewport.dimensions[0] / self.viewport.dimensions[1];
+        let commands = self.build_commands();
+        self.command_count = commands.len();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.02, 0.02, 0.02, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    view_projection: self.camera.view_projection(aspect).to_cols_array_2d(),
+                },
+            )
+            .bind_vertex_buffers(0, (self.vertices.clone(), self.objects.clone()))
+            .bind_index_buffer(self.indices.clone());
+
+        // However many commands there are, this is one call on the CPU. The GPU walks the buffer
+        // and runs each command as if it had been recorded with draw_indexed. A buffer can't be
+        // empty, so with nothing to draw there is no call at all
+        if !commands.is_empty() {
+            builder
+                .draw_indexed_indirect(self.commands.write_iter(commands))
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Indirect draw").show(ctx, |ui| {
+            ui.radio_value(
+                &mut self.batching,
+                Batching::PerObject,
+                "a command per object",
+            );
+            ui.radio_value(&mut self.batching, Batching::PerMesh, "a command per mesh");
+            ui.add(egui::Slider::new(&mut self.visible, 0..=self.shapes.len()).text("objects"));
+            ui.label(format!(
+                "{} commands in one draw_indexed_indirect call",
+                self.command_count
+            ));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    // More than one command per call needs multi_draw_indirect, and a first_instance other than
+    // 0 needs draw_indirect_first_instance. Desktop GPUs have both
+    window::run_with_features(
+        "vulkano-rs-guide-55",
+        DeviceExtensions::empty(),
+        Features {
+            multi_draw_indirect: true,
+            draw_indirect_first_instance: true,
+            ..Features::empty()
+        },
+        IndirectDraw::new,
+    );
+}
@@ -0,0 +1,21 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+// Per instance
+layout(location = 2) in vec4 center;
+layout(location = 3) in vec4 scale;
+layout(location = 4) in vec4 color;
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec3 v_color;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+} pc;
+
+void main() {
+    v_normal = normal;
+    v_color = color.rgb;
+    gl_Position = pc.view_projection * vec4(center.xyz + position * scale.xyz, 1.0);
+}
@@ -0,0 +1,479 @@
+//GPU culling: a compute shader tests every object against the view frustum and writes draw
+//commands for the ones that survive, which an indirect draw then reads without the CPU looking
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, DrawIndexedIndirectCommand, FillBufferInfo,
+    RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::{frustum_planes, Camera};
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::{cube, sphere};
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// A GRID by GRID field of cubes and spheres, large enough that most of it is off screen
+const GRID: u32 = 64;
+const SPACING: f32 = 2.5;
+// Has to match local_size_x in the compute shader
+const WORK_GROUP_SIZE: u32 = 64;
+
+// Per instance for drawing as in chapter 55, and a storage buffer for the culling shader. The w
+// of scale is the radius of a sphere around the object, which is all the test looks at
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct Object {
+    #[format(R32G32B32A32_SFLOAT)]
+    center: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    scale: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    color: [f32; 4],
+}
+
+// Where one mesh lives in the shared vertex and index buffers. The culling shader copies these
+// into the commands it writes, so they go to the GPU too
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct MeshRange {
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: u32,
+}
+
+const CUBE: u32 = 0;
+const SPHERE: u32 = 1;
+
+// Appends every mesh to one vertex and one index list, as in chapter 55
+fn merge(
+    meshes: Vec<(Vec<PosNormalUv>, Vec<u32>)>,
+) -> (Vec<PosNormalUv>, Vec<u32>, Vec<MeshRange>) {
+    let mut all_vertices = Vec::new();
+    let mut all_indices = Vec::new();
+    let mut ranges = Vec::new();
+    for (vertices, indices) in meshes {
+        ranges.push(MeshRange {
+            first_index: all_indices.len() as u32,
+            index_count: indices.len() as u32,
+            vertex_offset: all_vertices.len() as u32,
+        });
+        all_vertices.extend(vertices);
+        all_indices.extend(indices);
+    }
+    (all_vertices, all_indices, ranges)
+}
+
+mod cull_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cull_cs.comp",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct GpuCulling {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    cull_pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+    objects: Subbuffer<[Object]>,
+    // Written by the culling shader, read by the draw. Never touched by the CPU
+    commands: Subbuffer<[DrawIndexedIndirectCommand]>,
+    // One count per frame in flight, host visible so the overlay can show it. A frame only
+    // reads its own once the GPU is done with the last frame that used it
+    counts: Vec<Subbuffer<u32>>,
+    cull_sets: Vec<Arc<PersistentDescriptorSet>>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    drawn: u32,
+    // Parameters exposed in the overlay
+    culling: bool,
+    // The planes stay where they were when frozen, to fly out and look at what was culled
+    frozen: Option<[[f32; 4]; 6]>,
+}
+
+impl GpuCulling {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let (vertices, indices, meshes) = merge(vec![cube(), sphere(12, 24)]);
+
+        let offset = (GRID - 1) as f32 / 2.0;
+        let (shapes, objects): (Vec<_>, Vec<_>) = (0..GRID * GRID)
+            .map(|i| {
+                let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+                let shape = if (i % GRID + i / GRID) % 2 == 0 {
+                    CUBE
+                } else {
+                    SPHERE
+                };
+                let size = 0.4 + ((x * 1.3 + z * 0.7).sin() * 0.5 + 0.5) * 0.6;
+                // The corners of a cube stick out further than its faces
+                let (radius, color) = match shape {
+                    CUBE => (
+                        size * 3f32.sqrt(),
+                        [0.9, 0.5 + 0.4 * x / GRID as f32, 0.3, 1.0],
+                    ),
+                    _ => (size, [0.3, 0.5 + 0.4 * z / GRID as f32, 0.9, 1.0]),
+                };
+                let object = Object {
+                    center: [(x - offset) * SPACING, size, (z - offset) * SPACING, 0.0],
+                    scale: [size, size, size, radius],
+                    color,
+                };
+                (shape, object)
+            })
+            .unzip();
+        let object_count = objects.len() as u64;
+
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = |usage| AllocationCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let vertices = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::VERTEX_BUFFER),
+            allocation_info(MemoryUsage::Upload),
+            vertices,
+        )
+            .expect("failed to create vertex buffer");
+        let indices = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::INDEX_BUFFER),
+            allocation_info(MemoryUsage::Upload),
+            indices,
+        )
+            .expect("failed to create index buffer");
+        let objects = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::VERTEX_BUFFER | BufferUsage::STORAGE_BUFFER),
+            allocation_info(MemoryUsage::Upload),
+            objects,
+        )
+            .expect("failed to create object buffer");
+        let shapes = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::STORAGE_BUFFER),
+            allocation_info(MemoryUsage::Upload),
+            shapes,
+        )
+            .expect("failed to create shape buffer");
+        let meshes = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::STORAGE_BUFFER),
+            allocation_info(MemoryUsage::Upload),
+            meshes,
+        )
+            .expect("failed to create mesh buffer");
+        // Room for every object, in case they all survive
+        let commands = Buffer::new_slice::<DrawIndexedIndirectCommand>(
+            &allocators.memory,
+            buffer_info(
+                BufferUsage::STORAGE_BUFFER
+                    | BufferUsage::INDIRECT_BUFFER
+                    | BufferUsage::TRANSFER_DST,
+            ),
+            allocation_info(MemoryUsage::DeviceOnly),
+            object_count,
+        )
+            .expect("failed to create command buffer");
+        let counts: Vec<_> = renderer
+            .images
+            .iter()
+            .map(|_| {
+                Buffer::new_sized::<u32>(
+                    &allocators.memory,
+                    buffer_info(BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST),
+                    allocation_info(MemoryUsage::Download),
+                )
+                    .expect("failed to create count buffer")
+            })
+            .collect();
+
+        let cull_cs = cull_cs::load(device.clone()).expect("failed to create shader module");
+        let cull_pipeline = ComputePipeline::new(
+            device.clone(),
+            cull_cs.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+        let cull_sets = counts
+            .iter()
+            .map(|count| {
+                PersistentDescriptorSet::new(
+                    &allocators.descriptor,
+                    cull_pipeline.layout().set_layouts()[0].clone(),
+                    [
+                        WriteDescriptorSet::buffer(0, objects.clone()),
+                        WriteDescriptorSet::buffer(1, shapes.clone()),
+                        WriteDescriptorSet::buffer(2, meshes.clone()),
+                        WriteDescriptorSet::buffer(3, commands.clone()),
+                        WriteDescriptorSet::buffer(4, count.clone()),
+                    ],
+                )
+                    .unwrap()
+            })
+            .collect();
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state([PosNormalUv::per_vertex(), Object::per_instance()])
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        GpuCulling {
+            camera: Camera::new(Vec3::new(0.0, 4.0, 0.0), Vec3::new(20.0, 0.0, 20.0)),
+            render_pass,
+            pipeline,
+            cull_pipeline,
+            allocators,
+            vertices,
+            indices,
+            objects,
+            commands,
+            counts,
+            cull_sets,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            drawn: 0,
+            culling: true,
+            frozen: None,
+        }
+    }
+}
+
+impl App for GpuCulling {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let slot = image_index as usize;
+        // What the culling found the last time this slot was used. If the GPU isn't done with
+        // it yet, the number from an earlier frame stays up
+        if let Ok(count) = self.counts[slot].read() {
+            self.drawn = *count;
+        }
+
+        let aspect = self.viewport.dimensions[0] / self.viewport.dimensions[1];
+        let view_projection = self.camera.view_projection(aspect);
+        let planes = self
+            .frozen
+            .unwrap_or_else(|| frustum_planes(view_projection));
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        // The commands past the count keep whatever an earlier frame left there. Zeroing them
+        // turns them into draws of no instances, since the draw below reads the whole buffer
+        builder
+            .fill_buffer(FillBufferInfo::dst_buffer(
+                self.commands.clone().reinterpret::<[u32]>(),
+            ))
+            .unwrap()
+            .fill_buffer(FillBufferInfo::dst_buffer(
+                self.counts[slot].clone().reinterpret::<[u32]>(),
+            ))
+            .unwrap()
+            .bind_pipeline_compute(self.cull_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.cull_pipeline.layout().clone(),
+                0,
+                self.cull_sets[slot].clone(),
+            )
+            .push_constants(
+                self.cull_pipeline.layout().clone(),
+                0,
+                cull_cs::PushConstants {
+                    planes,
+                    object_count: self.objects.len() as u32,
+                    enabled: self.culling as u32,
+                },
+            )
+            .dispatch([
+                (self.objects.len() as u32 + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE,
+                1,
+                1,
+            ])
+            .unwrap();
+
+        // vulkano sees that the draw reads the commands as indirect arguments after the
+        // dispatch wrote them, and puts a barrier between the two
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.02, 0.02, 0.02, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    view_projection: view_projection.to_cols_array_2d(),
+                },
+            )
+            .bind_vertex_buffers(0, (self.vertices.clone(), self.objects.clone()))
+            .bind_index_buffer(self.indices.clone())
+            // Without VK_KHR_draw_indirect_count the number of commands is fixed when recording,
+            // so it's all of them. The empty ones past the count are skipped by the GPU, but
+            // still read
+            .draw_indexed_indirect(self.commands.clone())
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("GPU culling").show(ctx, |ui| {
+            ui.checkbox(&mut self.culling, "cull against the frustum");
+            let mut frozen = self.frozen.is_some();
+            if ui.checkbox(&mut frozen, "freeze the frustum").changed() {
+                let aspect = self.viewport.dimensions[0] / self.viewport.dimensions[1];
+                self.frozen = frozen.then(|| frustum_planes(self.camera.view_projection(aspect)));
+            }
+            ui.label(format!(
+                "{} of {} objects drawn",
+                self.drawn,
+                self.objects.len()
+            ));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    // The commands each draw one instance of their own object, picked with first_instance
+    window::run_with_features(
+        "vulkano-rs-guide-56",
+        DeviceExtensions::empty(),
+        Features {
+            multi_draw_indirect: true,
+            draw_indirect_first_instance: true,
+            ..Features::empty()
+        },
+        GpuCulling::new,
+    );
+}
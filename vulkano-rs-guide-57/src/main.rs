@@ -0,0 +1,177 @@
+//Text: glyphs rasterized once into an atlas, and a batch of quads per frame drawing strings
+//with it, here for a HUD of frame statistics drawn by the chapter itself
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::stats::FrameStats;
+use vulkano_rs_common::text::TextRenderer;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Distance from the edges of the window to the HUD, in pixels
+const MARGIN: f32 = 12.0;
+
+struct Text {
+    render_pass: Arc<RenderPass>,
+    allocators: Allocators,
+    // Every size needs its own atlas, glyphs scaled up from a small one would be blurry
+    small: TextRenderer,
+    large: TextRenderer,
+    // The chapter keeps its own statistics to draw them, the runner's only go to the overlay
+    stats: FrameStats,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    start: Instant,
+    // Parameters exposed in the overlay
+    message: String,
+    color: [f32; 3],
+    show_stats: bool,
+}
+
+impl Text {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+        let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+
+        Text {
+            small: TextRenderer::new(&allocators, renderer.queue(), subpass.clone(), 16.0),
+            large: TextRenderer::new(&allocators, renderer.queue(), subpass, 48.0),
+            stats: FrameStats::new(&allocators, renderer.queue(), renderer.images.len() as u32),
+            render_pass,
+            allocators,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            start: Instant::now(),
+            message: "Type here to change this line".into(),
+            color: [1.0, 0.8, 0.3],
+            show_stats: true,
+        }
+    }
+}
+
+impl App for Text {
+    fn resize(&mut self, renderer: &Renderer) {
+        self.extent = renderer.swapchain.image_extent();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        self.stats.frame();
+        let before = self.stats.begin_gpu(image_index, before);
+        let [width, height] = self.extent.map(|x| x as f32);
+
+        // Strings are only collected here, nothing is recorded until draw
+        if self.show_stats {
+            let stats = self.stats.lines().join("\n");
+            self.small
+                .add(&stats, [MARGIN, MARGIN], [1.0, 1.0, 1.0, 0.9]);
+        }
+
+        let title = "vulkano-rs-guide";
+        let [title_width, title_height] = self.large.measure(title);
+        let bob = (self.start.elapsed().as_secs_f32() * 2.0).sin() * 8.0;
+        self.large.add(
+            title,
+            [
+                (width - title_width) / 2.0,
+                (height - title_height) / 2.0 + bob,
+            ],
+            [1.0, 1.0, 1.0, 1.0],
+        );
+
+        let line_height = self.small.line_height();
+        self.small.add(
+            &self.message,
+            [MARGIN, height - MARGIN - line_height],
+            [self.color[0], self.color[1], self.color[2], 1.0],
+        );
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.07, 0.12, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap();
+        // One draw per font size, whatever the number of strings
+        self.small.draw(&mut builder, self.extent);
+        self.large.draw(&mut builder, self.extent);
+        builder.end_render_pass().unwrap();
+
+        let future = before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed();
+        self.stats.end_gpu(image_index, future)
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Text")
+            .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+            .show(ctx, |ui| {
+                ui.text_edit_singleline(&mut self.message);
+                ui.color_edit_button_rgb(&mut self.color);
+                ui.checkbox(&mut self.show_stats, "frame statistics");
+                ui.label("Only printable ASCII is in the atlas, the rest shows up as ?");
+            });
+    }
+}
+
+fn main() {
+    window::run("vulkano-rs-guide-57", DeviceExtensions::empty(), Text::new);
+}
@@ -0,0 +1,260 @@
+//Sprites: thousands of textured quads built on the CPU every frame, batched into one vertex
+//buffer and drawn with one call per texture
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::SamplerCreateInfo;
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::sprite::{Sprite, SpriteBatch, TextureId};
+use vulkano_rs_common::window::{self, App, Renderer};
+
+const MAX_SPRITES: usize = 20000;
+const TEXTURE_SIZE: u32 = 64;
+
+// A small xorshift generator, the sprites only need to look random
+struct Random(u32);
+
+impl Random {
+    // Uniform in 0..1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+}
+
+// The textures are drawn here rather than loaded, each from a distance field of the shape:
+// negative inside, with a pixel of smoothing at the edge
+fn shape_texture(distance: impl Fn(f32, f32) -> f32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(TEXTURE_SIZE, TEXTURE_SIZE, |x, y| {
+        // -1 to 1 across the texture, sampled at texel centers
+        let to_unit = |i: u32| (i as f32 + 0.5) / TEXTURE_SIZE as f32 * 2.0 - 1.0;
+        let (u, v) = (to_unit(x), to_unit(y));
+        let d = distance(u, v) * TEXTURE_SIZE as f32 / 2.0;
+        let coverage = (0.5 - d).clamp(0.0, 1.0);
+        // Lighter towards the top left, so the rotation shows
+        let shade = 1.0 - (u + v + 2.0) * 0.12;
+        let value = (shade * 255.0) as u8;
+        image::Rgba([value, value, value, (coverage * 255.0) as u8])
+    })
+}
+
+fn ball() -> image::RgbaImage {
+    shape_texture(|u, v| (u * u + v * v).sqrt() - 0.9)
+}
+
+fn square() -> image::RgbaImage {
+    shape_texture(|u, v| u.abs().max(v.abs()) - 0.8)
+}
+
+fn star() -> image::RgbaImage {
+    shape_texture(|u, v| {
+        // A circle whose radius goes in and out five times around
+        let radius = 0.55 + 0.35 * (v.atan2(u) * 5.0).cos();
+        (u * u + v * v).sqrt() - radius
+    })
+}
+
+struct Body {
+    texture: usize,
+    position: [f32; 2],
+    velocity: [f32; 2],
+    angle: f32,
+    spin: f32,
+    size: f32,
+    color: [f32; 4],
+}
+
+struct Sprites {
+    render_pass: Arc<RenderPass>,
+    allocators: Allocators,
+    batch: SpriteBatch,
+    textures: Vec<TextureId>,
+    bodies: Vec<Body>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    extent: [u32; 2],
+    last_frame: Instant,
+    // Parameters exposed in the overlay
+    count: usize,
+}
+
+impl Sprites {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        // Sprites are scaled and rotated freely, linear filtering keeps their edges smooth
+        let mut batch = SpriteBatch::new(
+            &allocators,
+            renderer.queue(),
+            Subpass::from(render_pass.clone(), 0).unwrap(),
+            SamplerCreateInfo::simple_repeat_linear_no_mipmap(),
+        );
+        let textures = [ball(), square(), star()]
+            .iter()
+            .map(|image| batch.add_image(image))
+            .collect();
+
+        let [width, height] = renderer.swapchain.image_extent();
+        let mut random = Random(0x2545_f491);
+        let bodies = (0..MAX_SPRITES)
+            .map(|i| {
+                let direction = random.next() * TAU;
+                let speed = 40.0 + random.next() * 160.0;
+                Body {
+                    texture: i % 3,
+                    position: [random.next() * width as f32, random.next() * height as f32],
+                    velocity: [direction.cos() * speed, direction.sin() * speed],
+                    angle: random.next() * TAU,
+                    spin: (random.next() - 0.5) * 4.0,
+                    size: 12.0 + random.next() * 28.0,
+                    color: [
+                        0.4 + random.next() * 0.6,
+                        0.4 + random.next() * 0.6,
+                        0.4 + random.next() * 0.6,
+                        1.0,
+                    ],
+                }
+            })
+            .collect();
+
+        Sprites {
+            render_pass,
+            batch,
+            textures,
+            bodies,
+            framebuffers: Vec::new(),
+            extent: [0, 0],
+            last_frame: Instant::now(),
+            count: 2000,
+        }
+    }
+}
+
+impl App for Sprites {
+    fn resize(&mut self, renderer: &Renderer) {
+        self.extent = renderer.swapchain.image_extent();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let delta = self.last_frame.elapsed().as_secs_f32().min(0.1);
+        self.last_frame = Instant::now();
+        let bounds = self.extent.map(|x| x as f32);
+
+        // The simulation and the quads both happen on the CPU, the GPU only sees the vertices
+        for body in &mut self.bodies[..self.count] {
+            for axis in 0..2 {
+                body.position[axis] += body.velocity[axis] * delta;
+                // Bouncing off the edges of the window
+                if body.position[axis] < 0.0 || body.position[axis] > bounds[axis] {
+                    body.velocity[axis] = -body.velocity[axis];
+                    body.position[axis] = body.position[axis].clamp(0.0, bounds[axis]);
+                }
+            }
+            body.angle += body.spin * delta;
+
+            // The textures alternate from one sprite to the next, the batch still draws each
+            // one in a single call. The price is that a star is always drawn over every ball,
+            // whichever was added last
+            self.batch.add(
+                self.textures[body.texture],
+                &Sprite {
+                    rotation: body.angle,
+                    color: body.color,
+                    ..Sprite::new(body.position, [body.size, body.size])
+                },
+            );
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.08, 0.08, 0.1, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap();
+        self.batch.draw(&mut builder, self.extent);
+        builder.end_render_pass().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Sprites").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.count, 0..=MAX_SPRITES).text("sprites"));
+            ui.label(format!(
+                "{} draw calls for {} sprites",
+                self.batch.draw_count(),
+                self.count
+            ));
+        });
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-58",
+        DeviceExtensions::empty(),
+        Sprites::new,
+    );
+}
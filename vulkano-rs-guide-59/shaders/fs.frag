@@ -0,0 +1,44 @@
+#version 460
+
+const float TILE_SIZE = 16.0;
+
+layout(location = 0) out vec4 f_color;
+
+// One texel per tile, holding the index of its layer in the tileset
+layout(set = 0, binding = 0) uniform usampler2D map;
+layout(set = 0, binding = 1) uniform sampler2DArray tileset;
+
+layout(push_constant) uniform PushConstants {
+    // Where the top left corner of the window is, in tileset texels
+    vec2 offset;
+    // Screen pixels per tileset texel
+    float zoom;
+    uint grid;
+} pc;
+
+void main() {
+    // Every pixel works out which tile it is in on its own, so the cost depends on
+    // the size of the window and not on the size of the map
+    vec2 world = pc.offset + gl_FragCoord.xy / pc.zoom;
+    vec2 tile_position = world / TILE_SIZE;
+    ivec2 tile = ivec2(floor(tile_position));
+    ivec2 map_size = textureSize(map, 0);
+    if (any(lessThan(tile, ivec2(0))) || any(greaterThanEqual(tile, map_size))) {
+        f_color = vec4(0.02, 0.02, 0.02, 1.0);
+        return;
+    }
+
+    // Integer textures can't be filtered, texelFetch reads the exact tile
+    uint index = texelFetch(map, tile, 0).r;
+    // Each tile is its own layer, so sampling near its edge can't pick up texels of
+    // the tile next to it in the set, as it could in an atlas
+    vec3 color = texture(tileset, vec3(fract(tile_position), float(index))).rgb;
+
+    if (pc.grid != 0) {
+        vec2 from_edge = fract(tile_position) * TILE_SIZE * pc.zoom;
+        if (any(lessThan(from_edge, vec2(1.0)))) {
+            color *= 0.5;
+        }
+    }
+    f_color = vec4(color, 1.0);
+}
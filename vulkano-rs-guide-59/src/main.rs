@@ -0,0 +1,416 @@
+//Tilemap: a large grid of tiles drawn with a single triangle, the fragment shader looking up
+//which tile covers each pixel in a texture of tile indices
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::VertexInputState;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::winit::event::{
+    ElementState, KeyboardInput, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+// Tiles across and down the map
+const MAP_SIZE: u32 = 1024;
+// Texels per side of a tile, has to match TILE_SIZE in the fragment shader
+const TILE_SIZE: u32 = 16;
+// Screen pixels per second when scrolling, whatever the zoom
+const SCROLL_SPEED: f32 = 600.0;
+
+// The kinds of tile, in the order of the layers of the tileset
+#[derive(Clone, Copy)]
+enum Tile {
+    Water,
+    Sand,
+    Grass,
+    Forest,
+    Rock,
+    Snow,
+}
+
+const TILES: [Tile; 6] = [
+    Tile::Water,
+    Tile::Sand,
+    Tile::Grass,
+    Tile::Forest,
+    Tile::Rock,
+    Tile::Snow,
+];
+
+// A hash of a 2D integer position, the same position always gives the same value in 0..1
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32)
+        .wrapping_mul(0x8da6_b343)
+        .wrapping_add((y as u32).wrapping_mul(0xd816_3841))
+        .wrapping_add(seed.wrapping_mul(0xcb1a_b31f));
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+
+// Hashes on a grid of `cell` tiles, blended smoothly in between
+fn value_noise(x: u32, y: u32, cell: u32, seed: u32) -> f32 {
+    let (fx, fy) = (x as f32 / cell as f32, y as f32 / cell as f32);
+    let (ix, iy) = (fx.floor() as i32, fy.floor() as i32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(fx.fract()), smooth(fy.fract()));
+    let top = hash(ix, iy, seed) * (1.0 - tx) + hash(ix + 1, iy, seed) * tx;
+    let bottom = hash(ix, iy + 1, seed) * (1.0 - tx) + hash(ix + 1, iy + 1, seed) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
+
+// Islands from a few octaves of noise, the tile picked from the height
+fn generate_map() -> Vec<u8> {
+    (0..MAP_SIZE * MAP_SIZE)
+        .map(|i| {
+            let (x, y) = (i % MAP_SIZE, i / MAP_SIZE);
+            let height = value_noise(x, y, 128, 1) * 0.55
+                + value_noise(x, y, 32, 2) * 0.3
+                + value_noise(x, y, 8, 3) * 0.15;
+            let tile = match height {
+                h if h < 0.45 => Tile::Water,
+                h if h < 0.48 => Tile::Sand,
+                h if h < 0.58 => Tile::Grass,
+                h if h < 0.66 => Tile::Forest,
+                h if h < 0.74 => Tile::Rock,
+                _ => Tile::Snow,
+            };
+            tile as u8
+        })
+        .collect()
+}
+
+// The texels of one tile of the tileset, drawn here instead of loaded from a file
+fn tile_texels(tile: Tile) -> impl Iterator<Item = u8> {
+    (0..TILE_SIZE * TILE_SIZE).flat_map(move |i| {
+        let (x, y) = ((i % TILE_SIZE) as i32, (i / TILE_SIZE) as i32);
+        let speck = hash(x, y, tile as u32 + 10);
+        let rgb: [f32; 3] = match tile {
+            // Waves every few rows
+            Tile::Water if (y + x / 4) % 6 == 0 => [90.0, 140.0, 220.0],
+            Tile::Water => [40.0, 90.0, 190.0],
+            Tile::Sand => [220.0 - speck * 30.0, 200.0 - speck * 30.0, 140.0],
+            Tile::Grass => [70.0, 150.0 + speck * 40.0, 60.0],
+            // A round tree top on grass
+            Tile::Forest => {
+                let (dx, dy) = (x as f32 - 7.5, y as f32 - 7.5);
+                if dx * dx + dy * dy < 36.0 {
+                    [30.0, 90.0 + speck * 30.0, 40.0]
+                } else {
+                    [70.0, 150.0, 60.0]
+                }
+            }
+            Tile::Rock => {
+                let shade = 120.0 + speck * 40.0;
+                [shade, shade, shade + 10.0]
+            }
+            Tile::Snow => [240.0 - speck * 15.0, 245.0 - speck * 15.0, 250.0],
+        };
+        [rgb[0] as u8, rgb[1] as u8, rgb[2] as u8, 255]
+    })
+}
+
+mod fullscreen_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/fullscreen_vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Tilemap {
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    set: Arc<PersistentDescriptorSet>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    last_frame: Instant,
+    // Left, right, up and down, held or not
+    held: [bool; 4],
+    // The map position at the center of the window, in tileset texels
+    center: [f32; 2],
+    // Parameters exposed in the overlay
+    zoom: f32,
+    grid: bool,
+}
+
+impl Tilemap {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // A byte per tile: the whole million-tile map is a megabyte
+        let map = ImmutableImage::from_iter(
+            &allocators.memory,
+            generate_map(),
+            ImageDimensions::Dim2d {
+                width: MAP_SIZE,
+                height: MAP_SIZE,
+                array_layers: 1,
+            },
+            MipmapsCount::One,
+            Format::R8_UINT,
+            &mut uploads,
+        )
+            .expect("failed to create map texture");
+        let tileset = ImmutableImage::from_iter(
+            &allocators.memory,
+            TILES.into_iter().flat_map(tile_texels).collect::<Vec<_>>(),
+            ImageDimensions::Dim2d {
+                width: TILE_SIZE,
+                height: TILE_SIZE,
+                array_layers: TILES.len() as u32,
+            },
+            MipmapsCount::One,
+            Format::R8G8B8A8_SRGB,
+            &mut uploads,
+        )
+            .expect("failed to create tileset");
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+            .unwrap();
+
+        let vs = fullscreen_vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(VertexInputState::new())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // Nearest filtering for both: the map holds indices, and the tiles are pixel art
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::default())
+            .expect("failed to create sampler");
+        let set = PersistentDescriptorSet::new(
+            &allocators.descriptor,
+            pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(
+                    0,
+                    ImageView::new_default(map).unwrap(),
+                    sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    ImageView::new_default(tileset).unwrap(),
+                    sampler,
+                ),
+            ],
+        )
+            .unwrap();
+
+        let middle = (MAP_SIZE * TILE_SIZE) as f32 / 2.0;
+        Tilemap {
+            render_pass,
+            pipeline,
+            allocators,
+            set,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            last_frame: Instant::now(),
+            held: [false; 4],
+            center: [middle, middle],
+            zoom: 2.0,
+            grid: false,
+        }
+    }
+}
+
+impl App for Tilemap {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let delta = self.last_frame.elapsed().as_secs_f32().min(0.1);
+        self.last_frame = Instant::now();
+        let [left, right, up, down] = self.held.map(|held| held as i32 as f32);
+        let step = SCROLL_SPEED * delta / self.zoom;
+        self.center[0] += (right - left) * step;
+        self.center[1] += (down - up) * step;
+
+        // Whole screen pixels, so the tiles don't shimmer while scrolling
+        let half_screen = self.viewport.dimensions.map(|x| x / 2.0);
+        let offset = [0, 1]
+            .map(|axis| (self.center[axis] * self.zoom - half_screen[axis]).round() / self.zoom);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        // Every pixel gets written, so there's nothing to clear
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![None],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                fs::PushConstants {
+                    offset,
+                    zoom: self.zoom,
+                    grid: self.grid as u32,
+                },
+            )
+            .draw(3, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Tilemap").show(ctx, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.zoom, 0.25..=8.0)
+                    .logarithmic(true)
+                    .text("zoom"),
+            );
+            ui.checkbox(&mut self.grid, "tile grid");
+            let tile = self.center.map(|x| (x / TILE_SIZE as f32).floor() as i32);
+            ui.label(format!("Centered on tile {}, {}", tile[0], tile[1]));
+            ui.label("Arrow keys or WASD to scroll, mouse wheel to zoom");
+        });
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => {
+                let direction = match key {
+                    VirtualKeyCode::Left | VirtualKeyCode::A => 0,
+                    VirtualKeyCode::Right | VirtualKeyCode::D => 1,
+                    VirtualKeyCode::Up | VirtualKeyCode::W => 2,
+                    VirtualKeyCode::Down | VirtualKeyCode::S => 3,
+                    _ => return,
+                };
+                self.held[direction] = *state == ElementState::Pressed;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+                };
+                self.zoom = (self.zoom * 1.2f32.powf(lines)).clamp(0.25, 8.0);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-59",
+        DeviceExtensions::empty(),
+        Tilemap::new,
+    );
+}
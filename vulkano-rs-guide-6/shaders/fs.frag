@@ -0,0 +1,15 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+
+layout(location = 0) out vec4 f_color;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.5, 1.0, 0.3));
+
+void main() {
+    // Simple diffuse lighting from a directional light, plus some ambient light so
+    // the unlit side isn't completely black
+    float diffuse = max(dot(normalize(v_normal), LIGHT_DIRECTION), 0.0);
+    vec3 color = vec3(0.8, 0.7, 0.6) * (0.15 + diffuse);
+    f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    mat4 model;
+} pc;
+
+void main() {
+    // Fine as long as the model matrix only scales uniformly
+    v_normal = mat3(pc.model) * normal;
+    gl_Position = pc.view_projection * pc.model * vec4(position, 1.0);
+}
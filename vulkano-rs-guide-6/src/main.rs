@@ -0,0 +1,364 @@
+//Loading a Wavefront OBJ model instead of hard-coding the vertices
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::glam::{Mat4, Vec3};
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::{args, egui, tracing};
+
+// Positions, normals and texture coordinates interleaved in a single buffer, each vertex is 32
+// bytes
+struct Mesh {
+    vertices: Vec<PosNormalUv>,
+    indices: Vec<u32>,
+    computed_normals: bool,
+}
+
+// Merges every object of the file into one indexed mesh
+fn load_obj(path: &str) -> Mesh {
+    let (models, _materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            // OBJ faces index positions, normals and texture coordinates separately, Vulkan
+            // only has one index per vertex so tobj duplicates the vertices where needed
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        },
+    )
+        .expect("failed to load model");
+
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        computed_normals: false,
+    };
+
+    for model in models {
+        let positions: Vec<[f32; 3]> = model
+            .mesh
+            .positions
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+
+        // Plenty of exported models don't bother with normals
+        let normals: Vec<[f32; 3]> = if model.mesh.normals.is_empty() {
+            mesh.computed_normals = true;
+            compute_normals(&positions, &model.mesh.indices)
+        } else {
+            model
+                .mesh
+                .normals
+                .chunks_exact(3)
+                .map(|n| [n[0], n[1], n[2]])
+                .collect()
+        };
+
+        // Indices of later objects have to skip the vertices of the previous ones
+        let offset = mesh.vertices.len() as u32;
+        mesh.indices
+            .extend(model.mesh.indices.iter().map(|index| index + offset));
+        // Not drawn with a texture yet, models without coordinates get 0, 0
+        let mut tex_coords = model.mesh.texcoords.chunks_exact(2).map(|t| [t[0], t[1]]);
+        mesh.vertices.extend(
+            positions
+                .into_iter()
+                .zip(normals)
+                .map(|(position, normal)| PosNormalUv {
+                    position,
+                    normal,
+                    tex_coord: tex_coords.next().unwrap_or_default(),
+                }),
+        );
+    }
+
+    mesh
+}
+
+// Smooth normals: every vertex gets the average of the normals of the faces around it
+fn compute_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+        // The length of the cross product is twice the area of the triangle, so bigger faces
+        // weigh more in the average
+        let normal = (b - a).cross(c - a);
+        for &index in triangle {
+            normals[index as usize] += normal;
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or_zero().to_array())
+        .collect()
+}
+
+// Scales and centers the model so it fits in a sphere of radius 1 around the origin
+fn fit_to_unit_sphere(vertices: &[PosNormalUv]) -> Mat4 {
+    let (min, max) = vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), vertex| {
+            let position = Vec3::from(vertex.position);
+            (min.min(position), max.max(position))
+        },
+    );
+    let center = (min + max) / 2.0;
+    let radius = (max - min).length() / 2.0;
+
+    Mat4::from_scale(Vec3::splat(1.0 / radius)) * Mat4::from_translation(-center)
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct ModelViewer {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+    model: Mat4,
+    computed_normals: bool,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+}
+
+impl ModelViewer {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+
+        let path = args::value::<String>("--model")
+            .unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/assets/torus.obj").into());
+        let mesh = load_obj(&path);
+        tracing::info!(
+            "loaded {path}: {} vertices, {} triangles",
+            mesh.vertices.len(),
+            mesh.indices.len() / 3
+        );
+
+        let model = fit_to_unit_sphere(&mesh.vertices);
+        let allocators = renderer.allocators.clone();
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            mesh.vertices,
+        )
+            .expect("failed to create vertex buffer");
+
+        // The index buffer lists the vertices of each triangle, so shared vertices are stored
+        // only once
+        let index_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            mesh.indices,
+        )
+            .expect("failed to create index buffer");
+
+        // A depth attachment keeps the far side of the model from being drawn over the near side
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            // The viewport is set when recording, so the pipeline survives window resizes
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        ModelViewer {
+            camera: Camera::new(Vec3::new(0.0, 1.0, 2.5), Vec3::ZERO),
+            render_pass,
+            pipeline,
+            allocators,
+            vertex_buffer,
+            index_buffer,
+            model,
+            computed_normals: mesh.computed_normals,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+        }
+    }
+}
+
+impl App for ModelViewer {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let push_constants = vs::PushConstants {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+            model: self.model.to_cols_array_2d(),
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.1, 0.1, 0.1, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .bind_index_buffer(self.index_buffer.clone())
+            .draw_indexed(self.index_buffer.len() as u32, 1, 0, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Model").show(ctx, |ui| {
+            ui.label(format!("vertices: {}", self.vertex_buffer.len()));
+            ui.label(format!("triangles: {}", self.index_buffer.len() / 3));
+            if self.computed_normals {
+                ui.label("normals computed on load");
+            }
+            ui.label("WASD to move, hold the right mouse button to look around");
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-6",
+        DeviceExtensions::empty(),
+        ModelViewer::new,
+    );
+}
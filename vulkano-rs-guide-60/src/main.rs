@@ -0,0 +1,161 @@
+//The multiply example of chapter 2 over u32, i32 and f32, with one Rust function driving all three
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use bytemuck::Pod;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage};
+use vulkano::device::{Device, DeviceExtensions};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::shader::ShaderModule;
+use vulkano_rs_common::compute::{run_compute, ComputeContext};
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::Resource;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+const LENGTH: u32 = 65536;
+// Has to match local_size_x in the shaders
+const WORK_GROUP_SIZE: u32 = 64;
+
+// GLSL has no generics, so each element type gets its own copy of the shader. Only the type of
+// the array and the factor differ
+mod u32_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/u32_cs.comp",
+    }
+}
+
+mod i32_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/i32_cs.comp",
+    }
+}
+
+mod f32_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/f32_cs.comp",
+    }
+}
+
+// What the driver needs to know about an element type: its shader, the input data and what the
+// shader should have turned it into. Pod rules out padding and invalid bit patterns, so whatever
+// the GPU writes reads back as a valid value
+trait Element: BufferContents + Pod + Debug + PartialEq {
+    const NAME: &'static str;
+
+    fn load_shader(device: Arc<Device>) -> Arc<ShaderModule>;
+
+    fn input(index: u32) -> Self;
+
+    // The multiplication of the shader, done on the CPU
+    fn expected(self) -> Self;
+}
+
+impl Element for u32 {
+    const NAME: &'static str = "u32";
+
+    fn load_shader(device: Arc<Device>) -> Arc<ShaderModule> {
+        u32_cs::load(device).expect("failed to create shader module")
+    }
+
+    fn input(index: u32) -> Self {
+        index
+    }
+
+    // Unsigned overflow wraps around in GLSL
+    fn expected(self) -> Self {
+        self.wrapping_mul(12)
+    }
+}
+
+impl Element for i32 {
+    const NAME: &'static str = "i32";
+
+    fn load_shader(device: Arc<Device>) -> Arc<ShaderModule> {
+        i32_cs::load(device).expect("failed to create shader module")
+    }
+
+    // Centered on zero, so negative inputs get tested too
+    fn input(index: u32) -> Self {
+        index as i32 - (LENGTH / 2) as i32
+    }
+
+    fn expected(self) -> Self {
+        self.wrapping_mul(-7)
+    }
+}
+
+impl Element for f32 {
+    const NAME: &'static str = "f32";
+
+    fn load_shader(device: Arc<Device>) -> Arc<ShaderModule> {
+        f32_cs::load(device).expect("failed to create shader module")
+    }
+
+    fn input(index: u32) -> Self {
+        index as f32 * 0.25
+    }
+
+    // Halving only changes the exponent, so the GPU and the CPU get exactly the same bits and
+    // the results can be compared without a tolerance
+    fn expected(self) -> Self {
+        self * 0.5
+    }
+}
+
+// The same driver for every element type
+fn multiply<T: Element>(compute: &ComputeContext) {
+    let data_buffer = Buffer::from_iter(
+        &compute.allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        (0..LENGTH).map(T::input),
+    )
+        .expect("failed to create buffer");
+
+    let shader = T::load_shader(compute.queue.device().clone());
+    let future = run_compute(
+        compute,
+        &shader,
+        "main",
+        &[(0, Resource::buffer(data_buffer.clone()))],
+        [LENGTH / WORK_GROUP_SIZE, 1, 1],
+    );
+    wait::fence(&future);
+
+    let content = data_buffer.read().unwrap();
+    for (index, &value) in content.iter().enumerate() {
+        let expected = T::input(index as u32).expected();
+        assert_eq!(
+            value,
+            expected,
+            "{} element {index} doesn't match the CPU",
+            T::NAME
+        );
+    }
+    println!("{}: all {LENGTH} elements match the CPU", T::NAME);
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let compute = ComputeContext::new(&context);
+
+    multiply::<u32>(&compute);
+    multiply::<i32>(&compute);
+    multiply::<f32>(&compute);
+
+    tracing::info!("everything succeeded");
+}
@@ -0,0 +1,111 @@
+//Running a compute shader nobody compiled in: the SPIR-V is loaded from a file at run time and
+//its descriptor layout is read back out of it
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::descriptor_set::layout::DescriptorType;
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano_rs_common::compute::{run_compute, ComputeContext};
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::Resource;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::{args, reflect, wait};
+
+// The values printed from each buffer afterwards
+const PREVIEW: usize = 8;
+
+fn main() {
+    let Some(path) = args::value::<String>("--shader") else {
+        eprintln!("Usage: --shader file.spv [--entry main] [--length 1024] [--groups 16]");
+        eprintln!("shaders/square.comp is an example, compile it with glslc first");
+        std::process::exit(1);
+    };
+    let entry_name = args::value::<String>("--entry").unwrap_or_else(|| "main".to_owned());
+    let length = args::value::<u32>("--length").unwrap_or(1024).max(1);
+    // One work group per 64 elements suits the usual local_size_x = 64
+    let groups = args::value::<u32>("--groups").unwrap_or((length + 63) / 64);
+
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let compute = ComputeContext::new(&context);
+
+    let shader = reflect::load_spirv(&context.device, &path);
+    let Some(entry_point) = shader.entry_point(&entry_name) else {
+        tracing::error!("{path} has no entry point {entry_name}");
+        std::process::exit(1);
+    };
+
+    // Everything the pipeline layout is built from, straight out of the SPIR-V
+    let bindings = reflect::bindings(&entry_point);
+    println!("{path}, entry point {entry_name}:");
+    for binding in &bindings {
+        let count = match binding.descriptor_count {
+            Some(count) => count.to_string(),
+            None => "runtime-sized".to_owned(),
+        };
+        println!(
+            "  set {} binding {}: {:?}, {count}",
+            binding.set, binding.binding, binding.descriptor_types
+        );
+    }
+    let push_constant_range = reflect::push_constant_range(&entry_point);
+    if let Some(range) = push_constant_range {
+        println!("  push constants: {} bytes at {}", range.size, range.offset);
+    }
+
+    // The shader can ask for anything, this chapter only knows how to make buffers of numbers
+    if push_constant_range.is_some() {
+        tracing::error!("the shader takes push constants, which there is no way to fill in here");
+        std::process::exit(1);
+    }
+    for binding in &bindings {
+        let is_buffer = binding.descriptor_types.iter().any(|descriptor_type| {
+            matches!(
+                descriptor_type,
+                DescriptorType::StorageBuffer | DescriptorType::UniformBuffer
+            )
+        });
+        if binding.set != 0 || !is_buffer {
+            tracing::error!("only buffers in set 0 are supported");
+            std::process::exit(1);
+        }
+    }
+
+    // Every buffer starts out as 0, 1, 2..., whether the shader reads or writes it
+    let buffers: Vec<(u32, Subbuffer<[u32]>)> = bindings
+        .iter()
+        .map(|binding| {
+            let buffer = Buffer::from_iter(
+                &compute.allocators.memory,
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER | BufferUsage::UNIFORM_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::Upload,
+                    ..Default::default()
+                },
+                0..length,
+            )
+                .expect("failed to create buffer");
+            (binding.binding, buffer)
+        })
+        .collect();
+    let resources: Vec<_> = buffers
+        .iter()
+        .map(|(binding, buffer)| (*binding, Resource::buffer(buffer.clone())))
+        .collect();
+
+    let future = run_compute(&compute, &shader, &entry_name, &resources, [groups, 1, 1]);
+    wait::fence(&future);
+
+    for (binding, buffer) in &buffers {
+        let content = buffer.read().unwrap();
+        println!(
+            "binding {binding}: {:?}...",
+            &content[..PREVIEW.min(content.len())]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//Uploading buffers three ways: host memory like the guide, a staging copy into device memory, and
+//device memory the CPU can write directly, timed over a range of sizes
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+use vulkano::device::{Device, DeviceExtensions, Queue};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::memory::MemoryPropertyFlags;
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::memory::{buffer_in_memory, TrackingAllocator};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// The GPU copies each uploaded buffer this many times, to see how fast it reads from it
+const READS: u32 = 8;
+
+#[derive(Clone, Copy, Debug)]
+enum Strategy {
+    // What the guide does: Buffer::from_iter with MemoryUsage::Upload. On a discrete GPU without
+    // resizable BAR the buffer lands in system memory, and the GPU reads it over the bus every
+    // time it is used. Integrated GPUs only have the one memory, so every row looks alike there
+    FromIter,
+    // Written to host memory first, then copied once into a device-only buffer. Costs a copy and
+    // a wait up front, after which the GPU reads at full speed
+    Staging,
+    // Device-local memory the CPU can map, the 256 MiB BAR window, or all of VRAM with resizable
+    // BAR (ReBAR). No copy and fast GPU reads, but CPU writes cross the bus
+    DeviceLocalHostVisible,
+}
+
+struct Benchmark {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<TrackingAllocator>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+}
+
+impl Benchmark {
+    // Uploads `data` with `strategy`, returns the buffer the GPU then uses and how long it took
+    // until the GPU could use it. None if the device has no memory for the strategy
+    fn upload(&self, strategy: Strategy, data: &[u32]) -> Option<(Subbuffer<[u32]>, f64)> {
+        let start = Instant::now();
+        let buffer = match strategy {
+            Strategy::FromIter => self.host_buffer(data, BufferUsage::TRANSFER_SRC),
+            Strategy::Staging => {
+                let staging_buffer = self.host_buffer(data, BufferUsage::TRANSFER_SRC);
+                let device_buffer = Buffer::new_slice::<u32>(
+                    &self.memory_allocator,
+                    BufferCreateInfo {
+                        usage: BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::DeviceOnly,
+                        ..Default::default()
+                    },
+                    data.len() as u64,
+                )
+                    .expect("failed to create buffer");
+                self.copy(&staging_buffer, &device_buffer, 1);
+                device_buffer
+            }
+            Strategy::DeviceLocalHostVisible => {
+                let buffer = buffer_in_memory::<u32>(
+                    &self.memory_allocator,
+                    data.len() as u64,
+                    BufferUsage::TRANSFER_SRC,
+                    |flags| {
+                        flags.contains(
+                            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
+                        )
+                    },
+                )?;
+                buffer.write().unwrap().copy_from_slice(data);
+                buffer
+            }
+        };
+        Some((buffer, start.elapsed().as_secs_f64()))
+    }
+
+    fn host_buffer(&self, data: &[u32], usage: BufferUsage) -> Subbuffer<[u32]> {
+        Buffer::from_iter(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            data.iter().copied(),
+        )
+            .expect("failed to create buffer")
+    }
+
+    // Copies `source` into `destination` `times` times in one submission, and waits
+    fn copy(&self, source: &Subbuffer<[u32]>, destination: &Subbuffer<[u32]>, times: u32) {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        for _ in 0..times {
+            builder
+                .copy_buffer(CopyBufferInfo::buffers(source.clone(), destination.clone()))
+                .unwrap();
+        }
+        let future = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+    }
+
+    // How fast the GPU reads `buffer`, in GB/s. A copy into device memory stands in for a
+    // shader reading it, both are limited by where the source lives
+    fn read_speed(&self, buffer: &Subbuffer<[u32]>) -> f64 {
+        let scratch = Buffer::new_slice::<u32>(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            buffer.len(),
+        )
+            .expect("failed to create buffer");
+
+        let start = Instant::now();
+        self.copy(buffer, &scratch, READS);
+        // Includes the submission and the wait, which dominate for the small sizes
+        (buffer.size() * READS as u64) as f64 / start.elapsed().as_secs_f64() / 1e9
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{} MiB", bytes / (1024 * 1024))
+    } else {
+        format!("{} KiB", bytes / 1024)
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let allocators = Allocators::new(&context.device);
+
+    let benchmark = Benchmark {
+        device: context.device.clone(),
+        queue: context.queue.clone(),
+        memory_allocator: allocators.memory.clone(),
+        command_buffer_allocator: allocators.command.clone(),
+    };
+
+    // From 64 KiB, 16 times larger each step up to --max-size MiB
+    let max_size = args::value::<u64>("--max-size").unwrap_or(256).max(1) * 1024 * 1024;
+    let sizes: Vec<u64> = std::iter::successors(Some(64 * 1024), |size| Some(size * 16))
+        .take_while(|&size| size <= max_size)
+        .collect();
+
+    println!(
+        "{:>9}  {:<24}  {:>11}  {:>14}",
+        "size", "strategy", "upload", "GPU reads"
+    );
+    for size in sizes {
+        let data: Vec<u32> = (0..(size / 4) as u32).collect();
+        for strategy in [
+            Strategy::FromIter,
+            Strategy::Staging,
+            Strategy::DeviceLocalHostVisible,
+        ] {
+            let name = format!("{strategy:?}");
+            match benchmark.upload(strategy, &data) {
+                Some((buffer, seconds)) => {
+                    let read_speed = benchmark.read_speed(&buffer);
+                    println!(
+                        "{:>9}  {name:<24}  {:>8.3} ms  {read_speed:>9.2} GB/s",
+                        format_size(size),
+                        seconds * 1000.0
+                    );
+                }
+                None => println!(
+                    "{:>9}  {name:<24}  no device-local host-visible memory left",
+                    format_size(size)
+                ),
+            }
+        }
+    }
+
+    tracing::info!("everything succeeded");
+}
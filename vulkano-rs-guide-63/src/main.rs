@@ -0,0 +1,394 @@
+//Ray tracing with a BVH built on the CPU: raygen, closest-hit and miss shaders as functions of a
+//compute shader, tracing the bottom and top levels a ray tracing pipeline would be given
+
+use std::f32::consts::{PI, TAU};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::bvh::{BottomLevel, BvhNode, BvhTriangle, Instance};
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Quat, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        include: ["../shaders/common"],
+        path: "shaders/cs.comp",
+    }
+}
+
+// The meshes, in the order the bottom level is built from
+const PLANE: usize = 0;
+const CUBE: usize = 1;
+const SPHERE: usize = 2;
+
+// Custom indices, which pick the material in the shader
+const FLOOR: u32 = 0;
+const RED: u32 = 1;
+const BLUE: u32 = 2;
+const MIRROR: u32 = 3;
+const GOLD: u32 = 4;
+
+// A square from -1 to 1 facing up
+fn plane() -> (Vec<Vec3>, Vec<u32>) {
+    let positions = vec![
+        Vec3::new(-1.0, 0.0, -1.0),
+        Vec3::new(1.0, 0.0, -1.0),
+        Vec3::new(1.0, 0.0, 1.0),
+        Vec3::new(-1.0, 0.0, 1.0),
+    ];
+    (positions, vec![0, 2, 1, 0, 3, 2])
+}
+
+// A cube from -1 to 1. Only positions: the shader takes the normal of the triangle it hits
+fn cube() -> (Vec<Vec3>, Vec<u32>) {
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    for axis in 0..3 {
+        for sign in [-1.0f32, 1.0] {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let base = positions.len() as u32;
+            for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let mut position = Vec3::ZERO;
+                position[axis] = sign;
+                position[u] = a * sign;
+                position[v] = b;
+                positions.push(position);
+            }
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    (positions, indices)
+}
+
+// A UV sphere of radius 1, like in chapter 8. Fine enough that flat triangles hardly show
+fn sphere(stacks: u32, sectors: u32) -> (Vec<Vec3>, Vec<u32>) {
+    let mut positions = Vec::new();
+    for i in 0..=stacks {
+        let phi = PI * i as f32 / stacks as f32;
+        for j in 0..=sectors {
+            let theta = TAU * j as f32 / sectors as f32;
+            positions.push(Vec3::new(
+                phi.sin() * theta.cos(),
+                phi.cos(),
+                phi.sin() * theta.sin(),
+            ));
+        }
+    }
+
+    let mut indices = Vec::new();
+    for i in 0..stacks {
+        for j in 0..sectors {
+            let a = i * (sectors + 1) + j;
+            let b = a + sectors + 1;
+            indices.extend([a, b + 1, b, a, a + 1, b + 1]);
+        }
+    }
+
+    (positions, indices)
+}
+
+struct RayTracer {
+    camera: Camera,
+    pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // The top level is built again every frame and streamed in, the bottom level never changes
+    streaming: StreamingBuffer,
+    bottom_level: BottomLevel,
+    blas_nodes: Subbuffer<[BvhNode]>,
+    triangles: Subbuffer<[BvhTriangle]>,
+    // Recreated with the swapchain: the image traced into, and the set binding it
+    image: Option<(Arc<StorageImage>, Arc<PersistentDescriptorSet>)>,
+    start: Instant,
+    // How long the last top level took to build, and how many nodes it has
+    build_time: Duration,
+    top_level_nodes: usize,
+    // Parameters exposed in the overlay
+    animate: bool,
+    time: f32,
+    shadows: bool,
+    max_bounces: u32,
+    sun_angle: f32,
+    exposure: f32,
+}
+
+impl RayTracer {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        // The hardware this stands in for, worth knowing about even though 0.33 can't drive it
+        if device
+            .physical_device()
+            .supported_extensions()
+            .khr_ray_tracing_pipeline
+        {
+            tracing::info!(
+                "VK_KHR_ray_tracing_pipeline is supported, but vulkano 0.33 can't use it: \
+                 tracing in a compute shader"
+            );
+        }
+
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+
+        let bottom_level = BottomLevel::new(&[plane(), cube(), sphere(32, 64)]);
+        tracing::info!(
+            nodes = bottom_level.nodes.len(),
+            triangles = bottom_level.triangles.len(),
+            "built the bottom level"
+        );
+
+        let buffer_info = || BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        };
+        let allocation_info = || AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        };
+        let blas_nodes = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(),
+            allocation_info(),
+            bottom_level.nodes.iter().copied(),
+        )
+            .expect("failed to create node buffer");
+        let triangles = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(),
+            allocation_info(),
+            bottom_level.triangles.iter().copied(),
+        )
+            .expect("failed to create triangle buffer");
+
+        RayTracer {
+            camera: Camera::new(Vec3::new(0.0, 4.0, 10.0), Vec3::new(0.0, 1.0, 0.0)),
+            pipeline,
+            streaming: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            bottom_level,
+            blas_nodes,
+            triangles,
+            image: None,
+            start: Instant::now(),
+            build_time: Duration::ZERO,
+            top_level_nodes: 0,
+            animate: true,
+            time: 0.0,
+            shadows: true,
+            max_bounces: 4,
+            sun_angle: 0.8,
+            exposure: 1.0,
+        }
+    }
+
+    // The scene at `time`: a ring of cubes turning around a mirror ball
+    fn instances(&self) -> Vec<Instance> {
+        let mut instances = vec![
+            Instance {
+                mesh: PLANE,
+                transform: Mat4::from_scale(Vec3::splat(20.0)),
+                custom_index: FLOOR,
+            },
+            Instance {
+                mesh: SPHERE,
+                transform: Mat4::from_scale_rotation_translation(
+                    Vec3::splat(1.5),
+                    Quat::IDENTITY,
+                    Vec3::new(0.0, 1.5, 0.0),
+                ),
+                custom_index: MIRROR,
+            },
+        ];
+
+        let count = 8;
+        for i in 0..count {
+            let angle = TAU * i as f32 / count as f32 + self.time * 0.3;
+            let position = Vec3::new(angle.cos() * 4.0, 0.6, angle.sin() * 4.0);
+            let (mesh, custom_index) = match i % 3 {
+                0 => (SPHERE, GOLD),
+                1 => (CUBE, RED),
+                _ => (CUBE, BLUE),
+            };
+            instances.push(Instance {
+                mesh,
+                transform: Mat4::from_scale_rotation_translation(
+                    Vec3::splat(0.6),
+                    Quat::from_rotation_y(self.time + i as f32),
+                    position,
+                ),
+                custom_index,
+            });
+        }
+
+        instances
+    }
+}
+
+impl App for RayTracer {
+    fn resize(&mut self, renderer: &Renderer) {
+        let [width, height] = renderer.swapchain.image_extent();
+        let display = StorageImage::new(
+            &self.allocators.memory,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(renderer.queue().queue_family_index()),
+        )
+            .unwrap();
+
+        let set = bind_resources(
+            &self.allocators.descriptor,
+            &self.pipeline,
+            1,
+            [(
+                0,
+                Resource::image(ImageView::new_default(display.clone()).unwrap()),
+            )],
+        );
+        self.image = Some((display, set));
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let (image, image_set) = self.image.clone().unwrap();
+        let [width, height] = renderer.swapchain.image_extent();
+        if self.animate {
+            self.time = self.start.elapsed().as_secs_f32();
+        }
+
+        // Rebuilding the top level is what a ray tracing pipeline would do for moving instances
+        // too, only on the GPU
+        let build_start = Instant::now();
+        let top_level = self.bottom_level.top_level(&self.instances());
+        self.build_time = build_start.elapsed();
+        self.top_level_nodes = top_level.nodes.len();
+
+        let bvh_set = bind_resources(
+            &self.allocators.descriptor,
+            &self.pipeline,
+            0,
+            [
+                (0, Resource::buffer(self.streaming.write_iter(top_level.nodes))),
+                (
+                    1,
+                    Resource::buffer(self.streaming.write_iter(top_level.instances)),
+                ),
+                (2, Resource::buffer(self.blas_nodes.clone())),
+                (3, Resource::buffer(self.triangles.clone())),
+            ],
+        );
+
+        let forward = self.camera.forward();
+        let right = self.camera.right();
+        let up = right.cross(forward);
+        let sun = Vec3::new(self.sun_angle.cos(), self.sun_angle.sin(), 0.4).normalize();
+        let push_constants = cs::PushConstants {
+            position: self.camera.position.extend(0.0).to_array(),
+            forward: forward.extend((self.camera.fov_y / 2.0).tan()).to_array(),
+            right: right.extend(0.0).to_array(),
+            up: up.extend(0.0).to_array(),
+            light: sun.extend(0.0).to_array(),
+            max_bounces: self.max_bounces,
+            shadows: self.shadows as u32,
+            ambient: 0.15,
+            exposure: self.exposure,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                vec![bvh_set, image_set],
+            )
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap()
+            .blit_image(BlitImageInfo::images(
+                image,
+                renderer.images[image_index as usize].clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Ray tracing").show(ctx, |ui| {
+            ui.label(format!(
+                "top level: {} nodes, built in {:.0?}",
+                self.top_level_nodes, self.build_time
+            ));
+            ui.label(format!(
+                "bottom level: {} nodes, {} triangles",
+                self.bottom_level.nodes.len(),
+                self.bottom_level.triangles.len()
+            ));
+            ui.checkbox(&mut self.animate, "animate");
+            ui.checkbox(&mut self.shadows, "shadow rays");
+            ui.add(egui::Slider::new(&mut self.max_bounces, 0..=8).text("mirror bounces"));
+            ui.add(egui::Slider::new(&mut self.sun_angle, 0.1..=3.0).text("sun angle"));
+            ui.add(
+                egui::Slider::new(&mut self.exposure, 0.1..=4.0)
+                    .logarithmic(true)
+                    .text("exposure"),
+            );
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-63",
+        DeviceExtensions::empty(),
+        RayTracer::new,
+    );
+}
@@ -0,0 +1,67 @@
+#version 460
+
+layout(location = 0) in vec3 v_position;
+layout(location = 1) in vec3 v_normal;
+
+layout(location = 0) out vec4 f_color;
+
+// The scene's BVH in set 0, read where a ray query would read its acceleration structure
+#include <bvh.glsl>
+#include <random.glsl>
+
+layout(set = 1, binding = 0) uniform Frame {
+    mat4 view_projection;
+    // xyz: towards the light, w: the tangent of its angular radius
+    vec4 light;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 color;
+    uint samples;
+    uint shadows;
+} pc;
+
+// Whether anything is between p and the light. The same question as a ray query initialized
+// with gl_RayFlagsTerminateOnFirstHitEXT, whose loop ends at the first candidate
+bool occluded(vec3 origin, vec3 direction) {
+    Hit hit;
+    return bvh_trace(origin, direction, 0.0, 1e30, true, hit);
+}
+
+float visibility(vec3 p, vec3 normal) {
+    // Leaving along the normal keeps the ray off the surface it starts on. Unlike a shadow map
+    // there is no depth resolution to hide, so no bias to tune per scene
+    vec3 origin = p + normal * 1e-3;
+    vec3 to_light = frame.light.xyz;
+    if (pc.samples <= 1) {
+        return occluded(origin, to_light) ? 0.0 : 1.0;
+    }
+
+    // Rays towards random points of the sun's disc: the shadow softens with the distance to
+    // whatever casts it, as it does outside
+    vec3 up = abs(to_light.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, to_light));
+    vec3 bitangent = cross(to_light, tangent);
+    uint seed = uint(gl_FragCoord.x) * 1973u + uint(gl_FragCoord.y) * 9277u;
+    float lit = 0.0;
+    for (uint i = 0; i < pc.samples; i++) {
+        float angle = pcg_random(seed) * 6.28318;
+        float radius = sqrt(pcg_random(seed)) * frame.light.w;
+        vec3 offset = (tangent * cos(angle) + bitangent * sin(angle)) * radius;
+        if (!occluded(origin, normalize(to_light + offset))) {
+            lit += 1.0;
+        }
+    }
+    return lit / float(pc.samples);
+}
+
+void main() {
+    vec3 normal = normalize(v_normal);
+    float diffuse = max(dot(normal, frame.light.xyz), 0.0);
+    // Faces turned away from the light are dark anyway, no rays needed
+    float lit = diffuse > 0.0 && pc.shadows != 0 ? visibility(v_position, normal) : 1.0;
+
+    vec3 color = pc.color.rgb * (0.15 + diffuse * lit);
+    f_color = vec4(color, 1.0);
+}
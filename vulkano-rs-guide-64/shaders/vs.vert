@@ -0,0 +1,27 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_position;
+layout(location = 1) out vec3 v_normal;
+
+layout(set = 1, binding = 0) uniform Frame {
+    mat4 view_projection;
+    // xyz: towards the light, w: the tangent of its angular radius
+    vec4 light;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 color;
+    uint samples;
+    uint shadows;
+} pc;
+
+void main() {
+    vec4 world_position = pc.model * vec4(position, 1.0);
+    v_position = world_position.xyz;
+    v_normal = mat3(pc.model) * normal;
+    gl_Position = frame.view_projection * world_position;
+}
@@ -0,0 +1,470 @@
+//Shadow rays from the fragment shader: the scene of chapter 9, lit by tracing towards the light
+//through a BVH instead of comparing against a shadow map
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::bvh::{BottomLevel, Instance};
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Quat, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Appends a square with the given center, normal and half-axes, counter-clockwise from outside
+fn push_quad(
+    vertices: &mut Vec<PosNormalUv>,
+    indices: &mut Vec<u32>,
+    center: Vec3,
+    normal: Vec3,
+    u: Vec3,
+    v: Vec3,
+) {
+    let base = vertices.len() as u32;
+    for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        vertices.push(PosNormalUv {
+            position: (center + u * su + v * sv).to_array(),
+            normal: normal.to_array(),
+            ..Default::default()
+        });
+    }
+    indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+// A unit cube with one quad per face, so every face has its own normal
+fn cube() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+    for (normal, u, v) in faces {
+        push_quad(
+            &mut vertices,
+            &mut indices,
+            normal * 0.5,
+            normal,
+            u * 0.5,
+            v * 0.5,
+        );
+    }
+    (vertices, indices)
+}
+
+fn ground(size: f32) -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    push_quad(
+        &mut vertices,
+        &mut indices,
+        Vec3::ZERO,
+        Vec3::Y,
+        Vec3::X * size,
+        Vec3::NEG_Z * size,
+    );
+    (vertices, indices)
+}
+
+// A buffer the fragment shader reads the BVH from
+fn storage<T: BufferContents>(allocators: &Allocators, data: Vec<T>) -> Resource {
+    let buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        data,
+    )
+        .expect("failed to create BVH buffer");
+    Resource::buffer(buffer)
+}
+
+struct Mesh {
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+}
+
+struct Object {
+    mesh: usize,
+    model: Mat4,
+    color: [f32; 4],
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        include: ["../shaders/common"],
+        path: "shaders/fs.frag",
+    }
+}
+
+struct RayShadows {
+    camera: Camera,
+    allocators: Allocators,
+    uniform_buffer: StreamingBuffer,
+    meshes: Vec<Mesh>,
+    objects: Vec<Object>,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    // The BVH never changes, nothing in the scene moves
+    bvh_set: Arc<PersistentDescriptorSet>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    light_azimuth: f32,
+    light_elevation: f32,
+    // In radians. The sun is about 0.005 across, larger lights give wider penumbrae
+    light_radius: f32,
+    samples: u32,
+    shadows: bool,
+}
+
+impl RayShadows {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        // What this chapter stands in for, which 0.33 can't build the acceleration structure of
+        if device.physical_device().supported_extensions().khr_ray_query {
+            tracing::info!(
+                "VK_KHR_ray_query is supported, but vulkano 0.33 can't build acceleration \
+                 structures: tracing a BVH in the fragment shader"
+            );
+        }
+
+        let mesh_data = [cube(), ground(6.0)];
+        let meshes = mesh_data
+            .iter()
+            .map(|(vertices, indices)| Mesh {
+                vertex_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::VERTEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    vertices.iter().copied(),
+                )
+                    .expect("failed to create vertex buffer"),
+                index_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::INDEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    indices.iter().copied(),
+                )
+                    .expect("failed to create index buffer"),
+            })
+            .collect();
+
+        let objects = vec![
+            Object {
+                mesh: 1,
+                model: Mat4::IDENTITY,
+                color: [0.6, 0.6, 0.6, 1.0],
+            },
+            Object {
+                mesh: 0,
+                model: Mat4::from_translation(Vec3::new(0.0, 0.5, 0.0)),
+                color: [0.8, 0.3, 0.2, 1.0],
+            },
+            Object {
+                mesh: 0,
+                model: Mat4::from_scale_rotation_translation(
+                    Vec3::new(0.5, 2.0, 0.5),
+                    Quat::from_rotation_y(0.6),
+                    Vec3::new(2.0, 1.0, -1.0),
+                ),
+                color: [0.2, 0.5, 0.8, 1.0],
+            },
+            Object {
+                mesh: 0,
+                model: Mat4::from_scale_rotation_translation(
+                    Vec3::splat(0.7),
+                    Quat::from_rotation_x(0.4) * Quat::from_rotation_z(0.3),
+                    Vec3::new(-1.5, 1.6, 1.0),
+                ),
+                color: [0.3, 0.7, 0.3, 1.0],
+            },
+        ];
+
+        // The same meshes and objects the rasterizer draws, as a bottom level per mesh and a
+        // top level over the objects
+        let bottom_level = BottomLevel::new(
+            &mesh_data
+                .iter()
+                .map(|(vertices, indices)| {
+                    let positions: Vec<Vec3> =
+                        vertices.iter().map(|v| Vec3::from(v.position)).collect();
+                    (positions, indices.clone())
+                })
+                .collect::<Vec<_>>(),
+        );
+        let top_level = bottom_level.top_level(
+            &objects
+                .iter()
+                .enumerate()
+                .map(|(i, object)| Instance {
+                    mesh: object.mesh,
+                    transform: object.model,
+                    custom_index: i as u32,
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let bvh_set = bind_resources(
+            &allocators.descriptor,
+            &pipeline,
+            0,
+            [
+                (0, storage(&allocators, top_level.nodes)),
+                (1, storage(&allocators, top_level.instances)),
+                (2, storage(&allocators, bottom_level.nodes)),
+                (3, storage(&allocators, bottom_level.triangles)),
+            ],
+        );
+
+        RayShadows {
+            camera: Camera::new(Vec3::new(0.0, 4.0, 8.0), Vec3::ZERO),
+            uniform_buffer: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            meshes,
+            objects,
+            render_pass,
+            pipeline,
+            bvh_set,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            light_azimuth: 0.8,
+            light_elevation: 0.9,
+            light_radius: 0.02,
+            samples: 1,
+            shadows: true,
+        }
+    }
+
+    // Points from the scene towards the light, the opposite of where its rays travel
+    fn light_direction(&self) -> Vec3 {
+        Vec3::new(
+            self.light_elevation.cos() * self.light_azimuth.cos(),
+            self.light_elevation.sin(),
+            self.light_elevation.cos() * self.light_azimuth.sin(),
+        )
+    }
+}
+
+impl App for RayShadows {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let uniform_subbuffer = self.uniform_buffer.write(vs::Frame {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+            light: self
+                .light_direction()
+                .extend(self.light_radius.tan())
+                .to_array(),
+        });
+        let frame_set = bind_resources(
+            &self.allocators.descriptor,
+            &self.pipeline,
+            1,
+            [(0, Resource::buffer(uniform_subbuffer))],
+        );
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.05, 0.08, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                vec![self.bvh_set.clone(), frame_set],
+            );
+
+        for object in &self.objects {
+            let mesh = &self.meshes[object.mesh];
+            let push_constants = vs::PushConstants {
+                model: object.model.to_cols_array_2d(),
+                color: object.color,
+                samples: self.samples,
+                shadows: self.shadows as u32,
+            };
+
+            builder
+                .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+                .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                .bind_index_buffer(mesh.index_buffer.clone())
+                .draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0)
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Ray traced shadows").show(ctx, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.light_azimuth, 0.0..=std::f32::consts::TAU)
+                    .text("light azimuth"),
+            );
+            ui.add(egui::Slider::new(&mut self.light_elevation, 0.1..=1.5).text("light elevation"));
+            ui.checkbox(&mut self.shadows, "shadow rays");
+            // One ray gives hard shadows, more of them spread over the light's disc soften the
+            // edges, at a cost per pixel
+            ui.add(egui::Slider::new(&mut self.samples, 1..=32).text("rays per pixel"));
+            ui.add(egui::Slider::new(&mut self.light_radius, 0.0..=0.2).text("light radius"));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-64",
+        DeviceExtensions::empty(),
+        RayShadows::new,
+    );
+}
@@ -0,0 +1,11 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec3 v_color;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    float diffuse = max(dot(normalize(v_normal), normalize(vec3(0.4, 1.0, 0.6))), 0.0);
+    f_color = vec4(v_color * (diffuse * 0.8 + 0.2), 1.0);
+}
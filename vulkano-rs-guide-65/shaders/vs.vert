@@ -0,0 +1,29 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec3 v_color;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    // 0 colors by height, otherwise every meshlet gets its own color
+    uint show_meshlets;
+} pc;
+
+#include <random.glsl>
+
+void main() {
+    v_normal = normal;
+    if (pc.show_meshlets != 0) {
+        // The draw of each meshlet starts at its own instance
+        uint meshlet = gl_InstanceIndex;
+        v_color = vec3(hash(meshlet), hash(meshlet + 7919u), hash(meshlet + 104729u));
+        v_color = v_color * 0.7 + 0.3;
+    } else {
+        float t = smoothstep(-1.0, 2.0, position.y);
+        v_color = mix(vec3(0.2, 0.45, 0.2), vec3(0.85, 0.8, 0.7), t);
+    }
+    gl_Position = pc.view_projection * vec4(position, 1.0);
+}
@@ -0,0 +1,416 @@
+//Meshlets without mesh shaders: a compute pass culls a procedural terrain meshlet by meshlet and
+//generates the vertices of the survivors, then one indirect draw renders them
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, DrawIndexedIndirectCommand, FillBufferInfo,
+    RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::{frustum_planes, Camera};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+// Vertices along a side of a meshlet: 64 vertices and 98 triangles, within what mesh shaders
+// are usually tuned for. Has to match SIDE in the compute shader
+const MESHLET_SIDE: u32 = 8;
+const MESHLET_VERTICES: u32 = MESHLET_SIDE * MESHLET_SIDE;
+// Meshlets along a side of the terrain
+const GRID: u32 = 48;
+const SPACING: f32 = 0.25;
+
+// The triangles of one meshlet, indexing its own vertices. Every meshlet has the same ones, its
+// draw adds where its vertices start
+fn meshlet_indices() -> Vec<u32> {
+    let mut indices = Vec::new();
+    for z in 0..MESHLET_SIDE - 1 {
+        for x in 0..MESHLET_SIDE - 1 {
+            let a = z * MESHLET_SIDE + x;
+            let (b, c, d) = (a + 1, a + MESHLET_SIDE, a + MESHLET_SIDE + 1);
+            // Counter-clockwise seen from above
+            indices.extend([c, d, b, c, b, a]);
+        }
+    }
+    indices
+}
+
+mod meshlet_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/meshlet_cs.comp",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        include: ["../shaders/common"],
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Meshlets {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    meshlet_pipeline: Arc<ComputePipeline>,
+    allocators: Allocators,
+    // Written by the compute pass every frame, only for the meshlets that survive
+    vertices: Subbuffer<[PosNormalUv]>,
+    indices: Subbuffer<[u32]>,
+    commands: Subbuffer<[DrawIndexedIndirectCommand]>,
+    // One count per frame in flight, read back for the overlay as in chapter 56
+    counts: Vec<Subbuffer<u32>>,
+    meshlet_sets: Vec<Arc<PersistentDescriptorSet>>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    start: Instant,
+    drawn: u32,
+    // Parameters exposed in the overlay
+    culling: bool,
+    frozen: Option<[[f32; 4]; 6]>,
+    show_meshlets: bool,
+    animate: bool,
+    time: f32,
+}
+
+impl Meshlets {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        // What this chapter stands in for, which 0.33 has no pipeline or draw command for
+        if device.physical_device().supported_extensions().ext_mesh_shader {
+            tracing::info!(
+                "VK_EXT_mesh_shader is supported, but vulkano 0.33 can't build mesh shader \
+                 pipelines: generating meshlets in a compute pass"
+            );
+        }
+
+        let meshlet_count = (GRID * GRID) as u64;
+        let buffer_info = |usage| BufferCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let allocation_info = |usage| AllocationCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        // Room for every meshlet, each has its own place whether it is generated or not
+        let vertices = Buffer::new_slice::<PosNormalUv>(
+            &allocators.memory,
+            buffer_info(BufferUsage::STORAGE_BUFFER | BufferUsage::VERTEX_BUFFER),
+            allocation_info(MemoryUsage::DeviceOnly),
+            meshlet_count * MESHLET_VERTICES as u64,
+        )
+            .expect("failed to create vertex buffer");
+        let indices = Buffer::from_iter(
+            &allocators.memory,
+            buffer_info(BufferUsage::INDEX_BUFFER),
+            allocation_info(MemoryUsage::Upload),
+            meshlet_indices(),
+        )
+            .expect("failed to create index buffer");
+        let commands = Buffer::new_slice::<DrawIndexedIndirectCommand>(
+            &allocators.memory,
+            buffer_info(
+                BufferUsage::STORAGE_BUFFER
+                    | BufferUsage::INDIRECT_BUFFER
+                    | BufferUsage::TRANSFER_DST,
+            ),
+            allocation_info(MemoryUsage::DeviceOnly),
+            meshlet_count,
+        )
+            .expect("failed to create command buffer");
+        let counts: Vec<_> = renderer
+            .images
+            .iter()
+            .map(|_| {
+                Buffer::new_sized::<u32>(
+                    &allocators.memory,
+                    buffer_info(BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST),
+                    allocation_info(MemoryUsage::Download),
+                )
+                    .expect("failed to create count buffer")
+            })
+            .collect();
+
+        let meshlet_cs = meshlet_cs::load(device.clone()).expect("failed to create shader module");
+        let meshlet_pipeline = ComputePipeline::new(
+            device.clone(),
+            meshlet_cs.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline");
+        let meshlet_sets = counts
+            .iter()
+            .map(|count| {
+                bind_resources(
+                    &allocators.descriptor,
+                    &meshlet_pipeline,
+                    0,
+                    [
+                        (0, Resource::buffer(vertices.clone())),
+                        (1, Resource::buffer(commands.clone())),
+                        (2, Resource::buffer(count.clone())),
+                    ],
+                )
+            })
+            .collect();
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        // Still a vertex shader, which only transforms what the compute pass generated. With mesh
+        // shaders the generating and transforming would be one stage
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        Meshlets {
+            camera: Camera::new(Vec3::new(0.0, 6.0, 12.0), Vec3::new(0.0, 0.0, 0.0)),
+            render_pass,
+            pipeline,
+            meshlet_pipeline,
+            allocators,
+            vertices,
+            indices,
+            commands,
+            counts,
+            meshlet_sets,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            start: Instant::now(),
+            drawn: 0,
+            culling: true,
+            frozen: None,
+            show_meshlets: true,
+            animate: true,
+            time: 0.0,
+        }
+    }
+}
+
+impl App for Meshlets {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let slot = image_index as usize;
+        if let Ok(count) = self.counts[slot].read() {
+            self.drawn = *count;
+        }
+        if self.animate {
+            self.time = self.start.elapsed().as_secs_f32();
+        }
+
+        let aspect = self.viewport.dimensions[0] / self.viewport.dimensions[1];
+        let view_projection = self.camera.view_projection(aspect);
+        let planes = self
+            .frozen
+            .unwrap_or_else(|| frustum_planes(view_projection));
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        // As in chapter 56, the commands past the count are zeroed into draws of nothing
+        builder
+            .fill_buffer(FillBufferInfo::dst_buffer(
+                self.commands.clone().reinterpret::<[u32]>(),
+            ))
+            .unwrap()
+            .fill_buffer(FillBufferInfo::dst_buffer(
+                self.counts[slot].clone().reinterpret::<[u32]>(),
+            ))
+            .unwrap()
+            .bind_pipeline_compute(self.meshlet_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.meshlet_pipeline.layout().clone(),
+                0,
+                self.meshlet_sets[slot].clone(),
+            )
+            .push_constants(
+                self.meshlet_pipeline.layout().clone(),
+                0,
+                meshlet_cs::PushConstants {
+                    planes,
+                    time: self.time,
+                    grid: GRID,
+                    spacing: SPACING,
+                    culling: self.culling as u32,
+                },
+            )
+            // One group per meshlet, like vkCmdDrawMeshTasksEXT would launch
+            .dispatch([GRID * GRID, 1, 1])
+            .unwrap();
+
+        // vulkano puts a barrier between the vertices and commands the dispatch wrote and the
+        // draw reading them
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.5, 0.65, 0.8, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    view_projection: view_projection.to_cols_array_2d(),
+                    show_meshlets: self.show_meshlets as u32,
+                },
+            )
+            .bind_vertex_buffers(0, self.vertices.clone())
+            .bind_index_buffer(self.indices.clone())
+            .draw_indexed_indirect(self.commands.clone())
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), builder.build().unwrap())
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Meshlets").show(ctx, |ui| {
+            ui.checkbox(&mut self.culling, "cull meshlets against the frustum");
+            let mut frozen = self.frozen.is_some();
+            if ui.checkbox(&mut frozen, "freeze the frustum").changed() {
+                let aspect = self.viewport.dimensions[0] / self.viewport.dimensions[1];
+                self.frozen = frozen.then(|| frustum_planes(self.camera.view_projection(aspect)));
+            }
+            ui.checkbox(&mut self.show_meshlets, "color by meshlet");
+            ui.checkbox(&mut self.animate, "animate");
+            ui.label(format!(
+                "{} of {} meshlets generated and drawn",
+                self.drawn,
+                GRID * GRID
+            ));
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    // One command per meshlet, each telling the vertex shader which meshlet it is through
+    // first_instance
+    window::run_with_features(
+        "vulkano-rs-guide-65",
+        DeviceExtensions::empty(),
+        Features {
+            multi_draw_indirect: true,
+            draw_indirect_first_instance: true,
+            ..Features::empty()
+        },
+        Meshlets::new,
+    );
+}
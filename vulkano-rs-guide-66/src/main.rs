@@ -0,0 +1,454 @@
+//Timeline semaphores: a single counter orders work across two queues and lets the host wait for
+//any point of it, where binary semaphores need one per dependency and a fence for the host
+
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::ptr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::{Device, DeviceExtensions, Features, Queue};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture, Sharing};
+use vulkano::{Version, VulkanObject};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::ash::vk;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::raw::{self, RawCommandBuffer};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+const ELEMENTS: u32 = 4096;
+// Has to match local_size_x in the shader
+const WORK_GROUP_SIZE: u32 = 64;
+const ITERATIONS: u32 = 8;
+
+// The steps of every iteration, as in the shader. The middle one runs on the compute queue
+const PRODUCE: u32 = 0;
+const DOUBLE: u32 = 1;
+const SUM: u32 = 2;
+const STEPS: [u32; 3] = [PRODUCE, DOUBLE, SUM];
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// What the timeline reads once `step` of `iteration` is done. The host signals 1 to let the
+// first step start, every value after that belongs to one step
+fn value(iteration: u32, step: u32) -> u64 {
+    2 + (iteration * STEPS.len() as u32 + step) as u64
+}
+
+// Every iteration sums 2 * (i + iteration) over the elements
+fn expected_sum(iteration: u32) -> u32 {
+    ELEMENTS * (ELEMENTS - 1) + 2 * ELEMENTS * iteration
+}
+
+// A timeline semaphore, made and used through the raw functions: vulkano 0.33 only creates
+// binary ones, and its submissions carry no counter values
+struct Timeline {
+    device: Arc<Device>,
+    handle: vk::Semaphore,
+}
+
+impl Timeline {
+    fn new(device: &Arc<Device>) -> Self {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let create_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
+        let mut handle = vk::Semaphore::null();
+        unsafe {
+            (device.fns().v1_0.create_semaphore)(
+                device.handle(),
+                &*create_info,
+                ptr::null(),
+                &mut handle,
+            )
+        }
+            .result()
+            .expect("failed to create timeline semaphore");
+
+        Timeline {
+            device: device.clone(),
+            handle,
+        }
+    }
+
+    // The counter as it is now, without waiting
+    fn value(&self) -> u64 {
+        let mut value = 0;
+        unsafe {
+            (self.device.fns().v1_2.get_semaphore_counter_value)(
+                self.device.handle(),
+                self.handle,
+                &mut value,
+            )
+        }
+            .result()
+            .expect("failed to read timeline semaphore");
+        value
+    }
+
+    // Sets the counter from the host, which releases every submission waiting for it
+    fn signal(&self, value: u64) {
+        let signal_info = vk::SemaphoreSignalInfo::builder()
+            .semaphore(self.handle)
+            .value(value);
+        unsafe { (self.device.fns().v1_2.signal_semaphore)(self.device.handle(), &*signal_info) }
+            .result()
+            .expect("failed to signal timeline semaphore");
+    }
+
+    // Blocks until the counter reaches `value`, giving up after --gpu-timeout like a fence
+    fn wait(&self, value: u64) {
+        let semaphores = [self.handle];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values);
+        // The submissions on both queues signal it, so no one queue is to blame
+        wait::with_timeout(&self.device, None, |step| {
+            let result = unsafe {
+                (self.device.fns().v1_2.wait_semaphores)(
+                    self.device.handle(),
+                    &*wait_info,
+                    step.as_nanos() as u64,
+                )
+            };
+            match result {
+                vk::Result::SUCCESS => true,
+                vk::Result::TIMEOUT => false,
+                error => panic!("failed to wait for timeline semaphore: {error}"),
+            }
+        });
+    }
+
+    // Submits `commands` to `queue`, to start once the counter reaches `wait` and to set it to
+    // `signal` when done
+    fn submit(&self, queue: &Arc<Queue>, commands: &RawCommandBuffer, wait: u64, signal: u64) {
+        let wait_values = [wait];
+        let signal_values = [signal];
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let semaphores = [self.handle];
+        let wait_stages = [vk::PipelineStageFlags::COMPUTE_SHADER];
+        let command_buffers = [commands.handle()];
+        let submit_info = vk::SubmitInfo::builder()
+            .push_next(&mut timeline_info)
+            .wait_semaphores(&semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&semaphores);
+        unsafe { raw::queue_submit(queue, &[*submit_info], vk::Fence::null()) }
+            .expect("failed to submit commands");
+    }
+}
+
+impl Drop for Timeline {
+    fn drop(&mut self) {
+        unsafe {
+            (self.device.fns().v1_0.destroy_semaphore)(
+                self.device.handle(),
+                self.handle,
+                ptr::null(),
+            )
+        };
+    }
+}
+
+// One step, recorded through the raw functions so the timeline can submit it
+fn record_raw(
+    device: &Arc<Device>,
+    queue: &Queue,
+    pipeline: &ComputePipeline,
+    set: &PersistentDescriptorSet,
+    step: u32,
+    iteration: u32,
+) -> RawCommandBuffer {
+    let commands = RawCommandBuffer::begin(device, queue.queue_family_index());
+    let fns = device.fns();
+    let push_constants = cs::PushConstants { step, iteration };
+    unsafe {
+        (fns.v1_0.cmd_bind_pipeline)(
+            commands.handle(),
+            vk::PipelineBindPoint::COMPUTE,
+            pipeline.handle(),
+        );
+        (fns.v1_0.cmd_bind_descriptor_sets)(
+            commands.handle(),
+            vk::PipelineBindPoint::COMPUTE,
+            pipeline.layout().handle(),
+            0,
+            1,
+            &set.inner().handle(),
+            0,
+            ptr::null(),
+        );
+        (fns.v1_0.cmd_push_constants)(
+            commands.handle(),
+            pipeline.layout().handle(),
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            size_of::<cs::PushConstants>() as u32,
+            &push_constants as *const cs::PushConstants as *const c_void,
+        );
+        (fns.v1_0.cmd_dispatch)(commands.handle(), ELEMENTS / WORK_GROUP_SIZE, 1, 1);
+
+        // Waiting for a semaphore on the host doesn't make the writes visible to it, this does
+        if step == SUM {
+            let barrier = vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ);
+            (fns.v1_0.cmd_pipeline_barrier)(
+                commands.handle(),
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                1,
+                &*barrier,
+                0,
+                ptr::null(),
+                0,
+                ptr::null(),
+            );
+        }
+    }
+    commands.end();
+    commands
+}
+
+// The same step through vulkano, for the binary semaphore version
+fn record(
+    allocators: &Allocators,
+    queue: &Queue,
+    pipeline: &Arc<ComputePipeline>,
+    set: &Arc<PersistentDescriptorSet>,
+    step: u32,
+    iteration: u32,
+) -> PrimaryAutoCommandBuffer {
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .bind_pipeline_compute(pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            pipeline.layout().clone(),
+            0,
+            set.clone(),
+        )
+        .push_constants(
+            pipeline.layout().clone(),
+            0,
+            cs::PushConstants { step, iteration },
+        )
+        .dispatch([ELEMENTS / WORK_GROUP_SIZE, 1, 1])
+        .unwrap();
+    builder.build().unwrap()
+}
+
+fn check(sums: &Subbuffer<[u32]>) {
+    let sums = sums.read().unwrap();
+    for iteration in 0..ITERATIONS {
+        assert_eq!(
+            sums[iteration as usize],
+            expected_sum(iteration),
+            "wrong sum for iteration {iteration}"
+        );
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::with_features(
+        instance,
+        DeviceExtensions::empty(),
+        Features {
+            timeline_semaphore: true,
+            ..Features::empty()
+        },
+        None,
+    );
+    let device = context.device.clone();
+    // The feature can also come from VK_KHR_timeline_semaphore on older devices, whose
+    // functions are named differently. This chapter only calls the Vulkan 1.2 ones
+    if device.api_version() < Version::V1_2 {
+        tracing::error!(
+            "{} only has Vulkan {}, this chapter needs 1.2",
+            device.physical_device().properties().device_name,
+            device.api_version()
+        );
+        std::process::exit(1);
+    }
+
+    // Produce and sum on the graphics queue, double on the compute one. Without a compute-only
+    // family both are the same queue, and the semaphores still order the steps
+    let graphics = context.queues.graphics.clone();
+    let compute = context.queues.compute.clone();
+    if !context.queues.has_async_compute() {
+        tracing::warn!("no compute-only queue family, every step runs on the graphics queue");
+    }
+    let queue_for = |step| if step == DOUBLE { &compute } else { &graphics };
+
+    let allocators = Allocators::new(&device);
+    // Both families use the buffers, without handing them over in between
+    let sharing = if context.queues.has_async_compute() {
+        Sharing::Concurrent(
+            vec![graphics.queue_family_index(), compute.queue_family_index()].into(),
+        )
+    } else {
+        Sharing::Exclusive
+    };
+    let data = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            sharing: sharing.clone(),
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        ELEMENTS as u64,
+    )
+        .expect("failed to create buffer");
+    // One set of sums per version, starting at zero
+    let [timeline_sums, binary_sums] = [(); 2].map(|_| {
+        Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                sharing: sharing.clone(),
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            (0..ITERATIONS).map(|_| 0u32),
+        )
+            .expect("failed to create buffer")
+    });
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    let [timeline_set, binary_set] = [&timeline_sums, &binary_sums].map(|sums| {
+        bind_resources(
+            &allocators.descriptor,
+            &pipeline,
+            0,
+            [
+                (0, Resource::buffer(data.clone())),
+                (1, Resource::buffer(sums.clone())),
+            ],
+        )
+    });
+
+    // Timeline: every step of every iteration is submitted up front, each waiting for the value
+    // of the step before, on whichever queue. Nothing can start until the host signals 1
+    let timeline = Timeline::new(&device);
+    let start = Instant::now();
+    let mut submitted = Vec::new();
+    let mut previous = 1;
+    for iteration in 0..ITERATIONS {
+        for step in STEPS {
+            let queue = queue_for(step);
+            let commands = record_raw(&device, queue, &pipeline, &timeline_set, step, iteration);
+            timeline.submit(queue, &commands, previous, value(iteration, step));
+            previous = value(iteration, step);
+            // The command buffers have to outlive the work, until the last wait below
+            submitted.push(commands);
+        }
+    }
+    println!(
+        "Submitted {} steps, the counter is at {}",
+        submitted.len(),
+        timeline.value()
+    );
+
+    timeline.signal(1);
+    // The host waits for any value it wants: here the end of each iteration, to read its sum
+    // while the later ones run
+    for iteration in 0..ITERATIONS {
+        timeline.wait(value(iteration, SUM));
+        let sum = timeline_sums.read().unwrap()[iteration as usize];
+        println!(
+            "  iteration {iteration} done at {}, sum {sum}",
+            value(iteration, SUM)
+        );
+    }
+    let timeline_time = start.elapsed();
+    drop(submitted);
+    check(&timeline_sums);
+
+    // Binary semaphores and a fence: each dependency needs a semaphore of its own, and the
+    // host can only wait for the fence at the end of a chain
+    let start = Instant::now();
+    for iteration in 0..ITERATIONS {
+        let future = sync::now(device.clone())
+            .then_execute(
+                graphics.clone(),
+                record(
+                    &allocators,
+                    &graphics,
+                    &pipeline,
+                    &binary_set,
+                    PRODUCE,
+                    iteration,
+                ),
+            )
+            .unwrap()
+            .then_signal_semaphore()
+            .then_execute(
+                compute.clone(),
+                record(&allocators, &compute, &pipeline, &binary_set, DOUBLE, iteration),
+            )
+            .unwrap()
+            .then_signal_semaphore()
+            .then_execute(
+                graphics.clone(),
+                record(&allocators, &graphics, &pipeline, &binary_set, SUM, iteration),
+            )
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+    }
+    let binary_time = start.elapsed();
+    check(&binary_sums);
+
+    println!(
+        "Timeline: 1 semaphore, {:.2?}. Binary: {} semaphores and {} fences, {:.2?}",
+        timeline_time,
+        ITERATIONS * 2,
+        ITERATIONS,
+        binary_time
+    );
+
+    tracing::info!("everything succeeded");
+}
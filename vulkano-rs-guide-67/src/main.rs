@@ -0,0 +1,316 @@
+//Explicit synchronization: two dependent dispatches without a barrier, with a pipeline barrier
+//and with an event, recorded by hand instead of letting vulkano work the barriers out
+
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::ptr;
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::{Device, DeviceExtensions, Queue};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline};
+use vulkano::VulkanObject;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::ash::vk;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::raw::RawCommandBuffer;
+use vulkano_rs_common::tracing;
+
+const ELEMENTS: u32 = 1 << 20;
+// Has to match local_size_x in the shader
+const WORK_GROUP_SIZE: u32 = 64;
+
+// The passes, as in the shader
+const WRITE: u32 = 0;
+const READ: u32 = 1;
+const INDEPENDENT: u32 = 2;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// How the read pass is kept from starting before the write pass is done
+#[derive(Clone, Copy, Debug)]
+enum Dependency {
+    // Not at all: a hazard, which may or may not show in the results
+    None,
+    // A pipeline barrier right between the two
+    Barrier,
+    // An event set after the write pass and waited for before the read pass, with unrelated
+    // work in between that is free to overlap with both
+    Event,
+}
+
+// What the shader writes at `index`, computed the same way
+fn scramble(index: u32, seed: u32) -> u32 {
+    let mut x = index ^ seed;
+    for _ in 0..64 {
+        x = x.wrapping_mul(1664525).wrapping_add(1013904223);
+    }
+    x
+}
+
+// The writes of the compute shader stage made visible to its reads, over the whole buffer
+fn buffer_barrier(buffer: &Subbuffer<[u32]>) -> vk::BufferMemoryBarrier {
+    *vk::BufferMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(buffer.buffer().handle())
+        .offset(buffer.offset())
+        .size(buffer.size())
+}
+
+// Made through the raw functions like the commands that use it
+struct Event {
+    device: Arc<Device>,
+    handle: vk::Event,
+}
+
+impl Event {
+    fn new(device: &Arc<Device>) -> Self {
+        let create_info = vk::EventCreateInfo::builder();
+        let mut handle = vk::Event::null();
+        unsafe {
+            (device.fns().v1_0.create_event)(
+                device.handle(),
+                &*create_info,
+                ptr::null(),
+                &mut handle,
+            )
+        }
+            .result()
+            .expect("failed to create event");
+
+        Event {
+            device: device.clone(),
+            handle,
+        }
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe {
+            (self.device.fns().v1_0.destroy_event)(self.device.handle(), self.handle, ptr::null())
+        };
+    }
+}
+
+struct Passes {
+    device: Arc<Device>,
+    pipeline: Arc<ComputePipeline>,
+    set: Arc<PersistentDescriptorSet>,
+    data: Subbuffer<[u32]>,
+    reversed: Subbuffer<[u32]>,
+    event: Event,
+}
+
+impl Passes {
+    fn dispatch(&self, commands: &RawCommandBuffer, pass: u32, seed: u32) {
+        let fns = self.device.fns();
+        let push_constants = cs::PushConstants { pass, seed };
+        unsafe {
+            (fns.v1_0.cmd_push_constants)(
+                commands.handle(),
+                self.pipeline.layout().handle(),
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                size_of::<cs::PushConstants>() as u32,
+                &push_constants as *const cs::PushConstants as *const c_void,
+            );
+            (fns.v1_0.cmd_dispatch)(commands.handle(), ELEMENTS / WORK_GROUP_SIZE, 1, 1);
+        }
+    }
+
+    // Records and runs both passes, returning how many elements the read pass got wrong
+    fn run(&self, queue: &Arc<Queue>, dependency: Dependency, seed: u32) -> usize {
+        let fns = self.device.fns();
+        let commands = RawCommandBuffer::begin(&self.device, queue.queue_family_index());
+        unsafe {
+            (fns.v1_0.cmd_bind_pipeline)(
+                commands.handle(),
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.handle(),
+            );
+            (fns.v1_0.cmd_bind_descriptor_sets)(
+                commands.handle(),
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.layout().handle(),
+                0,
+                1,
+                &self.set.inner().handle(),
+                0,
+                ptr::null(),
+            );
+        }
+
+        self.dispatch(&commands, WRITE, seed);
+        let barrier = buffer_barrier(&self.data);
+        match dependency {
+            Dependency::None => {}
+            Dependency::Barrier => unsafe {
+                // The read pass waits for the whole write pass, and nothing can run in between
+                (fns.v1_0.cmd_pipeline_barrier)(
+                    commands.handle(),
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::DependencyFlags::empty(),
+                    0,
+                    ptr::null(),
+                    1,
+                    &barrier,
+                    0,
+                    ptr::null(),
+                );
+            },
+            Dependency::Event => unsafe {
+                // The event is set once the write pass is done, and only the read pass waits
+                // for it, so the independent pass can fill the gap
+                (fns.v1_0.cmd_set_event)(
+                    commands.handle(),
+                    self.event.handle,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                );
+                self.dispatch(&commands, INDEPENDENT, seed);
+                (fns.v1_0.cmd_wait_events)(
+                    commands.handle(),
+                    1,
+                    &self.event.handle,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    0,
+                    ptr::null(),
+                    1,
+                    &barrier,
+                    0,
+                    ptr::null(),
+                );
+                // Ready to be set again by the next run
+                (fns.v1_0.cmd_reset_event)(
+                    commands.handle(),
+                    self.event.handle,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                );
+            },
+        }
+        self.dispatch(&commands, READ, seed);
+
+        // Waiting for the fence doesn't make the writes visible to the host, this does
+        let host_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ);
+        unsafe {
+            (fns.v1_0.cmd_pipeline_barrier)(
+                commands.handle(),
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                1,
+                &*host_barrier,
+                0,
+                ptr::null(),
+                0,
+                ptr::null(),
+            );
+        }
+        commands.submit_and_wait(queue);
+
+        let reversed = self.reversed.read().unwrap();
+        (0..ELEMENTS)
+            .filter(|&i| reversed[i as usize] != scramble(ELEMENTS - 1 - i, seed))
+            .count()
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+    let allocators = Allocators::new(&device);
+
+    if !args::flag("--validation") {
+        tracing::warn!(
+            "run with --validation and synchronization validation enabled to have the hazard \
+             reported, e.g. with VK_LAYER_ENABLES={}",
+            "VK_VALIDATION_FEATURE_ENABLE_SYNCHRONIZATION_VALIDATION_EXT"
+        );
+    }
+
+    let buffer = |usage| {
+        Buffer::new_slice::<u32>(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage,
+                ..Default::default()
+            },
+            ELEMENTS as u64,
+        )
+            .expect("failed to create buffer")
+    };
+    let data = buffer(MemoryUsage::DeviceOnly);
+    let reversed = buffer(MemoryUsage::Download);
+    let other = buffer(MemoryUsage::DeviceOnly);
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    let set = bind_resources(
+        &allocators.descriptor,
+        &pipeline,
+        0,
+        [
+            (0, Resource::buffer(data.clone())),
+            (1, Resource::buffer(reversed.clone())),
+            (2, Resource::buffer(other)),
+        ],
+    );
+
+    let passes = Passes {
+        device: device.clone(),
+        pipeline,
+        set,
+        data,
+        reversed,
+        event: Event::new(&device),
+    };
+
+    // Every run writes different values, so one reading what the previous run left shows up
+    let runs = args::value::<u32>("--runs").unwrap_or(8);
+    let mut seed = 0;
+    for dependency in [Dependency::None, Dependency::Barrier, Dependency::Event] {
+        let mut wrong = 0;
+        for _ in 0..runs {
+            seed += 1;
+            wrong += passes.run(&queue, dependency, seed);
+        }
+        println!("{dependency:?}: {wrong} wrong elements over {runs} runs");
+        // Without a barrier nothing is guaranteed either way, many GPUs happen to get it right
+        if !matches!(dependency, Dependency::None) {
+            assert_eq!(wrong, 0, "wrong results with {dependency:?}");
+        }
+    }
+
+    tracing::info!("everything succeeded");
+}
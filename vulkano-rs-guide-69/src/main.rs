@@ -0,0 +1,407 @@
+//Virtual texturing: a texture far larger than what is kept in memory, where only the tiles the
+//view reads are loaded into a cache and a page table says where each one is
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BufferImageCopy, CommandBufferUsage, CopyBufferToImageInfo,
+    CopyImageToBufferInfo, FillBufferInfo, PrimaryAutoCommandBuffer,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageAccess, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::wait;
+
+// Have to match the constants in the shader. The virtual texture is PAGES by PAGES tiles of
+// TILE by TILE texels, 16384 on a side, and the cache holds CACHE_SIDE by CACHE_SIDE of them
+const TILE: u32 = 128;
+const PAGES: u32 = 128;
+const CACHE_SIDE: u32 = 8;
+const VIRTUAL_SIZE: u32 = PAGES * TILE;
+const SLOTS: usize = (CACHE_SIDE * CACHE_SIDE) as usize;
+
+const OUTPUT_SIZE: u32 = 1024;
+// Texels per output pixel. The view spans 768 texels, so at most 7 by 7 pages, which the cache
+// always has room for
+const SCALE: f32 = 0.75;
+// How far the view moves every frame, in texels
+const PAN: [f32; 2] = [150.0, 60.0];
+
+// The modes of the shader
+const FEEDBACK: u32 = 0;
+const RESOLVE: u32 = 1;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cs.comp",
+    }
+}
+
+// What a tile loaded from disk would hold. Made up from the texel coordinates, so neighboring
+// tiles meet without seams
+fn load_tile(page: u32) -> Vec<u8> {
+    let (page_x, page_y) = (page % PAGES, page / PAGES);
+    let mut texels = Vec::with_capacity((TILE * TILE * 4) as usize);
+    for y in 0..TILE {
+        for x in 0..TILE {
+            let u = (page_x * TILE + x) as f32;
+            let v = (page_y * TILE + y) as f32;
+            let wave = |t: f32| (t.sin() * 0.5 + 0.5) * 255.0;
+            // Lines every 1024 texels, to see the view move
+            let line = (page_x * TILE + x) % 1024 < 4 || (page_y * TILE + y) % 1024 < 4;
+            if line {
+                texels.extend([240, 240, 240, 255]);
+            } else {
+                texels.extend([
+                    wave(u * 0.013) as u8,
+                    wave(v * 0.011) as u8,
+                    wave((u + v) * 0.004 + (u * 0.002).cos() * 3.0) as u8,
+                    255,
+                ]);
+            }
+        }
+    }
+    texels
+}
+
+// Which pages are in which slots, with the least recently needed one evicted when a page needs
+// a slot and none is free
+struct Cache {
+    // The page in each slot, and the last frame it was needed in
+    slots: Vec<Option<(u32, u32)>>,
+    // What the shader reads: per page, 0 when not resident, otherwise 1 + its slot
+    table: Vec<u32>,
+}
+
+impl Cache {
+    fn new() -> Self {
+        Cache {
+            slots: vec![None; SLOTS],
+            table: vec![0; (PAGES * PAGES) as usize],
+        }
+    }
+
+    fn resident(&self, page: u32) -> bool {
+        self.table[page as usize] != 0
+    }
+
+    fn touch(&mut self, page: u32, frame: u32) {
+        let slot = self.table[page as usize] as usize - 1;
+        self.slots[slot] = Some((page, frame));
+    }
+
+    // Puts `page` in a slot, returning the slot and the page evicted from it, if any. Pages
+    // needed this frame are never evicted, they were all touched before any insert
+    fn insert(&mut self, page: u32, frame: u32) -> (u32, Option<u32>) {
+        let slot = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or_else(|| {
+                (0..SLOTS)
+                    .filter(|&slot| self.slots[slot].unwrap().1 != frame)
+                    .min_by_key(|&slot| self.slots[slot].unwrap().1)
+                    .expect("the view needs more pages than the cache holds")
+            });
+        let evicted = self.slots[slot].map(|(page, _)| page);
+        if let Some(evicted) = evicted {
+            self.table[evicted as usize] = 0;
+        }
+        self.slots[slot] = Some((page, frame));
+        self.table[page as usize] = slot as u32 + 1;
+        (slot as u32, evicted)
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+    let allocators = Allocators::new(&device);
+
+    // What this chapter stands in for, which 0.33 can't create a sampled image for
+    if device.physical_device().supported_features().sparse_binding {
+        tracing::info!(
+            "sparse binding is supported, but vulkano 0.33 images are always fully bound: \
+             keeping the tiles in a cache instead"
+        );
+    }
+
+    let frames = args::value::<u32>("--frames").unwrap_or(12);
+    let show_tiles = !args::flag("--no-outlines");
+
+    let storage_image = |size, usage| {
+        StorageImage::with_usage(
+            &allocators.memory,
+            ImageDimensions::Dim2d {
+                width: size,
+                height: size,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            ImageUsage::STORAGE | usage,
+            ImageCreateFlags::empty(),
+            [queue.queue_family_index()],
+        )
+            .unwrap()
+    };
+    let cache_image = storage_image(CACHE_SIDE * TILE, ImageUsage::TRANSFER_DST);
+    let target = storage_image(OUTPUT_SIZE, ImageUsage::TRANSFER_SRC);
+
+    let page_table = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        (0..PAGES * PAGES).map(|_| 0u32),
+    )
+        .expect("failed to create page table");
+    let feedback = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (PAGES * PAGES) as u64,
+    )
+        .expect("failed to create feedback buffer");
+    let pixels = Buffer::new_slice::<u8>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (OUTPUT_SIZE * OUTPUT_SIZE * 4) as u64,
+    )
+        .expect("failed to create readback buffer");
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    let set = bind_resources(
+        &allocators.descriptor,
+        &pipeline,
+        0,
+        [
+            (0, Resource::image(ImageView::new_default(cache_image.clone()).unwrap())),
+            (1, Resource::buffer(page_table.clone())),
+            (2, Resource::buffer(feedback.clone())),
+            (3, Resource::image(ImageView::new_default(target.clone()).unwrap())),
+        ],
+    );
+    let groups = [(OUTPUT_SIZE + 7) / 8, (OUTPUT_SIZE + 7) / 8, 1];
+
+    // Records a dispatch of the shader in `mode` over the view at `origin`
+    let dispatch = |builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+                    mode,
+                    origin: [f32; 2]| {
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set.clone(),
+            )
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                cs::PushConstants {
+                    origin,
+                    scale: SCALE,
+                    mode,
+                    show_tiles: show_tiles as u32,
+                },
+            )
+            .dispatch(groups)
+            .unwrap();
+    };
+
+    let mut cache = Cache::new();
+    let mut loaded_total = 0;
+    let start = [VIRTUAL_SIZE as f32 * 0.3, VIRTUAL_SIZE as f32 * 0.4];
+    for frame in 0..frames {
+        let origin = [
+            start[0] + PAN[0] * frame as f32,
+            start[1] + PAN[1] * frame as f32,
+        ];
+
+        // Feedback: which pages the view reads. Sparse residency would tell after the fact,
+        // through the residency code of each sample
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .fill_buffer(FillBufferInfo::dst_buffer(feedback.clone()))
+            .unwrap();
+        dispatch(&mut builder, FEEDBACK, origin);
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        // Pages already in the cache are kept there first, so loading the others can't evict
+        // one this frame needs
+        let needed: Vec<u32> = feedback
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_, &needed)| needed != 0)
+            .map(|(page, _)| page as u32)
+            .collect();
+        let mut missing = Vec::new();
+        for &page in &needed {
+            if cache.resident(page) {
+                cache.touch(page, frame);
+            } else {
+                missing.push(page);
+            }
+        }
+
+        // Each missing page goes into a slot of the cache, like binding memory to the tile of
+        // a sparse image
+        let mut staging = Vec::new();
+        let mut regions = Vec::new();
+        let mut evicted = 0;
+        for page in missing {
+            let (slot, old) = cache.insert(page, frame);
+            evicted += old.is_some() as u32;
+            regions.push(BufferImageCopy {
+                buffer_offset: staging.len() as u64,
+                image_subresource: cache_image.subresource_layers(),
+                image_offset: [(slot % CACHE_SIDE) * TILE, (slot / CACHE_SIDE) * TILE, 0],
+                image_extent: [TILE, TILE, 1],
+                ..Default::default()
+            });
+            staging.extend(load_tile(page));
+        }
+        let loaded = regions.len();
+        loaded_total += loaded;
+        page_table.write().unwrap().copy_from_slice(&cache.table);
+        println!(
+            "frame {frame}: {} pages needed, {loaded} loaded, {evicted} evicted",
+            needed.len()
+        );
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        if !regions.is_empty() {
+            let staging_buffer = Buffer::from_iter(
+                &allocators.memory,
+                BufferCreateInfo {
+                    usage: BufferUsage::TRANSFER_SRC,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::Upload,
+                    ..Default::default()
+                },
+                staging,
+            )
+                .expect("failed to create staging buffer");
+            builder
+                .copy_buffer_to_image(CopyBufferToImageInfo {
+                    regions: regions.into(),
+                    ..CopyBufferToImageInfo::buffer_image(staging_buffer, cache_image.clone())
+                })
+                .unwrap();
+        }
+        dispatch(&mut builder, RESOLVE, origin);
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                target.clone(),
+                pixels.clone(),
+            ))
+            .unwrap();
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+    }
+
+    let pixels = pixels.read().unwrap();
+    image::save_buffer(
+        "image.png",
+        &pixels,
+        OUTPUT_SIZE,
+        OUTPUT_SIZE,
+        image::ColorType::Rgba8,
+    )
+        .expect("failed to write image.png");
+
+    // A pixel per page: green for the resident ones, gray for the rest
+    let residency: Vec<u8> = cache
+        .table
+        .iter()
+        .flat_map(|&entry| {
+            if entry != 0 {
+                [40, 200, 60, 255]
+            } else {
+                [50, 50, 50, 255]
+            }
+        })
+        .collect();
+    image::save_buffer(
+        "residency.png",
+        &residency,
+        PAGES,
+        PAGES,
+        image::ColorType::Rgba8,
+    )
+        .expect("failed to write residency.png");
+
+    let tile_bytes = (TILE * TILE * 4) as u64;
+    let resident = cache.slots.iter().flatten().count();
+    println!(
+        "{resident} of {} pages resident, {loaded_total} loaded over {frames} frames: {} MiB of \
+         cache for a {} MiB texture",
+        PAGES * PAGES,
+        SLOTS as u64 * tile_bytes / (1 << 20),
+        (PAGES * PAGES) as u64 * tile_bytes / (1 << 20)
+    );
+
+    tracing::info!("everything succeeded");
+}
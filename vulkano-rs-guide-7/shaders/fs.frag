@@ -0,0 +1,20 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec2 v_tex_coord;
+
+layout(location = 0) out vec4 f_color;
+
+// One descriptor set per material
+layout(set = 0, binding = 0) uniform sampler2D base_color_texture;
+layout(set = 0, binding = 1) uniform Material {
+    vec4 base_color_factor;
+} material;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.5, 1.0, 0.3));
+
+void main() {
+    vec4 base_color = texture(base_color_texture, v_tex_coord) * material.base_color_factor;
+    float diffuse = max(dot(normalize(v_normal), LIGHT_DIRECTION), 0.0);
+    f_color = vec4(base_color.rgb * (0.15 + diffuse), base_color.a);
+}
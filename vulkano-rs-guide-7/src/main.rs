@@ -0,0 +1,550 @@
+//Loading a whole glTF scene: meshes, materials, textures and the node hierarchy
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::glam::{Mat4, Vec3};
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+use vulkano_rs_common::{args, egui, tracing};
+
+// glTF meshes are made of primitives, each with its own buffers and material
+struct Primitive {
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+    material: usize,
+}
+
+// A primitive placed in the world by the node it belongs to
+struct Draw {
+    primitive: usize,
+    transform: Mat4,
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+// Smooth normals for primitives that come without them
+fn compute_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+        let normal = (b - a).cross(c - a);
+        for &index in triangle {
+            normals[index as usize] += normal;
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or_zero().to_array())
+        .collect()
+}
+
+// Vulkan has no three channel 8-bit formats that are widely supported, so everything is expanded
+// to RGBA
+fn to_rgba8(image: &gltf::image::Data) -> Vec<u8> {
+    let channels = match image.format {
+        gltf::image::Format::R8 => 1,
+        gltf::image::Format::R8G8 => 2,
+        gltf::image::Format::R8G8B8 => 3,
+        gltf::image::Format::R8G8B8A8 => return image.pixels.clone(),
+        format => panic!("unsupported texture format {format:?}"),
+    };
+
+    image
+        .pixels
+        .chunks_exact(channels)
+        .flat_map(|pixel| match pixel {
+            [r] => [*r, *r, *r, 255],
+            [r, g] => [*r, *g, 0, 255],
+            [r, g, b] => [*r, *g, *b, 255],
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn upload_texture(
+    memory_allocator: &(impl MemoryAllocator + ?Sized),
+    uploads: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+) -> Arc<ImageView<ImmutableImage>> {
+    // Immutable images are filled once through a staging buffer, the copy is recorded in uploads
+    let image = ImmutableImage::from_iter(
+        memory_allocator,
+        pixels,
+        ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        },
+        MipmapsCount::One,
+        // Color textures are stored in sRGB, the sampler converts them to linear values
+        Format::R8G8B8A8_SRGB,
+        uploads,
+    )
+        .expect("failed to create texture");
+
+    ImageView::new_default(image).unwrap()
+}
+
+// Mipmaps aren't generated, so only the nearest/linear part of the filters matters
+fn sampler_create_info(sampler: gltf::texture::Sampler) -> SamplerCreateInfo {
+    use gltf::texture::{MagFilter, MinFilter};
+
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => Filter::Nearest,
+        _ => Filter::Linear,
+    };
+    let min_filter = match sampler.min_filter() {
+        Some(
+            MinFilter::Nearest | MinFilter::NearestMipmapNearest | MinFilter::NearestMipmapLinear,
+        ) => Filter::Nearest,
+        _ => Filter::Linear,
+    };
+
+    SamplerCreateInfo {
+        mag_filter,
+        min_filter,
+        address_mode: [SamplerAddressMode::Repeat; 3],
+        ..Default::default()
+    }
+}
+
+// Walks the node tree, accumulating the transforms of the parents
+fn collect_draws(
+    node: gltf::Node,
+    parent_transform: Mat4,
+    mesh_primitives: &[Vec<usize>],
+    draws: &mut Vec<Draw>,
+) {
+    let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        for &primitive in &mesh_primitives[mesh.index()] {
+            draws.push(Draw {
+                primitive,
+                transform,
+            });
+        }
+    }
+
+    for child in node.children() {
+        collect_draws(child, transform, mesh_primitives, draws);
+    }
+}
+
+struct SceneViewer {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    primitives: Vec<Primitive>,
+    // Indexed by material, the last one is the default material for primitives without one
+    materials: Vec<Arc<PersistentDescriptorSet>>,
+    draws: Vec<Draw>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+}
+
+impl SceneViewer {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let queue = renderer.queue();
+
+        let path = args::value::<String>("--scene")
+            .unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/assets/scene.glb").into());
+        // import also decodes the buffers and images, whether embedded in the .glb or external
+        let (document, buffers, images) = gltf::import(&path).expect("failed to load scene");
+
+        let allocators = renderer.allocators.clone();
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        // Geometry: one vertex and index buffer per primitive
+        let mut primitives = Vec::new();
+        let mut mesh_primitives = Vec::new();
+        for mesh in document.meshes() {
+            let mut indices_of_mesh = Vec::new();
+
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    tracing::warn!(
+                        "skipping a {:?} primitive of mesh {:?}",
+                        primitive.mode(),
+                        mesh.name()
+                    );
+                    continue;
+                }
+
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let positions: Vec<[f32; 3]> = reader
+                    .read_positions()
+                    .expect("primitive without positions")
+                    .collect();
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    // Non-indexed primitives just use every vertex once
+                    None => (0..positions.len() as u32).collect(),
+                };
+                let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                    Some(normals) => normals.collect(),
+                    None => compute_normals(&positions, &indices),
+                };
+                let tex_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+                    Some(tex_coords) => tex_coords.into_f32().collect(),
+                    None => vec![[0.0, 0.0]; positions.len()],
+                };
+
+                let vertices = positions.into_iter().zip(normals).zip(tex_coords).map(
+                    |((position, normal), tex_coord)| PosNormalUv {
+                        position,
+                        normal,
+                        tex_coord,
+                    },
+                );
+
+                let vertex_buffer = Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::VERTEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    vertices.collect::<Vec<_>>(),
+                )
+                    .expect("failed to create vertex buffer");
+                let index_buffer = Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::INDEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    indices,
+                )
+                    .expect("failed to create index buffer");
+
+                indices_of_mesh.push(primitives.len());
+                primitives.push(Primitive {
+                    vertex_buffer,
+                    index_buffer,
+                    material: primitive
+                        .material()
+                        .index()
+                        .unwrap_or(document.materials().len()),
+                });
+            }
+
+            mesh_primitives.push(indices_of_mesh);
+        }
+
+        // Textures are uploaded with a single command buffer, submitted before the first frame
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        // Materials without a base color texture sample a single white pixel instead, so every
+        // material can use the same pipeline
+        let white = upload_texture(
+            &allocators.memory,
+            &mut uploads,
+            1,
+            1,
+            vec![255, 255, 255, 255],
+        );
+        let textures: Vec<_> = images
+            .iter()
+            .map(|image| {
+                upload_texture(
+                    &allocators.memory,
+                    &mut uploads,
+                    image.width,
+                    image.height,
+                    to_rgba8(image),
+                )
+            })
+            .collect();
+
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let create_material =
+            |base_color_factor: [f32; 4], texture: Option<gltf::texture::Texture>| {
+                let (view, sampler_info) = match &texture {
+                    Some(texture) => (
+                        textures[texture.source().index()].clone(),
+                        sampler_create_info(texture.sampler()),
+                    ),
+                    None => (white.clone(), SamplerCreateInfo::simple_repeat_linear()),
+                };
+
+                let uniform_buffer = Buffer::from_data(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::UNIFORM_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    fs::Material { base_color_factor },
+                )
+                    .expect("failed to create uniform buffer");
+
+                PersistentDescriptorSet::new(
+                    &allocators.descriptor,
+                    layout.clone(),
+                    [
+                        WriteDescriptorSet::image_view_sampler(
+                            0,
+                            view,
+                            Sampler::new(device.clone(), sampler_info).unwrap(),
+                        ),
+                        WriteDescriptorSet::buffer(1, uniform_buffer),
+                    ],
+                )
+                    .unwrap()
+            };
+
+        let mut materials: Vec<_> = document
+            .materials()
+            .map(|material| {
+                let pbr = material.pbr_metallic_roughness();
+                create_material(
+                    pbr.base_color_factor(),
+                    pbr.base_color_texture().map(|info| info.texture()),
+                )
+            })
+            .collect();
+        // glTF's default material is plain white
+        materials.push(create_material([1.0; 4], None));
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        // The hierarchy is static, so the world transforms are only computed once
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .expect("file without scenes");
+        let mut draws = Vec::new();
+        for node in scene.nodes() {
+            collect_draws(node, Mat4::IDENTITY, &mesh_primitives, &mut draws);
+        }
+
+        tracing::info!(
+            "loaded {path}: {} primitives, {} materials, {} textures, {} draws",
+            primitives.len(),
+            materials.len() - 1,
+            textures.len(),
+            draws.len()
+        );
+
+        SceneViewer {
+            camera: Camera::new(Vec3::new(0.0, 1.5, 4.0), Vec3::ZERO),
+            render_pass,
+            pipeline,
+            allocators,
+            primitives,
+            materials,
+            draws,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+        }
+    }
+}
+
+impl App for SceneViewer {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        let [width, height] = self.viewport.dimensions;
+        let view_projection = self.camera.view_projection(width / height);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.1, 0.1, 0.1, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone());
+
+        for draw in &self.draws {
+            let primitive = &self.primitives[draw.primitive];
+            let push_constants = vs::PushConstants {
+                view_projection: view_projection.to_cols_array_2d(),
+                model: draw.transform.to_cols_array_2d(),
+            };
+
+            builder
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.pipeline.layout().clone(),
+                    0,
+                    self.materials[primitive.material].clone(),
+                )
+                .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+                .bind_vertex_buffers(0, primitive.vertex_buffer.clone())
+                .bind_index_buffer(primitive.index_buffer.clone())
+                .draw_indexed(primitive.index_buffer.len() as u32, 1, 0, 0, 0)
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Scene").show(ctx, |ui| {
+            ui.label(format!("primitives: {}", self.primitives.len()));
+            ui.label(format!("draws: {}", self.draws.len()));
+            ui.label("WASD to move, hold the right mouse button to look around");
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-7",
+        DeviceExtensions::empty(),
+        SceneViewer::new,
+    );
+}
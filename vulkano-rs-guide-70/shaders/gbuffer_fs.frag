@@ -0,0 +1,17 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec3 v_color;
+layout(location = 2) in vec3 v_position;
+
+// One output per color attachment of the subpass. The shading pass tells the background from
+// the objects by the w of the position, which stays at the clear value of 0 there
+layout(location = 0) out vec4 f_albedo;
+layout(location = 1) out vec4 f_normal;
+layout(location = 2) out vec4 f_position;
+
+void main() {
+    f_albedo = vec4(v_color, 1.0);
+    f_normal = vec4(normalize(v_normal), 0.0);
+    f_position = vec4(v_position, 1.0);
+}
@@ -0,0 +1,23 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+// Per instance
+layout(location = 2) in vec4 center;
+layout(location = 3) in vec4 scale;
+layout(location = 4) in vec4 color;
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec3 v_color;
+layout(location = 2) out vec3 v_position;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+} pc;
+
+void main() {
+    v_normal = normal;
+    v_color = color.rgb;
+    v_position = center.xyz + position * scale.xyz;
+    gl_Position = pc.view_projection * vec4(v_position, 1.0);
+}
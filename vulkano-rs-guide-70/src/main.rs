@@ -0,0 +1,479 @@
+//Coarse shading: the lighting of a deferred renderer evaluated at full rate in the middle of the
+//image and once per 2x2 or 4x4 pixels towards the edges, following a rate map
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::{cube, sphere};
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::types::PosNormalUv;
+use vulkano_rs_common::wait;
+
+// A multiple of TILE both ways, so every tile is whole
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+// Pixels per side of a tile of the rate map, as the shading rate attachments of most GPUs use.
+// Has to match the work group size of the shading shader
+const TILE: u32 = 16;
+// Has to match the work group size of the rate shader
+const RATE_GROUP_SIZE: u32 = 8;
+
+const GRID: u32 = 24;
+const SPACING: f32 = 2.5;
+
+// Per instance, as in chapter 56
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct Object {
+    #[format(R32G32B32A32_SFLOAT)]
+    center: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    scale: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    color: [f32; 4],
+}
+
+mod gbuffer_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/gbuffer_vs.vert",
+    }
+}
+
+mod gbuffer_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/gbuffer_fs.frag",
+    }
+}
+
+mod rate_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/rate_cs.comp",
+    }
+}
+
+mod shade_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/shade_cs.comp",
+        include: ["../shaders/common"],
+    }
+}
+
+// The color the rate map is shown in: green at full rate, yellow and red for coarser ones
+fn rate_color(rate: u32) -> [u8; 3] {
+    match rate {
+        1 => [40, 200, 60],
+        2 => [230, 200, 40],
+        _ => [220, 50, 40],
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    let context = VulkanContext::new(instance, DeviceExtensions::empty(), None);
+    let device = context.device.clone();
+    let queue = context.queue.clone();
+    let allocators = Allocators::new(&device);
+
+    // What this chapter stands in for, which 0.33 has no attachment, state or command for
+    if device
+        .physical_device()
+        .supported_extensions()
+        .khr_fragment_shading_rate
+    {
+        tracing::info!(
+            "VK_KHR_fragment_shading_rate is supported, but vulkano 0.33 can't set a shading \
+             rate: shading coarsely in a compute pass"
+        );
+    }
+
+    let full_rate = args::flag("--full-rate");
+    let light_count = args::value::<u32>("--lights").unwrap_or(64);
+
+    // Cubes first and spheres after, so each shape is one instanced draw
+    let offset = (GRID - 1) as f32 / 2.0;
+    let object = |i: u32, round: bool| {
+        let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+        let size = 0.4 + ((x * 1.3 + z * 0.7).sin() * 0.5 + 0.5) * 0.6;
+        Object {
+            center: [(x - offset) * SPACING, size, (z - offset) * SPACING, 0.0],
+            scale: [size, size, size, 0.0],
+            color: if round {
+                [0.3, 0.5 + 0.4 * z / GRID as f32, 0.9, 1.0]
+            } else {
+                [0.9, 0.5 + 0.4 * x / GRID as f32, 0.3, 1.0]
+            },
+        }
+    };
+    let is_sphere = |i: u32| (i % GRID + i / GRID) % 2 == 1;
+    let cubes: Vec<_> = (0..GRID * GRID).filter(|&i| !is_sphere(i)).collect();
+    let spheres: Vec<_> = (0..GRID * GRID).filter(|&i| is_sphere(i)).collect();
+    let cube_count = cubes.len() as u32;
+    let objects: Vec<_> = cubes
+        .into_iter()
+        .map(|i| object(i, false))
+        .chain(spheres.into_iter().map(|i| object(i, true)))
+        .collect();
+
+    let (cube_vertices, cube_indices) = cube();
+    let (sphere_vertices, sphere_indices) = sphere(12, 24);
+    let cube_index_count = cube_indices.len() as u32;
+    let sphere_index_count = sphere_indices.len() as u32;
+    let cube_vertex_count = cube_vertices.len() as i32;
+    let vertices: Vec<_> = cube_vertices.into_iter().chain(sphere_vertices).collect();
+    let indices: Vec<_> = cube_indices.into_iter().chain(sphere_indices).collect();
+
+    let buffer_info = |usage| BufferCreateInfo {
+        usage,
+        ..Default::default()
+    };
+    let allocation_info = |usage| AllocationCreateInfo {
+        usage,
+        ..Default::default()
+    };
+    let vertices = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::VERTEX_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        vertices,
+    )
+        .expect("failed to create vertex buffer");
+    let indices = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::INDEX_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        indices,
+    )
+        .expect("failed to create index buffer");
+    let objects = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::VERTEX_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        objects,
+    )
+        .expect("failed to create object buffer");
+    let object_count = objects.len() as u32;
+    let counter = Buffer::from_data(
+        &allocators.memory,
+        buffer_info(BufferUsage::STORAGE_BUFFER),
+        allocation_info(MemoryUsage::Download),
+        0u32,
+    )
+        .expect("failed to create counter buffer");
+
+    // The G-buffer is written by the render pass and read by the shading pass as storage images
+    let gbuffer_image = |format| {
+        AttachmentImage::with_usage(
+            &allocators.memory,
+            [WIDTH, HEIGHT],
+            format,
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::STORAGE,
+        )
+            .unwrap()
+    };
+    let albedo = gbuffer_image(Format::R8G8B8A8_UNORM);
+    let normals = gbuffer_image(Format::R16G16B16A16_SFLOAT);
+    let positions = gbuffer_image(Format::R32G32B32A32_SFLOAT);
+    let depth =
+        AttachmentImage::transient(&allocators.memory, [WIDTH, HEIGHT], Format::D16_UNORM).unwrap();
+
+    let storage_image = |[width, height]: [u32; 2], format| {
+        StorageImage::with_usage(
+            &allocators.memory,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            format,
+            ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+            ImageCreateFlags::empty(),
+            [queue.queue_family_index()],
+        )
+            .unwrap()
+    };
+    let tiles = [WIDTH / TILE, HEIGHT / TILE];
+    let rates = storage_image(tiles, Format::R32_UINT);
+    let target = storage_image([WIDTH, HEIGHT], Format::R8G8B8A8_UNORM);
+
+    let rate_buffer = Buffer::new_slice::<u32>(
+        &allocators.memory,
+        buffer_info(BufferUsage::TRANSFER_DST),
+        allocation_info(MemoryUsage::Download),
+        (tiles[0] * tiles[1]) as u64,
+    )
+        .expect("failed to create readback buffer");
+    let pixels = Buffer::new_slice::<u8>(
+        &allocators.memory,
+        buffer_info(BufferUsage::TRANSFER_DST),
+        allocation_info(MemoryUsage::Download),
+        (WIDTH * HEIGHT * 4) as u64,
+    )
+        .expect("failed to create readback buffer");
+
+    let render_pass = vulkano::single_pass_renderpass!(
+        device.clone(),
+        attachments: {
+            albedo: {
+                load: Clear,
+                store: Store,
+                format: Format::R8G8B8A8_UNORM,
+                samples: 1,
+            },
+            normals: {
+                load: Clear,
+                store: Store,
+                format: Format::R16G16B16A16_SFLOAT,
+                samples: 1,
+            },
+            positions: {
+                load: Clear,
+                store: Store,
+                format: Format::R32G32B32A32_SFLOAT,
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: DontCare,
+                format: Format::D16_UNORM,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [albedo, normals, positions],
+            depth_stencil: {depth},
+        },
+    )
+        .unwrap();
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![
+                ImageView::new_default(albedo.clone()).unwrap(),
+                ImageView::new_default(normals.clone()).unwrap(),
+                ImageView::new_default(positions.clone()).unwrap(),
+                ImageView::new_default(depth).unwrap(),
+            ],
+            ..Default::default()
+        },
+    )
+        .unwrap();
+
+    let vs = gbuffer_vs::load(device.clone()).expect("failed to create shader module");
+    let fs = gbuffer_fs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state([PosNormalUv::per_vertex(), Object::per_instance()])
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+            Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [WIDTH as f32, HEIGHT as f32],
+                depth_range: 0.0..1.0,
+            },
+        ]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .expect("failed to create graphics pipeline");
+
+    let compute_pipeline = |shader: &ShaderModule| {
+        ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+            .expect("failed to create compute pipeline")
+    };
+    let rate_pipeline = compute_pipeline(
+        &rate_cs::load(device.clone()).expect("failed to create shader module"),
+    );
+    let shade_pipeline = compute_pipeline(
+        &shade_cs::load(device.clone()).expect("failed to create shader module"),
+    );
+    let rate_set = bind_resources(
+        &allocators.descriptor,
+        &rate_pipeline,
+        0,
+        [(0, Resource::image(ImageView::new_default(rates.clone()).unwrap()))],
+    );
+    let shade_set = bind_resources(
+        &allocators.descriptor,
+        &shade_pipeline,
+        0,
+        [
+            (0, Resource::image(ImageView::new_default(albedo).unwrap())),
+            (1, Resource::image(ImageView::new_default(normals).unwrap())),
+            (2, Resource::image(ImageView::new_default(positions).unwrap())),
+            (3, Resource::image(ImageView::new_default(rates.clone()).unwrap())),
+            (4, Resource::image(ImageView::new_default(target.clone()).unwrap())),
+            (5, Resource::buffer(counter.clone())),
+        ],
+    );
+
+    let camera = Camera::new(Vec3::new(0.0, 12.0, 26.0), Vec3::new(0.0, 0.0, 0.0));
+    let view_projection = camera.view_projection(WIDTH as f32 / HEIGHT as f32);
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &*allocators.command,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: vec![
+                    Some([0.0; 4].into()),
+                    Some([0.0; 4].into()),
+                    Some([0.0; 4].into()),
+                    Some(1f32.into()),
+                ],
+                ..RenderPassBeginInfo::framebuffer(framebuffer)
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .push_constants(
+            pipeline.layout().clone(),
+            0,
+            gbuffer_vs::PushConstants {
+                view_projection: view_projection.to_cols_array_2d(),
+            },
+        )
+        .bind_vertex_buffers(0, (vertices, objects))
+        .bind_index_buffer(indices)
+        .draw_indexed(cube_index_count, cube_count, 0, 0, 0)
+        .unwrap()
+        .draw_indexed(
+            sphere_index_count,
+            object_count - cube_count,
+            cube_index_count,
+            cube_vertex_count,
+            cube_count,
+        )
+        .unwrap()
+        .end_render_pass()
+        .unwrap();
+
+    // The rate map. A real renderer would also make it follow the content, e.g. coarser where
+    // motion blur or depth of field smear the image anyway
+    builder
+        .bind_pipeline_compute(rate_pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            rate_pipeline.layout().clone(),
+            0,
+            rate_set,
+        )
+        .push_constants(
+            rate_pipeline.layout().clone(),
+            0,
+            rate_cs::PushConstants {
+                inner: 0.45,
+                outer: 0.8,
+                full_rate: full_rate as u32,
+            },
+        )
+        .dispatch([
+            (tiles[0] + RATE_GROUP_SIZE - 1) / RATE_GROUP_SIZE,
+            (tiles[1] + RATE_GROUP_SIZE - 1) / RATE_GROUP_SIZE,
+            1,
+        ])
+        .unwrap()
+        // One work group per tile, which reads its rate
+        .bind_pipeline_compute(shade_pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            shade_pipeline.layout().clone(),
+            0,
+            shade_set,
+        )
+        .push_constants(
+            shade_pipeline.layout().clone(),
+            0,
+            shade_cs::PushConstants {
+                light_count,
+                extent: GRID as f32 * SPACING / 2.0,
+            },
+        )
+        .dispatch([tiles[0], tiles[1], 1])
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(rates, rate_buffer.clone()))
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(target, pixels.clone()))
+        .unwrap();
+
+    let future = sync::now(device.clone())
+        .then_execute(queue.clone(), builder.build().unwrap())
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    wait::fence(&future);
+
+    let shaded = *counter.read().unwrap();
+    let rate_map = rate_buffer.read().unwrap();
+    let full_rate_pixels = rate_map.iter().filter(|&&rate| rate == 1).count() as u32 * TILE * TILE;
+    println!(
+        "Lit {shaded} times with {light_count} lights for {} pixels, {:.0}% of the image at full \
+         rate",
+        WIDTH * HEIGHT,
+        100.0 * full_rate_pixels as f32 / (WIDTH * HEIGHT) as f32
+    );
+
+    let pixels = pixels.read().unwrap();
+    image::save_buffer("image.png", &pixels, WIDTH, HEIGHT, image::ColorType::Rgba8)
+        .expect("failed to write image.png");
+
+    // The same image tinted by the rate of each tile, with the tile edges drawn
+    let mut tinted = pixels.to_vec();
+    for (i, pixel) in tinted.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+        let rate = rate_map[(y / TILE * tiles[0] + x / TILE) as usize];
+        let color = rate_color(rate);
+        let edge = x % TILE == 0 || y % TILE == 0;
+        for channel in 0..3 {
+            pixel[channel] = if edge {
+                color[channel]
+            } else {
+                ((pixel[channel] as u32 + color[channel] as u32) / 2) as u8
+            };
+        }
+    }
+    image::save_buffer("rates.png", &tinted, WIDTH, HEIGHT, image::ColorType::Rgba8)
+        .expect("failed to write rates.png");
+
+    tracing::info!("everything succeeded");
+}
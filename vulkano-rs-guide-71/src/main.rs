@@ -0,0 +1,595 @@
+//Indirect draws with a count buffer: the culling of chapter 56, where the GPU also decides how
+//many of the commands the one indirect draw reads
+
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::ptr;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::DrawIndexedIndirectCommand;
+use vulkano::descriptor_set::DescriptorSet;
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageAccess, ImageLayout, ImageUsage, SampleCount};
+use vulkano::instance::InstanceExtensions;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{
+    AttachmentDescription, AttachmentReference, Framebuffer, FramebufferCreateInfo, LoadOp,
+    RenderPass, RenderPassCreateInfo, StoreOp, Subpass, SubpassDescription,
+};
+use vulkano::{Version, VulkanObject};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::args;
+use vulkano_rs_common::ash::vk;
+use vulkano_rs_common::camera::{frustum_planes, Camera};
+use vulkano_rs_common::context::{self, VulkanContext};
+use vulkano_rs_common::descriptors::{bind_resources, Resource};
+use vulkano_rs_common::glam::Vec3;
+use vulkano_rs_common::mesh::{cube, sphere};
+use vulkano_rs_common::raw::RawCommandBuffer;
+use vulkano_rs_common::tracing;
+use vulkano_rs_common::types::PosNormalUv;
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+// Four bytes per pixel in the order PNG files want them
+const FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+// A GRID by GRID field of cubes and spheres, as in chapter 56
+const GRID: u32 = 64;
+const SPACING: f32 = 2.5;
+// Has to match local_size_x in the compute shader
+const WORK_GROUP_SIZE: u32 = 64;
+
+// Per instance for drawing, and a storage buffer for the culling shader. The w of scale is the
+// radius of a sphere around the object, which is all the test looks at
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct Object {
+    #[format(R32G32B32A32_SFLOAT)]
+    center: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    scale: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    color: [f32; 4],
+}
+
+// Where one mesh lives in the shared vertex and index buffers. The culling shader copies these
+// into the commands it writes, so they go to the GPU too
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct MeshRange {
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: u32,
+}
+
+const CUBE: u32 = 0;
+const SPHERE: u32 = 1;
+
+// Appends every mesh to one vertex and one index list, as in chapter 55
+fn merge(
+    meshes: Vec<(Vec<PosNormalUv>, Vec<u32>)>,
+) -> (Vec<PosNormalUv>, Vec<u32>, Vec<MeshRange>) {
+    let mut all_vertices = Vec::new();
+    let mut all_indices = Vec::new();
+    let mut ranges = Vec::new();
+    for (vertices, indices) in meshes {
+        ranges.push(MeshRange {
+            first_index: all_indices.len() as u32,
+            index_count: indices.len() as u32,
+            vertex_offset: all_vertices.len() as u32,
+        });
+        all_vertices.extend(vertices);
+        all_indices.extend(indices);
+    }
+    (all_vertices, all_indices, ranges)
+}
+
+mod cull_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "shaders/cull_cs.comp",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance = context::create_instance(library, InstanceExtensions::empty());
+    // Devices without the feature are skipped, and if none has it the context says so
+    let context = VulkanContext::with_features(
+        instance,
+        DeviceExtensions::empty(),
+        Features {
+            draw_indirect_count: true,
+            ..Features::empty()
+        },
+        None,
+    );
+    let device = context.device.clone();
+    // The feature can also come from VK_KHR_draw_indirect_count on older devices, whose
+    // command is named differently. This chapter only calls the Vulkan 1.2 one
+    if device.api_version() < Version::V1_2 {
+        tracing::error!(
+            "{} only has Vulkan {}, this chapter needs 1.2",
+            device.physical_device().properties().device_name,
+            device.api_version()
+        );
+        std::process::exit(1);
+    }
+    let queue = context.queue.clone();
+    let allocators = Allocators::new(&device);
+    // Lets every object through, to compare
+    let culling = !args::flag("--no-culling");
+
+    let (vertices, indices, meshes) = merge(vec![cube(), sphere(12, 24)]);
+
+    let offset = (GRID - 1) as f32 / 2.0;
+    let (shapes, objects): (Vec<_>, Vec<_>) = (0..GRID * GRID)
+        .map(|i| {
+            let (x, z) = ((i % GRID) as f32, (i / GRID) as f32);
+            let shape = if (i % GRID + i / GRID) % 2 == 0 {
+                CUBE
+            } else {
+                SPHERE
+            };
+            let size = 0.4 + ((x * 1.3 + z * 0.7).sin() * 0.5 + 0.5) * 0.6;
+            // The corners of a cube stick out further than its faces
+            let (radius, color) = match shape {
+                CUBE => (
+                    size * 3f32.sqrt(),
+                    [0.9, 0.5 + 0.4 * x / GRID as f32, 0.3, 1.0],
+                ),
+                _ => (size, [0.3, 0.5 + 0.4 * z / GRID as f32, 0.9, 1.0]),
+            };
+            let object = Object {
+                center: [(x - offset) * SPACING, size, (z - offset) * SPACING, 0.0],
+                scale: [size, size, size, radius],
+                color,
+            };
+            (shape, object)
+        })
+        .unzip();
+    let object_count = objects.len() as u32;
+
+    let buffer_info = |usage| BufferCreateInfo {
+        usage,
+        ..Default::default()
+    };
+    let allocation_info = |usage| AllocationCreateInfo {
+        usage,
+        ..Default::default()
+    };
+    let vertices = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::VERTEX_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        vertices,
+    )
+        .expect("failed to create vertex buffer");
+    let indices = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::INDEX_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        indices,
+    )
+        .expect("failed to create index buffer");
+    let objects = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::VERTEX_BUFFER | BufferUsage::STORAGE_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        objects,
+    )
+        .expect("failed to create object buffer");
+    let shapes = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::STORAGE_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        shapes,
+    )
+        .expect("failed to create shape buffer");
+    let meshes = Buffer::from_iter(
+        &allocators.memory,
+        buffer_info(BufferUsage::STORAGE_BUFFER),
+        allocation_info(MemoryUsage::Upload),
+        meshes,
+    )
+        .expect("failed to create mesh buffer");
+    // Room for every object, in case they all survive. Only the first `count` are ever read, so
+    // unlike chapter 56 nothing past them has to be zeroed
+    let draws = Buffer::new_slice::<DrawIndexedIndirectCommand>(
+        &allocators.memory,
+        buffer_info(BufferUsage::STORAGE_BUFFER | BufferUsage::INDIRECT_BUFFER),
+        allocation_info(MemoryUsage::DeviceOnly),
+        object_count as u64,
+    )
+        .expect("failed to create draw buffer");
+    // Written by the culling shader and read by the draw as the number of commands. Host
+    // visible so it can be printed
+    let count = Buffer::new_sized::<u32>(
+        &allocators.memory,
+        buffer_info(
+            BufferUsage::STORAGE_BUFFER | BufferUsage::INDIRECT_BUFFER | BufferUsage::TRANSFER_DST,
+        ),
+        allocation_info(MemoryUsage::Download),
+    )
+        .expect("failed to create count buffer");
+    let pixels = Buffer::new_slice::<u8>(
+        &allocators.memory,
+        buffer_info(BufferUsage::TRANSFER_DST),
+        allocation_info(MemoryUsage::Download),
+        (WIDTH * HEIGHT * 4) as u64,
+    )
+        .expect("failed to create readback buffer");
+
+    let cull_cs = cull_cs::load(device.clone()).expect("failed to create shader module");
+    let cull_pipeline = ComputePipeline::new(
+        device.clone(),
+        cull_cs.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+        .expect("failed to create compute pipeline");
+    let cull_set = bind_resources(
+        &allocators.descriptor,
+        &cull_pipeline,
+        0,
+        [
+            (0, Resource::buffer(objects.clone())),
+            (1, Resource::buffer(shapes)),
+            (2, Resource::buffer(meshes)),
+            (3, Resource::buffer(draws.clone())),
+            (4, Resource::buffer(count.clone())),
+        ],
+    );
+
+    // The layouts are given so the commands recorded below know what the attachments are in:
+    // the color one stays ready to be rendered to until it is copied out
+    let render_pass = RenderPass::new(
+        device.clone(),
+        RenderPassCreateInfo {
+            attachments: vec![
+                AttachmentDescription {
+                    format: Some(FORMAT),
+                    samples: SampleCount::Sample1,
+                    load_op: LoadOp::Clear,
+                    store_op: StoreOp::Store,
+                    initial_layout: ImageLayout::Undefined,
+                    final_layout: ImageLayout::ColorAttachmentOptimal,
+                    ..Default::default()
+                },
+                AttachmentDescription {
+                    format: Some(Format::D16_UNORM),
+                    samples: SampleCount::Sample1,
+                    load_op: LoadOp::Clear,
+                    store_op: StoreOp::DontCare,
+                    initial_layout: ImageLayout::Undefined,
+                    final_layout: ImageLayout::DepthStencilAttachmentOptimal,
+                    ..Default::default()
+                },
+            ],
+            subpasses: vec![SubpassDescription {
+                color_attachments: vec![Some(AttachmentReference {
+                    attachment: 0,
+                    layout: ImageLayout::ColorAttachmentOptimal,
+                    ..Default::default()
+                })],
+                depth_stencil_attachment: Some(AttachmentReference {
+                    attachment: 1,
+                    layout: ImageLayout::DepthStencilAttachmentOptimal,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+        .unwrap();
+
+    let color_image = AttachmentImage::with_usage(
+        &allocators.memory,
+        [WIDTH, HEIGHT],
+        FORMAT,
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    )
+        .unwrap();
+    let depth_image =
+        AttachmentImage::transient(&allocators.memory, [WIDTH, HEIGHT], Format::D16_UNORM).unwrap();
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![
+                ImageView::new_default(color_image.clone()).unwrap(),
+                ImageView::new_default(depth_image).unwrap(),
+            ],
+            ..Default::default()
+        },
+    )
+        .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state([PosNormalUv::per_vertex(), Object::per_instance()])
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+            Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [WIDTH as f32, HEIGHT as f32],
+                depth_range: 0.0..1.0,
+            },
+        ]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .build(device.clone())
+        .expect("failed to create graphics pipeline");
+
+    // Looking across the field from one corner, so most of it is behind or beside the camera
+    let camera = Camera::new(Vec3::new(-30.0, 4.0, -30.0), Vec3::new(0.0, 0.0, 0.0));
+    let view_projection = camera.view_projection(WIDTH as f32 / HEIGHT as f32);
+
+    // Everything is recorded through the raw functions: AutoCommandBufferBuilder has no
+    // indirect draw with a count buffer
+    let fns = device.fns();
+    let commands = RawCommandBuffer::begin(&device, queue.queue_family_index());
+    unsafe {
+        // The count starts at zero, and the culling shader adds the survivors to it
+        (fns.v1_0.cmd_fill_buffer)(
+            commands.handle(),
+            count.buffer().handle(),
+            count.offset(),
+            count.size(),
+            0,
+        );
+        let fill_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE);
+        (fns.v1_0.cmd_pipeline_barrier)(
+            commands.handle(),
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::DependencyFlags::empty(),
+            1,
+            &*fill_barrier,
+            0,
+            ptr::null(),
+            0,
+            ptr::null(),
+        );
+
+        let cull_push_constants = cull_cs::PushConstants {
+            planes: frustum_planes(view_projection),
+            object_count,
+            enabled: culling as u32,
+        };
+        (fns.v1_0.cmd_bind_pipeline)(
+            commands.handle(),
+            vk::PipelineBindPoint::COMPUTE,
+            cull_pipeline.handle(),
+        );
+        (fns.v1_0.cmd_bind_descriptor_sets)(
+            commands.handle(),
+            vk::PipelineBindPoint::COMPUTE,
+            cull_pipeline.layout().handle(),
+            0,
+            1,
+            &cull_set.inner().handle(),
+            0,
+            ptr::null(),
+        );
+        (fns.v1_0.cmd_push_constants)(
+            commands.handle(),
+            cull_pipeline.layout().handle(),
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            size_of::<cull_cs::PushConstants>() as u32,
+            &cull_push_constants as *const cull_cs::PushConstants as *const c_void,
+        );
+        (fns.v1_0.cmd_dispatch)(
+            commands.handle(),
+            (object_count + WORK_GROUP_SIZE - 1) / WORK_GROUP_SIZE,
+            1,
+            1,
+        );
+
+        // Both the commands and their count are read as indirect arguments
+        let indirect_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::INDIRECT_COMMAND_READ);
+        (fns.v1_0.cmd_pipeline_barrier)(
+            commands.handle(),
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::DRAW_INDIRECT,
+            vk::DependencyFlags::empty(),
+            1,
+            &*indirect_barrier,
+            0,
+            ptr::null(),
+            0,
+            ptr::null(),
+        );
+
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.02, 0.02, 0.02, 1.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_pass_begin = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass.handle())
+            .framebuffer(framebuffer.handle())
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D {
+                    width: WIDTH,
+                    height: HEIGHT,
+                },
+            })
+            .clear_values(&clear_values);
+        (fns.v1_0.cmd_begin_render_pass)(
+            commands.handle(),
+            &*render_pass_begin,
+            vk::SubpassContents::INLINE,
+        );
+
+        let vs_push_constants = vs::PushConstants {
+            view_projection: view_projection.to_cols_array_2d(),
+        };
+        (fns.v1_0.cmd_bind_pipeline)(
+            commands.handle(),
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.handle(),
+        );
+        (fns.v1_0.cmd_push_constants)(
+            commands.handle(),
+            pipeline.layout().handle(),
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            size_of::<vs::PushConstants>() as u32,
+            &vs_push_constants as *const vs::PushConstants as *const c_void,
+        );
+        let vertex_buffers = [vertices.buffer().handle(), objects.buffer().handle()];
+        let vertex_offsets = [vertices.offset(), objects.offset()];
+        (fns.v1_0.cmd_bind_vertex_buffers)(
+            commands.handle(),
+            0,
+            2,
+            vertex_buffers.as_ptr(),
+            vertex_offsets.as_ptr(),
+        );
+        (fns.v1_0.cmd_bind_index_buffer)(
+            commands.handle(),
+            indices.buffer().handle(),
+            indices.offset(),
+            vk::IndexType::UINT32,
+        );
+
+        // One command for every object that survived, however many that is. The GPU reads
+        // the count first and stops there, the rest of the buffer is never looked at
+        (fns.v1_2.cmd_draw_indexed_indirect_count)(
+            commands.handle(),
+            draws.buffer().handle(),
+            draws.offset(),
+            count.buffer().handle(),
+            count.offset(),
+            object_count,
+            size_of::<DrawIndexedIndirectCommand>() as u32,
+        );
+        (fns.v1_0.cmd_end_render_pass)(commands.handle());
+
+        // Copied out of the color attachment instead of presented
+        let color_handle = color_image.inner().image.handle();
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let to_transfer = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(color_handle)
+            .subresource_range(subresource_range);
+        (fns.v1_0.cmd_pipeline_barrier)(
+            commands.handle(),
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            0,
+            ptr::null(),
+            0,
+            ptr::null(),
+            1,
+            &*to_transfer,
+        );
+        let region = vk::BufferImageCopy {
+            buffer_offset: pixels.offset(),
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: WIDTH,
+                height: HEIGHT,
+                depth: 1,
+            },
+        };
+        (fns.v1_0.cmd_copy_image_to_buffer)(
+            commands.handle(),
+            color_handle,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            pixels.buffer().handle(),
+            1,
+            &region,
+        );
+
+        // The count and the pixels, for the host to read once the fence says it's done
+        let host_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ);
+        (fns.v1_0.cmd_pipeline_barrier)(
+            commands.handle(),
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST,
+            vk::DependencyFlags::empty(),
+            1,
+            &*host_barrier,
+            0,
+            ptr::null(),
+            0,
+            ptr::null(),
+        );
+    }
+    commands.submit_and_wait(&queue);
+
+    let drawn = *count.read().unwrap();
+    println!("{drawn} of {object_count} objects drawn by a single indirect draw");
+
+    let pixels = pixels.read().unwrap();
+    image::save_buffer("image.png", &pixels, WIDTH, HEIGHT, image::ColorType::Rgba8)
+        .expect("failed to write image.png");
+
+    tracing::info!("everything succeeded");
+}
@@ -0,0 +1,77 @@
+#version 460
+
+layout(location = 0) in vec3 v_position;
+layout(location = 1) in vec3 v_normal;
+layout(location = 2) in vec2 v_tex_coord;
+layout(location = 3) in vec4 v_tangent;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_projection;
+    vec3 position;
+} camera;
+
+layout(set = 0, binding = 1) uniform sampler2D normal_map;
+
+// The light parameters change whenever a slider moves, push constants avoid
+// touching any buffer for that
+layout(push_constant) uniform Light {
+    vec3 position;
+    float ambient;
+    vec3 color;
+    float specular;
+    float shininess;
+    uint blinn_phong;
+    // 0: flat, 1: normal mapped, 2: flat left of split_x and normal mapped right of it
+    uint normal_mapping;
+    float split_x;
+} light;
+
+const vec3 OBJECT_COLOR = vec3(0.9, 0.4, 0.2);
+
+void main() {
+    vec3 normal = normalize(v_normal);
+
+    bool mapped = light.normal_mapping == 1
+        || (light.normal_mapping == 2 && gl_FragCoord.x > light.split_x);
+    if (mapped) {
+        // The tangent, bitangent and normal are the axes of the tangent space
+        vec3 tangent = normalize(v_tangent.xyz);
+        vec3 bitangent = cross(normal, tangent) * v_tangent.w;
+        mat3 tbn = mat3(tangent, bitangent, normal);
+
+        vec3 tangent_normal = texture(normal_map, v_tex_coord).xyz * 2.0 - 1.0;
+        normal = normalize(tbn * tangent_normal);
+    }
+    vec3 to_light = light.position - v_position;
+    float light_distance = length(to_light);
+    vec3 light_direction = to_light / light_distance;
+    vec3 view_direction = normalize(camera.position - v_position);
+
+    // Diffuse: surfaces facing the light receive more of it
+    float diffuse = max(dot(normal, light_direction), 0.0);
+
+    // Specular: the highlight where the light bounces towards the viewer
+    float specular = 0.0;
+    if (diffuse > 0.0) {
+        if (light.blinn_phong != 0) {
+            // Blinn-Phong compares the normal with the vector halfway between the
+            // light and the viewer, which avoids the cut-off highlights of Phong at
+            // grazing angles
+            vec3 halfway = normalize(light_direction + view_direction);
+            specular = pow(max(dot(normal, halfway), 0.0), light.shininess);
+        } else {
+            vec3 reflected = reflect(-light_direction, normal);
+            specular = pow(max(dot(view_direction, reflected), 0.0), light.shininess);
+        }
+    }
+
+    // A point light fades with distance
+    float attenuation = 1.0 / (1.0 + 0.09 * light_distance
+        + 0.032 * light_distance * light_distance);
+
+    vec3 color = OBJECT_COLOR * (light.ambient + diffuse * attenuation) * light.color
+        + light.specular * specular * attenuation * light.color;
+    f_color = vec4(color, 1.0);
+}
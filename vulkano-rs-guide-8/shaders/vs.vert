@@ -0,0 +1,26 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 tex_coord;
+layout(location = 3) in vec4 tangent;
+
+// Lighting is computed per fragment, so the world space position and normal are
+// interpolated across each triangle
+layout(location = 0) out vec3 v_position;
+layout(location = 1) out vec3 v_normal;
+layout(location = 2) out vec2 v_tex_coord;
+layout(location = 3) out vec4 v_tangent;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_projection;
+    vec3 position;
+} camera;
+
+void main() {
+    v_position = position;
+    v_normal = normal;
+    v_tex_coord = tex_coord;
+    v_tangent = tangent;
+    gl_Position = camera.view_projection * vec4(position, 1.0);
+}
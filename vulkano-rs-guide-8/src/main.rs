@@ -0,0 +1,500 @@
+//Phong and Blinn-Phong lighting from a point light, with optional normal mapping
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Vec2, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::CameraUniform;
+use vulkano_rs_common::wait;
+use vulkano_rs_common::window::{self, App, Renderer};
+
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct LitVertex {
+    #[format(R32G32B32_SFLOAT)]
+    position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    normal: [f32; 3],
+    #[format(R32G32_SFLOAT)]
+    tex_coord: [f32; 2],
+    // Direction of increasing u on the surface, w is the handedness of the bitangent
+    #[format(R32G32B32A32_SFLOAT)]
+    tangent: [f32; 4],
+}
+
+// A UV sphere of radius 1, on a unit sphere the normal is simply the position
+fn sphere(stacks: u32, sectors: u32) -> (Vec<LitVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    for i in 0..=stacks {
+        let phi = PI * i as f32 / stacks as f32;
+        for j in 0..=sectors {
+            let theta = 2.0 * PI * j as f32 / sectors as f32;
+            let position = [phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin()];
+            vertices.push(LitVertex {
+                position,
+                normal: position,
+                // The texture repeats four times around and twice from pole to pole
+                tex_coord: [
+                    4.0 * j as f32 / sectors as f32,
+                    2.0 * i as f32 / stacks as f32,
+                ],
+                tangent: [0.0; 4],
+            });
+        }
+    }
+
+    let mut indices = Vec::new();
+    for i in 0..stacks {
+        for j in 0..sectors {
+            let a = i * (sectors + 1) + j;
+            let b = a + sectors + 1;
+            // Counter-clockwise when seen from outside
+            indices.extend([a, b + 1, b, a, a + 1, b + 1]);
+        }
+    }
+
+    compute_tangents(&mut vertices, &indices);
+    (vertices, indices)
+}
+
+// The normal map stores normals relative to the surface (tangent space), the tangent and
+// bitangent tell the shader which way the texture's u and v axes point on the model
+fn compute_tangents(vertices: &mut [LitVertex], indices: &[u32]) {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+        let edge1 = Vec3::from(b.position) - Vec3::from(a.position);
+        let edge2 = Vec3::from(c.position) - Vec3::from(a.position);
+        let delta_uv1 = Vec2::from(b.tex_coord) - Vec2::from(a.tex_coord);
+        let delta_uv2 = Vec2::from(c.tex_coord) - Vec2::from(a.tex_coord);
+
+        // Solve edge = delta_u * tangent + delta_v * bitangent for both edges, triangles
+        // without any area in texture space have no meaningful answer
+        let determinant = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) / determinant;
+        let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) / determinant;
+
+        for &index in triangle {
+            tangents[index as usize] += tangent;
+            bitangents[index as usize] += bitangent;
+        }
+    }
+
+    for (vertex, (tangent, bitangent)) in vertices
+        .iter_mut()
+        .zip(tangents.into_iter().zip(bitangents))
+    {
+        let normal = Vec3::from(vertex.normal);
+        // Gram-Schmidt: make the averaged tangent perpendicular to the normal again
+        let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+        // Mirrored texture coordinates flip the bitangent
+        let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = tangent.extend(handedness).to_array();
+    }
+}
+
+// A tileable grid of round bumps, turned into a normal map by comparing neighbouring heights
+fn bump_normal_map(size: u32) -> Vec<u8> {
+    const CELL: f32 = 32.0;
+    const STRENGTH: f32 = 2.0;
+
+    let height = |x: i64, y: i64| {
+        let x = x.rem_euclid(size as i64) as f32;
+        let y = y.rem_euclid(size as i64) as f32;
+        let dx = (x % CELL) / CELL - 0.5;
+        let dy = (y % CELL) / CELL - 0.5;
+        (0.16 - dx * dx - dy * dy).max(0.0).sqrt()
+    };
+
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size as i64 {
+        for x in 0..size as i64 {
+            let du = height(x + 1, y) - height(x - 1, y);
+            let dv = height(x, y + 1) - height(x, y - 1);
+            let normal = Vec3::new(-du * STRENGTH, -dv * STRENGTH, 1.0).normalize();
+            // Map the -1..1 components to 0..255
+            let encoded = (normal * 0.5 + 0.5) * 255.0;
+            pixels.extend([encoded.x as u8, encoded.y as u8, encoded.z as u8, 255]);
+        }
+    }
+
+    pixels
+}
+
+fn upload_normal_map(
+    memory_allocator: &(impl MemoryAllocator + ?Sized),
+    uploads: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+) -> Arc<ImageView<ImmutableImage>> {
+    const SIZE: u32 = 256;
+
+    let image = ImmutableImage::from_iter(
+        memory_allocator,
+        bump_normal_map(SIZE),
+        ImageDimensions::Dim2d {
+            width: SIZE,
+            height: SIZE,
+            array_layers: 1,
+        },
+        MipmapsCount::One,
+        // Normals are data, not colors, so no sRGB conversion
+        Format::R8G8B8A8_UNORM,
+        uploads,
+    )
+        .expect("failed to create normal map");
+
+    ImageView::new_default(image).unwrap()
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Lighting {
+    camera: Camera,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    allocators: Allocators,
+    // Hands out a fresh piece of uniform memory every frame, so the GPU can still read the
+    // previous frame's data while the next one is written
+    uniform_buffer: StreamingBuffer,
+    normal_map: Arc<ImageView<ImmutableImage>>,
+    sampler: Arc<Sampler>,
+    vertex_buffer: Subbuffer<[LitVertex]>,
+    index_buffer: Subbuffer<[u32]>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    start: Instant,
+    // Parameters exposed in the overlay
+    orbit: bool,
+    light_position: [f32; 3],
+    light_color: [f32; 3],
+    ambient: f32,
+    specular: f32,
+    shininess: f32,
+    blinn_phong: bool,
+    normal_mapping: NormalMapping,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NormalMapping {
+    Off,
+    On,
+    // Left half of the window without, right half with normal mapping
+    Split,
+}
+
+impl Lighting {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let (vertices, indices) = sphere(32, 64);
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            vertices,
+        )
+            .expect("failed to create vertex buffer");
+        let index_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            indices,
+        )
+            .expect("failed to create index buffer");
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &*allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        let normal_map = upload_normal_map(&allocators.memory, &mut uploads);
+        let future = sync::now(device.clone())
+            .then_execute(renderer.queue().clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        wait::fence(&future);
+
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::simple_repeat_linear())
+            .expect("failed to create sampler");
+
+        let uniform_buffer = StreamingBuffer::new(allocators.memory.clone());
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(LitVertex::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        Lighting {
+            camera: Camera::new(Vec3::new(0.0, 1.0, 4.0), Vec3::ZERO),
+            render_pass,
+            pipeline,
+            allocators,
+            uniform_buffer,
+            normal_map,
+            sampler,
+            vertex_buffer,
+            index_buffer,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            start: Instant::now(),
+            orbit: true,
+            light_position: [2.0, 1.5, 0.0],
+            light_color: [1.0, 1.0, 1.0],
+            ambient: 0.1,
+            specular: 0.5,
+            shininess: 32.0,
+            blinn_phong: true,
+            normal_mapping: NormalMapping::Split,
+        }
+    }
+}
+
+impl App for Lighting {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // Circle the light around the sphere, keeping the height and radius from the sliders
+        if self.orbit {
+            let [x, y, z] = self.light_position;
+            let radius = (x * x + z * z).sqrt();
+            let angle = self.start.elapsed().as_secs_f32() * 0.8;
+            self.light_position = [radius * angle.cos(), y, radius * angle.sin()];
+        }
+
+        let [width, height] = self.viewport.dimensions;
+        let uniform_subbuffer = self.uniform_buffer.write(CameraUniform::new(
+            self.camera.view_projection(width / height),
+            self.camera.position,
+        ));
+
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [
+                WriteDescriptorSet::buffer(0, uniform_subbuffer),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    self.normal_map.clone(),
+                    self.sampler.clone(),
+                ),
+            ],
+        )
+            .unwrap();
+
+        let light = fs::Light {
+            position: self.light_position,
+            ambient: self.ambient,
+            color: self.light_color,
+            specular: self.specular,
+            shininess: self.shininess,
+            blinn_phong: self.blinn_phong as u32,
+            normal_mapping: self.normal_mapping as u32,
+            split_x: width / 2.0,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.05, 0.05, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .push_constants(self.pipeline.layout().clone(), 0, light)
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .bind_index_buffer(self.index_buffer.clone())
+            .draw_indexed(self.index_buffer.len() as u32, 1, 0, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Light").show(ctx, |ui| {
+            ui.checkbox(&mut self.orbit, "orbit");
+            ui.add(egui::Slider::new(&mut self.light_position[0], -5.0..=5.0).text("x"));
+            ui.add(egui::Slider::new(&mut self.light_position[1], -5.0..=5.0).text("y"));
+            ui.add(egui::Slider::new(&mut self.light_position[2], -5.0..=5.0).text("z"));
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut self.light_color);
+                ui.label("color");
+            });
+            ui.add(egui::Slider::new(&mut self.ambient, 0.0..=1.0).text("ambient"));
+            ui.add(egui::Slider::new(&mut self.specular, 0.0..=2.0).text("specular"));
+            ui.add(
+                egui::Slider::new(&mut self.shininess, 1.0..=256.0)
+                    .logarithmic(true)
+                    .text("shininess"),
+            );
+            ui.checkbox(&mut self.blinn_phong, "Blinn-Phong");
+            ui.horizontal(|ui| {
+                ui.label("normal mapping");
+                ui.radio_value(&mut self.normal_mapping, NormalMapping::Off, "off");
+                ui.radio_value(&mut self.normal_mapping, NormalMapping::On, "on");
+                ui.radio_value(&mut self.normal_mapping, NormalMapping::Split, "split");
+            });
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-8",
+        DeviceExtensions::empty(),
+        Lighting::new,
+    );
+}
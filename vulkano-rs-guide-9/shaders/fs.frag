@@ -0,0 +1,66 @@
+#version 460
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec4 v_light_space_position;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+    mat4 light_view_projection;
+} frame;
+
+// A shadow sampler compares the given depth with the stored one instead of
+// returning the depth itself: 1.0 when lit, 0.0 when in shadow
+layout(set = 0, binding = 1) uniform sampler2DShadow shadow_map;
+
+layout(set = 0, binding = 2) uniform Light {
+    vec3 direction;
+    float intensity;
+    vec3 color;
+} light;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 color;
+    float min_bias;
+    float max_bias;
+    uint pcf;
+} pc;
+
+float shadow_factor(float bias) {
+    // The light uses an orthographic projection, so w is 1 and this is already NDC
+    vec3 ndc = v_light_space_position.xyz / v_light_space_position.w;
+    vec2 uv = ndc.xy * 0.5 + 0.5;
+    float depth = ndc.z - bias;
+
+    if (pc.pcf == 0) {
+        return texture(shadow_map, vec3(uv, depth));
+    }
+
+    // Percentage closer filtering: average the comparison over the neighbouring
+    // texels to soften the jagged shadow edges
+    vec2 texel_size = 1.0 / vec2(textureSize(shadow_map, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadow_map, vec3(uv + vec2(x, y) * texel_size, depth));
+        }
+    }
+    return lit / 9.0;
+}
+
+void main() {
+    vec3 normal = normalize(v_normal);
+    // The light travels along its direction, lighting is computed towards it
+    vec3 light_direction = -light.direction;
+    float diffuse = max(dot(normal, light_direction), 0.0);
+
+    // Surfaces at a grazing angle to the light cover more depth per shadow map
+    // texel, so they need a larger bias against shadow acne
+    float bias = max(pc.max_bias * (1.0 - dot(normal, light_direction)), pc.min_bias);
+    float lit = shadow_factor(bias);
+
+    vec3 color = pc.color.rgb * (0.15 + diffuse * lit * light.intensity) * light.color;
+    f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(push_constant) uniform PushConstants {
+    mat4 light_view_projection;
+    mat4 model;
+} pc;
+
+void main() {
+    gl_Position = pc.light_view_projection * pc.model * vec4(position, 1.0);
+}
@@ -0,0 +1,28 @@
+#version 460
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec4 v_light_space_position;
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 view_projection;
+    mat4 light_view_projection;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 color;
+    float min_bias;
+    float max_bias;
+    uint pcf;
+} pc;
+
+void main() {
+    vec4 world_position = pc.model * vec4(position, 1.0);
+    v_normal = mat3(pc.model) * normal;
+    // Where this vertex ended up in the shadow map
+    v_light_space_position = frame.light_view_projection * world_position;
+    gl_Position = frame.view_projection * world_position;
+}
@@ -0,0 +1,556 @@
+//Shadow mapping: render the scene's depth from the light, then compare against it
+
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceExtensions;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthStencilState};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::rasterization::{CullMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{
+    BorderColor, Filter, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode,
+};
+use vulkano::sync::GpuFuture;
+use vulkano_rs_common::allocators::Allocators;
+use vulkano_rs_common::camera::Camera;
+use vulkano_rs_common::egui;
+use vulkano_rs_common::glam::{Mat4, Quat, Vec3};
+use vulkano_rs_common::streaming::StreamingBuffer;
+use vulkano_rs_common::types::{DirectionalLight, PosNormalUv};
+use vulkano_rs_common::window::{self, App, Renderer};
+
+const SHADOW_MAP_SIZE: u32 = 2048;
+
+// Appends a square with the given center, normal and half-axes, counter-clockwise from outside
+fn push_quad(
+    vertices: &mut Vec<PosNormalUv>,
+    indices: &mut Vec<u32>,
+    center: Vec3,
+    normal: Vec3,
+    u: Vec3,
+    v: Vec3,
+) {
+    let base = vertices.len() as u32;
+    for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        vertices.push(PosNormalUv {
+            position: (center + u * su + v * sv).to_array(),
+            normal: normal.to_array(),
+            ..Default::default()
+        });
+    }
+    indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+// A unit cube with one quad per face, so every face has its own normal
+fn cube() -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+    for (normal, u, v) in faces {
+        push_quad(
+            &mut vertices,
+            &mut indices,
+            normal * 0.5,
+            normal,
+            u * 0.5,
+            v * 0.5,
+        );
+    }
+    (vertices, indices)
+}
+
+fn ground(size: f32) -> (Vec<PosNormalUv>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    push_quad(
+        &mut vertices,
+        &mut indices,
+        Vec3::ZERO,
+        Vec3::Y,
+        Vec3::X * size,
+        Vec3::NEG_Z * size,
+    );
+    (vertices, indices)
+}
+
+struct Mesh {
+    vertex_buffer: Subbuffer<[PosNormalUv]>,
+    index_buffer: Subbuffer<[u32]>,
+}
+
+struct Object {
+    mesh: usize,
+    model: Mat4,
+    color: [f32; 4],
+}
+
+// The depth pass only needs positions, nothing is written to a color attachment
+mod shadow_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/shadow_vs.vert",
+    }
+}
+
+mod shadow_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/shadow_fs.frag",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs.frag",
+    }
+}
+
+struct Shadows {
+    camera: Camera,
+    allocators: Allocators,
+    uniform_buffer: StreamingBuffer,
+    meshes: Vec<Mesh>,
+    objects: Vec<Object>,
+    // First pass: depth from the light's point of view
+    shadow_pipeline: Arc<GraphicsPipeline>,
+    shadow_framebuffer: Arc<Framebuffer>,
+    shadow_map: Arc<ImageView<AttachmentImage>>,
+    shadow_sampler: Arc<Sampler>,
+    // Second pass: the scene from the camera
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    // Parameters exposed in the overlay
+    light_azimuth: f32,
+    light_elevation: f32,
+    min_bias: f32,
+    max_bias: f32,
+    pcf: bool,
+}
+
+impl Shadows {
+    fn new(renderer: &Renderer) -> Self {
+        let device = renderer.device();
+        let allocators = renderer.allocators.clone();
+
+        let meshes = [cube(), ground(6.0)]
+            .into_iter()
+            .map(|(vertices, indices)| Mesh {
+                vertex_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::VERTEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    vertices,
+                )
+                    .expect("failed to create vertex buffer"),
+                index_buffer: Buffer::from_iter(
+                    &allocators.memory,
+                    BufferCreateInfo {
+                        usage: BufferUsage::INDEX_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Upload,
+                        ..Default::default()
+                    },
+                    indices,
+                )
+                    .expect("failed to create index buffer"),
+            })
+            .collect();
+
+        let objects = vec![
+            Object {
+                mesh: 1,
+                model: Mat4::IDENTITY,
+                color: [0.6, 0.6, 0.6, 1.0],
+            },
+            Object {
+                mesh: 0,
+                model: Mat4::from_translation(Vec3::new(0.0, 0.5, 0.0)),
+                color: [0.8, 0.3, 0.2, 1.0],
+            },
+            Object {
+                mesh: 0,
+                model: Mat4::from_scale_rotation_translation(
+                    Vec3::new(0.5, 2.0, 0.5),
+                    Quat::from_rotation_y(0.6),
+                    Vec3::new(2.0, 1.0, -1.0),
+                ),
+                color: [0.2, 0.5, 0.8, 1.0],
+            },
+            Object {
+                mesh: 0,
+                model: Mat4::from_scale_rotation_translation(
+                    Vec3::splat(0.7),
+                    Quat::from_rotation_x(0.4) * Quat::from_rotation_z(0.3),
+                    Vec3::new(-1.5, 1.6, 1.0),
+                ),
+                color: [0.3, 0.7, 0.3, 1.0],
+            },
+        ];
+
+        // The shadow map is written as a depth attachment in the first pass and sampled in the
+        // second one
+        let shadow_image = AttachmentImage::sampled(
+            &allocators.memory,
+            [SHADOW_MAP_SIZE, SHADOW_MAP_SIZE],
+            Format::D16_UNORM,
+        )
+            .unwrap();
+        let shadow_map = ImageView::new_default(shadow_image).unwrap();
+
+        let shadow_render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                depth: {
+                    load: Clear,
+                    store: Store,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+        let shadow_framebuffer = Framebuffer::new(
+            shadow_render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![shadow_map.clone()],
+                ..Default::default()
+            },
+        )
+            .unwrap();
+
+        let shadow_vs = shadow_vs::load(device.clone()).expect("failed to create shader module");
+        let shadow_fs = shadow_fs::load(device.clone()).expect("failed to create shader module");
+
+        let shadow_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(shadow_vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            // The shadow map never changes size, so the viewport can be baked in
+            .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+                Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [SHADOW_MAP_SIZE as f32, SHADOW_MAP_SIZE as f32],
+                    depth_range: 0.0..1.0,
+                },
+            ]))
+            .fragment_shader(shadow_fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .render_pass(Subpass::from(shadow_render_pass, 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create shadow pipeline");
+
+        // Outside of the shadow map everything is lit, thanks to the white border
+        let shadow_sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                mipmap_mode: SamplerMipmapMode::Nearest,
+                address_mode: [SamplerAddressMode::ClampToBorder; 3],
+                border_color: BorderColor::FloatOpaqueWhite,
+                compare: Some(CompareOp::LessOrEqual),
+                ..Default::default()
+            },
+        )
+            .expect("failed to create shadow sampler");
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: renderer.swapchain.image_format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth},
+            },
+        )
+            .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(PosNormalUv::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        Shadows {
+            camera: Camera::new(Vec3::new(0.0, 4.0, 8.0), Vec3::ZERO),
+            uniform_buffer: StreamingBuffer::new(allocators.memory.clone()),
+            allocators,
+            meshes,
+            objects,
+            shadow_pipeline,
+            shadow_framebuffer,
+            shadow_map,
+            shadow_sampler,
+            render_pass,
+            pipeline,
+            framebuffers: Vec::new(),
+            viewport: Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [0.0, 0.0],
+                depth_range: 0.0..1.0,
+            },
+            light_azimuth: 0.8,
+            light_elevation: 0.9,
+            min_bias: 0.0005,
+            max_bias: 0.005,
+            pcf: true,
+        }
+    }
+
+    // Points from the scene towards the light, the opposite of where its rays travel
+    fn light_direction(&self) -> Vec3 {
+        Vec3::new(
+            self.light_elevation.cos() * self.light_azimuth.cos(),
+            self.light_elevation.sin(),
+            self.light_elevation.cos() * self.light_azimuth.sin(),
+        )
+    }
+}
+
+impl App for Shadows {
+    fn resize(&mut self, renderer: &Renderer) {
+        let extent = renderer.swapchain.image_extent();
+        self.viewport.dimensions = [extent[0] as f32, extent[1] as f32];
+
+        let depth_buffer = ImageView::new_default(
+            AttachmentImage::transient(&self.allocators.memory, extent, Format::D16_UNORM).unwrap(),
+        )
+            .unwrap();
+
+        self.framebuffers = renderer
+            .image_views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    self.render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth_buffer.clone()],
+                        ..Default::default()
+                    },
+                )
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    fn render(
+        &mut self,
+        renderer: &Renderer,
+        image_index: u32,
+        before: Box<dyn GpuFuture>,
+    ) -> Box<dyn GpuFuture> {
+        // A directional light has no position, so an orthographic projection looking at the
+        // scene from along the light direction covers it with parallel rays
+        let light_direction = self.light_direction();
+        let light_view = Mat4::look_at_rh(light_direction * 10.0, Vec3::ZERO, Vec3::Y);
+        let light_projection = Mat4::orthographic_rh(-7.0, 7.0, -7.0, 7.0, 0.1, 20.0);
+        let light_view_projection = light_projection * light_view;
+
+        let [width, height] = self.viewport.dimensions;
+        let uniform_subbuffer = self.uniform_buffer.write(vs::Frame {
+            view_projection: self
+                .camera
+                .view_projection(width / height)
+                .to_cols_array_2d(),
+            light_view_projection: light_view_projection.to_cols_array_2d(),
+        });
+        let light_subbuffer =
+            self.uniform_buffer
+                .write(DirectionalLight::new(-light_direction, Vec3::ONE, 1.0));
+
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor,
+            layout.clone(),
+            [
+                WriteDescriptorSet::buffer(0, uniform_subbuffer),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    self.shadow_map.clone(),
+                    self.shadow_sampler.clone(),
+                ),
+                WriteDescriptorSet::buffer(2, light_subbuffer),
+            ],
+        )
+            .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &*self.allocators.command,
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        // First pass: only depth, from the light
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(self.shadow_framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(self.shadow_pipeline.clone());
+
+        for object in &self.objects {
+            let mesh = &self.meshes[object.mesh];
+            let push_constants = shadow_vs::PushConstants {
+                light_view_projection: light_view_projection.to_cols_array_2d(),
+                model: object.model.to_cols_array_2d(),
+            };
+
+            builder
+                .push_constants(self.shadow_pipeline.layout().clone(), 0, push_constants)
+                .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                .bind_index_buffer(mesh.index_buffer.clone())
+                .draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0)
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+
+        // Second pass: the scene from the camera, sampling the shadow map. The command buffer
+        // builder inserts the barrier that makes the depth writes visible to the shader
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.05, 0.05, 0.08, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [self.viewport.clone()])
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                set,
+            );
+
+        for object in &self.objects {
+            let mesh = &self.meshes[object.mesh];
+            let push_constants = vs::PushConstants {
+                model: object.model.to_cols_array_2d(),
+                color: object.color,
+                min_bias: self.min_bias,
+                max_bias: self.max_bias,
+                pcf: self.pcf as u32,
+            };
+
+            builder
+                .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+                .bind_vertex_buffers(0, mesh.vertex_buffer.clone())
+                .bind_index_buffer(mesh.index_buffer.clone())
+                .draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0)
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        before
+            .then_execute(renderer.queue().clone(), command_buffer)
+            .unwrap()
+            .boxed()
+    }
+
+    fn gui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Shadows").show(ctx, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.light_azimuth, 0.0..=std::f32::consts::TAU)
+                    .text("light azimuth"),
+            );
+            ui.add(egui::Slider::new(&mut self.light_elevation, 0.1..=1.5).text("light elevation"));
+            // Set both to zero to see shadow acne, push them too high and shadows detach from
+            // their casters (peter panning)
+            ui.add(
+                egui::Slider::new(&mut self.min_bias, 0.0..=0.01)
+                    .logarithmic(true)
+                    .text("min bias"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.max_bias, 0.0..=0.05)
+                    .logarithmic(true)
+                    .text("max bias"),
+            );
+            ui.checkbox(&mut self.pcf, "PCF");
+        });
+    }
+
+    fn camera(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.camera)
+    }
+}
+
+fn main() {
+    window::run(
+        "vulkano-rs-guide-9",
+        DeviceExtensions::empty(),
+        Shadows::new,
+    );
+}